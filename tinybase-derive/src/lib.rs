@@ -2,11 +2,17 @@ mod utils;
 use core::panic;
 
 use proc_macro::TokenStream;
-use quote::quote;
-use syn::{parse_macro_input, Data, DeriveInput, Field, Fields, FieldsNamed, Ident};
-use utils::{get_list_attr, has_attribute, validate_attributes};
+use quote::{quote, quote_spanned};
+use syn::{ext::IdentExt, parse_macro_input, Data, DeriveInput, Field, Fields, FieldsNamed, Ident};
+use utils::{get_list_attr, has_attribute, index_options, validate_attributes};
 
-#[proc_macro_derive(Repository, attributes(index, unique, check))]
+/// Same as deriving `Repository`, named after the table whose records are the struct.
+#[proc_macro_derive(TableEntity, attributes(index, unique, check, column, lazy))]
+pub fn table_entity(input: TokenStream) -> TokenStream {
+    repository(input)
+}
+
+#[proc_macro_derive(Repository, attributes(index, unique, check, column, lazy))]
 pub fn repository(input: TokenStream) -> TokenStream {
     let ast = parse_macro_input!(input as DeriveInput);
     let name = ast.ident;
@@ -37,14 +43,19 @@ pub fn repository(input: TokenStream) -> TokenStream {
     }
     .iter()
     .map(|check_fn| {
-        return quote! {
-            _table.constraint(tinybase::Constraint::check(#check_fn))?;
-        };
+        let check_name = check_fn.to_string();
+        quote! {
+            _table.constraint(tinybase::Constraint::named_check(#check_name, #check_fn))?;
+        }
     })
     .collect();
 
     let vis = ast.vis.clone();
-    let wrapper_name = syn::Ident::new(&format!("{}Repository", name.to_string()), name.span());
+    let wrapper_name = syn::Ident::new(&format!("{}Repository", name), name.span());
+    let patch = match create_patch(&vis, &name, fields.iter()) {
+        Ok(v) => v,
+        Err(e) => return e.into(),
+    };
 
     let expanded = quote! {
         #[derive(Clone)]
@@ -65,6 +76,8 @@ pub fn repository(input: TokenStream) -> TokenStream {
             #(#by_index)*
         }
 
+        #patch
+
         impl #name {
             pub fn init(db: &tinybase::TinyBase, name: &str) -> tinybase::DbResult<#wrapper_name> {
                 let _table: tinybase::Table<#name> = db.open_table(name)?;
@@ -81,19 +94,19 @@ pub fn repository(input: TokenStream) -> TokenStream {
     expanded.into()
 }
 
+/// Generated pieces for all indexed and column fields: names, members, methods and initializers.
+type ProcessedFields = (
+    Vec<Ident>,
+    Vec<proc_macro2::TokenStream>,
+    Vec<proc_macro2::TokenStream>,
+    Vec<proc_macro2::TokenStream>,
+);
+
 /// Process fields and decide what should be generated for each field.
 fn process_fields<'a>(
     struct_name: &proc_macro2::Ident,
     fields: impl Iterator<Item = &'a Field>,
-) -> Result<
-    (
-        Vec<Ident>,
-        Vec<proc_macro2::TokenStream>,
-        Vec<proc_macro2::TokenStream>,
-        Vec<proc_macro2::TokenStream>,
-    ),
-    TokenStream,
-> {
+) -> Result<ProcessedFields, TokenStream> {
     let mut index_names = vec![];
     let mut index_members = vec![];
 
@@ -104,11 +117,12 @@ fn process_fields<'a>(
         validate_attributes(
             &field.attrs,
             Some("index"),
-            &[("unique", false), ("index", false)], // index is here as a hack to prevent allowing list.
+            &[("unique", false)],
             &["check"],
         )?;
 
         if has_attribute(&field.attrs, "index").is_some() {
+            let options = index_options(&field.attrs)?;
             let (field_name, type_name) = (field.ident.as_ref().unwrap(), &field.ty);
 
             index_names.push(field_name.clone());
@@ -121,32 +135,154 @@ fn process_fields<'a>(
 
             by_index.push(methods);
 
-            let field_str = format!("{}", field_name);
+            let index_str = options
+                .name
+                .unwrap_or_else(|| format!("{}", field_name.unraw()));
 
             index_initializers.push(quote! {
-                let #field_name = _table.create_index(#field_str, |record| record.#field_name.clone())?;
+                let #field_name = _table.create_index(#index_str, |record| record.#field_name.clone())?;
             });
 
-            if has_attribute(&field.attrs, "unique").is_some() {
+            if let Some((unique, _)) = has_attribute(&field.attrs, "unique") {
+                // Derive macros can't emit warnings, using a deprecated item does.
+                index_initializers.push(quote_spanned! {unique.span()=>
+                    #[deprecated(note = "use `#[index(unique)]` instead of `#[unique]`")]
+                    #[allow(non_camel_case_types)]
+                    struct unique;
+                    let _ = unique;
+                });
+            }
+
+            if options.unique || has_attribute(&field.attrs, "unique").is_some() {
                 index_initializers.push(quote! {
                     _table.constraint(tinybase::Constraint::unique(&#field_name))?;
                 })
             }
         }
+
+        if has_attribute(&field.attrs, "column").is_some() {
+            let (field_name, type_name) = (field.ident.as_ref().unwrap(), &field.ty);
+            let column_name =
+                syn::Ident::new(&format!("{}_column", field_name.unraw()), field_name.span());
+            let field_str = format!("{}", field_name.unraw());
+
+            index_names.push(column_name.clone());
+
+            index_members.push(quote! {
+                pub #column_name: tinybase::Column<#struct_name, #type_name>,
+            });
+
+            index_initializers.push(quote! {
+                let #column_name = _table.create_column(#field_str, |record| record.#field_name.clone())?;
+            });
+        }
+
+        if has_attribute(&field.attrs, "lazy").is_some() {
+            let field_name = field.ident.as_ref().unwrap();
+            let field_str = format!("{}", field_name.unraw());
+
+            index_initializers.push(quote! {
+                _table.lazy_field(#field_str, |record| &mut record.#field_name)?;
+            });
+        }
     }
 
     Ok((index_names, index_members, by_index, index_initializers))
 }
 
+/// Create the patch enum with a variant setting each field.
+///
+/// Variants are the field names in upper camel case, fields whose names map to the
+/// same variant are a compile error.
+fn create_patch<'a>(
+    vis: &syn::Visibility,
+    name: &Ident,
+    fields: impl Iterator<Item = &'a Field>,
+) -> Result<proc_macro2::TokenStream, proc_macro2::TokenStream> {
+    let patch_name = syn::Ident::new(&format!("{}Patch", name), name.span());
+    let mut seen: Vec<(String, &Ident)> = vec![];
+    let (variants, fields): (Vec<_>, Vec<_>) = fields
+        .map(|field| {
+            let field_name = field.ident.as_ref().unwrap();
+            let variant: String = field_name
+                .unraw()
+                .to_string()
+                .split('_')
+                .map(|part| {
+                    let mut chars = part.chars();
+                    chars
+                        .next()
+                        .map(|first| first.to_uppercase().chain(chars).collect())
+                        .unwrap_or_default()
+                })
+                .collect::<Vec<String>>()
+                .concat();
+
+            if syn::parse_str::<Ident>(&variant).is_err() {
+                return Err(syn::Error::new(
+                    field_name.span(),
+                    format!("`{}` has no valid patch variant name", field_name),
+                )
+                .to_compile_error());
+            }
+            if let Some((_, other)) = seen.iter().find(|(seen, _)| *seen == variant) {
+                return Err(syn::Error::new(
+                    field_name.span(),
+                    format!(
+                        "`{}` and `{}` both map to the patch variant `{}`",
+                        other, field_name, variant
+                    ),
+                )
+                .to_compile_error());
+            }
+            seen.push((variant.clone(), field_name));
+
+            Ok((
+                syn::Ident::new(&variant, field_name.span()),
+                (field_name, &field.ty),
+            ))
+        })
+        .collect::<Result<Vec<_>, _>>()?
+        .into_iter()
+        .unzip();
+    let (field_names, types): (Vec<_>, Vec<_>) = fields.into_iter().unzip();
+
+    Ok(quote! {
+        /// Sets a single field, see [`tinybase::Patch`].
+        #vis enum #patch_name {
+            #(#variants(#types)),*
+        }
+
+        impl tinybase::Patch<#name> for #patch_name {
+            fn apply(&self, data: &mut #name) -> tinybase::DbResult<()> {
+                match self {
+                    #(#patch_name::#variants(value) => data.#field_names = value.clone()),*
+                }
+
+                Ok(())
+            }
+        }
+    })
+}
+
 /// Create methods for an index.
 fn create_methods(
     field_name: &Ident,
     type_name: &syn::Type,
     name: &Ident,
 ) -> proc_macro2::TokenStream {
-    let find_method = syn::Ident::new(&format!("find_by_{}", field_name), field_name.span());
-    let delete_method = syn::Ident::new(&format!("delete_by_{}", field_name), field_name.span());
-    let update_method = syn::Ident::new(&format!("update_by_{}", field_name), field_name.span());
+    let find_method = syn::Ident::new(
+        &format!("find_by_{}", field_name.unraw()),
+        field_name.span(),
+    );
+    let delete_method = syn::Ident::new(
+        &format!("delete_by_{}", field_name.unraw()),
+        field_name.span(),
+    );
+    let update_method = syn::Ident::new(
+        &format!("update_by_{}", field_name.unraw()),
+        field_name.span(),
+    );
 
     quote! {
         pub fn #find_method(&self, #field_name: #type_name) -> tinybase::result::DbResult<Vec<tinybase::Record<#name>>> {
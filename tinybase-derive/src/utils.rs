@@ -1,5 +1,14 @@
 use proc_macro2::{Ident, TokenStream};
-use syn::{Attribute, Meta};
+use syn::{Attribute, Lit, Meta, MetaNameValue, NestedMeta};
+
+/// Options of an `#[index]` attribute.
+#[derive(Default)]
+pub struct IndexOptions {
+    /// Whether records need a unique key, like with the deprecated `#[unique]` attribute.
+    pub unique: bool,
+    /// Name of the index, the name of the field if not set.
+    pub name: Option<String>,
+}
 
 /// This returns the attribute [`Ident`] if the attribute was found.
 pub fn has_attribute(attrs: &Vec<Attribute>, attr_name: &str) -> Option<(Ident, Meta)> {
@@ -17,6 +26,33 @@ pub fn has_attribute(attrs: &Vec<Attribute>, attr_name: &str) -> Option<(Ident,
     None
 }
 
+/// Parse the options of an `#[index]` or `#[index(unique, name = "...")]` attribute.
+pub fn index_options(attrs: &Vec<Attribute>) -> Result<IndexOptions, TokenStream> {
+    let mut options = IndexOptions::default();
+    let Some((_, Meta::List(list))) = has_attribute(attrs, "index") else {
+        return Ok(options);
+    };
+
+    for nested in list.nested {
+        match nested {
+            NestedMeta::Meta(Meta::Path(path)) if path.is_ident("unique") => options.unique = true,
+            NestedMeta::Meta(Meta::NameValue(MetaNameValue {
+                path,
+                lit: Lit::Str(name),
+                ..
+            })) if path.is_ident("name") => options.name = Some(name.value()),
+            other => {
+                return Err(
+                    syn::Error::new_spanned(other, "Expected `unique` or `name = \"...\"`")
+                        .to_compile_error(),
+                )
+            }
+        }
+    }
+
+    Ok(options)
+}
+
 /// Get a value in an attribute.
 pub fn get_list_attr(
     attrs: &Vec<Attribute>,
@@ -51,8 +87,7 @@ pub fn validate_attributes(
         if let Some(ident) = has_attribute(attrs, attr) {
             return Err(
                 syn::Error::new(ident.0.span(), "This attribute is not allowed here")
-                    .to_compile_error()
-                    .into(),
+                    .to_compile_error(),
             );
         }
     }
@@ -61,13 +96,12 @@ pub fn validate_attributes(
         let found = has_attribute(attrs, attr.0);
         if let Some(found) = found {
             if let Some(base) = base {
-                if !has_attribute(attrs, base).is_some() {
+                if has_attribute(attrs, base).is_none() {
                     return Err(syn::Error::new(
                         found.0.span(),
                         format!("This attribute requires the #[{}] attribute", base),
                     )
-                    .to_compile_error()
-                    .into());
+                    .to_compile_error());
                 }
             }
 
@@ -78,16 +112,14 @@ pub fn validate_attributes(
                             found.0.span(),
                             "This attribute is missing a parameter",
                         )
-                        .to_compile_error()
-                        .into());
+                        .to_compile_error());
                     }
                 }
                 Meta::List(_) => {
                     if !attr.1 {
                         return Err(
                             syn::Error::new(found.0.span(), "This attribute isn't a list")
-                                .to_compile_error()
-                                .into(),
+                                .to_compile_error(),
                         );
                     }
                 }
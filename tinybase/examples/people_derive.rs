@@ -1,14 +1,14 @@
 use serde::{Deserialize, Serialize};
 use tinybase::{ConditionBuilder, QueryBuilder, Table, TinyBase};
-use tinybase_derive::Repository;
+use tinybase_derive::TableEntity;
 
-#[derive(Repository, Serialize, Deserialize, Debug, Clone)]
+#[derive(TableEntity, Serialize, Deserialize, Debug, Clone)]
 struct Person {
-    #[index]
-    #[unique]
+    #[index(unique)]
     pub name: String,
-    #[index]
+    #[index(name = "last_name_idx")]
     pub last_name: String,
+    #[column]
     pub age: u8,
 }
 
@@ -23,6 +23,23 @@ fn main() {
         people.find_by_last_name("Smith".to_owned()).unwrap()
     );
 
+    let ages: Vec<u8> = people
+        .age_column
+        .scan()
+        .map(|entry| entry.map(|(_, age)| age))
+        .collect::<Result<_, _>>()
+        .unwrap();
+    println!(
+        "Average age: {}",
+        ages.iter().map(|age| *age as u32).sum::<u32>() / ages.len() as u32
+    );
+
+    let john = people.find_by_name("John".to_owned()).unwrap()[0].id;
+    println!(
+        "Patched John's age:\n{:#?}",
+        people.patch(john, PersonPatch::Age(19)).unwrap()
+    );
+
     println!(
         "Replaced lastnames with Brown:\n{:#?}",
         QueryBuilder::new(&people)
@@ -0,0 +1,56 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::Instant;
+
+use crate::result::{DbResult, TinyBaseError};
+
+/// Cancels the queries it is passed to, from any thread.
+///
+/// See [`crate::QueryBuilder::with_cancellation`].
+#[derive(Debug, Clone, Default)]
+pub struct CancellationToken(Arc<AtomicBool>);
+
+impl CancellationToken {
+    /// Creates a token which isn't cancelled.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Cancel all queries using this token, including running ones.
+    pub fn cancel(&self) {
+        self.0.store(true, Ordering::Relaxed);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.0.load(Ordering::Relaxed)
+    }
+}
+
+/// Conditions under which the evaluation of a query is aborted.
+#[derive(Default)]
+pub(crate) struct Abort {
+    pub(crate) deadline: Option<Instant>,
+    pub(crate) token: Option<CancellationToken>,
+}
+
+impl Abort {
+    /// Checks whether the query should stop.
+    ///
+    /// # Returns
+    ///
+    /// [`TinyBaseError::Cancelled`] if the deadline passed or the token was cancelled.
+    pub(crate) fn check(&self) -> DbResult<()> {
+        let expired = self
+            .deadline
+            .is_some_and(|deadline| Instant::now() >= deadline);
+        let cancelled = self
+            .token
+            .as_ref()
+            .is_some_and(|token| token.is_cancelled());
+
+        match expired || cancelled {
+            true => Err(TinyBaseError::Cancelled),
+            false => Ok(()),
+        }
+    }
+}
@@ -0,0 +1,431 @@
+use std::cmp::Ordering;
+use std::sync::Arc;
+
+use serde::{Deserialize, Serialize};
+
+#[cfg(feature = "icu")]
+use crate::result::{DbResult, TinyBaseError};
+
+/// Latin letters with their base letters and the combining mark which was removed,
+/// sorted by the letter. Letters without a canonical decomposition, like the dotless
+/// `ı`, are base letters of their own and aren't listed.
+const DECOMPOSITIONS: &[(char, &str, u16)] = &[
+    ('À', "A", 0x300),
+    ('Á', "A", 0x301),
+    ('Â', "A", 0x302),
+    ('Ã', "A", 0x303),
+    ('Ä', "A", 0x308),
+    ('Å', "A", 0x30A),
+    ('Æ', "AE", 0),
+    ('Ç', "C", 0x327),
+    ('È', "E", 0x300),
+    ('É', "E", 0x301),
+    ('Ê', "E", 0x302),
+    ('Ë', "E", 0x308),
+    ('Ì', "I", 0x300),
+    ('Í', "I", 0x301),
+    ('Î', "I", 0x302),
+    ('Ï', "I", 0x308),
+    ('Ð', "D", 0x335),
+    ('Ñ', "N", 0x303),
+    ('Ò', "O", 0x300),
+    ('Ó', "O", 0x301),
+    ('Ô', "O", 0x302),
+    ('Õ', "O", 0x303),
+    ('Ö', "O", 0x308),
+    ('Ø', "O", 0x338),
+    ('Ù', "U", 0x300),
+    ('Ú', "U", 0x301),
+    ('Û', "U", 0x302),
+    ('Ü', "U", 0x308),
+    ('Ý', "Y", 0x301),
+    ('Þ', "TH", 0),
+    ('ß', "ss", 0),
+    ('à', "a", 0x300),
+    ('á', "a", 0x301),
+    ('â', "a", 0x302),
+    ('ã', "a", 0x303),
+    ('ä', "a", 0x308),
+    ('å', "a", 0x30A),
+    ('æ', "ae", 0),
+    ('ç', "c", 0x327),
+    ('è', "e", 0x300),
+    ('é', "e", 0x301),
+    ('ê', "e", 0x302),
+    ('ë', "e", 0x308),
+    ('ì', "i", 0x300),
+    ('í', "i", 0x301),
+    ('î', "i", 0x302),
+    ('ï', "i", 0x308),
+    ('ð', "d", 0x335),
+    ('ñ', "n", 0x303),
+    ('ò', "o", 0x300),
+    ('ó', "o", 0x301),
+    ('ô', "o", 0x302),
+    ('õ', "o", 0x303),
+    ('ö', "o", 0x308),
+    ('ø', "o", 0x338),
+    ('ù', "u", 0x300),
+    ('ú', "u", 0x301),
+    ('û', "u", 0x302),
+    ('ü', "u", 0x308),
+    ('ý', "y", 0x301),
+    ('þ', "th", 0),
+    ('ÿ', "y", 0x308),
+    ('Ā', "A", 0x304),
+    ('ā', "a", 0x304),
+    ('Ă', "A", 0x306),
+    ('ă', "a", 0x306),
+    ('Ą', "A", 0x328),
+    ('ą', "a", 0x328),
+    ('Ć', "C", 0x301),
+    ('ć', "c", 0x301),
+    ('Ĉ', "C", 0x302),
+    ('ĉ', "c", 0x302),
+    ('Ċ', "C", 0x307),
+    ('ċ', "c", 0x307),
+    ('Č', "C", 0x30C),
+    ('č', "c", 0x30C),
+    ('Ď', "D", 0x30C),
+    ('ď', "d", 0x30C),
+    ('Đ', "D", 0x335),
+    ('đ', "d", 0x335),
+    ('Ē', "E", 0x304),
+    ('ē', "e", 0x304),
+    ('Ĕ', "E", 0x306),
+    ('ĕ', "e", 0x306),
+    ('Ė', "E", 0x307),
+    ('ė', "e", 0x307),
+    ('Ę', "E", 0x328),
+    ('ę', "e", 0x328),
+    ('Ě', "E", 0x30C),
+    ('ě', "e", 0x30C),
+    ('Ĝ', "G", 0x302),
+    ('ĝ', "g", 0x302),
+    ('Ğ', "G", 0x306),
+    ('ğ', "g", 0x306),
+    ('Ġ', "G", 0x307),
+    ('ġ', "g", 0x307),
+    ('Ģ', "G", 0x327),
+    ('ģ', "g", 0x327),
+    ('Ĥ', "H", 0x302),
+    ('ĥ', "h", 0x302),
+    ('Ħ', "H", 0x335),
+    ('ħ', "h", 0x335),
+    ('Ĩ', "I", 0x303),
+    ('ĩ', "i", 0x303),
+    ('Ī', "I", 0x304),
+    ('ī', "i", 0x304),
+    ('Ĭ', "I", 0x306),
+    ('ĭ', "i", 0x306),
+    ('Į', "I", 0x328),
+    ('į', "i", 0x328),
+    ('İ', "I", 0x307),
+    ('Ĵ', "J", 0x302),
+    ('ĵ', "j", 0x302),
+    ('Ķ', "K", 0x327),
+    ('ķ', "k", 0x327),
+    ('Ĺ', "L", 0x301),
+    ('ĺ', "l", 0x301),
+    ('Ļ', "L", 0x327),
+    ('ļ', "l", 0x327),
+    ('Ľ', "L", 0x30C),
+    ('ľ', "l", 0x30C),
+    ('Ł', "L", 0x335),
+    ('ł', "l", 0x335),
+    ('Ń', "N", 0x301),
+    ('ń', "n", 0x301),
+    ('Ņ', "N", 0x327),
+    ('ņ', "n", 0x327),
+    ('Ň', "N", 0x30C),
+    ('ň', "n", 0x30C),
+    ('Ō', "O", 0x304),
+    ('ō', "o", 0x304),
+    ('Ŏ', "O", 0x306),
+    ('ŏ', "o", 0x306),
+    ('Ő', "O", 0x30B),
+    ('ő', "o", 0x30B),
+    ('Œ', "OE", 0),
+    ('œ', "oe", 0),
+    ('Ŕ', "R", 0x301),
+    ('ŕ', "r", 0x301),
+    ('Ŗ', "R", 0x327),
+    ('ŗ', "r", 0x327),
+    ('Ř', "R", 0x30C),
+    ('ř', "r", 0x30C),
+    ('Ś', "S", 0x301),
+    ('ś', "s", 0x301),
+    ('Ŝ', "S", 0x302),
+    ('ŝ', "s", 0x302),
+    ('Ş', "S", 0x327),
+    ('ş', "s", 0x327),
+    ('Š', "S", 0x30C),
+    ('š', "s", 0x30C),
+    ('Ţ', "T", 0x327),
+    ('ţ', "t", 0x327),
+    ('Ť', "T", 0x30C),
+    ('ť', "t", 0x30C),
+    ('Ŧ', "T", 0x335),
+    ('ŧ', "t", 0x335),
+    ('Ũ', "U", 0x303),
+    ('ũ', "u", 0x303),
+    ('Ū', "U", 0x304),
+    ('ū', "u", 0x304),
+    ('Ŭ', "U", 0x306),
+    ('ŭ', "u", 0x306),
+    ('Ů', "U", 0x30A),
+    ('ů', "u", 0x30A),
+    ('Ű', "U", 0x30B),
+    ('ű', "u", 0x30B),
+    ('Ų', "U", 0x328),
+    ('ų', "u", 0x328),
+    ('Ŵ', "W", 0x302),
+    ('ŵ', "w", 0x302),
+    ('Ŷ', "Y", 0x302),
+    ('ŷ', "y", 0x302),
+    ('Ÿ', "Y", 0x308),
+    ('Ź', "Z", 0x301),
+    ('ź', "z", 0x301),
+    ('Ż', "Z", 0x307),
+    ('ż', "z", 0x307),
+    ('Ž', "Z", 0x30C),
+    ('ž', "z", 0x30C),
+];
+
+/// Fold the case of a string, so strings differing only in case are equal.
+pub fn fold_case(value: &str) -> String {
+    value.to_lowercase()
+}
+
+/// Which differences between strings are significant.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Strength {
+    /// Only base letters differ, `a`, `á` and `A` are equal.
+    Primary,
+    /// Accents differ too, `a` and `A` are equal.
+    Secondary,
+    /// Accents and case differ.
+    Tertiary,
+}
+
+/// Key of a string which sorts and compares like the string under a [`Collator`].
+///
+/// Use it as index key so lookups and ordering follow the collation instead of
+/// the raw bytes of the string.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+pub struct CollationKey(Vec<u8>);
+
+enum Rules {
+    Bundled,
+    #[cfg(feature = "icu")]
+    Icu(Box<icu_collator::Collator>),
+}
+
+/// Compares strings in dictionary order rather than by their bytes.
+///
+/// The bundled rules fold case and accents of Latin letters and otherwise order by
+/// code point. With the `icu` feature [`Collator::locale`] uses the rules of a locale
+/// for comparisons. Sort keys always use the bundled rules, so collators of a locale
+/// can't key an index.
+#[derive(Clone)]
+pub struct Collator {
+    strength: Strength,
+    rules: Arc<Rules>,
+}
+
+impl Collator {
+    /// Create a collator with the bundled rules.
+    ///
+    /// # Arguments
+    ///
+    /// * `strength` - Which differences are significant.
+    pub fn new(strength: Strength) -> Self {
+        Self {
+            strength,
+            rules: Arc::new(Rules::Bundled),
+        }
+    }
+
+    /// Create a collator with the rules of a locale.
+    ///
+    /// # Arguments
+    ///
+    /// * `locale` - A BCP 47 locale identifier, such as `de` or `sv-SE`.
+    /// * `strength` - Which differences are significant.
+    #[cfg(feature = "icu")]
+    pub fn locale(locale: &str, strength: Strength) -> DbResult<Self> {
+        let locale: icu_locid::Locale = locale
+            .parse()
+            .map_err(|err| TinyBaseError::Collation(format!("{}", err)))?;
+
+        let mut options = icu_collator::CollatorOptions::new();
+        options.strength = Some(match strength {
+            Strength::Primary => icu_collator::Strength::Primary,
+            Strength::Secondary => icu_collator::Strength::Secondary,
+            Strength::Tertiary => icu_collator::Strength::Tertiary,
+        });
+
+        let collator = icu_collator::Collator::try_new(&(&locale).into(), options)
+            .map_err(|err| TinyBaseError::Collation(format!("{}", err)))?;
+
+        Ok(Self {
+            strength,
+            rules: Arc::new(Rules::Icu(Box::new(collator))),
+        })
+    }
+
+    /// Which differences between strings are significant.
+    pub fn strength(&self) -> Strength {
+        self.strength
+    }
+
+    /// Whether [`Collator::sort_key`] compares like [`Collator::compare`], which only
+    /// holds for the bundled rules.
+    #[cfg(feature = "icu")]
+    pub(crate) fn has_sort_keys(&self) -> bool {
+        matches!(&*self.rules, Rules::Bundled)
+    }
+
+    /// Compare two strings.
+    pub fn compare(&self, a: &str, b: &str) -> Ordering {
+        match &*self.rules {
+            Rules::Bundled => self.sort_key(a).cmp(&self.sort_key(b)),
+            #[cfg(feature = "icu")]
+            Rules::Icu(collator) => collator.compare(a, b),
+        }
+    }
+
+    /// Compute the key of a string with the bundled rules, even for collators of a locale.
+    pub fn sort_key(&self, value: &str) -> CollationKey {
+        let mut primary = vec![];
+        let mut secondary = vec![];
+        let mut tertiary = vec![];
+
+        for char in value.chars() {
+            let (base, mark) = match DECOMPOSITIONS.binary_search_by_key(&char, |(char, ..)| *char)
+            {
+                Ok(i) => (DECOMPOSITIONS[i].1.chars().collect(), DECOMPOSITIONS[i].2),
+                Err(_) => (vec![char], 0),
+            };
+
+            for base in base {
+                for lower in base.to_lowercase() {
+                    primary.extend(lower.encode_utf8(&mut [0; 4]).as_bytes());
+                    // Combining marks are all within U+0300 to U+036F.
+                    secondary.push(if mark == 0 { 0 } else { (mark - 0x2ff) as u8 });
+                    tertiary.push(base.is_uppercase() as u8);
+                }
+            }
+        }
+
+        let mut key = primary;
+        if self.strength != Strength::Primary {
+            key.push(0);
+            key.extend(secondary);
+        }
+        if self.strength == Strength::Tertiary {
+            key.push(0);
+            key.extend(tertiary);
+        }
+
+        CollationKey(key)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Table, TinyBase};
+
+    #[test]
+    fn collator_strength() {
+        let primary = Collator::new(Strength::Primary);
+        assert_eq!(primary.sort_key("Élan"), primary.sort_key("elan"));
+        assert_eq!(primary.sort_key("Straße"), primary.sort_key("strasse"));
+        assert_eq!(primary.sort_key("İstanbul"), primary.sort_key("istanbul"));
+        assert_ne!(primary.sort_key("ılık"), primary.sort_key("ilik"));
+
+        let secondary = Collator::new(Strength::Secondary);
+        assert_ne!(secondary.sort_key("élan"), secondary.sort_key("elan"));
+        assert_eq!(secondary.sort_key("Élan"), secondary.sort_key("élan"));
+
+        let tertiary = Collator::new(Strength::Tertiary);
+        let mut words = vec!["zebra", "Zebra", "éclair", "apple", "Eclair"];
+        words.sort_by(|a, b| tertiary.compare(a, b));
+        assert_eq!(words, ["apple", "Eclair", "éclair", "zebra", "Zebra"]);
+    }
+
+    #[test]
+    fn order_by_collated() {
+        use crate::{ConditionBuilder, Direction, QueryBuilder, Table, TinyBase};
+
+        let db = TinyBase::new(None, true);
+        let table: Table<String> = db.open_table("test_table").unwrap();
+        let name = table
+            .create_index("name", |value| value.to_owned())
+            .unwrap();
+        let length = table.create_index("length", |value| value.len()).unwrap();
+
+        for value in ["Zoe", "émile", "adam", "Emma"] {
+            table.insert(value.to_string()).unwrap();
+        }
+
+        let values: Vec<_> = QueryBuilder::new(&table)
+            .with_condition(ConditionBuilder::or(
+                ConditionBuilder::or(
+                    ConditionBuilder::by(&length, 3),
+                    ConditionBuilder::by(&length, 4),
+                ),
+                ConditionBuilder::by(&length, 6),
+            ))
+            .order_by_collated(&name, &Collator::new(Strength::Tertiary), Direction::Asc)
+            .select()
+            .unwrap()
+            .into_iter()
+            .map(|record| record.data)
+            .collect();
+
+        // Byte order would put uppercase letters first and accented letters last.
+        assert_eq!(values, ["adam", "émile", "Emma", "Zoe"]);
+    }
+
+    #[cfg(feature = "icu")]
+    #[test]
+    fn collator_locale() {
+        let swedish = Collator::locale("sv", Strength::Primary).unwrap();
+        assert_eq!(swedish.compare("ä", "z"), Ordering::Greater);
+        assert_eq!(swedish.compare("a", "A"), Ordering::Equal);
+
+        // Keys of an index would be ordered by the bundled rules instead of the locale.
+        let db = TinyBase::new(None, true);
+        let table: Table<String> = db.open_table("words").unwrap();
+        assert!(matches!(
+            table.create_collated_index("word", &swedish, |word| word.to_owned()),
+            Err(TinyBaseError::Collation(_))
+        ));
+    }
+
+    #[test]
+    fn collator_index() {
+        let db = TinyBase::new(None, true);
+        let table: Table<String> = db.open_table("words").unwrap();
+        let collator = Collator::new(Strength::Primary);
+        let word = table
+            .create_collated_index("word", &collator, |word| word.to_owned())
+            .unwrap();
+
+        for value in ["zebra", "Élan", "apple", "elan"] {
+            table.insert(value.to_string()).unwrap();
+        }
+
+        assert_eq!(word.select(&collator.sort_key("ELAN")).unwrap().len(), 2);
+
+        let ordered: Vec<_> = word
+            .select_range(..)
+            .unwrap()
+            .into_iter()
+            .map(|record| record.data)
+            .collect();
+        assert_eq!(ordered, ["apple", "Élan", "elan", "zebra"]);
+    }
+}
@@ -0,0 +1,135 @@
+use std::ops::Deref;
+use std::sync::Arc;
+
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use sled::Tree;
+
+use crate::encoding::{decode, encode};
+use crate::result::DbResult;
+use crate::table::TableType;
+
+pub trait ColumnType: Serialize + DeserializeOwned {}
+impl<T: Serialize + DeserializeOwned> ColumnType for T {}
+
+/// A single field of a table, stored separately from the records.
+///
+/// Columns are written in the same transaction as the table, so scans over one
+/// field never have to decode entire records.
+pub struct Column<T: TableType + 'static, C: ColumnType>(pub(crate) Arc<ColumnInner<T, C>>);
+
+impl<T: TableType, C: ColumnType> Clone for Column<T, C> {
+    fn clone(&self) -> Self {
+        Self(self.0.clone())
+    }
+}
+
+impl<T: TableType, C: ColumnType> Deref for Column<T, C> {
+    type Target = Arc<ColumnInner<T, C>>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+/// Inner state of a column on a typed table.
+pub struct ColumnInner<T: TableType + 'static, C: ColumnType> {
+    /// Function which extracts the column value of a record.
+    value_func: Box<dyn Fn(&T) -> C + Send + Sync>,
+    /// Column values by record ID.
+    values: Tree,
+}
+
+impl<T: TableType, C: ColumnType> ColumnInner<T, C> {
+    /// Creates a new column.
+    ///
+    /// This method is intended for internal use and should not be called directly. Instead, use the
+    /// [`crate::Table`]'s `create_column()` method.
+    ///
+    /// # Arguments
+    ///
+    /// * `values` - The tree storing the column.
+    /// * `value_func` - A function which extracts the column value of a record.
+    pub(crate) fn new(values: Tree, value_func: impl Fn(&T) -> C + Send + Sync + 'static) -> Self {
+        Self {
+            value_func: Box::new(value_func),
+            values,
+        }
+    }
+
+    /// Get the column value of a record.
+    ///
+    /// # Arguments
+    ///
+    /// * `id` - The ID of the record.
+    ///
+    /// # Returns
+    ///
+    /// An [`Option`] containing the value if the record exists, or [`None`] otherwise.
+    pub fn get(&self, id: u64) -> DbResult<Option<C>> {
+        match self.values.get(encode(&id)?)? {
+            Some(bytes) => Ok(Some(decode(&bytes)?)),
+            None => Ok(None),
+        }
+    }
+
+    /// Iterate over the column values of all records in ID order.
+    ///
+    /// # Returns
+    ///
+    /// An iterator of record IDs and their values.
+    pub fn scan(&self) -> impl Iterator<Item = DbResult<(u64, C)>> {
+        self.values.iter().map(|entry| {
+            let (id, value) = entry?;
+            Ok((decode(&id)?, decode(&value)?))
+        })
+    }
+
+    pub fn column_name(&self) -> String {
+        std::str::from_utf8(&self.values.name())
+            .unwrap()
+            .to_string()
+    }
+}
+
+/// Type erased column, used by the table to write its columns.
+pub(crate) trait AnyColumn<T>: Send + Sync {
+    /// Tree storing the column.
+    fn tree(&self) -> &Tree;
+    /// Extract and encode the column value of a record.
+    fn encode_value(&self, data: &T) -> DbResult<Vec<u8>>;
+}
+
+impl<T: TableType, C: ColumnType> AnyColumn<T> for ColumnInner<T, C> {
+    fn tree(&self) -> &Tree {
+        &self.values
+    }
+
+    fn encode_value(&self, data: &T) -> DbResult<Vec<u8>> {
+        encode(&(self.value_func)(data))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{Table, TinyBase};
+
+    #[test]
+    fn column_scan() {
+        let db = TinyBase::new(None, true);
+        let table: Table<String> = db.open_table("test_table").unwrap();
+
+        let id1 = table.insert("value1".to_string()).unwrap();
+        // Existing records are written to new columns.
+        let column = table.create_column("length", |value| value.len()).unwrap();
+
+        let id2 = table.insert("value22".to_string()).unwrap();
+        table.update(&[id1], |_| "v1".to_string()).unwrap();
+
+        let values: Vec<_> = column.scan().collect::<Result<_, _>>().unwrap();
+        assert_eq!(values, vec![(id1, 2), (id2, 7)]);
+
+        table.delete(id2).unwrap();
+        assert_eq!(column.get(id2).unwrap(), None);
+    }
+}
@@ -8,10 +8,10 @@ use crate::{
 pub struct Constraint<T: TableType + 'static>(pub(crate) ConstraintInner<T>);
 
 pub(crate) enum ConstraintInner<T: TableType + 'static> {
-    /// Unique constraint based on index.
-    Unique(Box<dyn AnyIndex<T>>),
-    /// Constraint based on closure check.
-    Check(fn(&T) -> bool),
+    /// Unique constraint based on index, only applying to records matching the optional filter.
+    Unique(Box<dyn AnyIndex<T>>, Option<fn(&T) -> bool>),
+    /// Constraint based on closure check, with an optional name.
+    Check(Option<String>, fn(&T) -> bool),
 }
 
 impl<T: TableType> Constraint<T> {
@@ -21,7 +21,24 @@ impl<T: TableType> Constraint<T> {
     ///
     /// * `index` - A reference to the [`Index`] instance to be used for enforcing the unique constraint.
     pub fn unique<I: IndexType + 'static>(index: &Index<T, I>) -> Self {
-        Self(ConstraintInner::Unique(Box::new(index.clone())))
+        Self(ConstraintInner::Unique(Box::new(index.clone()), None))
+    }
+
+    /// Creates a new unique constraint which only applies to records matching a filter,
+    /// such as email addresses which are unique among users which aren't deleted.
+    ///
+    /// # Arguments
+    ///
+    /// * `index` - A reference to the [`Index`] instance to be used for enforcing the unique constraint.
+    /// * `filter` - A function that returns `true` for records which must have a unique key.
+    pub fn unique_where<I: IndexType + 'static>(
+        index: &Index<T, I>,
+        filter: fn(&T) -> bool,
+    ) -> Self {
+        Self(ConstraintInner::Unique(
+            Box::new(index.clone()),
+            Some(filter),
+        ))
     }
 
     /// Creates a new constraint based on a custom check function.
@@ -30,7 +47,18 @@ impl<T: TableType> Constraint<T> {
     ///
     /// * `check` - A function that takes a reference to the value `T` and returns a boolean indicating if the constraint is satisfied.
     pub fn check(check: fn(&T) -> bool) -> Self {
-        Self(ConstraintInner::Check(check))
+        Self(ConstraintInner::Check(None, check))
+    }
+
+    /// Creates a new constraint based on a custom check function, which is reported by name
+    /// when it isn't satisfied.
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - The name of the constraint.
+    /// * `check` - A function that takes a reference to the value `T` and returns a boolean indicating if the constraint is satisfied.
+    pub fn named_check(name: &str, check: fn(&T) -> bool) -> Self {
+        Self(ConstraintInner::Check(Some(name.to_owned()), check))
     }
 }
 
@@ -57,18 +85,49 @@ mod tests {
             .constraint(Constraint::check(|value: &String| value.len() >= 5))
             .is_ok());
 
-        table.insert("greater".to_owned()).unwrap();
+        let id = table.insert("greater".to_owned()).unwrap();
 
         // Unique constraint.
+        let err = table.insert("greater".to_owned()).unwrap_err();
         assert!(matches!(
-            table.insert("greater".to_owned()),
-            Err(TinyBaseError::Exists { .. })
+            &err,
+            TinyBaseError::Exists { constraint, conflicting, .. }
+                if constraint == "test_table_idx_name" && *conflicting == id
         ));
+        assert_eq!(err.decode_key::<String>().unwrap(), "greater");
 
         // Check constraint.
         assert!(matches!(
             table.insert("less".to_owned()),
-            Err(TinyBaseError::Condition)
+            Err(TinyBaseError::Condition {
+                constraint: None,
+                ..
+            })
         ));
     }
+
+    #[test]
+    fn partial_unique_constraint() {
+        let db = TinyBase::new(None, true);
+        let table: Table<(String, bool)> = db.open_table("users").unwrap();
+
+        let email = table
+            .create_index("email", |(email, _)| email.to_owned())
+            .unwrap();
+
+        // Emails only have to be unique among users which aren't deleted.
+        table
+            .constraint(Constraint::unique_where(&email, |(_, deleted)| !deleted))
+            .unwrap();
+
+        let id = table.insert(("a@example.com".to_owned(), false)).unwrap();
+        assert!(table.insert(("a@example.com".to_owned(), true)).is_ok());
+        assert!(matches!(
+            table.insert(("a@example.com".to_owned(), false)),
+            Err(TinyBaseError::Exists { conflicting, .. }) if conflicting == id
+        ));
+
+        table.update(&[id], |(email, _)| (email, true)).unwrap();
+        assert!(table.insert(("a@example.com".to_owned(), false)).is_ok());
+    }
 }
@@ -0,0 +1,121 @@
+use crate::index::{Index, IndexType};
+use crate::key_encoding::encode_key;
+use crate::result::DbResult;
+use crate::table::{Table, TableType};
+
+/// Index which stores a projection of every record next to its key, so selecting
+/// the projections doesn't read the records from the table.
+///
+/// Created with [`Table::create_covering_index`]. The index tree is keyed by the key
+/// followed by the projection, which costs the space of a copy of every projection.
+pub struct CoveringIndex<T: TableType + 'static, I: IndexType, P: IndexType> {
+    index: Index<T, (I, P)>,
+}
+
+impl<T: TableType, I: IndexType, P: IndexType> Clone for CoveringIndex<T, I, P> {
+    fn clone(&self) -> Self {
+        Self {
+            index: self.index.clone(),
+        }
+    }
+}
+
+impl<T: TableType + 'static, I: IndexType + 'static, P: IndexType + Clone + 'static>
+    CoveringIndex<T, I, P>
+{
+    pub(crate) fn new(
+        table: &Table<T>,
+        name: &str,
+        key_func: impl Fn(&T) -> I + Send + Sync + 'static,
+        project: impl Fn(&T) -> P + Send + Sync + 'static,
+    ) -> DbResult<Self> {
+        let index = table.create_index(name, move |data| (key_func(data), project(data)))?;
+        Ok(Self { index })
+    }
+
+    /// The underlying index on the keys together with the projections.
+    pub fn index(&self) -> &Index<T, (I, P)> {
+        &self.index
+    }
+
+    /// Select the projections of the records with a key.
+    ///
+    /// # Arguments
+    ///
+    /// * `query` - A reference to the query key.
+    ///
+    /// # Returns
+    ///
+    /// The ID and projection of every matching record, in ID order.
+    pub fn select(&self, query: &I) -> DbResult<Vec<(u64, P)>> {
+        let mut results = vec![];
+        for ((_, projection), ids) in self.index.prefix_groups(&encode_key(query)?)? {
+            results.extend(ids.into_iter().map(|id| (id, projection.clone())));
+        }
+
+        results.sort_unstable_by_key(|(id, _)| *id);
+        Ok(results)
+    }
+
+    /// Select the IDs of the records with a key.
+    ///
+    /// # Arguments
+    ///
+    /// * `query` - A reference to the query key.
+    ///
+    /// # Returns
+    ///
+    /// The IDs of all matching records, in ID order.
+    pub fn select_ids(&self, query: &I) -> DbResult<Vec<u64>> {
+        self.index.prefix_ids(&encode_key(query)?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::TinyBase;
+
+    #[test]
+    fn covering_index_select() {
+        let db = TinyBase::new(None, true);
+        let table = db.open_table::<(String, String, u32)>("users").unwrap();
+        let by_team = table
+            .create_covering_index(
+                "team",
+                |(team, _, _): &(String, String, u32)| team.clone(),
+                |(_, name, age): &(String, String, u32)| (name.clone(), *age),
+            )
+            .unwrap();
+
+        let ann = table
+            .insert(("red".to_string(), "ann".to_string(), 30))
+            .unwrap();
+        let bob = table
+            .insert(("red".to_string(), "bob".to_string(), 25))
+            .unwrap();
+        // Keys which start with the bytes of another key aren't matched.
+        table
+            .insert(("redder".to_string(), "eve".to_string(), 40))
+            .unwrap();
+
+        assert_eq!(
+            by_team.select(&"red".to_string()).unwrap(),
+            [
+                (ann, ("ann".to_string(), 30)),
+                (bob, ("bob".to_string(), 25))
+            ]
+        );
+        assert_eq!(by_team.select_ids(&"red".to_string()).unwrap(), [ann, bob]);
+
+        table
+            .update(&[bob], |(team, name, _)| (team, name, 26))
+            .unwrap();
+        assert_eq!(
+            by_team.select(&"red".to_string()).unwrap()[1],
+            (bob, ("bob".to_string(), 26))
+        );
+
+        table.delete(ann).unwrap();
+        assert_eq!(by_team.select_ids(&"red".to_string()).unwrap(), [bob]);
+    }
+}
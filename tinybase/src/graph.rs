@@ -0,0 +1,140 @@
+use std::collections::{HashSet, VecDeque};
+
+use crate::index::Index;
+use crate::record::Record;
+use crate::result::DbResult;
+use crate::table::{Table, TableType};
+
+/// Directed edges between the records of a node table, stored as records of an edge table.
+///
+/// Edges are looked up by the index on their source, so traversals read only the
+/// edges of reached nodes.
+pub struct Graph<N: TableType + 'static, E: TableType + 'static> {
+    nodes: Table<N>,
+    /// Index of the edges by their source node.
+    edges: Index<E, u64>,
+    target: Box<dyn Fn(&E) -> u64 + Send + Sync>,
+}
+
+impl<N: TableType, E: TableType> Graph<N, E> {
+    /// Create a graph from a node table and an edge index.
+    ///
+    /// # Arguments
+    ///
+    /// * `nodes` - The table containing the nodes.
+    /// * `edges` - An index on the edge table with the source node ID as key.
+    /// * `target` - A function returning the target node ID of an edge.
+    pub fn new(
+        nodes: &Table<N>,
+        edges: &Index<E, u64>,
+        target: impl Fn(&E) -> u64 + Send + Sync + 'static,
+    ) -> Self {
+        Self {
+            nodes: nodes.clone(),
+            edges: edges.clone(),
+            target: Box::new(target),
+        }
+    }
+
+    /// Nodes with an edge from a node.
+    ///
+    /// # Arguments
+    ///
+    /// * `id` - The ID of the source node.
+    pub fn neighbors(&self, id: u64) -> DbResult<Vec<Record<N>>> {
+        let mut neighbors = vec![];
+        for edge in self.edges.select(&id)? {
+            neighbors.extend(self.nodes.select((self.target)(&edge.data))?);
+        }
+
+        Ok(neighbors)
+    }
+
+    /// Breadth-first traversal from a node.
+    ///
+    /// # Arguments
+    ///
+    /// * `start` - The ID of the node to start from.
+    /// * `depth` - The maximum amount of edges between the start and a reached node.
+    ///
+    /// # Returns
+    ///
+    /// Every reached node once, together with its distance from the start, closest first.
+    /// The start node itself is not included.
+    pub fn traverse(&self, start: u64, depth: usize) -> DbResult<Vec<(usize, Record<N>)>> {
+        let mut visited = HashSet::from([start]);
+        let mut queue = VecDeque::from([(start, 0)]);
+        let mut reached = vec![];
+
+        while let Some((id, distance)) = queue.pop_front() {
+            if distance == depth {
+                continue;
+            }
+
+            for edge in self.edges.select(&id)? {
+                let target = (self.target)(&edge.data);
+                if !visited.insert(target) {
+                    continue;
+                }
+
+                // Edges to missing nodes are skipped.
+                if let Some(node) = self.nodes.select(target)? {
+                    queue.push_back((target, distance + 1));
+                    reached.push((distance + 1, node));
+                }
+            }
+        }
+
+        Ok(reached)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde::{Deserialize, Serialize};
+
+    use super::*;
+    use crate::TinyBase;
+
+    #[derive(Serialize, Deserialize, Debug, Clone)]
+    struct Follows {
+        from: u64,
+        to: u64,
+    }
+
+    #[test]
+    fn graph_traverse() {
+        let db = TinyBase::new(None, true);
+        let people: Table<String> = db.open_table("people").unwrap();
+        let follows: Table<Follows> = db.open_table("follows").unwrap();
+        let from = follows.create_index("from", |edge| edge.from).unwrap();
+        let graph = Graph::new(&people, &from, |edge| edge.to);
+
+        let [alice, bob, carol, dave] =
+            ["alice", "bob", "carol", "dave"].map(|name| people.insert(name.to_string()).unwrap());
+        for (from, to) in [(alice, bob), (bob, carol), (carol, alice), (carol, dave)] {
+            follows.insert(Follows { from, to }).unwrap();
+        }
+
+        let reached = |depth| {
+            graph
+                .traverse(alice, depth)
+                .unwrap()
+                .into_iter()
+                .map(|(distance, record)| (distance, record.data))
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(reached(1), [(1, "bob".to_string())]);
+        // The cycle back to alice is not followed.
+        assert_eq!(
+            reached(5),
+            [
+                (1, "bob".to_string()),
+                (2, "carol".to_string()),
+                (3, "dave".to_string())
+            ]
+        );
+        assert_eq!(graph.neighbors(carol).unwrap().len(), 2);
+    }
+}
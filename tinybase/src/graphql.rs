@@ -0,0 +1,494 @@
+use std::collections::VecDeque;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll, Waker};
+use std::thread;
+use std::time::Duration;
+
+use async_graphql::dynamic::{
+    Field, FieldFuture, FieldValue, InputValue, Object, ResolverContext, Scalar, Schema,
+    SchemaError, Subscription, SubscriptionField, SubscriptionFieldFuture, TypeRef,
+};
+use async_graphql::futures_util::{Stream, StreamExt};
+use async_graphql::{to_value, Value};
+
+use crate::index::IndexType;
+use crate::table::TableType;
+use crate::{Event, Index, Record, Table};
+
+/// Name of the scalar used for record data, which is passed as arbitrary JSON.
+const JSON: &str = "JSON";
+
+/// Builds a GraphQL schema exposing tables and their indexes.
+///
+/// For every table registered as `Person` the schema contains:
+///
+/// * `person(id: ID!): Person` and `personBy<Index>(value: JSON!): [Person!]!` queries.
+/// * `insertPerson(data: JSON!): ID!`, `updatePerson(id: ID!, data: JSON!): Person`
+///   and `deletePerson(id: ID!): Person` mutations.
+/// * A `personEvents: PersonEvent!` subscription streaming all changes to the table.
+///
+/// Records are returned as `{ id: ID!, data: JSON! }`.
+pub struct GraphQlBuilder {
+    types: Vec<Object>,
+    query: Object,
+    mutation: Object,
+    subscription: Subscription,
+}
+
+impl Default for GraphQlBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl GraphQlBuilder {
+    pub fn new() -> Self {
+        Self {
+            types: Vec::new(),
+            query: Object::new("Query"),
+            mutation: Object::new("Mutation"),
+            subscription: Subscription::new("Subscription"),
+        }
+    }
+
+    /// Expose a table in the schema.
+    ///
+    /// # Arguments
+    ///
+    /// * `table` - The table and indexes to expose.
+    pub fn table<T: TableType + 'static>(mut self, table: TableSchema<T>) -> Self {
+        let TableSchema {
+            name,
+            table,
+            indexes,
+        } = table;
+        let field = lower_first(&name);
+
+        self.types.push(record_type(&name));
+        self.types.push(event_type(&name));
+
+        self.query = self.query.field(
+            Field::new(&field, TypeRef::named(&name), {
+                let table = table.clone();
+                move |ctx| {
+                    let table = table.clone();
+                    FieldFuture::new(async move {
+                        let id = id_arg(&ctx)?;
+                        table.select(id)?.map(record_value).transpose()
+                    })
+                }
+            })
+            .argument(InputValue::new("id", TypeRef::named_nn(TypeRef::ID))),
+        );
+
+        for (index, resolver) in indexes {
+            self.query = self.query.field(
+                Field::new(
+                    format!("{}By{}", field, upper_first(&index)),
+                    TypeRef::named_nn_list_nn(&name),
+                    resolver,
+                )
+                .argument(InputValue::new("value", TypeRef::named_nn(JSON))),
+            );
+        }
+
+        self.mutation = self
+            .mutation
+            .field(
+                Field::new(format!("insert{}", name), TypeRef::named_nn(TypeRef::ID), {
+                    let table = table.clone();
+                    move |ctx| {
+                        let table = table.clone();
+                        FieldFuture::new(async move {
+                            let data = ctx.args.try_get("data")?.deserialize::<T>()?;
+                            Ok(Some(Value::from(table.insert(data)?.to_string())))
+                        })
+                    }
+                })
+                .argument(InputValue::new("data", TypeRef::named_nn(JSON))),
+            )
+            .field(
+                Field::new(format!("update{}", name), TypeRef::named(&name), {
+                    let table = table.clone();
+                    move |ctx| {
+                        let table = table.clone();
+                        FieldFuture::new(async move {
+                            let id = id_arg(&ctx)?;
+                            let data = ctx.args.try_get("data")?.deserialize::<T>()?;
+                            let record = table.update(&[id], |_| data.clone())?.pop();
+                            record.map(record_value).transpose()
+                        })
+                    }
+                })
+                .argument(InputValue::new("id", TypeRef::named_nn(TypeRef::ID)))
+                .argument(InputValue::new("data", TypeRef::named_nn(JSON))),
+            )
+            .field(
+                Field::new(format!("delete{}", name), TypeRef::named(&name), {
+                    let table = table.clone();
+                    move |ctx| {
+                        let table = table.clone();
+                        FieldFuture::new(async move {
+                            let id = id_arg(&ctx)?;
+                            table.delete(id)?.map(record_value).transpose()
+                        })
+                    }
+                })
+                .argument(InputValue::new("id", TypeRef::named_nn(TypeRef::ID))),
+            );
+
+        self.subscription = self.subscription.field(SubscriptionField::new(
+            format!("{}Events", field),
+            TypeRef::named_nn(format!("{}Event", name)),
+            move |_| {
+                let table = table.clone();
+                SubscriptionFieldFuture::new(async move {
+                    let events = EventStream::new(&table)?;
+                    Ok(events.map(|event| event.map(FieldValue::owned_any)))
+                })
+            },
+        ));
+
+        self
+    }
+
+    /// Build the schema.
+    ///
+    /// # Returns
+    ///
+    /// The [`Schema`], or an error if no tables were registered.
+    pub fn finish(self) -> Result<Schema, SchemaError> {
+        let mut builder = Schema::build(
+            self.query.type_name(),
+            Some(self.mutation.type_name()),
+            Some(self.subscription.type_name()),
+        )
+        .register(Scalar::new(JSON))
+        .register(self.query)
+        .register(self.mutation)
+        .register(self.subscription);
+
+        for ty in self.types {
+            builder = builder.register(ty);
+        }
+
+        builder.finish()
+    }
+}
+
+type Resolver = Box<dyn for<'a> Fn(ResolverContext<'a>) -> FieldFuture<'a> + Send + Sync>;
+
+/// A table and the indexes which should be queryable through GraphQL.
+pub struct TableSchema<T: TableType + 'static> {
+    name: String,
+    table: Table<T>,
+    indexes: Vec<(String, Resolver)>,
+}
+
+impl<T: TableType + 'static> TableSchema<T> {
+    /// # Arguments
+    ///
+    /// * `name` - Name of the GraphQL type of the records, like `Person`.
+    /// * `table` - The table to expose.
+    pub fn new(name: &str, table: &Table<T>) -> Self {
+        Self {
+            name: name.to_owned(),
+            table: table.clone(),
+            indexes: Vec::new(),
+        }
+    }
+
+    /// Expose an index as a query.
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - Name of the index in the query, `name` becomes `personByName`.
+    /// * `index` - The index to query.
+    pub fn index<I: IndexType + 'static>(mut self, name: &str, index: &Index<T, I>) -> Self {
+        let index = index.clone();
+        let resolver: Resolver = Box::new(move |ctx| {
+            let index = index.clone();
+            FieldFuture::new(async move {
+                let query = ctx.args.try_get("value")?.deserialize::<I>()?;
+                let records = index
+                    .select(&query)?
+                    .into_iter()
+                    .map(record_value)
+                    .collect::<async_graphql::Result<Vec<_>>>()?;
+
+                Ok(Some(FieldValue::list(records)))
+            })
+        });
+
+        self.indexes.push((name.to_owned(), resolver));
+        self
+    }
+}
+
+/// Object type of a record, resolved from a [`Record<Value>`].
+fn record_type(name: &str) -> Object {
+    Object::new(name)
+        .field(Field::new("id", TypeRef::named_nn(TypeRef::ID), |ctx| {
+            FieldFuture::new(async move {
+                let record = ctx.parent_value.try_downcast_ref::<Record<Value>>()?;
+                Ok(Some(Value::from(record.id.to_string())))
+            })
+        }))
+        .field(Field::new("data", TypeRef::named_nn(JSON), |ctx| {
+            FieldFuture::new(async move {
+                let record = ctx.parent_value.try_downcast_ref::<Record<Value>>()?;
+                Ok(Some(record.data.clone()))
+            })
+        }))
+}
+
+/// A change to a table, converted for GraphQL.
+struct EventValue {
+    kind: &'static str,
+    /// ID of the changed record, [`None`] if the table was cleared.
+    id: Option<u64>,
+    data: Option<Value>,
+    old_data: Option<Value>,
+}
+
+impl EventValue {
+    fn new<T: TableType>(event: Event<T>) -> async_graphql::Result<Self> {
+        Ok(match event {
+            Event::Insert(record) => Self {
+                kind: "INSERT",
+                id: Some(record.id),
+                data: Some(to_value(&record.data)?),
+                old_data: None,
+            },
+            Event::Update {
+                id,
+                old_data,
+                new_data,
+            } => Self {
+                kind: "UPDATE",
+                id: Some(id),
+                data: Some(to_value(&new_data)?),
+                old_data: Some(to_value(&old_data)?),
+            },
+            Event::Remove(record) => Self {
+                kind: "REMOVE",
+                id: Some(record.id),
+                data: None,
+                old_data: Some(to_value(&record.data)?),
+            },
+            Event::Cleared => Self {
+                kind: "CLEARED",
+                id: None,
+                data: None,
+                old_data: None,
+            },
+        })
+    }
+}
+
+/// Object type of a change, resolved from an [`EventValue`].
+fn event_type(name: &str) -> Object {
+    let field = |name: &str, ty: TypeRef, get: fn(&EventValue) -> Option<Value>| {
+        Field::new(name, ty, move |ctx| {
+            FieldFuture::new(async move {
+                let event = ctx.parent_value.try_downcast_ref::<EventValue>()?;
+                Ok(get(event))
+            })
+        })
+    };
+
+    Object::new(format!("{}Event", name))
+        .field(field("kind", TypeRef::named_nn(TypeRef::STRING), |event| {
+            Some(Value::from(event.kind))
+        }))
+        .field(field("id", TypeRef::named(TypeRef::ID), |event| {
+            event.id.map(|id| Value::from(id.to_string()))
+        }))
+        .field(field("data", TypeRef::named(JSON), |event| {
+            event.data.clone()
+        }))
+        .field(field("oldData", TypeRef::named(JSON), |event| {
+            event.old_data.clone()
+        }))
+}
+
+/// Convert a record so it can be resolved by its object type.
+fn record_value<T: TableType>(record: Record<T>) -> async_graphql::Result<FieldValue<'static>> {
+    Ok(FieldValue::owned_any(Record {
+        id: record.id,
+        data: to_value(&record.data)?,
+    }))
+}
+
+/// Parse the `id` argument of a field.
+fn id_arg(ctx: &ResolverContext) -> async_graphql::Result<u64> {
+    Ok(ctx.args.try_get("id")?.string()?.parse()?)
+}
+
+fn lower_first(name: &str) -> String {
+    let mut chars = name.chars();
+    chars
+        .next()
+        .map(|first| first.to_lowercase().chain(chars).collect())
+        .unwrap_or_default()
+}
+
+fn upper_first(name: &str) -> String {
+    let mut chars = name.chars();
+    chars
+        .next()
+        .map(|first| first.to_uppercase().chain(chars).collect())
+        .unwrap_or_default()
+}
+
+/// Events received from a table subscription which weren't polled yet.
+#[derive(Default)]
+struct StreamState {
+    events: VecDeque<async_graphql::Result<EventValue>>,
+    waker: Option<Waker>,
+    closed: bool,
+}
+
+/// Async stream of table events.
+///
+/// Subscriptions are blocking, so a thread forwards events until either the table
+/// is closed or the stream is dropped.
+struct EventStream(Arc<Mutex<StreamState>>);
+
+impl EventStream {
+    /// How often the forwarding thread checks if the stream was dropped.
+    const POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+    fn new<T: TableType + 'static>(table: &Table<T>) -> crate::DbResult<Self> {
+        let subscription = table.subscribe()?;
+        let state = Arc::new(Mutex::new(StreamState::default()));
+        let shared = Arc::downgrade(&state);
+
+        thread::spawn(move || loop {
+            let received = subscription.recv_timeout(Self::POLL_INTERVAL);
+            let Some(state) = shared.upgrade() else {
+                break;
+            };
+            let mut state = state.lock().unwrap();
+
+            match received {
+                Ok(Some(event)) => state.events.push_back(EventValue::new(event)),
+                Ok(None) => continue,
+                Err(_) => state.closed = true,
+            }
+
+            if let Some(waker) = state.waker.take() {
+                waker.wake();
+            }
+
+            if state.closed {
+                break;
+            }
+        });
+
+        Ok(Self(state))
+    }
+}
+
+impl Stream for EventStream {
+    type Item = async_graphql::Result<EventValue>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let mut state = self.0.lock().unwrap();
+
+        match state.events.pop_front() {
+            Some(event) => Poll::Ready(Some(event)),
+            None if state.closed => Poll::Ready(None),
+            None => {
+                state.waker = Some(cx.waker().clone());
+                Poll::Pending
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::future::Future;
+    use std::pin::pin;
+    use std::task::{Context, Poll, Waker};
+
+    use async_graphql::value;
+
+    use super::*;
+    use crate::TinyBase;
+
+    /// Drive a future to completion on the current thread.
+    fn block_on<F: Future>(future: F) -> F::Output {
+        let mut future = pin!(future);
+        let mut cx = Context::from_waker(Waker::noop());
+        loop {
+            if let Poll::Ready(output) = future.as_mut().poll(&mut cx) {
+                return output;
+            }
+            thread::yield_now();
+        }
+    }
+
+    #[test]
+    fn graphql_queries_and_mutations() {
+        let db = TinyBase::new(None, true);
+        let table: Table<String> = db.open_table("people").unwrap();
+        let by_name = table.create_index("name", |name| name.to_owned()).unwrap();
+
+        let schema = GraphQlBuilder::new()
+            .table(TableSchema::new("Person", &table).index("name", &by_name))
+            .finish()
+            .unwrap();
+
+        let response = block_on(schema.execute(r#"mutation { insertPerson(data: "John") }"#));
+        let id = table.insert("Jane".to_string()).unwrap();
+        assert!(response.errors.is_empty());
+
+        let response = block_on(schema.execute(format!(
+            r#"mutation {{ updatePerson(id: "{}", data: "Jenny") {{ data }} }}"#,
+            id
+        )));
+        assert_eq!(
+            response.data,
+            value!({ "updatePerson": { "data": "Jenny" } })
+        );
+
+        let response = block_on(schema.execute(format!(
+            r#"{{ person(id: "{}") {{ id data }} personByName(value: "John") {{ data }} }}"#,
+            id
+        )));
+        assert_eq!(
+            response.data,
+            value!({
+                "person": { "id": id.to_string(), "data": "Jenny" },
+                "personByName": [{ "data": "John" }],
+            })
+        );
+    }
+
+    #[test]
+    fn graphql_subscription() {
+        let db = TinyBase::new(None, true);
+        let table: Table<String> = db.open_table("people").unwrap();
+
+        let schema = GraphQlBuilder::new()
+            .table(TableSchema::new("Person", &table))
+            .finish()
+            .unwrap();
+
+        let mut stream = schema.execute_stream("subscription { personEvents { kind data } }");
+        let mut cx = Context::from_waker(Waker::noop());
+        // Start the subscription before writing.
+        assert!(stream.poll_next_unpin(&mut cx).is_pending());
+
+        table.insert("John".to_string()).unwrap();
+
+        let response = block_on(stream.next()).unwrap();
+        assert_eq!(
+            response.data,
+            value!({ "personEvents": { "kind": "INSERT", "data": "John" } })
+        );
+    }
+}
@@ -0,0 +1,398 @@
+use std::cell::Cell;
+use std::marker::PhantomData;
+use std::ops::Deref;
+use std::sync::Arc;
+
+use crate::index::IndexType;
+use crate::patch::Patch;
+use crate::query_builder::QueryCondition;
+use crate::result::DbResult;
+use crate::table::{TableType, UpsertOutcome};
+use crate::transaction::Transaction;
+use crate::{
+    ConditionBuilder, Constraint, Cursor, Direction, Index, QueryBuilder, QueryPlan, Record, Table,
+    TinyBase,
+};
+
+/// The only handle which can write to a database split with [`TinyBase::split`].
+///
+/// The writer can be moved to another thread but not shared, and the tables it
+/// opens borrow it, so all writes happen on the thread owning the writer.
+pub struct Writer {
+    db: Arc<TinyBase>,
+    _not_sync: PhantomData<Cell<()>>,
+}
+
+impl Writer {
+    /// Open a table for writing.
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - The name of the table.
+    ///
+    /// # Returns
+    ///
+    /// A [`WriteTable`] sharing its state with the readers' tables of the same name.
+    pub fn open_table<T: TableType>(&self, name: &str) -> DbResult<WriteTable<'_, T>> {
+        Ok(WriteTable {
+            table: ReadTable(self.db.open_table(name)?),
+            _writer: PhantomData,
+        })
+    }
+
+    /// Flush all pending writes to disk.
+    pub fn flush(&self) -> DbResult<()> {
+        self.db.flush()
+    }
+
+    /// Publish a snapshot for readers of other processes, see [`TinyBase::publish_snapshot`].
+    pub fn publish_snapshot(&self) -> DbResult<()> {
+        self.db.publish_snapshot()
+    }
+}
+
+/// A read-only handle to a database split with [`TinyBase::split`].
+#[derive(Clone)]
+pub struct Reader(Arc<TinyBase>);
+
+impl Reader {
+    /// Open a table for reading.
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - The name of the table.
+    ///
+    /// # Returns
+    ///
+    /// A [`ReadTable`] sharing its state with the writer's table of the same name.
+    pub fn open_table<T: TableType>(&self, name: &str) -> DbResult<ReadTable<T>> {
+        Ok(ReadTable(self.0.open_table(name)?))
+    }
+}
+
+pub(crate) fn split(db: TinyBase) -> (Writer, Reader) {
+    let db = Arc::new(db);
+    (
+        Writer {
+            db: db.clone(),
+            _not_sync: PhantomData,
+        },
+        Reader(db),
+    )
+}
+
+/// A table opened by the [`Writer`], which can't outlive it or leave its thread.
+///
+/// Reads are available through [`ReadTable`].
+pub struct WriteTable<'w, T: TableType + 'static> {
+    table: ReadTable<T>,
+    _writer: PhantomData<&'w Writer>,
+}
+
+impl<T: TableType> Deref for WriteTable<'_, T> {
+    type Target = ReadTable<T>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.table
+    }
+}
+
+impl<'w, T: TableType> WriteTable<'w, T> {
+    /// Create an index on the table, see [`Table::create_index`].
+    ///
+    /// # Returns
+    ///
+    /// A [`WriteIndex`] for the created index.
+    pub fn create_index<I: IndexType + 'static>(
+        &self,
+        name: &str,
+        key_func: impl Fn(&T) -> I + Send + Sync + 'static,
+    ) -> DbResult<WriteIndex<'w, T, I>> {
+        Ok(WriteIndex {
+            index: ReadIndex(self.table.0.create_index(name, key_func)?),
+            _writer: PhantomData,
+        })
+    }
+
+    /// Add a constraint to the table, see [`Table::constraint`].
+    pub fn constraint(&self, constraint: Constraint<T>) -> DbResult<()> {
+        self.table.0.constraint(constraint)
+    }
+
+    /// Insert a new record, see [`Table::insert`].
+    pub fn insert(&self, value: T) -> DbResult<u64> {
+        self.table.0.insert(value)
+    }
+
+    /// Insert many values in a single commit, see [`Table::insert_many`].
+    pub fn insert_many(&self, values: Vec<T>) -> DbResult<Vec<u64>> {
+        self.table.0.insert_many(values)
+    }
+
+    /// Update records by their IDs, see [`Table::update`].
+    pub fn update(&self, ids: &[u64], updater: impl Fn(T) -> T) -> DbResult<Vec<Record<T>>> {
+        self.table.0.update(ids, updater)
+    }
+
+    /// Replace the data of a record if it wasn't written since it was read, see [`Table::update_cas`].
+    pub fn update_cas(&self, id: u64, expected_version: u64, value: T) -> DbResult<Option<u64>> {
+        self.table.0.update_cas(id, expected_version, value)
+    }
+
+    /// Apply a patch to a record, see [`Table::patch`].
+    pub fn patch(&self, id: u64, patch: impl Patch<T>) -> DbResult<Option<Record<T>>> {
+        self.table.0.patch(id, patch)
+    }
+
+    /// Insert a value or replace the record with the same key, see [`Table::upsert`].
+    pub fn upsert<I: IndexType + 'static>(
+        &self,
+        key_index: &WriteIndex<'w, T, I>,
+        value: T,
+    ) -> DbResult<UpsertOutcome> {
+        self.table.0.upsert(&key_index.index.0, value)
+    }
+
+    /// Delete a record by its ID, see [`Table::delete`].
+    pub fn delete(&self, id: u64) -> DbResult<Option<Record<T>>> {
+        self.table.0.delete(id)
+    }
+
+    /// Delete records by their IDs in a single commit, see [`Table::delete_many`].
+    pub fn delete_many(&self, ids: &[u64]) -> DbResult<Vec<Record<T>>> {
+        self.table.0.delete_many(ids)
+    }
+
+    /// Delete all records, see [`Table::clear`].
+    pub fn clear(&self) -> DbResult<usize> {
+        self.table.0.clear()
+    }
+
+    /// Write to the table in a transaction, see [`Table::transaction`].
+    pub fn transaction<R>(
+        &self,
+        f: impl FnOnce(&mut Transaction<'_, T>) -> DbResult<R>,
+    ) -> DbResult<R> {
+        self.table.0.transaction(f)
+    }
+}
+
+/// A table which only exposes read operations.
+pub struct ReadTable<T: TableType + 'static>(Table<T>);
+
+impl<T: TableType> Clone for ReadTable<T> {
+    fn clone(&self) -> Self {
+        Self(self.0.clone())
+    }
+}
+
+impl<T: TableType> ReadTable<T> {
+    /// Select a record by its ID.
+    ///
+    /// # Arguments
+    ///
+    /// * `id` - The ID of the record to select.
+    ///
+    /// # Returns
+    ///
+    /// An [`Option`] containing the selected record if it exists, or [`None`] otherwise.
+    pub fn select(&self, id: u64) -> DbResult<Option<Record<T>>> {
+        self.0.select(id)
+    }
+
+    /// Build a query on the table which can only read the selected records.
+    ///
+    /// Conditions are built with the [`ReadIndex`] instances of the table.
+    ///
+    /// # Returns
+    ///
+    /// A [`ReadQuery`] without a condition.
+    pub fn query(&self) -> ReadQuery<T> {
+        ReadQuery(QueryBuilder::new(&self.0))
+    }
+}
+
+/// A query on a [`ReadTable`], see [`QueryBuilder`] for its operations.
+pub struct ReadQuery<T: TableType + 'static>(QueryBuilder<T>);
+
+impl<T: TableType> ReadQuery<T> {
+    /// Set the condition of the query, see [`QueryBuilder::with_condition`].
+    pub fn with_condition<C: Into<QueryCondition<T>>>(self, condition: C) -> Self {
+        Self(self.0.with_condition(condition))
+    }
+
+    /// Order the selected records by the keys of an index, see [`QueryBuilder::order_by`].
+    pub fn order_by<I: IndexType + Ord + 'static>(
+        self,
+        index: &ReadIndex<T, I>,
+        direction: Direction,
+    ) -> Self {
+        Self(self.0.order_by(&index.0, direction))
+    }
+
+    /// Order records with equal keys by the keys of another index, see [`QueryBuilder::then_by`].
+    pub fn then_by<I: IndexType + Ord + 'static>(
+        self,
+        index: &ReadIndex<T, I>,
+        direction: Direction,
+    ) -> Self {
+        Self(self.0.then_by(&index.0, direction))
+    }
+
+    /// Select the records matching the query, see [`QueryBuilder::select`].
+    pub fn select(self) -> DbResult<Vec<Record<T>>> {
+        self.0.select()
+    }
+
+    /// Select the IDs of the records matching the query, see [`QueryBuilder::select_ids`].
+    pub fn select_ids(self) -> DbResult<Vec<u64>> {
+        self.0.select_ids()
+    }
+
+    /// Select one page of the records matching the query, see [`QueryBuilder::select_page`].
+    pub fn select_page(
+        self,
+        cursor: Option<Cursor>,
+        page_size: usize,
+    ) -> DbResult<(Vec<Record<T>>, Option<Cursor>)> {
+        self.0.select_page(cursor, page_size)
+    }
+
+    /// Select the first record matching the query, see [`QueryBuilder::first`].
+    pub fn first(self) -> DbResult<Option<Record<T>>> {
+        self.0.first()
+    }
+
+    /// Count the records matching the query, see [`QueryBuilder::count`].
+    pub fn count(self) -> DbResult<usize> {
+        self.0.count()
+    }
+
+    /// Describe how the query would be executed, see [`QueryBuilder::explain`].
+    pub fn explain(&self) -> DbResult<QueryPlan> {
+        self.0.explain()
+    }
+}
+
+/// An index which only exposes read operations.
+///
+/// Indexes are created by the [`Writer`], readers use clones of the [`ReadIndex`]
+/// which a [`WriteIndex`] dereferences to.
+pub struct ReadIndex<T: TableType + 'static, I: IndexType>(Index<T, I>);
+
+impl<T: TableType, I: IndexType> Clone for ReadIndex<T, I> {
+    fn clone(&self) -> Self {
+        Self(self.0.clone())
+    }
+}
+
+impl<T: TableType, I: IndexType + 'static> ReadIndex<T, I> {
+    /// Select records from the table based on the given query.
+    ///
+    /// # Arguments
+    ///
+    /// * `query` - A reference to the query key.
+    ///
+    /// # Returns
+    ///
+    /// All selected [`Record`] instances.
+    pub fn select(&self, query: &I) -> DbResult<Vec<Record<T>>> {
+        self.0.select(query)
+    }
+
+    pub fn index_name(&self) -> String {
+        self.0.index_name()
+    }
+
+    /// Condition matching the records with a key, see [`ConditionBuilder::by`].
+    pub fn by(&self, value: I) -> ConditionBuilder<T> {
+        ConditionBuilder::by(&self.0, value)
+    }
+
+    /// Condition matching the records with any of many keys, see [`ConditionBuilder::by_any`].
+    pub fn by_any(&self, values: Vec<I>) -> ConditionBuilder<T> {
+        ConditionBuilder::by_any(&self.0, values)
+    }
+
+    /// Condition matching the records with a key in a range, see [`ConditionBuilder::between`].
+    pub fn between(&self, low: I, high: I) -> ConditionBuilder<T> {
+        ConditionBuilder::between(&self.0, low, high)
+    }
+
+    /// Condition matching the records with a greater key, see [`ConditionBuilder::greater_than`].
+    pub fn greater_than(&self, value: I) -> ConditionBuilder<T> {
+        ConditionBuilder::greater_than(&self.0, value)
+    }
+
+    /// Condition matching the records with a smaller key, see [`ConditionBuilder::less_than`].
+    pub fn less_than(&self, value: I) -> ConditionBuilder<T> {
+        ConditionBuilder::less_than(&self.0, value)
+    }
+}
+
+/// An index created by the [`Writer`], see [`WriteTable`].
+///
+/// Reads are available through [`ReadIndex`].
+pub struct WriteIndex<'w, T: TableType + 'static, I: IndexType> {
+    index: ReadIndex<T, I>,
+    _writer: PhantomData<&'w Writer>,
+}
+
+impl<T: TableType, I: IndexType> Deref for WriteIndex<'_, T, I> {
+    type Target = ReadIndex<T, I>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.index
+    }
+}
+
+impl<T: TableType, I: IndexType + 'static> WriteIndex<'_, T, I> {
+    /// A constraint requiring the keys of this index to be unique, see [`Constraint::unique`].
+    pub fn unique(&self) -> Constraint<T> {
+        Constraint::unique(&self.index.0)
+    }
+
+    /// Delete the records with a key, see [`Index::delete`].
+    pub fn delete(&self, query: &I) -> DbResult<Vec<Record<T>>> {
+        self.index.0.delete(query)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{Direction, TinyBase};
+
+    #[test]
+    fn split_writer_reader() {
+        let (writer, reader) = TinyBase::new(None, true).split().unwrap();
+
+        let table = writer.open_table::<String>("test_table").unwrap();
+        let id = table.insert("value1".to_string()).unwrap();
+        let write_index = table
+            .create_index("name", |value| value.to_owned())
+            .unwrap();
+        let index = (*write_index).clone();
+
+        let reader = reader.clone();
+        let read_table = reader.open_table::<String>("test_table").unwrap();
+        assert_eq!(read_table.select(id).unwrap().unwrap().data, "value1");
+
+        // Writes through the writer reach the indexes of the readers.
+        table.insert("value2".to_string()).unwrap();
+        assert_eq!(index.select(&"value2".to_string()).unwrap().len(), 1);
+
+        let query = || {
+            read_table
+                .query()
+                .with_condition(index.between("value1".to_string(), "value2".to_string()))
+                .order_by(&index, Direction::Desc)
+        };
+        assert_eq!(query().count().unwrap(), 2);
+        assert_eq!(query().first().unwrap().unwrap().data, "value2");
+
+        let (page, cursor) = query().select_page(None, 1).unwrap();
+        assert_eq!(page[0].data, "value2");
+        let (page, _) = query().select_page(cursor, 1).unwrap();
+        assert_eq!(page[0].data, "value1");
+    }
+}
@@ -0,0 +1,177 @@
+use std::collections::{HashSet, VecDeque};
+
+use crate::index::Index;
+use crate::record::Record;
+use crate::result::{DbResult, TinyBaseError};
+use crate::table::{Table, TableType};
+
+/// Parent-child relations between the records of a table, such as category trees
+/// or comment threads.
+///
+/// The parent of every record is indexed, so whole subtrees are resolved without
+/// querying each level from application code. Relations forming a cycle are
+/// reported as [`TinyBaseError::Cycle`].
+pub struct Hierarchy<T: TableType + 'static> {
+    table: Table<T>,
+    parents: Index<T, Option<u64>>,
+}
+
+impl<T: TableType> Hierarchy<T> {
+    /// Create the parent index of a table.
+    ///
+    /// # Arguments
+    ///
+    /// * `table` - The table containing the records.
+    /// * `name` - The name of the parent index.
+    /// * `parent` - A function returning the parent ID of a record, [`None`] for roots.
+    pub fn new(
+        table: &Table<T>,
+        name: &str,
+        parent: impl Fn(&T) -> Option<u64> + Send + Sync + 'static,
+    ) -> DbResult<Self> {
+        Ok(Self {
+            table: table.clone(),
+            parents: table.create_index(name, parent)?,
+        })
+    }
+
+    /// Records without a parent.
+    pub fn roots(&self) -> DbResult<Vec<Record<T>>> {
+        self.parents.select(&None)
+    }
+
+    /// Direct children of a record.
+    ///
+    /// # Arguments
+    ///
+    /// * `id` - The ID of the parent record.
+    pub fn children(&self, id: u64) -> DbResult<Vec<Record<T>>> {
+        self.parents.select(&Some(id))
+    }
+
+    /// All records below a record, level by level.
+    ///
+    /// # Arguments
+    ///
+    /// * `id` - The ID of the record whose subtree is selected.
+    ///
+    /// # Returns
+    ///
+    /// The descendants, closest first, not including the record itself.
+    pub fn descendants(&self, id: u64) -> DbResult<Vec<Record<T>>> {
+        let mut visited = HashSet::from([id]);
+        let mut queue = VecDeque::from([id]);
+        let mut descendants = vec![];
+
+        while let Some(parent) = queue.pop_front() {
+            for child in self.children(parent)? {
+                // Every record has a single parent, so it can only be reached twice through a cycle.
+                if !visited.insert(child.id) {
+                    return Err(TinyBaseError::Cycle(child.id));
+                }
+
+                queue.push_back(child.id);
+                descendants.push(child);
+            }
+        }
+
+        Ok(descendants)
+    }
+
+    /// All records above a record up to its root.
+    ///
+    /// # Arguments
+    ///
+    /// * `id` - The ID of the record whose ancestors are selected.
+    ///
+    /// # Returns
+    ///
+    /// The ancestors starting with the parent. A missing parent ends the chain.
+    pub fn ancestors(&self, id: u64) -> DbResult<Vec<Record<T>>> {
+        let mut visited = HashSet::from([id]);
+        let mut ancestors: Vec<Record<T>> = vec![];
+        let mut current = self.table.select(id)?;
+
+        while let Some(parent) = current
+            .as_ref()
+            .and_then(|record| self.parents.keys_of(&record.data).pop().flatten())
+        {
+            if !visited.insert(parent) {
+                return Err(TinyBaseError::Cycle(parent));
+            }
+
+            current = self.table.select(parent)?;
+            ancestors.extend(current.clone());
+        }
+
+        Ok(ancestors)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde::{Deserialize, Serialize};
+
+    use super::*;
+    use crate::TinyBase;
+
+    #[derive(Serialize, Deserialize, Debug, Clone)]
+    struct Category {
+        name: String,
+        parent: Option<u64>,
+    }
+
+    #[test]
+    fn hierarchy_traversal() {
+        let db = TinyBase::new(None, true);
+        let table: Table<Category> = db.open_table("categories").unwrap();
+        let hierarchy = Hierarchy::new(&table, "parent", |category| category.parent).unwrap();
+
+        let insert = |name: &str, parent| {
+            table
+                .insert(Category {
+                    name: name.to_string(),
+                    parent,
+                })
+                .unwrap()
+        };
+
+        let root = insert("root", None);
+        let child = insert("child", Some(root));
+        let grandchild = insert("grandchild", Some(child));
+        insert("sibling", Some(root));
+
+        let names = |records: Vec<Record<Category>>| {
+            records
+                .into_iter()
+                .map(|record| record.data.name)
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(names(hierarchy.roots().unwrap()), ["root"]);
+        assert_eq!(
+            names(hierarchy.descendants(root).unwrap()),
+            ["child", "sibling", "grandchild"]
+        );
+        assert_eq!(
+            names(hierarchy.ancestors(grandchild).unwrap()),
+            ["child", "root"]
+        );
+
+        // Make the root a child of its grandchild.
+        table
+            .update(&[root], |category| Category {
+                parent: Some(grandchild),
+                ..category
+            })
+            .unwrap();
+        assert!(matches!(
+            hierarchy.descendants(root),
+            Err(TinyBaseError::Cycle(_))
+        ));
+        assert!(matches!(
+            hierarchy.ancestors(child),
+            Err(TinyBaseError::Cycle(_))
+        ));
+    }
+}
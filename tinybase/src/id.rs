@@ -0,0 +1,152 @@
+use std::hash::{BuildHasher, RandomState};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+use std::thread;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use crate::result::DbResult;
+
+/// Generates the IDs of records inserted into a table, see [`crate::table::TableInner::id_generator`].
+///
+/// Generated IDs must be unique within the table, inserting a record with an ID
+/// which is already used fails with [`crate::result::TinyBaseError::DuplicateId`].
+/// Tables without a generator use sequential IDs shared by the whole database.
+pub trait IdGenerator: Send + Sync {
+    fn generate_id(&self) -> DbResult<u64>;
+}
+
+impl<F: Fn() -> u64 + Send + Sync> IdGenerator for F {
+    fn generate_id(&self) -> DbResult<u64> {
+        Ok(self())
+    }
+}
+
+/// IDs counting up from `1`, independent of other tables and the database state.
+///
+/// Meant for deterministic IDs in tests, the counter isn't persisted so it
+/// can't be used for tables which already contain records.
+#[derive(Default)]
+pub struct Counter(AtomicU64);
+
+impl Counter {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl IdGenerator for Counter {
+    fn generate_id(&self) -> DbResult<u64> {
+        Ok(self.0.fetch_add(1, Ordering::Relaxed) + 1)
+    }
+}
+
+/// Random IDs which don't reveal how many records were inserted.
+#[derive(Default)]
+pub struct RandomIds {
+    state: RandomState,
+    counter: AtomicU64,
+}
+
+impl RandomIds {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl IdGenerator for RandomIds {
+    fn generate_id(&self) -> DbResult<u64> {
+        Ok(self
+            .state
+            .hash_one(self.counter.fetch_add(1, Ordering::Relaxed)))
+    }
+}
+
+/// Time ordered IDs in the Snowflake layout: 41 bits of milliseconds since
+/// [`Snowflake::EPOCH`], 10 bits of node ID and a 12 bit sequence per millisecond.
+///
+/// Multiple processes can generate IDs for the same data without coordination
+/// as long as each uses another node ID.
+pub struct Snowflake {
+    node: u64,
+    /// Millisecond and sequence of the last generated ID.
+    last: Mutex<(u64, u64)>,
+}
+
+impl Snowflake {
+    /// Start of the timestamps, 2020-01-01 in milliseconds since the Unix epoch.
+    pub const EPOCH: u64 = 1_577_836_800_000;
+    const NODE_BITS: u64 = 10;
+    const SEQUENCE_BITS: u64 = 12;
+
+    /// # Arguments
+    ///
+    /// * `node` - ID of the generating node, only the lower 10 bits are used.
+    pub fn new(node: u16) -> Self {
+        Self {
+            node: node as u64 & ((1 << Self::NODE_BITS) - 1),
+            last: Mutex::new((0, 0)),
+        }
+    }
+
+    fn now() -> u64 {
+        let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap();
+        (now.as_millis() as u64).saturating_sub(Self::EPOCH)
+    }
+}
+
+impl IdGenerator for Snowflake {
+    fn generate_id(&self) -> DbResult<u64> {
+        let mut last = self.last.lock().unwrap();
+
+        let (millis, sequence) = loop {
+            // Never go back in time, even if the clock does.
+            let now = Self::now().max(last.0);
+            if now > last.0 {
+                break (now, 0);
+            }
+
+            if last.1 + 1 < 1 << Self::SEQUENCE_BITS {
+                break (now, last.1 + 1);
+            }
+
+            // The sequence of this millisecond is exhausted.
+            thread::sleep(Duration::from_millis(1));
+        };
+
+        *last = (millis, sequence);
+        Ok(millis << (Self::NODE_BITS + Self::SEQUENCE_BITS)
+            | self.node << Self::SEQUENCE_BITS
+            | sequence)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::result::TinyBaseError;
+    use crate::{Table, TinyBase};
+
+    #[test]
+    fn snowflake_ordered() {
+        let generator = Snowflake::new(1);
+        let ids: Vec<u64> = (0..10_000)
+            .map(|_| generator.generate_id().unwrap())
+            .collect();
+
+        assert!(ids.windows(2).all(|ids| ids[0] < ids[1]));
+        assert_eq!(ids[0] >> 12 & 0x3ff, 1);
+    }
+
+    #[test]
+    fn table_id_generator() {
+        let db = TinyBase::new(None, true);
+        let table: Table<String> = db.open_table("test_table").unwrap();
+        table.id_generator(|| 42);
+
+        assert_eq!(table.insert("value1".to_string()).unwrap(), 42);
+        assert!(matches!(
+            table.insert("value2".to_string()),
+            Err(TinyBaseError::DuplicateId(42))
+        ));
+    }
+}
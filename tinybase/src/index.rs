@@ -1,23 +1,46 @@
-use std::any::Any;
-use std::ops::Deref;
-use std::sync::{Arc, Weak};
+use std::collections::{BTreeSet, HashSet};
+use std::ops::{Bound, Deref, RangeBounds};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, RwLock, Weak};
 use std::vec;
 
 use serde::de::DeserializeOwned;
-use serde::Serialize;
+use serde::{Deserialize, Serialize};
+use sled::transaction::{
+    ConflictableTransactionError, ConflictableTransactionResult, TransactionalTree,
+};
 use sled::{Db, Tree};
 
 use crate::encoding::{decode, encode};
+use crate::journal::Journal;
+use crate::key_encoding::{decode_key, encode_key};
+use crate::posting::{decode_posting, encode_posting, posting_len, POSTING_FORMAT};
+use crate::query_builder::{Direction, PrefixKey};
+use crate::rebuild::{RebuildProgress, REBUILDS};
 use crate::record::Record;
-use crate::result::DbResult;
-use crate::subscriber::{self, Subscriber};
-use crate::table::{TableInner, TableType};
+use crate::result::{DbResult, TinyBaseError};
+use crate::sketch::{BloomFilter, HyperLogLog, SKETCHES};
+use crate::subscriber::Event;
+use crate::table::{Table, TableInner, TableType};
 
-use self::private::AnyIndexInternal;
+use self::private::{AnyIndexInternal, KeyLookup};
 
 pub trait IndexType: Serialize + DeserializeOwned {}
 impl<T: Serialize + DeserializeOwned> IndexType for T {}
 
+type KeysFn<T, I> = Arc<dyn Fn(&T) -> DbResult<Vec<I>> + Send + Sync>;
+
+/// Version of the encoding of index keys.
+const KEY_FORMAT: u8 = 1;
+
+/// Version of the encoding of keys and posting lists, stored with the sketch of every index.
+pub(crate) const FORMAT: u8 = KEY_FORMAT | POSTING_FORMAT << 4;
+
+/// State of an index stored in the sketches tree: the format, the version of the
+/// key function and the sketch, which is [`None`] once the index was written after
+/// it was saved.
+type StoredSketch = (u8, String, Option<HyperLogLog>);
+
 /// Provides methods for interacting with an index on a typed table.
 pub struct Index<T: TableType + 'static, I: IndexType>(pub(crate) Arc<IndexInner<T, I>>);
 
@@ -27,6 +50,26 @@ impl<T: TableType, I: IndexType> Clone for Index<T, I> {
     }
 }
 
+impl<T: TableType, I: IndexType + 'static> Index<T, I> {
+    /// Rename the index, keeping its data, cursor and metadata. Unique constraints on
+    /// the index are moved to the renamed index.
+    ///
+    /// Writes to the table wait until the index was renamed. [`Index`] handles of the
+    /// old name must not be used afterwards.
+    ///
+    /// # Arguments
+    ///
+    /// * `new_name` - The new name of the index.
+    ///
+    /// # Returns
+    ///
+    /// An [`Index`] instance for the renamed index, or [`TinyBaseError::IndexExists`]
+    /// if the table already has an index with the new name.
+    pub fn rename(&self, new_name: &str) -> DbResult<Index<T, I>> {
+        Table(self.table.upgrade().unwrap()).rename_index(self, new_name)
+    }
+}
+
 impl<T: TableType, I: IndexType> Deref for Index<T, I> {
     type Target = Arc<IndexInner<T, I>>;
 
@@ -35,19 +78,101 @@ impl<T: TableType, I: IndexType> Deref for Index<T, I> {
     }
 }
 
+/// Size and shape of an index, returned by [`IndexInner::stats`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct IndexStats<I> {
+    /// Amount of distinct keys.
+    pub keys: usize,
+    /// Amount of entries of all posting lists, a record with many keys is counted once per key.
+    pub entries: usize,
+    /// Smallest key, [`None`] if the index is empty.
+    pub min: Option<I>,
+    /// Largest key, [`None`] if the index is empty.
+    pub max: Option<I>,
+    /// Average amount of records per key, zero if the index is empty.
+    pub avg_entries_per_key: f64,
+}
+
+/// Description of an index stored with its table, so indexes can be inspected without
+/// their key functions. Returned by [`crate::Table::index_metadata`].
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct IndexMetadata {
+    /// Name of the index.
+    pub name: String,
+    /// Name of the key type, as given by [`std::any::type_name`].
+    ///
+    /// Empty for indexes stored before their metadata was, until they're created again.
+    pub key_type: String,
+    /// Version of the encoding of the keys and posting lists.
+    pub format: u8,
+    /// Version of the key function, see [`crate::Table::create_index_versioned`].
+    pub version: String,
+    /// Whether records can have any amount of keys rather than exactly one.
+    pub multi_key: bool,
+    /// Whether the string keys are stored case-folded.
+    pub case_folded: bool,
+    /// Whether a unique constraint was added on the index.
+    pub unique: bool,
+    /// When the index was first created, in milliseconds since the Unix epoch.
+    ///
+    /// Zero for indexes stored before their metadata was.
+    pub created_at: u64,
+}
+
+/// Inconsistencies between an index and its table, returned by [`IndexInner::verify`].
+///
+/// Every entry is a key with the ID of a record.
+#[derive(Debug, Clone, PartialEq)]
+pub struct IndexReport<I> {
+    /// Keys of records which are missing from the posting list of the key.
+    pub missing: Vec<(I, u64)>,
+    /// IDs in posting lists whose records don't exist anymore.
+    pub orphaned: Vec<(I, u64)>,
+    /// IDs in posting lists of keys which the key function doesn't return for the record.
+    pub misplaced: Vec<(I, u64)>,
+}
+
+impl<I> IndexReport<I> {
+    /// Whether the index matches the table.
+    pub fn is_consistent(&self) -> bool {
+        self.missing.is_empty() && self.orphaned.is_empty() && self.misplaced.is_empty()
+    }
+}
+
 /// Inner state of an index on a typed table.
 pub struct IndexInner<T: TableType + 'static, I: IndexType> {
-    table: Weak<TableInner<T>>,
-    /// Function which will be used to compute the key per insert.
-    key_func: Box<dyn Fn(&T) -> I + Send + Sync>,
+    pub(crate) table: Weak<TableInner<T>>,
+    /// Function which will be used to compute the keys per insert.
+    pub(crate) keys_func: KeysFn<T, I>,
+    /// Whether every record has exactly one key, so walking the index visits every record once.
+    pub(crate) single_key: bool,
+    /// Version of the key function, see [`crate::Table::create_index_versioned`].
+    pub(crate) version: String,
+    /// Held while the index is rebuilt in the background.
+    rebuild_lock: Mutex<()>,
+    /// Sequence number a running background rebuild started at, the journal entries
+    /// after it are kept until the rebuild finished.
+    rebuilding: Mutex<Option<u64>>,
     /// Built index, each key can have multiple matching records.
     indexed_data: Tree,
-    /// Reference to uncommitted operation log.
-    subscriber: Subscriber<T>,
+    /// Journal of the table, which is replayed when the index was left behind.
+    journal: Journal,
+    /// Sequence number of the last journal entry applied to the index.
+    applied: AtomicU64,
+    /// Sketch of all keys inserted since the last sync, persisted on close and sync.
+    sketch: Mutex<HyperLogLog>,
+    sketches: Tree,
+    /// Whether the stored sketch matches the index, so the next commit has to mark it stale.
+    sketch_saved: AtomicBool,
+    /// Whether the string keys are stored case-folded, see [`crate::Table::create_index_ci`].
+    pub(crate) case_folded: bool,
+    /// Filter of the stored keys, see [`IndexInner::enable_bloom_filter`].
+    bloom: RwLock<Option<BloomFilter>>,
 }
 
 impl<T: TableType, I: IndexType> IndexInner<T, I> {
-    /// Creates a new index with the given name, engine, table data, key function, and subscriber.
+    /// Creates a new index with the given name, engine, table data and key function.
+    /// The index is empty until it caught up with the table, see [`IndexInner::catch_up`].
     ///
     /// This method is intended for internal use and should not be called directly. Instead, use the
     /// [`crate::Table`]'s `create_index()` method.
@@ -57,8 +182,9 @@ impl<T: TableType, I: IndexType> IndexInner<T, I> {
     /// * `idx_name` - The name of the index.
     /// * `engine` - The database engine.
     /// * `table` - A weak pointer to the table.
-    /// * `key_func` - A function which computes the index key for each record.
-    /// * `subscriber` - A subscriber to uncommitted operation log.
+    /// * `keys_func` - A function which computes the index keys for each record.
+    /// * `single_key` - Whether `keys_func` always returns exactly one key.
+    /// * `version` - Version of `keys_func`, the index is rebuilt if it was built with another version.
     ///
     /// # Returns
     ///
@@ -67,108 +193,319 @@ impl<T: TableType, I: IndexType> IndexInner<T, I> {
         idx_name: &str,
         engine: &Db,
         table: Weak<TableInner<T>>,
-        key_func: impl Fn(&T) -> I + Send + Sync + 'static,
-        subscriber: Subscriber<T>,
+        keys_func: impl Fn(&T) -> DbResult<Vec<I>> + Send + Sync + 'static,
+        single_key: bool,
+        version: &str,
     ) -> DbResult<Self> {
-        let new_index = Self {
+        let journal = table.upgrade().unwrap().journal.clone();
+
+        Ok(Self {
             table,
-            key_func: Box::new(key_func),
+            keys_func: Arc::new(keys_func),
+            single_key,
+            version: version.to_owned(),
+            rebuild_lock: Mutex::default(),
+            rebuilding: Mutex::default(),
             indexed_data: engine.open_tree(idx_name)?,
-            subscriber,
-        };
+            journal,
+            applied: AtomicU64::new(0),
+            sketch: Mutex::default(),
+            sketches: engine.open_tree(SKETCHES)?,
+            sketch_saved: AtomicBool::new(false),
+            case_folded: false,
+            bloom: RwLock::default(),
+        })
+    }
 
-        new_index.sync()?;
+    /// Bring a new index up to date with the table, before the table writes to it.
+    ///
+    /// # Arguments
+    ///
+    /// * `root` - The table tree, obtained via a write lock.
+    pub(crate) fn catch_up(&self, root: &Tree) -> DbResult<()> {
+        let name = self.index_name();
+
+        // Indexes built with another key encoding or key function are rebuilt.
+        let sketch = match self.sketches.get(&name)? {
+            Some(bytes) => decode::<StoredSketch>(&bytes)
+                .ok()
+                .filter(|(format, version, _)| *format == FORMAT && *version == self.version),
+            None => None,
+        };
 
-        Ok(new_index)
+        // Catch up with the journal if it still has all entries the index missed.
+        match (self.journal.cursor(&name)?, sketch) {
+            (Some(cursor), Some((_, _, sketch))) if cursor >= self.journal.truncated()? => {
+                // The sketch is stale if the process ended without saving it after writes.
+                *self.sketch.lock().unwrap() = match sketch {
+                    Some(sketch) => sketch,
+                    None => self.sketch_keys()?,
+                };
+                self.applied.store(cursor, Ordering::Release);
+                self.replay()
+            }
+            _ => self.sync_tree(root),
+        }
     }
 
     /// Resync index to be up to date with table.
     pub fn sync(&self) -> DbResult<()> {
-        self.indexed_data.clear()?;
+        let table = self.table.upgrade().unwrap();
+        let root = table.lock_root();
+        self.sync_tree(&root)
+    }
 
+    /// Rebuild the index from the table tree, obtained via a write lock.
+    fn sync_tree(&self, root: &Tree) -> DbResult<()> {
         let table = self.table.upgrade().unwrap();
-        let root = table.root.write().unwrap();
+
+        self.indexed_data.clear()?;
+        *self.sketch.lock().unwrap() = HyperLogLog::default();
         for key in root.iter().keys() {
             // This should always succeed
             if let Some(data) = root.get(&key.clone()?)? {
-                self.insert(&Record {
-                    id: decode(&key?)?,
-                    data: decode(&data)?,
-                })?;
+                let record = table.decode_record(decode(&key?)?, &data)?;
+                self.insert(&self.indexed_data, &self.sketch, &record)?;
             }
         }
 
+        // Every write which happened before the rebuild has a lower sequence number.
+        let applied = table.engine.generate_id()?;
+        self.applied.store(applied, Ordering::Release);
+        self.refill_bloom_filter()?;
+        self.save_cursor()
+    }
+
+    /// Build a sketch of the keys stored in the index.
+    fn sketch_keys(&self) -> DbResult<HyperLogLog> {
+        let mut sketch = HyperLogLog::default();
+        for key in self.indexed_data.iter().keys() {
+            sketch.add(&key?);
+        }
+
+        Ok(sketch)
+    }
+
+    /// Apply the journal entries which the index missed.
+    fn replay(&self) -> DbResult<()> {
+        for entry in self.journal.since(self.applied.load(Ordering::Acquire))? {
+            let (seq, event) = entry?;
+            self.apply(seq, event)?;
+        }
+
+        self.save_cursor()
+    }
+
+    /// Apply a single event, skipping events which were already applied.
+    fn apply(&self, seq: u64, event: Event<T>) -> DbResult<()> {
+        if seq <= self.applied.load(Ordering::Acquire) {
+            return Ok(());
+        }
+
+        self.apply_to(&self.indexed_data, &self.sketch, event)?;
+        self.applied.store(seq, Ordering::Release);
         Ok(())
     }
 
-    /// Commits the received events from the main table to the index.
-    fn commit_log(&self) -> DbResult<()> {
-        // Commit log of events on the main table.
-        while let Ok(event) = self.subscriber.rx.try_recv() {
-            match event {
-                subscriber::Event::Remove(record) => self.remove(&record)?,
-                subscriber::Event::Insert(record) => self.insert(&record)?,
-                subscriber::Event::Update {
-                    id,
-                    old_data,
-                    new_data,
-                } => {
-                    self.remove(&Record { id, data: old_data })?;
-                    self.insert(&Record { id, data: new_data })?;
-                }
+    /// Apply a single event to a tree of the index.
+    fn apply_to(&self, tree: &Tree, sketch: &Mutex<HyperLogLog>, event: Event<T>) -> DbResult<()> {
+        match event {
+            Event::Remove(record) => self.remove(tree, &record),
+            Event::Insert(record) => self.insert(tree, sketch, &record),
+            Event::Update {
+                id,
+                old_data,
+                new_data,
+            } => {
+                self.remove(tree, &Record { id, data: old_data })?;
+                self.insert(tree, sketch, &Record { id, data: new_data })
+            }
+            Event::Cleared => {
+                tree.clear()?;
+                *sketch.lock().unwrap() = HyperLogLog::default();
+                Ok(())
             }
         }
+    }
 
-        Ok(())
+    /// Rebuild the index into a shadow tree while the table stays writable, then replace
+    /// the contents of the index in a single batch.
+    ///
+    /// Writes made during the rebuild are replayed from the journal before the swap.
+    ///
+    /// # Arguments
+    ///
+    /// * `progress` - Counts the records which were indexed.
+    pub(crate) fn rebuild(&self, progress: &RebuildProgress) -> DbResult<()> {
+        // Only one rebuild of the index runs at a time.
+        let _rebuild = self.rebuild_lock.lock().unwrap();
+
+        let table = self.table.upgrade().unwrap();
+        let shadow_name = format!("{}{}", REBUILDS, self.index_name());
+        let shadow = table.engine.open_tree(&shadow_name)?;
+        shadow.clear()?;
+
+        // Writes hold the root lock, so every later write has a higher sequence number.
+        let (root, start) = {
+            let root = table.lock_root();
+            let start = table.engine.generate_id()?;
+            // Keep the journal entries of the writes made during the rebuild.
+            *self.rebuilding.lock().unwrap() = Some(start);
+            (root.clone(), start)
+        };
+        progress.total.store(root.len() as u64, Ordering::Relaxed);
+
+        let result = (|| {
+            let sketch = Mutex::default();
+            for entry in root.iter() {
+                let (id, data) = entry?;
+                let record = table.decode_record(decode(&id)?, &data)?;
+                self.insert(&shadow, &sketch, &record)?;
+                progress.indexed.fetch_add(1, Ordering::Relaxed);
+            }
+
+            let _root = table.lock_root();
+
+            let mut applied = start;
+            for entry in self.journal.since(start)? {
+                let (seq, event) = entry?;
+                self.apply_to(&shadow, &sketch, event)?;
+                applied = seq;
+            }
+
+            let mut batch = sled::Batch::default();
+            for key in self.indexed_data.iter().keys() {
+                batch.remove(key?);
+            }
+            for entry in shadow.iter() {
+                let (key, value) = entry?;
+                batch.insert(key, value);
+            }
+            self.indexed_data.apply_batch(batch)?;
+
+            *self.sketch.lock().unwrap() = sketch.into_inner().unwrap();
+            // Events still queued for the index are older and skipped.
+            self.applied.store(applied, Ordering::Release);
+            self.refill_bloom_filter()?;
+            self.save_cursor()
+        })();
+
+        *self.rebuilding.lock().unwrap() = None;
+        table.engine.drop_tree(&shadow_name)?;
+        result?;
+
+        // The entries kept for the rebuild aren't needed anymore.
+        table.truncate_journal()
+    }
+
+    /// Persist the sequence number of the last applied event and the sketch matching it.
+    fn save_cursor(&self) -> DbResult<()> {
+        let sketch = (FORMAT, &self.version, Some(&*self.sketch.lock().unwrap()));
+        self.sketches.insert(self.index_name(), encode(&sketch)?)?;
+        self.sketch_saved.store(true, Ordering::Release);
+        self.journal
+            .set_cursor(&self.index_name(), self.applied.load(Ordering::Acquire))
     }
 
-    /// Insert a record into the index. The index key will be computed.
+    /// Insert a record into a tree of the index. The index keys will be computed.
     ///
     /// # Arguments
     ///
+    /// * `tree` - The tree of the index, or the shadow tree of a rebuild.
+    /// * `sketch` - The sketch the keys are added to.
     /// * `record` - The record to insert.
-    fn insert(&self, record: &Record<T>) -> DbResult<()> {
-        let key = encode(&(self.key_func)(&record.data))?;
+    fn insert(&self, tree: &Tree, sketch: &Mutex<HyperLogLog>, record: &Record<T>) -> DbResult<()> {
+        for key in self.generate_keys(&record.data)? {
+            sketch.lock().unwrap().add(&key);
 
-        if let Some(data) = self.indexed_data.get(&key)? {
-            let mut vec: Vec<u64> = decode(&data)?;
-            vec.push(record.id);
-            self.indexed_data.insert(key, encode(&vec)?)?;
-        } else {
-            self.indexed_data.insert(key, encode(&vec![record.id])?)?;
+            if let Some(ids) = changed_posting(tree.get(&key)?.as_deref(), record.id, true)? {
+                tree.insert(key, encode_posting(&ids)?)?;
+            }
         }
 
         Ok(())
     }
 
-    /// Delete a record from the index.
-    /// The record will compute an index key to delete by.
+    /// Delete a record from a tree of the index.
+    /// The record will compute the index keys to delete by.
     ///
     /// # Arguments
     ///
+    /// * `tree` - The tree of the index, or the shadow tree of a rebuild.
     /// * `record` - The record to delete.
-    fn remove(&self, record: &Record<T>) -> DbResult<()> {
-        let key = encode(&(self.key_func)(&record.data))?;
+    fn remove(&self, tree: &Tree, record: &Record<T>) -> DbResult<()> {
+        for key in self.generate_keys(&record.data)? {
+            Self::remove_key(tree, &key, record.id)?;
+        }
 
-        if let Some(data) = self.indexed_data.get(&key)? {
-            let mut index_values: Vec<u64> = decode(&data)?;
+        Ok(())
+    }
 
-            // We can remove the entire node here since its one element.
-            if index_values.len() < 2 {
-                self.indexed_data.remove(&key)?;
-            } else {
-                // Remove the single ID from here.
-                if let Some(pos) = index_values.iter().position(|id| *id == record.id) {
-                    index_values.remove(pos);
-                    // Replace the row with one that doesn't have the element.
-                    self.indexed_data.insert(&key, encode(&index_values)?)?;
-                }
+    /// Delete the ID of a record from the posting list of a key.
+    fn remove_key(tree: &Tree, key: &[u8], id: u64) -> DbResult<()> {
+        match changed_posting(tree.get(key)?.as_deref(), id, false)? {
+            // We can remove the entire node here since it has no elements left.
+            Some(ids) if ids.is_empty() => {
+                tree.remove(key)?;
             }
+            Some(ids) => {
+                tree.insert(key, encode_posting(&ids)?)?;
+            }
+            None => {}
         }
 
         Ok(())
     }
 
+    /// Compute the posting list changes of events, without writing them.
+    ///
+    /// # Arguments
+    ///
+    /// * `events` - The events of a commit, in order.
+    ///
+    /// # Returns
+    ///
+    /// The [`IndexWrite`] to apply in the transaction of the commit.
+    fn prepare(&self, events: &[Event<T>]) -> DbResult<IndexWrite> {
+        let mut changes = vec![];
+        for event in events {
+            match event {
+                Event::Insert(record) => {
+                    for key in self.generate_keys(&record.data)? {
+                        changes.push((key, record.id, true));
+                    }
+                }
+                Event::Remove(record) => {
+                    for key in self.generate_keys(&record.data)? {
+                        changes.push((key, record.id, false));
+                    }
+                }
+                Event::Update {
+                    id,
+                    old_data,
+                    new_data,
+                } => {
+                    // Keys which the update doesn't change are left alone.
+                    let old_keys = self.generate_keys(old_data)?;
+                    let new_keys = self.generate_keys(new_data)?;
+                    for key in &old_keys {
+                        if !new_keys.contains(key) {
+                            changes.push((key.clone(), *id, false));
+                        }
+                    }
+                    for key in new_keys {
+                        if !old_keys.contains(&key) {
+                            changes.push((key, *id, true));
+                        }
+                    }
+                }
+                Event::Cleared => unreachable!("tables are cleared without a commit"),
+            }
+        }
+
+        Ok(IndexWrite { changes })
+    }
+
     /// Delete records from the table and the index based on the given query.
     ///
     /// # Arguments
@@ -200,170 +537,1125 @@ impl<T: TableType, I: IndexType> IndexInner<T, I> {
     ///
     /// All selected [`Record`] instances.
     pub fn select(&self, query: &I) -> DbResult<Vec<Record<T>>> {
-        self.commit_log()?;
-
         let table = self.table.upgrade().unwrap();
 
-        Ok(
-            if let Ok(Some(bytes)) = self.indexed_data.get(encode(&query)?) {
-                let ids: Vec<u64> = decode(&bytes)?;
-
-                let mut results = vec![];
-                for id in ids {
-                    if let Some(record) = table.select(id)? {
-                        results.push(record);
-                    }
-                }
+        let mut results = vec![];
+        for id in self.select_ids(query)? {
+            if let Some(record) = table.select(id)? {
+                results.push(record);
+            }
+        }
 
-                results
-            } else {
-                Vec::new()
-            },
-        )
+        Ok(results)
     }
 
-    /// Static select that doesn't obtain a read lock.
-    fn tree_select(&self, tree: &Tree, query: &I) -> DbResult<Vec<Record<T>>> {
-        self.commit_log()?;
-
+    /// Select records matching any of many keys.
+    ///
+    /// Records matching multiple keys are only read once, which is faster than calling
+    /// [`Index::select`] for each key.
+    ///
+    /// # Arguments
+    ///
+    /// * `queries` - The query keys.
+    ///
+    /// # Returns
+    ///
+    /// All selected [`Record`] instances without duplicates, in the order of their keys.
+    pub fn select_many(&self, queries: &[I]) -> DbResult<Vec<Record<T>>> {
         let table = self.table.upgrade().unwrap();
 
-        Ok(
-            if let Ok(Some(bytes)) = self.indexed_data.get(encode(&query)?) {
-                let ids: Vec<u64> = decode(&bytes)?;
+        let mut ids = vec![];
+        let mut seen = HashSet::new();
+        for query in queries {
+            if let Some(bytes) = self.indexed_data.get(encode_key(query)?)? {
+                let matches = decode_posting(&bytes)?;
+                ids.extend(matches.into_iter().filter(|id| seen.insert(*id)));
+            }
+        }
 
-                let mut results = vec![];
-                for id in ids {
-                    if let Some(record) = table.tree_select(tree, id)? {
-                        results.push(record);
-                    }
-                }
+        let mut results = Vec::with_capacity(ids.len());
+        for id in ids {
+            if let Some(record) = table.select(id)? {
+                results.push(record);
+            }
+        }
 
-                results
-            } else {
-                Vec::new()
-            },
-        )
+        Ok(results)
     }
 
-    /// Update records in the table and the index based on the given query and new value.
+    /// Select the IDs of records based on the given query, without reading the records.
     ///
     /// # Arguments
     ///
     /// * `query` - A reference to the query key.
-    /// * `updater` - Closure to generate the new data based on the old data.
     ///
     /// # Returns
     ///
-    /// All updated [`Record`] instances.
-    pub fn update(&self, query: &I, updater: fn(T) -> T) -> DbResult<Vec<Record<T>>> {
-        self.commit_log()?;
-
-        let table = self.table.upgrade().unwrap();
-
-        if let Ok(Some(bytes)) = self.indexed_data.get(encode(&query)?) {
-            let ids: Vec<u64> = decode(&bytes)?;
-            table.update(&ids, updater)
-        } else {
-            Ok(vec![])
+    /// The IDs of all matching records.
+    pub fn select_ids(&self, query: &I) -> DbResult<Vec<u64>> {
+        match self.indexed_data.get(encode_key(query)?)? {
+            Some(bytes) => decode_posting(&bytes),
+            None => Ok(Vec::new()),
         }
     }
 
-    pub fn index_name(&self) -> String {
-        std::str::from_utf8(&self.indexed_data.name())
-            .unwrap()
-            .to_string()
-    }
+    /// Select the IDs of records matching any of many keys, without reading the records.
+    ///
+    /// Every distinct key is looked up once, in the order of the index tree.
+    ///
+    /// # Arguments
+    ///
+    /// * `queries` - The query keys.
+    ///
+    /// # Returns
+    ///
+    /// The IDs of all matching records without duplicates, in ID order.
+    pub fn select_many_ids(&self, queries: &[I]) -> DbResult<Vec<u64>> {
+        let keys = queries
+            .iter()
+            .map(encode_key)
+            .collect::<DbResult<Vec<_>>>()?;
 
-    pub fn generate_key(&self, data: &T) -> DbResult<Vec<u8>> {
-        encode(&(self.key_func)(&data))
+        self.keys_ids(keys)
     }
-}
 
-pub(crate) mod private {
-    use super::*;
+    /// Select the IDs of records matching any of many encoded keys, like [`IndexInner::select_many_ids`].
+    pub(crate) fn keys_ids(&self, mut keys: Vec<Vec<u8>>) -> DbResult<Vec<u64>> {
+        keys.sort_unstable();
+        keys.dedup();
 
-    /// Additional methods for index which are only for internal use.
-    pub trait AnyIndexInternal<T: TableType> {
-        fn tree_exists(&self, tree: &Tree, record: &Record<T>) -> DbResult<Vec<u64>>;
+        let mut ids = vec![];
+        for key in keys {
+            if let Some(bytes) = self.indexed_data.get(key)? {
+                ids.extend(decode_posting(&bytes)?);
+            }
+        }
+
+        ids.sort_unstable();
+        ids.dedup();
+        Ok(ids)
     }
-}
 
-impl<T, I> private::AnyIndexInternal<T> for Index<T, I>
-where
-    T: TableType,
-    I: IndexType + 'static,
-{
-    fn tree_exists(&self, tree: &Tree, record: &Record<T>) -> DbResult<Vec<u64>> {
-        let i = (self.key_func)(&record.data);
+    /// Amount of records with any of many encoded keys, without collecting the posting lists.
+    pub(crate) fn count_keys(&self, keys: &[Vec<u8>]) -> DbResult<usize> {
+        let mut count = 0;
+        for key in keys {
+            if let Some(bytes) = self.indexed_data.get(key)? {
+                count += posting_len(&bytes)?;
+            }
+        }
 
-        Ok(self
-            .tree_select(tree, &i)?
-            .iter()
-            .map(|record: &Record<T>| record.id)
-            .collect())
+        Ok(count)
     }
-}
 
-/// Type which [`Index`] can be casted to which doesn't require the `I` type parameter.
-pub trait AnyIndex<T: TableType>: private::AnyIndexInternal<T> {
-    /// Check if a record exists by the index key.
+    /// Count the records with a key, reading only the header of its posting list.
     ///
     /// # Arguments
     ///
-    /// * `record` - The record to check for existence.
-    fn exists(&self, record: &Record<T>) -> DbResult<Vec<u64>>;
-    /// Select which allows any type.
-    fn search(&self, value: Box<dyn Any>) -> DbResult<Vec<Record<T>>>;
-    /// Alias for `index_name`.
-    fn idx_name(&self) -> String;
-    /// Generate a key and return encoded value.
-    fn gen_key(&self, data: &T) -> DbResult<Vec<u8>>;
-}
-
-impl<T, I> AnyIndex<T> for Index<T, I>
-where
-    T: TableType,
-    I: IndexType + 'static,
-{
-    fn search(&self, value: Box<dyn Any>) -> DbResult<Vec<Record<T>>> {
-        let i = *value.downcast::<I>().unwrap();
-        self.select(&i)
+    /// * `query` - A reference to the query key.
+    ///
+    /// # Returns
+    ///
+    /// The amount of records with the key.
+    pub fn count(&self, query: &I) -> DbResult<usize> {
+        self.count_keys(&[encode_key(query)?])
     }
 
-    fn idx_name(&self) -> String {
-        self.index_name()
+    /// Select the IDs of records whose string or byte key starts with a prefix.
+    ///
+    /// # Arguments
+    ///
+    /// * `prefix` - The encoded prefix, see [`PrefixKey::key_prefix`].
+    ///
+    /// # Returns
+    ///
+    /// The IDs of all matching records, in ID order.
+    pub(crate) fn prefix_ids(&self, prefix: &[u8]) -> DbResult<Vec<u64>> {
+        let mut ids = self.scan_prefix_ids(prefix)?;
+        ids.sort_unstable();
+        ids.dedup();
+        Ok(ids)
     }
 
-    fn exists(&self, record: &Record<T>) -> DbResult<Vec<u64>> {
-        self.tree_exists(&self.table.upgrade().unwrap().root.read().unwrap(), record)
-    }
+    /// Select the IDs of records whose key starts with a prefix, in key order and
+    /// with duplicates for records with many matching keys.
+    fn scan_prefix_ids(&self, prefix: &[u8]) -> DbResult<Vec<u64>> {
+        let mut ids = vec![];
+        for entry in self.indexed_data.scan_prefix(prefix) {
+            ids.extend(decode_posting(&entry?.1)?);
+        }
 
-    fn gen_key(&self, data: &T) -> DbResult<Vec<u8>> {
-        self.generate_key(data)
+        Ok(ids)
     }
-}
 
-#[cfg(test)]
-mod tests {
-    use super::*;
-    use crate::{Table, TinyBase};
+    /// Keys of the index starting with a prefix with the IDs of their records.
+    ///
+    /// # Arguments
+    ///
+    /// * `prefix` - The encoded prefix, such as the encoding of the first field of tuple keys.
+    ///
+    /// # Returns
+    ///
+    /// Every matching key in key order, with the IDs in ascending order.
+    pub(crate) fn prefix_groups(&self, prefix: &[u8]) -> DbResult<Vec<(I, Vec<u64>)>> {
+        let mut groups = vec![];
+        for entry in self.indexed_data.scan_prefix(prefix) {
+            let (key, value) = entry?;
+            groups.push((decode_key(&key)?, decode_posting(&value)?));
+        }
 
-    #[test]
-    fn index_sync() {
-        let db = TinyBase::new(None, true);
-        let table: Table<String> = db.open_table("test_table").unwrap();
+        Ok(groups)
+    }
 
-        // Insert a string value into the table
-        let id = table.insert("value1".to_string()).unwrap();
-        let id2 = table.insert("value2".to_string()).unwrap();
+    /// Select records whose key is within a range, iterating the index in key order.
+    ///
+    /// Keys are compared in their natural order for integers, floats, strings and tuples
+    /// of them.
+    ///
+    /// # Arguments
+    ///
+    /// * `range` - The range of keys, such as `10..20`.
+    ///
+    /// # Returns
+    ///
+    /// All selected [`Record`] instances in the order of their keys, records with the
+    /// same key in ID order.
+    pub fn select_range(&self, range: impl RangeBounds<I>) -> DbResult<Vec<Record<T>>> {
+        let encode_bound = |bound: Bound<&I>| -> DbResult<Bound<Vec<u8>>> {
+            Ok(match bound {
+                Bound::Included(key) => Bound::Included(encode_key(key)?),
+                Bound::Excluded(key) => Bound::Excluded(encode_key(key)?),
+                Bound::Unbounded => Bound::Unbounded,
+            })
+        };
 
-        // Create an index for the table
-        let index = table.create_index("length", |value| value.len()).unwrap();
+        let ids = self.range_ids(
+            encode_bound(range.start_bound())?,
+            encode_bound(range.end_bound())?,
+        )?;
+        self.read_records(ids)
+    }
 
-        assert!(index.sync().is_ok());
+    /// Read the records with the given IDs, skipping removed records.
+    pub(crate) fn read_records(&self, ids: Vec<u64>) -> DbResult<Vec<Record<T>>> {
+        let table = self.table.upgrade().unwrap();
 
-        let results = index.select(&6).unwrap();
+        let mut results = Vec::with_capacity(ids.len());
+        for id in ids {
+            if let Some(record) = table.select(id)? {
+                results.push(record);
+            }
+        }
+
+        Ok(results)
+    }
+
+    /// IDs of the records whose string key matches a pattern, reading every key of the index.
+    pub(crate) fn matching_ids(&self, pattern: &dyn Fn(&str) -> bool) -> DbResult<Vec<u64>> {
+        let mut ids = vec![];
+        for entry in self.indexed_data.iter() {
+            let (key, value) = entry?;
+            if pattern(&decode_key::<String>(&key)?) {
+                ids.extend(decode_posting(&value)?);
+            }
+        }
+
+        ids.sort_unstable();
+        ids.dedup();
+        Ok(ids)
+    }
+
+    /// Keys of the index with the amount of their records which are selected.
+    ///
+    /// # Arguments
+    ///
+    /// * `selected` - IDs of the selected records, sorted.
+    ///
+    /// # Returns
+    ///
+    /// Every key with at least one selected record.
+    pub(crate) fn key_counts(&self, selected: &[u64]) -> DbResult<Vec<(I, usize)>> {
+        Ok(self
+            .key_groups(selected)?
+            .into_iter()
+            .map(|(key, ids)| (key, ids.len()))
+            .collect())
+    }
+
+    /// The smallest or largest key with a selected record, walking the index from one end.
+    ///
+    /// # Arguments
+    ///
+    /// * `selected` - IDs of the selected records, sorted.
+    /// * `direction` - [`Direction::Asc`] for the smallest key, [`Direction::Desc`] for the largest.
+    ///
+    /// # Returns
+    ///
+    /// The first key in the direction with a selected record, none if there is none.
+    pub(crate) fn selected_key(
+        &self,
+        selected: &[u64],
+        direction: Direction,
+    ) -> DbResult<Option<I>> {
+        let entries: Box<dyn Iterator<Item = sled::Result<(sled::IVec, sled::IVec)>>> =
+            match direction {
+                Direction::Asc => Box::new(self.indexed_data.iter()),
+                Direction::Desc => Box::new(self.indexed_data.iter().rev()),
+            };
+
+        for entry in entries {
+            let (key, value) = entry?;
+            if decode_posting(&value)?
+                .iter()
+                .any(|id| selected.binary_search(id).is_ok())
+            {
+                return Ok(Some(decode_key(&key)?));
+            }
+        }
+
+        Ok(None)
+    }
+
+    /// Keys of the index with the IDs of their records which are selected.
+    ///
+    /// # Arguments
+    ///
+    /// * `selected` - IDs of the selected records, sorted.
+    ///
+    /// # Returns
+    ///
+    /// Every key with at least one selected record, with the IDs in ascending order.
+    pub(crate) fn key_groups(&self, selected: &[u64]) -> DbResult<Vec<(I, Vec<u64>)>> {
+        let mut groups = vec![];
+        for entry in self.indexed_data.iter() {
+            let (key, value) = entry?;
+            let ids: Vec<u64> = decode_posting(&value)?
+                .into_iter()
+                .filter(|id| selected.binary_search(id).is_ok())
+                .collect();
+
+            if !ids.is_empty() {
+                groups.push((decode_key(&key)?, ids));
+            }
+        }
+
+        Ok(groups)
+    }
+
+    /// IDs of all records in the order of their keys, by iterating the index tree.
+    ///
+    /// # Arguments
+    ///
+    /// * `descending` - Whether the largest key comes first.
+    /// * `from` - The first key to include, such as the key a page of records ended with.
+    ///
+    /// # Returns
+    ///
+    /// The IDs, with equal keys in ascending ID order, or [`None`] if records don't
+    /// have exactly one key.
+    pub(crate) fn ordered_ids(
+        &self,
+        descending: bool,
+        from: Option<&I>,
+    ) -> DbResult<Option<Vec<u64>>> {
+        if !self.single_key {
+            return Ok(None);
+        }
+
+        let from = from.map(encode_key).transpose()?;
+        let entries: Box<dyn Iterator<Item = sled::Result<(sled::IVec, sled::IVec)>>> =
+            match (descending, from) {
+                (true, Some(from)) => Box::new(self.indexed_data.range(..=from).rev()),
+                (true, None) => Box::new(self.indexed_data.iter().rev()),
+                (false, Some(from)) => Box::new(self.indexed_data.range(from..)),
+                (false, None) => Box::new(self.indexed_data.iter()),
+            };
+
+        let mut ids = vec![];
+        for entry in entries {
+            let (_, value) = entry?;
+            ids.extend(decode_posting(&value)?);
+        }
+
+        Ok(Some(ids))
+    }
+
+    /// Select the records with the smallest key, reading only the first entry of the index.
+    ///
+    /// # Returns
+    ///
+    /// All records with the smallest key in ID order, none if the index is empty.
+    pub fn min(&self) -> DbResult<Vec<Record<T>>> {
+        match self.indexed_data.first()? {
+            Some((_, value)) => self.read_records(decode_posting(&value)?),
+            None => Ok(vec![]),
+        }
+    }
+
+    /// Select the records with the largest key, reading only the last entry of the index.
+    ///
+    /// # Returns
+    ///
+    /// All records with the largest key in ID order, none if the index is empty.
+    pub fn max(&self) -> DbResult<Vec<Record<T>>> {
+        match self.indexed_data.last()? {
+            Some((_, value)) => self.read_records(decode_posting(&value)?),
+            None => Ok(vec![]),
+        }
+    }
+
+    /// Select the records with the smallest or largest keys, walking the index from one end.
+    ///
+    /// # Arguments
+    ///
+    /// * `n` - The maximum amount of records.
+    /// * `direction` - [`Direction::Asc`] for the smallest keys, [`Direction::Desc`] for the largest.
+    ///
+    /// # Returns
+    ///
+    /// Up to `n` [`Record`] instances in the order of their keys, records with the same
+    /// key in ID order. Records with many keys are selected once, for their first key.
+    pub fn top(&self, n: usize, direction: Direction) -> DbResult<Vec<Record<T>>> {
+        let entries: Box<dyn Iterator<Item = sled::Result<(sled::IVec, sled::IVec)>>> =
+            match direction {
+                Direction::Asc => Box::new(self.indexed_data.iter()),
+                Direction::Desc => Box::new(self.indexed_data.iter().rev()),
+            };
+
+        let mut ids = vec![];
+        let mut seen = HashSet::new();
+        for entry in entries {
+            if ids.len() >= n {
+                break;
+            }
+
+            let matches = decode_posting(&entry?.1)?;
+            ids.extend(
+                matches
+                    .into_iter()
+                    .filter(|id| seen.insert(*id))
+                    .take(n - ids.len()),
+            );
+        }
+
+        self.read_records(ids)
+    }
+
+    /// Select the IDs of records whose encoded key is within a range, in key order.
+    ///
+    /// Keys are compared by their encoding, which preserves the order of the keys.
+    ///
+    /// # Arguments
+    ///
+    /// * `start` - Lower bound of the encoded keys.
+    /// * `end` - Upper bound of the encoded keys.
+    pub(crate) fn range_ids(
+        &self,
+        start: Bound<Vec<u8>>,
+        end: Bound<Vec<u8>>,
+    ) -> DbResult<Vec<u64>> {
+        let mut ids: Vec<u64> = self.range_postings(start, end)?.concat();
+        self.dedup_ids(&mut ids);
+        Ok(ids)
+    }
+
+    /// Posting lists of the keys matched by a lookup, in key order.
+    ///
+    /// # Returns
+    ///
+    /// The IDs of the records of every matched key, each list in ascending order, with
+    /// the amount of keys which were looked up or read by a scan.
+    pub(crate) fn postings(&self, lookup: KeyLookup<'_>) -> DbResult<(Vec<Vec<u64>>, usize)> {
+        let mut postings = vec![];
+
+        let visited = match lookup {
+            KeyLookup::Keys(keys) => {
+                for key in &keys {
+                    if let Some(bytes) = self.indexed_data.get(key)? {
+                        postings.push(decode_posting(&bytes)?);
+                    }
+                }
+                keys.len()
+            }
+            KeyLookup::Prefix(prefix) => {
+                for entry in self.indexed_data.scan_prefix(prefix) {
+                    postings.push(decode_posting(&entry?.1)?);
+                }
+                postings.len()
+            }
+            KeyLookup::Matching(pattern) => {
+                let mut visited = 0;
+                for entry in self.indexed_data.iter() {
+                    let (key, value) = entry?;
+                    visited += 1;
+                    if pattern(&decode_key::<String>(&key)?) {
+                        postings.push(decode_posting(&value)?);
+                    }
+                }
+                visited
+            }
+            KeyLookup::Range(start, end) => {
+                postings = self.range_postings(start, end)?;
+                postings.len()
+            }
+        };
+
+        Ok((postings, visited))
+    }
+
+    /// Posting lists of the keys within a range of encoded keys, in key order.
+    ///
+    /// # Returns
+    ///
+    /// The IDs of the records of every key in the range, each list in ascending order.
+    fn range_postings(
+        &self,
+        start: Bound<Vec<u8>>,
+        end: Bound<Vec<u8>>,
+    ) -> DbResult<Vec<Vec<u64>>> {
+        let empty = match (&start, &end) {
+            (Bound::Included(start), Bound::Included(end)) => start > end,
+            (Bound::Included(start) | Bound::Excluded(start), Bound::Excluded(end))
+            | (Bound::Excluded(start), Bound::Included(end)) => start >= end,
+            _ => false,
+        };
+
+        let mut postings = vec![];
+        if !empty {
+            for entry in self.indexed_data.range((start, end)) {
+                postings.push(decode_posting(&entry?.1)?);
+            }
+        }
+
+        Ok(postings)
+    }
+
+    /// Static select that doesn't obtain a read lock.
+    fn tree_select(&self, tree: &Tree, query: &I) -> DbResult<Vec<Record<T>>> {
+        let table = self.table.upgrade().unwrap();
+
+        Ok(
+            if let Ok(Some(bytes)) = self.indexed_data.get(encode_key(query)?) {
+                let ids = decode_posting(&bytes)?;
+
+                let mut results = vec![];
+                for id in ids {
+                    if let Some(record) = table.tree_select(tree, id)? {
+                        results.push(record);
+                    }
+                }
+
+                results
+            } else {
+                Vec::new()
+            },
+        )
+    }
+
+    /// Update records in the table and the index based on the given query and new value.
+    ///
+    /// # Arguments
+    ///
+    /// * `query` - A reference to the query key.
+    /// * `updater` - Closure to generate the new data based on the old data.
+    ///
+    /// # Returns
+    ///
+    /// All updated [`Record`] instances.
+    pub fn update(&self, query: &I, updater: fn(T) -> T) -> DbResult<Vec<Record<T>>> {
+        let table = self.table.upgrade().unwrap();
+
+        if let Ok(Some(bytes)) = self.indexed_data.get(encode_key(query)?) {
+            let ids = decode_posting(&bytes)?;
+            table.update(&ids, updater)
+        } else {
+            Ok(vec![])
+        }
+    }
+
+    /// List the distinct keys of the index, without reading any record.
+    ///
+    /// # Returns
+    ///
+    /// Every key which at least one record has, in the order of their encoding.
+    pub fn keys(&self) -> DbResult<Vec<I>> {
+        let mut keys = vec![];
+        for key in self.indexed_data.iter().keys() {
+            keys.push(decode_key(&key?)?);
+        }
+
+        Ok(keys)
+    }
+
+    /// Find the keys a record is indexed under, as stored in the index.
+    ///
+    /// The record isn't read and its keys aren't computed, so keys which drifted from
+    /// the key function are returned as well. Every posting list is read.
+    ///
+    /// # Arguments
+    ///
+    /// * `id` - The ID of the record.
+    ///
+    /// # Returns
+    ///
+    /// Every key whose posting list contains the record, in the order of their encoding.
+    pub fn keys_for(&self, id: u64) -> DbResult<Vec<I>> {
+        let mut keys = vec![];
+        for entry in self.indexed_data.iter() {
+            let (key, value) = entry?;
+            if decode_posting(&value)?.binary_search(&id).is_ok() {
+                keys.push(decode_key(&key)?);
+            }
+        }
+
+        Ok(keys)
+    }
+
+    /// List the distinct keys of the index with the amount of their records, without
+    /// collecting the posting lists.
+    ///
+    /// # Returns
+    ///
+    /// Every key which at least one record has, in the order of their encoding.
+    pub fn keys_with_counts(&self) -> DbResult<Vec<(I, usize)>> {
+        let mut counts = vec![];
+        for entry in self.indexed_data.iter() {
+            let (key, value) = entry?;
+            counts.push((decode_key(&key)?, posting_len(&value)?));
+        }
+
+        Ok(counts)
+    }
+
+    /// Compute statistics of the index without collecting the posting lists.
+    ///
+    /// # Returns
+    ///
+    /// The [`IndexStats`] of the index.
+    pub fn stats(&self) -> DbResult<IndexStats<I>> {
+        let mut keys = 0;
+        let mut entries = 0;
+        for value in self.indexed_data.iter().values() {
+            keys += 1;
+            entries += posting_len(&value?)?;
+        }
+
+        let decode_entry = |entry: Option<(sled::IVec, sled::IVec)>| {
+            entry.map(|(key, _)| decode_key(&key)).transpose()
+        };
+
+        Ok(IndexStats {
+            keys,
+            entries,
+            min: decode_entry(self.indexed_data.first()?)?,
+            max: decode_entry(self.indexed_data.last()?)?,
+            avg_entries_per_key: match keys {
+                0 => 0.0,
+                _ => entries as f64 / keys as f64,
+            },
+        })
+    }
+
+    /// Cross-check the posting lists of the index against the records of the table.
+    ///
+    /// Writes to the table wait until the check finished.
+    ///
+    /// # Returns
+    ///
+    /// An [`IndexReport`] of every inconsistency, in key order.
+    pub fn verify(&self) -> DbResult<IndexReport<I>> {
+        self.check(false)
+    }
+
+    /// Cross-check the index against the table like [`IndexInner::verify`] and fix every
+    /// inconsistency, without rebuilding the whole index.
+    ///
+    /// # Returns
+    ///
+    /// An [`IndexReport`] of the inconsistencies which were fixed.
+    pub fn repair(&self) -> DbResult<IndexReport<I>> {
+        self.check(true)
+    }
+
+    /// Compare the index with the keys computed from every record, optionally fixing it.
+    fn check(&self, repair: bool) -> DbResult<IndexReport<I>> {
+        let table = self.table.upgrade().unwrap();
+        let root = table.lock_root();
+        let mut expected = BTreeSet::new();
+        for entry in root.iter() {
+            let (id, data) = entry?;
+            let record = table.decode_record(decode(&id)?, &data)?;
+            for key in self.generate_keys(&record.data)? {
+                let id = record.id;
+                expected.insert((key, id));
+            }
+        }
+
+        let mut actual = BTreeSet::new();
+        for entry in self.indexed_data.iter() {
+            let (key, value) = entry?;
+            for id in decode_posting(&value)? {
+                actual.insert((key.to_vec(), id));
+            }
+        }
+
+        let mut report = IndexReport {
+            missing: vec![],
+            orphaned: vec![],
+            misplaced: vec![],
+        };
+
+        for (key, id) in expected.difference(&actual) {
+            if repair {
+                let data = root.get(encode(id)?)?.unwrap();
+                let record = table.decode_record(*id, &data)?;
+                self.insert(&self.indexed_data, &self.sketch, &record)?;
+            }
+            report.missing.push((decode_key(key)?, *id));
+        }
+
+        for (key, id) in actual.difference(&expected) {
+            if repair {
+                Self::remove_key(&self.indexed_data, key, *id)?;
+            }
+            match root.contains_key(encode(id)?)? {
+                true => report.misplaced.push((decode_key(key)?, *id)),
+                false => report.orphaned.push((decode_key(key)?, *id)),
+            }
+        }
+
+        if repair && !report.is_consistent() {
+            self.refill_bloom_filter()?;
+            self.save_cursor()?;
+        }
+
+        Ok(report)
+    }
+
+    /// Keep a bloom filter of the keys in memory, so [`AnyIndex::exists`] and unique
+    /// constraints skip keys which certainly have no records without reading the index.
+    ///
+    /// Keys whose records were all removed stay in the filter until the next
+    /// [`IndexInner::sync`], which is only slower and never gives wrong results.
+    ///
+    /// # Arguments
+    ///
+    /// * `expected_keys` - The amount of distinct keys the filter is sized for, the false
+    ///   positive rate of about 1% grows once the index has more keys.
+    pub fn enable_bloom_filter(&self, expected_keys: usize) -> DbResult<()> {
+        let table = self.table.upgrade().unwrap();
+        // Keys of writes running while the filter is filled would be missed.
+        let _root = table.lock_root();
+
+        *self.bloom.write().unwrap() = Some(BloomFilter::new(expected_keys));
+        self.refill_bloom_filter()
+    }
+
+    /// Stop maintaining the bloom filter of the keys, freeing its memory.
+    pub fn disable_bloom_filter(&self) {
+        *self.bloom.write().unwrap() = None;
+    }
+
+    /// Fill the bloom filter with the keys of the index tree again, if it's enabled.
+    fn refill_bloom_filter(&self) -> DbResult<()> {
+        if let Some(bloom) = self.bloom.write().unwrap().as_mut() {
+            // The filter grows with the index when it's refilled.
+            *bloom = BloomFilter::new(bloom.expected().max(self.indexed_data.len()));
+            for key in self.indexed_data.iter().keys() {
+                bloom.add(&key?);
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Whether the index possibly has records with an encoded key.
+    fn may_contain(&self, key: &[u8]) -> bool {
+        match &*self.bloom.read().unwrap() {
+            Some(bloom) => bloom.may_contain(key),
+            None => true,
+        }
+    }
+
+    /// Estimate the amount of distinct keys without reading the index.
+    ///
+    /// The estimate comes from a HyperLogLog sketch with a standard error of about 1.6%.
+    /// Keys whose records were all removed are still counted until the next [`IndexInner::sync`].
+    pub fn estimate_cardinality(&self) -> DbResult<u64> {
+        Ok(self.sketch.lock().unwrap().estimate())
+    }
+
+    pub fn index_name(&self) -> String {
+        std::str::from_utf8(&self.indexed_data.name())
+            .unwrap()
+            .to_string()
+    }
+
+    /// Compute the encoded keys of the data.
+    ///
+    /// # Returns
+    ///
+    /// The keys sorted by their encoding and without duplicates, a single key for
+    /// indexes created with [`crate::Table::create_index`].
+    pub fn generate_keys(&self, data: &T) -> DbResult<Vec<Vec<u8>>> {
+        let mut keys = self
+            .try_keys_of(data)?
+            .iter()
+            .map(encode_key)
+            .collect::<DbResult<Vec<_>>>()?;
+        keys.sort_unstable();
+        keys.dedup();
+        Ok(keys)
+    }
+
+    /// Compute the keys of the data without encoding them.
+    ///
+    /// Only key functions of script indexes fail, and they can't fail for stored
+    /// records since writing them would have failed. Use [`IndexInner::try_keys_of`]
+    /// for data which isn't stored yet.
+    pub(crate) fn keys_of(&self, data: &T) -> Vec<I> {
+        self.try_keys_of(data).unwrap_or_default()
+    }
+
+    /// Compute the keys of the data without encoding them, or the error of the key function.
+    pub(crate) fn try_keys_of(&self, data: &T) -> DbResult<Vec<I>> {
+        (self.keys_func)(data)
+    }
+
+    /// Remove duplicates of records with many matching keys, keeping the first of them.
+    fn dedup_ids(&self, ids: &mut Vec<u64>) {
+        if !self.single_key {
+            let mut seen = HashSet::new();
+            ids.retain(|id| seen.insert(*id));
+        }
+    }
+}
+
+impl<T: TableType, I: PrefixKey> IndexInner<T, I> {
+    /// Select records whose string or byte key starts with a prefix, iterating the
+    /// index in key order.
+    ///
+    /// # Arguments
+    ///
+    /// * `prefix` - The value the matching keys start with.
+    ///
+    /// # Returns
+    ///
+    /// All selected [`Record`] instances in the order of their keys, records with the
+    /// same key in ID order.
+    pub fn select_prefix(&self, prefix: I) -> DbResult<Vec<Record<T>>> {
+        let mut ids = self.scan_prefix_ids(&prefix.key_prefix())?;
+        self.dedup_ids(&mut ids);
+        self.read_records(ids)
+    }
+}
+
+/// Add an ID to or remove it from an encoded posting list.
+///
+/// # Arguments
+///
+/// * `list` - The encoded posting list, [`None`] if the key has no records.
+/// * `id` - The ID of the record.
+/// * `add` - Whether the ID is added rather than removed.
+///
+/// # Returns
+///
+/// The changed IDs, empty if the key has no records left, or [`None`] if nothing changed.
+fn changed_posting(list: Option<&[u8]>, id: u64, add: bool) -> DbResult<Option<Vec<u64>>> {
+    let mut ids: Vec<u64> = match list {
+        Some(list) => decode_posting(list)?,
+        None => vec![],
+    };
+
+    // Keep the IDs sorted, so conditions can be intersected by merging.
+    match (ids.binary_search(&id), add) {
+        (Err(pos), true) => ids.insert(pos, id),
+        (Ok(pos), false) => {
+            ids.remove(pos);
+        }
+        // Replaying the journal may insert the same record again.
+        _ => return Ok(None),
+    }
+
+    Ok(Some(ids))
+}
+
+/// Posting list changes of an index for the events of a commit.
+pub(crate) struct IndexWrite {
+    /// Encoded keys with the ID added to or removed from their posting list, in order.
+    changes: Vec<(Vec<u8>, u64, bool)>,
+}
+
+impl IndexWrite {
+    /// Apply the changes to the index tree within the transaction of the commit.
+    pub(crate) fn apply(
+        &self,
+        tree: &TransactionalTree,
+    ) -> ConflictableTransactionResult<(), TinyBaseError> {
+        for (key, id, add) in &self.changes {
+            let list = tree.get(key)?;
+            match changed_posting(list.as_deref(), *id, *add)
+                .map_err(ConflictableTransactionError::Abort)?
+            {
+                Some(ids) if ids.is_empty() => {
+                    tree.remove(key.as_slice())?;
+                }
+                Some(ids) => {
+                    let ids = encode_posting(&ids).map_err(ConflictableTransactionError::Abort)?;
+                    tree.insert(key.as_slice(), ids)?;
+                }
+                None => {}
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Type erased index state, used by the table to maintain its indexes.
+pub(crate) trait AnyIndexInner<T>: Send + Sync {
+    /// Persist the sketch of the index with its cursor.
+    fn close(&self) -> DbResult<()>;
+    /// Sequence number of the last journal entry applied to the index.
+    fn applied(&self) -> u64;
+    /// Whether the index is rebuilt and needs the journal entries of new writes.
+    fn is_rebuilding(&self) -> bool;
+    /// The stored state marking the saved sketch stale, written by the next commit.
+    ///
+    /// # Returns
+    ///
+    /// The encoded [`StoredSketch`], or [`None`] if it is already marked.
+    fn stale_sketch(&self) -> DbResult<Option<Vec<u8>>>;
+    /// Tree storing the IDs of the records by their encoded key.
+    fn tree(&self) -> &Tree;
+    /// Compute the posting list changes of the events of a commit, see [`IndexWrite`].
+    fn prepare(&self, events: &[Event<T>]) -> DbResult<IndexWrite>;
+    /// Record that the changes of a commit were written, up to its last sequence number.
+    fn committed(&self, seq: u64, write: &IndexWrite);
+    /// Record that the index was emptied along with its table, see [`TableInner::clear`].
+    fn cleared(&self, seq: u64);
+}
+
+impl<T: TableType, I: IndexType> AnyIndexInner<T> for IndexInner<T, I> {
+    fn close(&self) -> DbResult<()> {
+        self.save_cursor()
+    }
+
+    fn applied(&self) -> u64 {
+        let applied = self.applied.load(Ordering::Acquire);
+        match *self.rebuilding.lock().unwrap() {
+            Some(start) => applied.min(start),
+            None => applied,
+        }
+    }
+
+    fn is_rebuilding(&self) -> bool {
+        self.rebuilding.lock().unwrap().is_some()
+    }
+
+    fn stale_sketch(&self) -> DbResult<Option<Vec<u8>>> {
+        if !self.sketch_saved.load(Ordering::Acquire) {
+            return Ok(None);
+        }
+
+        let stale: StoredSketch = (FORMAT, self.version.clone(), None);
+        Ok(Some(encode(&stale)?))
+    }
+
+    fn tree(&self) -> &Tree {
+        &self.indexed_data
+    }
+
+    fn prepare(&self, events: &[Event<T>]) -> DbResult<IndexWrite> {
+        self.prepare(events)
+    }
+
+    fn committed(&self, seq: u64, write: &IndexWrite) {
+        let mut sketch = self.sketch.lock().unwrap();
+        let mut bloom = self.bloom.write().unwrap();
+        for (key, _, add) in &write.changes {
+            if *add {
+                sketch.add(key);
+                if let Some(bloom) = bloom.as_mut() {
+                    bloom.add(key);
+                }
+            }
+        }
+
+        self.applied.store(seq, Ordering::Release);
+        self.sketch_saved.store(false, Ordering::Release);
+    }
+
+    fn cleared(&self, seq: u64) {
+        *self.sketch.lock().unwrap() = HyperLogLog::default();
+        if let Some(bloom) = self.bloom.write().unwrap().as_mut() {
+            *bloom = BloomFilter::new(bloom.expected());
+        }
+
+        self.applied.store(seq, Ordering::Release);
+        self.sketch_saved.store(false, Ordering::Release);
+    }
+}
+
+pub(crate) mod private {
+    use super::*;
+
+    /// Additional methods for index which are only for internal use.
+    pub trait AnyIndexInternal<T: TableType> {
+        /// Records sharing a key with a record, with the encoded key they share.
+        fn tree_exists(&self, tree: &Tree, record: &Record<T>) -> DbResult<Vec<(Vec<u8>, u64)>>;
+        /// Amount of records with any of many encoded keys.
+        fn count_keys(&self, keys: &[Vec<u8>]) -> DbResult<usize>;
+        /// Posting lists of the keys matched by a lookup, see [`IndexInner::postings`].
+        fn postings(&self, lookup: KeyLookup<'_>) -> DbResult<(Vec<Vec<u64>>, usize)>;
+        /// Clone the index behind a new box.
+        fn boxed(&self) -> Box<dyn AnyIndex<T>>;
+    }
+
+    /// Keys of an index which a condition looks up or scans.
+    pub enum KeyLookup<'a> {
+        /// Encoded keys, each looked up.
+        Keys(Vec<Vec<u8>>),
+        /// Keys starting with an encoded prefix.
+        Prefix(&'a [u8]),
+        /// String keys matching a pattern, found by reading every key.
+        Matching(&'a dyn Fn(&str) -> bool),
+        /// Keys within a range of encoded keys.
+        Range(Bound<Vec<u8>>, Bound<Vec<u8>>),
+    }
+}
+
+impl<T, I> private::AnyIndexInternal<T> for Index<T, I>
+where
+    T: TableType,
+    I: IndexType + 'static,
+{
+    fn tree_exists(&self, tree: &Tree, record: &Record<T>) -> DbResult<Vec<(Vec<u8>, u64)>> {
+        let mut existing = vec![];
+        for key in self.try_keys_of(&record.data)? {
+            let encoded = encode_key(&key)?;
+            if !self.may_contain(&encoded) {
+                continue;
+            }
+
+            for other in self.tree_select(tree, &key)? {
+                existing.push((encoded.clone(), other.id));
+            }
+        }
+
+        Ok(existing)
+    }
+
+    fn count_keys(&self, keys: &[Vec<u8>]) -> DbResult<usize> {
+        self.0.count_keys(keys)
+    }
+
+    fn postings(&self, lookup: KeyLookup<'_>) -> DbResult<(Vec<Vec<u64>>, usize)> {
+        self.0.postings(lookup)
+    }
+
+    fn boxed(&self) -> Box<dyn AnyIndex<T>> {
+        Box::new(self.clone())
+    }
+}
+
+/// Type which [`Index`] can be casted to which doesn't require the `I` type parameter.
+pub trait AnyIndex<T: TableType>: private::AnyIndexInternal<T> + Send + Sync {
+    /// Check if a record exists by the index key.
+    ///
+    /// # Arguments
+    ///
+    /// * `record` - The record to check for existence.
+    fn exists(&self, record: &Record<T>) -> DbResult<Vec<u64>>;
+    /// Select of IDs matching any of many encoded keys.
+    fn search_keys_ids(&self, keys: Vec<Vec<u8>>) -> DbResult<Vec<u64>>;
+    /// Select of IDs of string or byte keys starting with a prefix.
+    fn search_prefix_ids(&self, prefix: &[u8]) -> DbResult<Vec<u64>>;
+    /// Select of IDs of string keys matching a pattern.
+    fn search_matching_ids(&self, pattern: &dyn Fn(&str) -> bool) -> DbResult<Vec<u64>>;
+    /// Select of IDs within a range of encoded keys.
+    fn search_range_ids(&self, start: Bound<Vec<u8>>, end: Bound<Vec<u8>>) -> DbResult<Vec<u64>>;
+    /// Select of records within a range of encoded keys.
+    fn search_range(&self, start: Bound<Vec<u8>>, end: Bound<Vec<u8>>) -> DbResult<Vec<Record<T>>>;
+    /// Alias for `index_name`.
+    fn idx_name(&self) -> String;
+    /// Generate the keys and return their encoded values, see [`IndexInner::generate_keys`].
+    fn gen_keys(&self, data: &T) -> DbResult<Vec<Vec<u8>>>;
+}
+
+impl<T, I> AnyIndex<T> for Index<T, I>
+where
+    T: TableType,
+    I: IndexType + 'static,
+{
+    fn search_keys_ids(&self, keys: Vec<Vec<u8>>) -> DbResult<Vec<u64>> {
+        self.keys_ids(keys)
+    }
+
+    fn search_prefix_ids(&self, prefix: &[u8]) -> DbResult<Vec<u64>> {
+        self.prefix_ids(prefix)
+    }
+
+    fn search_matching_ids(&self, pattern: &dyn Fn(&str) -> bool) -> DbResult<Vec<u64>> {
+        self.matching_ids(pattern)
+    }
+
+    fn search_range_ids(&self, start: Bound<Vec<u8>>, end: Bound<Vec<u8>>) -> DbResult<Vec<u64>> {
+        self.range_ids(start, end)
+    }
+
+    fn search_range(&self, start: Bound<Vec<u8>>, end: Bound<Vec<u8>>) -> DbResult<Vec<Record<T>>> {
+        let table = self.table.upgrade().unwrap();
+
+        let mut results = vec![];
+        for id in self.range_ids(start, end)? {
+            if let Some(record) = table.select(id)? {
+                results.push(record);
+            }
+        }
+
+        Ok(results)
+    }
+
+    fn idx_name(&self) -> String {
+        self.index_name()
+    }
+
+    fn exists(&self, record: &Record<T>) -> DbResult<Vec<u64>> {
+        let mut ids: Vec<u64> = self
+            .tree_exists(&self.table.upgrade().unwrap().root.read().unwrap(), record)?
+            .into_iter()
+            .map(|(_, id)| id)
+            .collect();
+        self.dedup_ids(&mut ids);
+        Ok(ids)
+    }
+
+    fn gen_keys(&self, data: &T) -> DbResult<Vec<Vec<u8>>> {
+        self.generate_keys(data)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{ConditionBuilder, Constraint, QueryBuilder, Table, TinyBase};
+
+    #[test]
+    fn index_sync() {
+        let db = TinyBase::new(None, true);
+        let table: Table<String> = db.open_table("test_table").unwrap();
+
+        // Insert a string value into the table
+        let id = table.insert("value1".to_string()).unwrap();
+        let id2 = table.insert("value2".to_string()).unwrap();
+
+        // Create an index for the table
+        let index = table.create_index("length", |value| value.len()).unwrap();
+
+        assert!(index.sync().is_ok());
+
+        let results = index.select(&6).unwrap();
 
         assert_eq!(results.len(), 2);
         assert_eq!(results[0].id, id);
@@ -397,6 +1689,380 @@ mod tests {
         assert_eq!(record_2.len(), 0);
     }
 
+    #[test]
+    fn index_keys() {
+        let db = TinyBase::new(None, true);
+        let table: Table<String> = db.open_table("test_table").unwrap();
+        let index = table.create_index("length", |value| value.len()).unwrap();
+
+        table.insert("a".to_string()).unwrap();
+        table.insert("bb".to_string()).unwrap();
+        table.insert("cc".to_string()).unwrap();
+        let removed = table.insert("ddd".to_string()).unwrap();
+        table.delete(removed).unwrap();
+
+        assert_eq!(index.keys().unwrap(), vec![1, 2]);
+        assert_eq!(index.keys_with_counts().unwrap(), vec![(1, 1), (2, 2)]);
+    }
+
+    #[test]
+    fn index_multi_value() {
+        let db = TinyBase::new(None, true);
+        let table: Table<Vec<String>> = db.open_table("posts").unwrap();
+        let tags = table
+            .create_multi_index("tags", |tags: &Vec<String>| tags.clone())
+            .unwrap();
+
+        let tagged = |values: &[&str]| values.iter().map(|tag| tag.to_string()).collect();
+        let first = table.insert(tagged(&["rust", "db"])).unwrap();
+        let second = table.insert(tagged(&["rust", "rust"])).unwrap();
+        table.insert(tagged(&[])).unwrap();
+
+        let ids = |tag: &str| tags.select_ids(&tag.to_string()).unwrap();
+        assert_eq!(ids("rust"), vec![first, second]);
+        assert_eq!(ids("db"), vec![first]);
+        assert_eq!(
+            tags.select_many_ids(&["rust".to_string(), "db".to_string()])
+                .unwrap(),
+            vec![first, second]
+        );
+        assert_eq!(
+            tags.keys_with_counts().unwrap(),
+            vec![("db".to_string(), 1), ("rust".to_string(), 2)]
+        );
+
+        table.update(&[first], |_| tagged(&["go"])).unwrap();
+        assert_eq!(ids("rust"), vec![second]);
+        assert!(ids("db").is_empty());
+        assert_eq!(ids("go"), vec![first]);
+
+        table.delete(second).unwrap();
+        assert!(ids("rust").is_empty());
+    }
+
+    #[test]
+    fn index_sparse() {
+        let db = TinyBase::new(None, true);
+        let table: Table<(String, Option<String>)> = db.open_table("users").unwrap();
+        let email = table
+            .create_sparse_index("email", |(_, email): &(String, Option<String>)| {
+                email.clone()
+            })
+            .unwrap();
+        let admins = table
+            .create_index_where(
+                "admins",
+                |(name, _): &(String, Option<String>)| name.starts_with("admin"),
+                |(name, _): &(String, Option<String>)| name.clone(),
+            )
+            .unwrap();
+
+        let john = table
+            .insert(("john".to_string(), Some("john@example.com".to_string())))
+            .unwrap();
+        let admin = table.insert(("admin".to_string(), None)).unwrap();
+
+        assert_eq!(email.keys().unwrap(), vec!["john@example.com".to_string()]);
+        assert_eq!(admins.keys().unwrap(), vec!["admin".to_string()]);
+        assert_eq!(
+            admins.select_ids(&"admin".to_string()).unwrap(),
+            vec![admin]
+        );
+
+        table
+            .update(&[john], |(_, _)| ("admin-john".to_string(), None))
+            .unwrap();
+        assert!(email.keys().unwrap().is_empty());
+        assert_eq!(
+            admins.keys().unwrap(),
+            vec!["admin".to_string(), "admin-john".to_string()]
+        );
+
+        let selected = QueryBuilder::new(&table)
+            .with_condition(ConditionBuilder::not(ConditionBuilder::by(
+                &email,
+                "john@example.com".to_string(),
+            )))
+            .select()
+            .unwrap();
+        assert_eq!(selected.len(), 2);
+    }
+
+    #[test]
+    fn index_select_range() {
+        let db = TinyBase::new(None, true);
+        let table: Table<(String, u32)> = db.open_table("people").unwrap();
+        let name = table
+            .create_index("name", |(name, _): &(String, u32)| name.clone())
+            .unwrap();
+        let age = table
+            .create_index("age", |(_, age): &(String, u32)| *age)
+            .unwrap();
+
+        for (person, years) in [("Johnny", 40), ("John", 20), ("Jane", 30), ("Joe", 20)] {
+            table.insert((person.to_string(), years)).unwrap();
+        }
+
+        let names = |records: Vec<Record<(String, u32)>>| -> Vec<String> {
+            records.into_iter().map(|record| record.data.0).collect()
+        };
+        assert_eq!(
+            names(age.select_range(25..).unwrap()),
+            vec!["Jane", "Johnny"]
+        );
+        assert_eq!(
+            names(age.select_range(..=30).unwrap()),
+            vec!["John", "Joe", "Jane"]
+        );
+        assert!(age.select_range(30..30).unwrap().is_empty());
+        assert_eq!(
+            names(name.select_prefix("Jo".to_string()).unwrap()),
+            vec!["Joe", "John", "Johnny"]
+        );
+    }
+
+    #[test]
+    fn index_keys_for() {
+        let db = TinyBase::new(None, true);
+        let table: Table<Vec<String>> = db.open_table("test_table").unwrap();
+        let tags = table
+            .create_multi_index("tags", |tags: &Vec<String>| tags.clone())
+            .unwrap();
+
+        let id = table
+            .insert(vec!["b".to_string(), "a".to_string()])
+            .unwrap();
+        table.insert(vec!["c".to_string()]).unwrap();
+
+        assert_eq!(tags.keys_for(id).unwrap(), ["a", "b"]);
+
+        table.update(&[id], |_| vec!["c".to_string()]).unwrap();
+        assert_eq!(tags.keys_for(id).unwrap(), ["c"]);
+
+        table.delete(id).unwrap();
+        assert!(tags.keys_for(id).unwrap().is_empty());
+    }
+
+    #[test]
+    fn index_top() {
+        let db = TinyBase::new(None, true);
+        let table: Table<(String, u32)> = db.open_table("scores").unwrap();
+        let score = table
+            .create_index("score", |(_, score): &(String, u32)| *score)
+            .unwrap();
+
+        assert!(score.min().unwrap().is_empty());
+
+        for (name, points) in [("a", 20), ("b", 5), ("c", 300), ("d", 20), ("e", 5)] {
+            table.insert((name.to_string(), points)).unwrap();
+        }
+
+        let names = |records: Vec<Record<(String, u32)>>| -> Vec<String> {
+            records.into_iter().map(|record| record.data.0).collect()
+        };
+
+        assert_eq!(names(score.min().unwrap()), ["b", "e"]);
+        assert_eq!(names(score.max().unwrap()), ["c"]);
+        assert_eq!(names(score.top(2, Direction::Desc).unwrap()), ["c", "a"]);
+        assert_eq!(
+            names(score.top(3, Direction::Asc).unwrap()),
+            ["b", "e", "a"]
+        );
+        assert_eq!(score.top(10, Direction::Asc).unwrap().len(), 5);
+    }
+
+    #[test]
+    fn index_verify() {
+        let db = TinyBase::new(None, true);
+        let table: Table<String> = db.open_table("test_table").unwrap();
+        let name = table
+            .create_index("name", |value| value.to_owned())
+            .unwrap();
+
+        let value1 = table.insert("value1".to_string()).unwrap();
+        let value2 = table.insert("value2".to_string()).unwrap();
+        assert!(name.verify().unwrap().is_consistent());
+
+        // Corrupt the index behind the back of the table.
+        let tree = &name.indexed_data;
+        tree.remove(encode_key("value1").unwrap()).unwrap();
+        tree.insert(
+            encode_key("ghost").unwrap(),
+            encode_posting(&[999u64]).unwrap(),
+        )
+        .unwrap();
+        tree.insert(
+            encode_key("value3").unwrap(),
+            encode_posting(&[value2]).unwrap(),
+        )
+        .unwrap();
+
+        let expected = IndexReport {
+            missing: vec![("value1".to_string(), value1)],
+            orphaned: vec![("ghost".to_string(), 999)],
+            misplaced: vec![("value3".to_string(), value2)],
+        };
+        assert_eq!(name.verify().unwrap(), expected);
+        assert_eq!(name.repair().unwrap(), expected);
+
+        assert!(name.verify().unwrap().is_consistent());
+        assert_eq!(name.select_ids(&"value1".to_string()).unwrap(), [value1]);
+        assert!(name.select_ids(&"value3".to_string()).unwrap().is_empty());
+    }
+
+    #[test]
+    fn index_written_with_commit() {
+        let db = TinyBase::new(None, true);
+        let table: Table<String> = db.open_table("test_table").unwrap();
+        let name = table
+            .create_index("name", |value| value.to_owned())
+            .unwrap();
+
+        let id = table.insert("value1".to_string()).unwrap();
+        table.update(&[id], |_| "value2".to_string()).unwrap();
+
+        // The tree and cursor were written by the commits, before any select.
+        let keys: Vec<_> = name
+            .indexed_data
+            .iter()
+            .keys()
+            .collect::<Result<_, _>>()
+            .unwrap();
+        assert_eq!(keys, [encode_key("value2").unwrap()]);
+        assert_eq!(
+            table.journal.cursor(&name.index_name()).unwrap(),
+            Some(table.journal.head().unwrap())
+        );
+    }
+
+    #[test]
+    fn index_bloom_filter() {
+        let db = TinyBase::new(None, true);
+        let table: Table<String> = db.open_table("test_table").unwrap();
+        let name = table
+            .create_index("name", |value| value.to_owned())
+            .unwrap();
+        table.constraint(Constraint::unique(&name)).unwrap();
+
+        let value1 = table.insert("value1".to_string()).unwrap();
+        name.enable_bloom_filter(1).unwrap();
+        let value2 = table.insert("value2".to_string()).unwrap();
+
+        let record = |data: &str| Record {
+            id: 0,
+            data: data.to_string(),
+        };
+        assert_eq!(name.exists(&record("value1")).unwrap(), [value1]);
+        assert_eq!(name.exists(&record("value2")).unwrap(), [value2]);
+        assert!(name.exists(&record("value3")).unwrap().is_empty());
+        assert!(table.insert("value2".to_string()).is_err());
+
+        // Filled again from the index, which outgrew the expected amount of keys.
+        for i in 0..100 {
+            table.insert(format!("other{}", i)).unwrap();
+        }
+        name.sync().unwrap();
+        assert_eq!(name.exists(&record("other99")).unwrap().len(), 1);
+        assert!(table.insert("other50".to_string()).is_err());
+
+        name.disable_bloom_filter();
+        assert_eq!(name.exists(&record("value1")).unwrap(), [value1]);
+    }
+
+    #[test]
+    fn index_count() {
+        let db = TinyBase::new(None, true);
+        let table: Table<String> = db.open_table("test_table").unwrap();
+        let length = table.create_index("length", |value| value.len()).unwrap();
+
+        for value in ["a", "b", "cc"] {
+            table.insert(value.to_string()).unwrap();
+        }
+
+        assert_eq!(length.count(&1).unwrap(), 2);
+        assert_eq!(length.count(&2).unwrap(), 1);
+        assert_eq!(length.count(&3).unwrap(), 0);
+    }
+
+    #[test]
+    fn index_stats() {
+        let db = TinyBase::new(None, true);
+        let table: Table<Vec<i32>> = db.open_table("numbers").unwrap();
+        let numbers = table
+            .create_multi_index("numbers", |numbers: &Vec<i32>| numbers.clone())
+            .unwrap();
+
+        let stats = numbers.stats().unwrap();
+        assert_eq!((stats.keys, stats.entries, stats.min), (0, 0, None));
+
+        table.insert(vec![-5, 3]).unwrap();
+        table.insert(vec![3, 10]).unwrap();
+
+        assert_eq!(
+            numbers.stats().unwrap(),
+            IndexStats {
+                keys: 3,
+                entries: 4,
+                min: Some(-5),
+                max: Some(10),
+                avg_entries_per_key: 4.0 / 3.0,
+            }
+        );
+    }
+
+    #[test]
+    fn index_select_many() {
+        let db = TinyBase::new(None, true);
+        let table: Table<String> = db.open_table("test_table").unwrap();
+
+        let first = table.insert("a".to_string()).unwrap();
+        let second = table.insert("bb".to_string()).unwrap();
+        table.insert("ccc".to_string()).unwrap();
+
+        let index = table.create_index("length", |value| value.len()).unwrap();
+
+        let records = index.select_many(&[2, 1, 2, 4]).unwrap();
+        assert_eq!(
+            records.iter().map(|record| record.id).collect::<Vec<_>>(),
+            vec![second, first]
+        );
+    }
+
+    #[test]
+    fn index_estimate_cardinality() {
+        let db = TinyBase::new(None, true);
+        let table: Table<u64> = db.open_table("test_table").unwrap();
+        let index = table.create_index("modulo", |value| value % 1000).unwrap();
+
+        for value in 0..5000 {
+            table.insert(value).unwrap();
+        }
+
+        let estimate = index.estimate_cardinality().unwrap();
+        assert!((950..=1050).contains(&estimate), "estimate {}", estimate);
+    }
+
+    #[test]
+    fn index_stale_sketch() {
+        let db = TinyBase::new(None, true);
+        let table: Table<u64> = db.open_table("test_table").unwrap();
+        let index = table.create_index("modulo", |value| value % 100).unwrap();
+        let name = index.index_name();
+
+        // Writes mark the saved sketch stale, as if the process ended without saving it.
+        for value in 0..500 {
+            table.insert(value).unwrap();
+        }
+        let stored = index.sketches.get(&name).unwrap().unwrap();
+        assert!(decode::<StoredSketch>(&stored).unwrap().2.is_none());
+        drop(index);
+
+        // The sketch is rebuilt from the stored keys instead of the whole index.
+        let index = table.create_index("modulo", |value| value % 100).unwrap();
+        let estimate = index.estimate_cardinality().unwrap();
+        assert!((95..=105).contains(&estimate), "estimate {}", estimate);
+    }
+
     #[test]
     fn index_update() {
         let db = TinyBase::new(None, true);
@@ -0,0 +1,112 @@
+use sled::{Db, Tree};
+
+use crate::encoding::{decode, encode};
+use crate::result::DbResult;
+use crate::subscriber::Event;
+use crate::table::TableType;
+
+/// Tree storing how far each index has applied the journal of its table,
+/// and up to where each journal was truncated.
+pub(crate) const CURSORS: &str = "__tinybase_cursors";
+
+/// Name of the journal tree of a table.
+pub(crate) fn journal_name(table: &str) -> String {
+    format!("{}_journal", table)
+}
+
+/// Write-ahead journal of the mutations of a table.
+///
+/// Open indexes are written in the same transaction as the table, so writes are
+/// only recorded here while an index is rebuilt and has to replay them. Otherwise
+/// the journal is truncated up to the write in the same transaction. Indexes persist
+/// the sequence number of the last entry they applied, indexes which weren't open
+/// during writes removed from the journal are rebuilt when they are created again.
+#[derive(Clone)]
+pub(crate) struct Journal {
+    name: String,
+    pub(crate) entries: Tree,
+    pub(crate) cursors: Tree,
+}
+
+impl Journal {
+    /// Open the journal of a table.
+    ///
+    /// # Arguments
+    ///
+    /// * `engine` - The database engine.
+    /// * `table` - The name of the table.
+    pub(crate) fn open(engine: &Db, table: &str) -> DbResult<Self> {
+        let name = journal_name(table);
+
+        Ok(Self {
+            entries: engine.open_tree(&name)?,
+            cursors: engine.open_tree(CURSORS)?,
+            name,
+        })
+    }
+
+    /// Name of the journal tree, under which its truncation is stored in [`CURSORS`].
+    pub(crate) fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Highest sequence number which was removed from the journal.
+    pub(crate) fn truncated(&self) -> DbResult<u64> {
+        Ok(self.cursor(&self.name)?.unwrap_or(0))
+    }
+
+    /// Last sequence number applied by a consumer.
+    pub(crate) fn cursor(&self, consumer: &str) -> DbResult<Option<u64>> {
+        match self.cursors.get(consumer)? {
+            Some(bytes) => Ok(Some(decode(&bytes)?)),
+            None => Ok(None),
+        }
+    }
+
+    /// Persist the last sequence number applied by a consumer.
+    pub(crate) fn set_cursor(&self, consumer: &str, seq: u64) -> DbResult<()> {
+        self.cursors.insert(consumer, encode(&seq)?)?;
+        Ok(())
+    }
+
+    /// All entries recorded after `seq`, in order.
+    pub(crate) fn since<T: TableType>(
+        &self,
+        seq: u64,
+    ) -> DbResult<impl Iterator<Item = DbResult<(u64, Event<T>)>>> {
+        Ok(self
+            .entries
+            .range(encode(&seq.saturating_add(1))?..)
+            .map(|entry| {
+                let (seq, event) = entry?;
+                Ok((decode(&seq)?, decode(&event)?))
+            }))
+    }
+
+    /// Remove all entries up to and including `seq`.
+    pub(crate) fn truncate(&self, seq: u64) -> DbResult<()> {
+        for key in self.entries.range(..=encode(&seq)?).keys() {
+            self.entries.remove(key?)?;
+        }
+
+        if seq > self.truncated()? {
+            self.set_cursor(&self.name, seq)?;
+        }
+
+        Ok(())
+    }
+
+    /// Sequence number of the last write recorded by the journal, even if its entry
+    /// was never written or already removed.
+    pub(crate) fn head(&self) -> DbResult<u64> {
+        Ok(self.last()?.unwrap_or(0).max(self.truncated()?))
+    }
+
+    /// Sequence number of the last entry in the journal.
+    pub(crate) fn last(&self) -> DbResult<Option<u64>> {
+        match self.entries.last()? {
+            Some((seq, _)) => Ok(Some(decode(&seq)?)),
+            None => Ok(None),
+        }
+    }
+}
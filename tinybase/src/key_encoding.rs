@@ -0,0 +1,663 @@
+use serde::de::{
+    self, DeserializeOwned, DeserializeSeed, EnumAccess, IntoDeserializer, MapAccess, SeqAccess,
+    VariantAccess, Visitor,
+};
+use serde::ser::{self, Serialize};
+
+use crate::DbResult;
+
+/// Marks that another element of a sequence or map follows.
+pub(crate) const ELEMENT: u8 = 1;
+/// Marks the end of a sequence or map.
+const END: u8 = 0;
+
+type Error = bincode::Error;
+type Result<T> = std::result::Result<T, Error>;
+
+/// Encode an index key, preserving the order of keys.
+///
+/// Comparing two encoded keys byte by byte gives the same order as comparing the keys:
+///
+/// - Unsigned integers are stored big-endian, signed integers with their sign bit flipped.
+/// - Floats are stored with their sign bit flipped, negative floats with all bits flipped.
+/// - Strings and byte buffers are escaped (`0x00` becomes `0x00 0xFF`) and end with `0x00 0x00`.
+/// - Every element of a sequence or map is preceded by `0x01`, the end is marked by `0x00`.
+/// - Tuples and structs are their fields one after the other, enums start with the variant index.
+///
+/// The encoding isn't self-describing, so keys can't use `#[serde(flatten)]` or untagged enums.
+pub(crate) fn encode_key<S: ?Sized + Serialize>(key: &S) -> DbResult<Vec<u8>> {
+    let mut serializer = KeySerializer { output: vec![] };
+    key.serialize(&mut serializer)?;
+    Ok(serializer.output)
+}
+
+/// Decode an index key encoded with [`encode_key`].
+pub(crate) fn decode_key<T: DeserializeOwned>(bytes: &[u8]) -> DbResult<T> {
+    let mut deserializer = KeyDeserializer { input: bytes };
+    Ok(T::deserialize(&mut deserializer)?)
+}
+
+/// Escape the bytes of a string or byte buffer, without the terminator.
+///
+/// The escaped bytes are a prefix of the encoding of every string starting with them.
+pub(crate) fn escape(bytes: &[u8], output: &mut Vec<u8>) {
+    for &byte in bytes {
+        output.push(byte);
+        if byte == 0 {
+            output.push(0xFF);
+        }
+    }
+}
+
+fn float_bits(bits: u64, sign: u64) -> u64 {
+    match bits & sign {
+        0 => bits | sign,
+        _ => !bits,
+    }
+}
+
+fn float_from_bits(bits: u64, sign: u64) -> u64 {
+    match bits & sign {
+        0 => !bits,
+        _ => bits & !sign,
+    }
+}
+
+struct KeySerializer {
+    output: Vec<u8>,
+}
+
+impl KeySerializer {
+    fn write_bytes(&mut self, bytes: &[u8]) {
+        escape(bytes, &mut self.output);
+        self.output.extend([0, 0]);
+    }
+}
+
+impl ser::Serializer for &mut KeySerializer {
+    type Ok = ();
+    type Error = Error;
+    type SerializeSeq = Self;
+    type SerializeTuple = Self;
+    type SerializeTupleStruct = Self;
+    type SerializeTupleVariant = Self;
+    type SerializeMap = Self;
+    type SerializeStruct = Self;
+    type SerializeStructVariant = Self;
+
+    fn serialize_bool(self, value: bool) -> Result<()> {
+        self.output.push(value as u8);
+        Ok(())
+    }
+
+    fn serialize_i8(self, value: i8) -> Result<()> {
+        self.serialize_u8(value as u8 ^ (1 << 7))
+    }
+
+    fn serialize_i16(self, value: i16) -> Result<()> {
+        self.serialize_u16(value as u16 ^ (1 << 15))
+    }
+
+    fn serialize_i32(self, value: i32) -> Result<()> {
+        self.serialize_u32(value as u32 ^ (1 << 31))
+    }
+
+    fn serialize_i64(self, value: i64) -> Result<()> {
+        self.serialize_u64(value as u64 ^ (1 << 63))
+    }
+
+    fn serialize_i128(self, value: i128) -> Result<()> {
+        self.serialize_u128(value as u128 ^ (1 << 127))
+    }
+
+    fn serialize_u8(self, value: u8) -> Result<()> {
+        self.output.push(value);
+        Ok(())
+    }
+
+    fn serialize_u16(self, value: u16) -> Result<()> {
+        self.output.extend(value.to_be_bytes());
+        Ok(())
+    }
+
+    fn serialize_u32(self, value: u32) -> Result<()> {
+        self.output.extend(value.to_be_bytes());
+        Ok(())
+    }
+
+    fn serialize_u64(self, value: u64) -> Result<()> {
+        self.output.extend(value.to_be_bytes());
+        Ok(())
+    }
+
+    fn serialize_u128(self, value: u128) -> Result<()> {
+        self.output.extend(value.to_be_bytes());
+        Ok(())
+    }
+
+    fn serialize_f32(self, value: f32) -> Result<()> {
+        self.serialize_u32(float_bits(value.to_bits() as u64, 1 << 31) as u32)
+    }
+
+    fn serialize_f64(self, value: f64) -> Result<()> {
+        self.serialize_u64(float_bits(value.to_bits(), 1 << 63))
+    }
+
+    fn serialize_char(self, value: char) -> Result<()> {
+        self.serialize_u32(value as u32)
+    }
+
+    fn serialize_str(self, value: &str) -> Result<()> {
+        self.write_bytes(value.as_bytes());
+        Ok(())
+    }
+
+    fn serialize_bytes(self, value: &[u8]) -> Result<()> {
+        self.write_bytes(value);
+        Ok(())
+    }
+
+    fn serialize_none(self) -> Result<()> {
+        self.serialize_u8(0)
+    }
+
+    fn serialize_some<V: ?Sized + Serialize>(self, value: &V) -> Result<()> {
+        self.output.push(1);
+        value.serialize(self)
+    }
+
+    fn serialize_unit(self) -> Result<()> {
+        Ok(())
+    }
+
+    fn serialize_unit_struct(self, _name: &'static str) -> Result<()> {
+        Ok(())
+    }
+
+    fn serialize_unit_variant(
+        self,
+        _name: &'static str,
+        variant_index: u32,
+        _variant: &'static str,
+    ) -> Result<()> {
+        self.serialize_u32(variant_index)
+    }
+
+    fn serialize_newtype_struct<V: ?Sized + Serialize>(
+        self,
+        _name: &'static str,
+        value: &V,
+    ) -> Result<()> {
+        value.serialize(self)
+    }
+
+    fn serialize_newtype_variant<V: ?Sized + Serialize>(
+        self,
+        _name: &'static str,
+        variant_index: u32,
+        _variant: &'static str,
+        value: &V,
+    ) -> Result<()> {
+        self.serialize_u32(variant_index)?;
+        value.serialize(self)
+    }
+
+    fn serialize_seq(self, _len: Option<usize>) -> Result<Self> {
+        Ok(self)
+    }
+
+    fn serialize_tuple(self, _len: usize) -> Result<Self> {
+        Ok(self)
+    }
+
+    fn serialize_tuple_struct(self, _name: &'static str, _len: usize) -> Result<Self> {
+        Ok(self)
+    }
+
+    fn serialize_tuple_variant(
+        self,
+        _name: &'static str,
+        variant_index: u32,
+        _variant: &'static str,
+        _len: usize,
+    ) -> Result<Self> {
+        self.serialize_u32(variant_index)?;
+        Ok(self)
+    }
+
+    fn serialize_map(self, _len: Option<usize>) -> Result<Self> {
+        Ok(self)
+    }
+
+    fn serialize_struct(self, _name: &'static str, _len: usize) -> Result<Self> {
+        Ok(self)
+    }
+
+    fn serialize_struct_variant(
+        self,
+        _name: &'static str,
+        variant_index: u32,
+        _variant: &'static str,
+        _len: usize,
+    ) -> Result<Self> {
+        self.serialize_u32(variant_index)?;
+        Ok(self)
+    }
+}
+
+impl ser::SerializeSeq for &mut KeySerializer {
+    type Ok = ();
+    type Error = Error;
+
+    fn serialize_element<V: ?Sized + Serialize>(&mut self, value: &V) -> Result<()> {
+        self.output.push(ELEMENT);
+        value.serialize(&mut **self)
+    }
+
+    fn end(self) -> Result<()> {
+        self.output.push(END);
+        Ok(())
+    }
+}
+
+impl ser::SerializeMap for &mut KeySerializer {
+    type Ok = ();
+    type Error = Error;
+
+    fn serialize_key<K: ?Sized + Serialize>(&mut self, key: &K) -> Result<()> {
+        self.output.push(ELEMENT);
+        key.serialize(&mut **self)
+    }
+
+    fn serialize_value<V: ?Sized + Serialize>(&mut self, value: &V) -> Result<()> {
+        value.serialize(&mut **self)
+    }
+
+    fn end(self) -> Result<()> {
+        self.output.push(END);
+        Ok(())
+    }
+}
+
+macro_rules! serialize_fields {
+    ($($trait:ident :: $method:ident),*) => {
+        $(impl ser::$trait for &mut KeySerializer {
+            type Ok = ();
+            type Error = Error;
+
+            fn $method<V: ?Sized + Serialize>(&mut self, value: &V) -> Result<()> {
+                value.serialize(&mut **self)
+            }
+
+            fn end(self) -> Result<()> {
+                Ok(())
+            }
+        })*
+    };
+}
+
+serialize_fields!(
+    SerializeTuple::serialize_element,
+    SerializeTupleStruct::serialize_field,
+    SerializeTupleVariant::serialize_field
+);
+
+impl ser::SerializeStruct for &mut KeySerializer {
+    type Ok = ();
+    type Error = Error;
+
+    fn serialize_field<V: ?Sized + Serialize>(
+        &mut self,
+        _key: &'static str,
+        value: &V,
+    ) -> Result<()> {
+        value.serialize(&mut **self)
+    }
+
+    fn end(self) -> Result<()> {
+        Ok(())
+    }
+}
+
+impl ser::SerializeStructVariant for &mut KeySerializer {
+    type Ok = ();
+    type Error = Error;
+
+    fn serialize_field<V: ?Sized + Serialize>(
+        &mut self,
+        _key: &'static str,
+        value: &V,
+    ) -> Result<()> {
+        value.serialize(&mut **self)
+    }
+
+    fn end(self) -> Result<()> {
+        Ok(())
+    }
+}
+
+struct KeyDeserializer<'de> {
+    input: &'de [u8],
+}
+
+impl<'de> KeyDeserializer<'de> {
+    fn take<const N: usize>(&mut self) -> Result<[u8; N]> {
+        match self.input.split_first_chunk::<N>() {
+            Some((bytes, rest)) => {
+                self.input = rest;
+                Ok(*bytes)
+            }
+            None => Err(de::Error::custom("unexpected end of key")),
+        }
+    }
+
+    fn read_u8(&mut self) -> Result<u8> {
+        Ok(self.take::<1>()?[0])
+    }
+
+    fn read_u32(&mut self) -> Result<u32> {
+        Ok(u32::from_be_bytes(self.take()?))
+    }
+
+    fn read_u64(&mut self) -> Result<u64> {
+        Ok(u64::from_be_bytes(self.take()?))
+    }
+
+    fn read_bytes(&mut self) -> Result<Vec<u8>> {
+        let mut bytes = vec![];
+        loop {
+            match self.read_u8()? {
+                0 => match self.read_u8()? {
+                    0 => return Ok(bytes),
+                    0xFF => bytes.push(0),
+                    _ => return Err(de::Error::custom("invalid escape in key")),
+                },
+                byte => bytes.push(byte),
+            }
+        }
+    }
+
+    /// Whether another element of a sequence or map follows.
+    fn read_element(&mut self) -> Result<bool> {
+        match self.read_u8()? {
+            ELEMENT => Ok(true),
+            END => Ok(false),
+            _ => Err(de::Error::custom("invalid sequence marker in key")),
+        }
+    }
+}
+
+impl<'de> de::Deserializer<'de> for &mut KeyDeserializer<'de> {
+    type Error = Error;
+
+    fn deserialize_any<V: Visitor<'de>>(self, _visitor: V) -> Result<V::Value> {
+        Err(de::Error::custom("index keys aren't self-describing"))
+    }
+
+    fn deserialize_bool<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        visitor.visit_bool(self.read_u8()? != 0)
+    }
+
+    fn deserialize_i8<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        visitor.visit_i8((self.read_u8()? ^ (1 << 7)) as i8)
+    }
+
+    fn deserialize_i16<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        visitor.visit_i16((u16::from_be_bytes(self.take()?) ^ (1 << 15)) as i16)
+    }
+
+    fn deserialize_i32<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        visitor.visit_i32((self.read_u32()? ^ (1 << 31)) as i32)
+    }
+
+    fn deserialize_i64<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        visitor.visit_i64((self.read_u64()? ^ (1 << 63)) as i64)
+    }
+
+    fn deserialize_i128<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        visitor.visit_i128((u128::from_be_bytes(self.take()?) ^ (1 << 127)) as i128)
+    }
+
+    fn deserialize_u8<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        visitor.visit_u8(self.read_u8()?)
+    }
+
+    fn deserialize_u16<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        visitor.visit_u16(u16::from_be_bytes(self.take()?))
+    }
+
+    fn deserialize_u32<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        visitor.visit_u32(self.read_u32()?)
+    }
+
+    fn deserialize_u64<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        visitor.visit_u64(self.read_u64()?)
+    }
+
+    fn deserialize_u128<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        visitor.visit_u128(u128::from_be_bytes(self.take()?))
+    }
+
+    fn deserialize_f32<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        let bits = float_from_bits(self.read_u32()? as u64, 1 << 31);
+        visitor.visit_f32(f32::from_bits(bits as u32))
+    }
+
+    fn deserialize_f64<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        let bits = float_from_bits(self.read_u64()?, 1 << 63);
+        visitor.visit_f64(f64::from_bits(bits))
+    }
+
+    fn deserialize_char<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        match char::from_u32(self.read_u32()?) {
+            Some(value) => visitor.visit_char(value),
+            None => Err(de::Error::custom("invalid char in key")),
+        }
+    }
+
+    fn deserialize_str<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        self.deserialize_string(visitor)
+    }
+
+    fn deserialize_string<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        match String::from_utf8(self.read_bytes()?) {
+            Ok(value) => visitor.visit_string(value),
+            Err(_) => Err(de::Error::custom("invalid utf-8 in key")),
+        }
+    }
+
+    fn deserialize_bytes<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        self.deserialize_byte_buf(visitor)
+    }
+
+    fn deserialize_byte_buf<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        visitor.visit_byte_buf(self.read_bytes()?)
+    }
+
+    fn deserialize_option<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        match self.read_u8()? {
+            0 => visitor.visit_none(),
+            _ => visitor.visit_some(self),
+        }
+    }
+
+    fn deserialize_unit<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        visitor.visit_unit()
+    }
+
+    fn deserialize_unit_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value> {
+        visitor.visit_unit()
+    }
+
+    fn deserialize_newtype_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value> {
+        visitor.visit_newtype_struct(self)
+    }
+
+    fn deserialize_seq<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        visitor.visit_seq(Elements(self))
+    }
+
+    fn deserialize_tuple<V: Visitor<'de>>(self, len: usize, visitor: V) -> Result<V::Value> {
+        visitor.visit_seq(Fields(self, len))
+    }
+
+    fn deserialize_tuple_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        len: usize,
+        visitor: V,
+    ) -> Result<V::Value> {
+        visitor.visit_seq(Fields(self, len))
+    }
+
+    fn deserialize_map<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        visitor.visit_map(Elements(self))
+    }
+
+    fn deserialize_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        fields: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value> {
+        visitor.visit_seq(Fields(self, fields.len()))
+    }
+
+    fn deserialize_enum<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        _variants: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value> {
+        visitor.visit_enum(self)
+    }
+
+    fn deserialize_identifier<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        self.deserialize_u32(visitor)
+    }
+
+    fn deserialize_ignored_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        self.deserialize_any(visitor)
+    }
+}
+
+/// Elements of a sequence or map, each preceded by a marker.
+struct Elements<'a, 'de>(&'a mut KeyDeserializer<'de>);
+
+impl<'de> SeqAccess<'de> for Elements<'_, 'de> {
+    type Error = Error;
+
+    fn next_element_seed<S: DeserializeSeed<'de>>(&mut self, seed: S) -> Result<Option<S::Value>> {
+        match self.0.read_element()? {
+            true => seed.deserialize(&mut *self.0).map(Some),
+            false => Ok(None),
+        }
+    }
+}
+
+impl<'de> MapAccess<'de> for Elements<'_, 'de> {
+    type Error = Error;
+
+    fn next_key_seed<S: DeserializeSeed<'de>>(&mut self, seed: S) -> Result<Option<S::Value>> {
+        self.next_element_seed(seed)
+    }
+
+    fn next_value_seed<S: DeserializeSeed<'de>>(&mut self, seed: S) -> Result<S::Value> {
+        seed.deserialize(&mut *self.0)
+    }
+}
+
+/// A known amount of fields of a tuple or struct.
+struct Fields<'a, 'de>(&'a mut KeyDeserializer<'de>, usize);
+
+impl<'de> SeqAccess<'de> for Fields<'_, 'de> {
+    type Error = Error;
+
+    fn next_element_seed<S: DeserializeSeed<'de>>(&mut self, seed: S) -> Result<Option<S::Value>> {
+        match self.1 {
+            0 => Ok(None),
+            _ => {
+                self.1 -= 1;
+                seed.deserialize(&mut *self.0).map(Some)
+            }
+        }
+    }
+
+    fn size_hint(&self) -> Option<usize> {
+        Some(self.1)
+    }
+}
+
+impl<'de> EnumAccess<'de> for &mut KeyDeserializer<'de> {
+    type Error = Error;
+    type Variant = Self;
+
+    fn variant_seed<S: DeserializeSeed<'de>>(self, seed: S) -> Result<(S::Value, Self)> {
+        let index = self.read_u32()?;
+        let value = seed.deserialize(IntoDeserializer::<Error>::into_deserializer(index))?;
+        Ok((value, self))
+    }
+}
+
+impl<'de> VariantAccess<'de> for &mut KeyDeserializer<'de> {
+    type Error = Error;
+
+    fn unit_variant(self) -> Result<()> {
+        Ok(())
+    }
+
+    fn newtype_variant_seed<S: DeserializeSeed<'de>>(self, seed: S) -> Result<S::Value> {
+        seed.deserialize(self)
+    }
+
+    fn tuple_variant<V: Visitor<'de>>(self, len: usize, visitor: V) -> Result<V::Value> {
+        visitor.visit_seq(Fields(self, len))
+    }
+
+    fn struct_variant<V: Visitor<'de>>(
+        self,
+        fields: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value> {
+        visitor.visit_seq(Fields(self, fields.len()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn key_encoding_order() {
+        fn assert_ordered<K: Serialize + DeserializeOwned + PartialEq + std::fmt::Debug>(
+            keys: &[K],
+        ) {
+            let encoded: Vec<_> = keys.iter().map(|key| encode_key(key).unwrap()).collect();
+            assert!(encoded.windows(2).all(|pair| pair[0] < pair[1]));
+
+            for (key, bytes) in keys.iter().zip(&encoded) {
+                assert_eq!(&decode_key::<K>(bytes).unwrap(), key);
+            }
+        }
+
+        assert_ordered(&[i64::MIN, -300, -1, 0, 1, 300, i64::MAX]);
+        assert_ordered(&[u32::MIN, 1, 256, u32::MAX]);
+        assert_ordered(&[f64::NEG_INFINITY, -2.5, -0.0, 0.0, 1e-9, 3.0, f64::INFINITY]);
+        assert_ordered(&["", "a", "a\0", "a\0b", "ab", "b"].map(String::from));
+        assert_ordered(&[
+            ("a".to_string(), 2),
+            ("a".to_string(), 10),
+            ("b".to_string(), -1),
+        ]);
+        assert_ordered(&[None, Some(-1i8), Some(1)]);
+        assert_ordered(&[vec![], vec![1u16], vec![1, 2], vec![2]]);
+    }
+}
@@ -0,0 +1,643 @@
+use std::collections::BTreeMap;
+
+use serde::de::{
+    self, DeserializeOwned, DeserializeSeed, EnumAccess, IntoDeserializer, MapAccess, SeqAccess,
+    VariantAccess, Visitor,
+};
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+
+use crate::result::{DbResult, TinyBaseError};
+
+type Error = bincode::Error;
+type Result<T> = std::result::Result<T, Error>;
+
+/// Most times a type is traced to reach every variant of its enums.
+const MAX_TRACES: usize = 1024;
+/// Deepest nesting of a traced type, recursive types never reach the bottom.
+const MAX_DEPTH: usize = 64;
+
+/// Shape of a stored value, see [`RecordLayout`].
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub(crate) enum Layout {
+    Unit,
+    Bool,
+    I8,
+    I16,
+    I32,
+    I64,
+    I128,
+    U8,
+    U16,
+    U32,
+    U64,
+    U128,
+    F32,
+    F64,
+    Char,
+    String,
+    Bytes,
+    Option(Box<Layout>),
+    Seq(Box<Layout>),
+    Map(Box<Layout>, Box<Layout>),
+    Tuple(Vec<Layout>),
+    Struct(Vec<(String, Layout)>),
+    /// An enum by its name, its variants are listed in [`RecordLayout::enums`].
+    Enum(String),
+    /// A value which wasn't reached while tracing, it can't be read.
+    Unknown,
+}
+
+/// Layout of a record type, which reads the stored bytes of its records without the type.
+///
+/// The layout is traced from the [`Deserialize`] implementation of the type, so it only
+/// works for types which aren't self-describing, like the ones stored in tables anyway.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub(crate) struct RecordLayout {
+    root: Layout,
+    /// Variants of every enum by the name of the enum, with the layout of their fields.
+    enums: BTreeMap<String, Vec<(String, Layout)>>,
+}
+
+impl RecordLayout {
+    /// Trace the layout of a type.
+    ///
+    /// The type is deserialized from placeholder values until every variant of its
+    /// enums was picked once.
+    ///
+    /// # Returns
+    ///
+    /// The [`RecordLayout`], or [`TinyBaseError::Script`] if the type can't be traced.
+    pub(crate) fn trace<T: DeserializeOwned>() -> DbResult<Self> {
+        let mut tracer = Tracer::default();
+
+        for pass in 0..MAX_TRACES {
+            tracer.pass = pass;
+
+            let mut root = Layout::Unknown;
+            T::deserialize(Trace::new(&mut tracer, &mut root, 0)?)
+                .map_err(|err| TinyBaseError::Script(format!("can't trace record: {}", err)))?;
+
+            let complete = tracer
+                .enums
+                .values()
+                .flatten()
+                .all(|(_, layout)| layout.is_some());
+
+            if complete {
+                let enums = tracer
+                    .enums
+                    .into_iter()
+                    .map(|(name, variants)| {
+                        let variants = variants
+                            .into_iter()
+                            .map(|(variant, layout)| (variant, layout.unwrap()))
+                            .collect();
+                        (name, variants)
+                    })
+                    .collect();
+
+                return Ok(Self { root, enums });
+            }
+        }
+
+        Err(TinyBaseError::Script(
+            "can't trace record: too many enum variants".to_owned(),
+        ))
+    }
+
+    /// Convert the stored bytes of a record to JSON, like [`serde_json::to_value`] does
+    /// for the record.
+    pub(crate) fn to_json(&self, bytes: &[u8]) -> DbResult<Value> {
+        let mut input = bytes;
+        Ok(self.read(&self.root, &mut input)?)
+    }
+
+    fn read(&self, layout: &Layout, input: &mut &[u8]) -> Result<Value> {
+        Ok(match layout {
+            Layout::Unit => Value::Null,
+            Layout::Bool => Value::Bool(take::<1>(input)?[0] != 0),
+            Layout::I8 => i8::from_be_bytes(take(input)?).into(),
+            Layout::I16 => i16::from_be_bytes(take(input)?).into(),
+            Layout::I32 => i32::from_be_bytes(take(input)?).into(),
+            Layout::I64 => i64::from_be_bytes(take(input)?).into(),
+            Layout::I128 => wide(i128::from_be_bytes(take(input)?))?,
+            Layout::U8 => u8::from_be_bytes(take(input)?).into(),
+            Layout::U16 => u16::from_be_bytes(take(input)?).into(),
+            Layout::U32 => u32::from_be_bytes(take(input)?).into(),
+            Layout::U64 => u64::from_be_bytes(take(input)?).into(),
+            Layout::U128 => wide(u128::from_be_bytes(take(input)?))?,
+            Layout::F32 => f32::from_be_bytes(take(input)?).into(),
+            Layout::F64 => f64::from_be_bytes(take(input)?).into(),
+            Layout::Char => {
+                // Chars are stored as their UTF-8 bytes.
+                let len = match input.first() {
+                    Some(byte) if *byte < 0x80 => 1,
+                    Some(byte) if *byte < 0xE0 => 2,
+                    Some(byte) if *byte < 0xF0 => 3,
+                    _ => 4,
+                };
+                let bytes = take_slice(input, len)?;
+                match std::str::from_utf8(bytes) {
+                    Ok(char) => Value::String(char.to_owned()),
+                    Err(_) => return Err(de::Error::custom("invalid char in record")),
+                }
+            }
+            Layout::String => {
+                let len = read_len(input)?;
+                match String::from_utf8(take_slice(input, len)?.to_vec()) {
+                    Ok(string) => Value::String(string),
+                    Err(_) => return Err(de::Error::custom("invalid utf-8 in record")),
+                }
+            }
+            Layout::Bytes => {
+                let len = read_len(input)?;
+                Value::Array(
+                    take_slice(input, len)?
+                        .iter()
+                        .map(|byte| (*byte).into())
+                        .collect(),
+                )
+            }
+            Layout::Option(inner) => match take::<1>(input)?[0] {
+                0 => Value::Null,
+                _ => self.read(inner, input)?,
+            },
+            Layout::Seq(element) => {
+                // Not preallocated, the length may be corrupt.
+                let mut elements = vec![];
+                for _ in 0..read_len(input)? {
+                    elements.push(self.read(element, input)?);
+                }
+                Value::Array(elements)
+            }
+            Layout::Map(key, value) => {
+                let mut map = Map::new();
+                for _ in 0..read_len(input)? {
+                    let key = match self.read(key, input)? {
+                        Value::String(key) => key,
+                        Value::Number(key) => key.to_string(),
+                        _ => return Err(de::Error::custom("map key must be a string or number")),
+                    };
+                    map.insert(key, self.read(value, input)?);
+                }
+                Value::Object(map)
+            }
+            Layout::Tuple(fields) => Value::Array(
+                fields
+                    .iter()
+                    .map(|field| self.read(field, input))
+                    .collect::<Result<_>>()?,
+            ),
+            Layout::Struct(fields) => Value::Object(
+                fields
+                    .iter()
+                    .map(|(name, field)| Ok((name.clone(), self.read(field, input)?)))
+                    .collect::<Result<_>>()?,
+            ),
+            Layout::Enum(name) => {
+                let index = u32::from_be_bytes(take(input)?) as usize;
+                match self
+                    .enums
+                    .get(name)
+                    .and_then(|variants| variants.get(index))
+                {
+                    // Enums are externally tagged, unit variants are just their name.
+                    Some((variant, Layout::Unit)) => Value::String(variant.clone()),
+                    Some((variant, layout)) => {
+                        let mut map = Map::new();
+                        map.insert(variant.clone(), self.read(layout, input)?);
+                        Value::Object(map)
+                    }
+                    None => return Err(de::Error::custom("invalid enum variant in record")),
+                }
+            }
+            Layout::Unknown => return Err(de::Error::custom("record doesn't match its layout")),
+        })
+    }
+}
+
+fn take<const N: usize>(input: &mut &[u8]) -> Result<[u8; N]> {
+    match input.split_first_chunk::<N>() {
+        Some((bytes, rest)) => {
+            *input = rest;
+            Ok(*bytes)
+        }
+        None => Err(de::Error::custom("unexpected end of record")),
+    }
+}
+
+fn take_slice<'a>(input: &mut &'a [u8], len: usize) -> Result<&'a [u8]> {
+    if input.len() < len {
+        return Err(de::Error::custom("unexpected end of record"));
+    }
+
+    let (bytes, rest) = input.split_at(len);
+    *input = rest;
+    Ok(bytes)
+}
+
+/// Length of a string, sequence or map.
+fn read_len(input: &mut &[u8]) -> Result<usize> {
+    Ok(u64::from_be_bytes(take(input)?) as usize)
+}
+
+/// Convert a 128 bit integer to JSON, which only fits if it fits 64 bits.
+fn wide<N: TryInto<i64> + TryInto<u64> + Copy>(value: N) -> Result<Value> {
+    match (
+        TryInto::<i64>::try_into(value),
+        TryInto::<u64>::try_into(value),
+    ) {
+        (Ok(value), _) => Ok(value.into()),
+        (_, Ok(value)) => Ok(value.into()),
+        _ => Err(de::Error::custom("128 bit integer doesn't fit JSON")),
+    }
+}
+
+/// State of tracing a type, shared by all of its values.
+#[derive(Default)]
+struct Tracer {
+    /// Variants of every reached enum, with their layout once they were picked.
+    enums: BTreeMap<String, Vec<(String, Option<Layout>)>>,
+    /// The current pass, enums whose variants were all picked pick them in turn.
+    pass: usize,
+}
+
+/// Deserializer which records the layout of the value deserialized from it.
+struct Trace<'a> {
+    tracer: &'a mut Tracer,
+    layout: &'a mut Layout,
+    depth: usize,
+}
+
+impl<'a> Trace<'a> {
+    fn new(tracer: &'a mut Tracer, layout: &'a mut Layout, depth: usize) -> Result<Self> {
+        if depth > MAX_DEPTH {
+            return Err(de::Error::custom("recursive types aren't supported"));
+        }
+
+        Ok(Self {
+            tracer,
+            layout,
+            depth,
+        })
+    }
+}
+
+macro_rules! trace_primitive {
+    ($method:ident, $visit:ident, $layout:ident, $value:expr) => {
+        fn $method<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+            *self.layout = Layout::$layout;
+            visitor.$visit($value)
+        }
+    };
+}
+
+impl<'de> de::Deserializer<'de> for Trace<'_> {
+    type Error = Error;
+
+    fn deserialize_any<V: Visitor<'de>>(self, _visitor: V) -> Result<V::Value> {
+        Err(de::Error::custom("self-describing types aren't supported"))
+    }
+
+    trace_primitive!(deserialize_bool, visit_bool, Bool, false);
+    trace_primitive!(deserialize_i8, visit_i8, I8, 0);
+    trace_primitive!(deserialize_i16, visit_i16, I16, 0);
+    trace_primitive!(deserialize_i32, visit_i32, I32, 0);
+    trace_primitive!(deserialize_i64, visit_i64, I64, 0);
+    trace_primitive!(deserialize_i128, visit_i128, I128, 0);
+    trace_primitive!(deserialize_u8, visit_u8, U8, 0);
+    trace_primitive!(deserialize_u16, visit_u16, U16, 0);
+    trace_primitive!(deserialize_u32, visit_u32, U32, 0);
+    trace_primitive!(deserialize_u64, visit_u64, U64, 0);
+    trace_primitive!(deserialize_u128, visit_u128, U128, 0);
+    trace_primitive!(deserialize_f32, visit_f32, F32, 0.0);
+    trace_primitive!(deserialize_f64, visit_f64, F64, 0.0);
+    trace_primitive!(deserialize_char, visit_char, Char, '\0');
+    trace_primitive!(deserialize_str, visit_str, String, "");
+    trace_primitive!(deserialize_string, visit_string, String, String::new());
+    trace_primitive!(deserialize_bytes, visit_bytes, Bytes, &[]);
+    trace_primitive!(deserialize_byte_buf, visit_byte_buf, Bytes, vec![]);
+
+    fn deserialize_option<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        let mut inner = Layout::Unknown;
+        let value = visitor.visit_some(Trace::new(self.tracer, &mut inner, self.depth + 1)?)?;
+        *self.layout = Layout::Option(Box::new(inner));
+        Ok(value)
+    }
+
+    fn deserialize_unit<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        *self.layout = Layout::Unit;
+        visitor.visit_unit()
+    }
+
+    fn deserialize_unit_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value> {
+        self.deserialize_unit(visitor)
+    }
+
+    fn deserialize_newtype_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value> {
+        // Newtype structs are stored like the value they wrap.
+        visitor.visit_newtype_struct(self)
+    }
+
+    fn deserialize_seq<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        let mut element = Layout::Unknown;
+        let value = visitor.visit_seq(Single(Some(Trace::new(
+            self.tracer,
+            &mut element,
+            self.depth + 1,
+        )?)))?;
+        *self.layout = Layout::Seq(Box::new(element));
+        Ok(value)
+    }
+
+    fn deserialize_tuple<V: Visitor<'de>>(self, len: usize, visitor: V) -> Result<V::Value> {
+        let mut fields = vec![];
+        let value = visitor.visit_seq(Fields {
+            tracer: self.tracer,
+            layouts: &mut fields,
+            remaining: len,
+            depth: self.depth + 1,
+        })?;
+        *self.layout = Layout::Tuple(fields);
+        Ok(value)
+    }
+
+    fn deserialize_tuple_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        len: usize,
+        visitor: V,
+    ) -> Result<V::Value> {
+        self.deserialize_tuple(len, visitor)
+    }
+
+    fn deserialize_map<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        let (mut key, mut value) = (Layout::Unknown, Layout::Unknown);
+        let map = visitor.visit_map(Entry {
+            tracer: self.tracer,
+            key: &mut key,
+            value: &mut value,
+            depth: self.depth + 1,
+            done: false,
+        })?;
+        *self.layout = Layout::Map(Box::new(key), Box::new(value));
+        Ok(map)
+    }
+
+    fn deserialize_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        fields: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value> {
+        let mut layouts = vec![];
+        let value = visitor.visit_seq(Fields {
+            tracer: self.tracer,
+            layouts: &mut layouts,
+            remaining: fields.len(),
+            depth: self.depth + 1,
+        })?;
+        *self.layout = Layout::Struct(
+            fields
+                .iter()
+                .map(|field| field.to_string())
+                .zip(layouts)
+                .collect(),
+        );
+        Ok(value)
+    }
+
+    fn deserialize_enum<V: Visitor<'de>>(
+        self,
+        name: &'static str,
+        variants: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value> {
+        let known = self.tracer.enums.entry(name.to_owned()).or_insert_with(|| {
+            variants
+                .iter()
+                .map(|variant| (variant.to_string(), None))
+                .collect()
+        });
+
+        // Variants which weren't picked yet come first.
+        let index = known
+            .iter()
+            .position(|(_, layout)| layout.is_none())
+            .unwrap_or(self.tracer.pass % known.len().max(1));
+
+        let mut layout = Layout::Unknown;
+        let value = visitor.visit_enum(Variant {
+            trace: Trace::new(self.tracer, &mut layout, self.depth + 1)?,
+            index: index as u32,
+        })?;
+
+        self.tracer.enums.get_mut(name).unwrap()[index].1 = Some(layout);
+        *self.layout = Layout::Enum(name.to_owned());
+        Ok(value)
+    }
+
+    fn deserialize_identifier<V: Visitor<'de>>(self, _visitor: V) -> Result<V::Value> {
+        Err(de::Error::custom("identifiers can't be traced"))
+    }
+
+    fn deserialize_ignored_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        self.deserialize_any(visitor)
+    }
+}
+
+/// A sequence with a single traced element.
+struct Single<'a>(Option<Trace<'a>>);
+
+impl<'de> SeqAccess<'de> for Single<'_> {
+    type Error = Error;
+
+    fn next_element_seed<S: DeserializeSeed<'de>>(&mut self, seed: S) -> Result<Option<S::Value>> {
+        match self.0.take() {
+            Some(trace) => seed.deserialize(trace).map(Some),
+            None => Ok(None),
+        }
+    }
+}
+
+/// A map with a single traced entry.
+struct Entry<'a> {
+    tracer: &'a mut Tracer,
+    key: &'a mut Layout,
+    value: &'a mut Layout,
+    depth: usize,
+    /// Whether the key was traced.
+    done: bool,
+}
+
+impl<'de> MapAccess<'de> for Entry<'_> {
+    type Error = Error;
+
+    fn next_key_seed<S: DeserializeSeed<'de>>(&mut self, seed: S) -> Result<Option<S::Value>> {
+        if self.done {
+            return Ok(None);
+        }
+
+        self.done = true;
+        seed.deserialize(Trace::new(self.tracer, self.key, self.depth)?)
+            .map(Some)
+    }
+
+    fn next_value_seed<S: DeserializeSeed<'de>>(&mut self, seed: S) -> Result<S::Value> {
+        seed.deserialize(Trace::new(self.tracer, self.value, self.depth)?)
+    }
+}
+
+/// A known amount of traced fields of a tuple or struct.
+struct Fields<'a> {
+    tracer: &'a mut Tracer,
+    layouts: &'a mut Vec<Layout>,
+    remaining: usize,
+    depth: usize,
+}
+
+impl<'de> SeqAccess<'de> for Fields<'_> {
+    type Error = Error;
+
+    fn next_element_seed<S: DeserializeSeed<'de>>(&mut self, seed: S) -> Result<Option<S::Value>> {
+        if self.remaining == 0 {
+            return Ok(None);
+        }
+
+        self.remaining -= 1;
+        self.layouts.push(Layout::Unknown);
+        let layout = self.layouts.last_mut().unwrap();
+        seed.deserialize(Trace::new(self.tracer, layout, self.depth)?)
+            .map(Some)
+    }
+
+    fn size_hint(&self) -> Option<usize> {
+        Some(self.remaining)
+    }
+}
+
+/// The picked variant of a traced enum.
+struct Variant<'a> {
+    trace: Trace<'a>,
+    index: u32,
+}
+
+impl<'de, 'a> EnumAccess<'de> for Variant<'a> {
+    type Error = Error;
+    type Variant = Trace<'a>;
+
+    fn variant_seed<S: DeserializeSeed<'de>>(self, seed: S) -> Result<(S::Value, Trace<'a>)> {
+        let value = seed.deserialize(IntoDeserializer::<Error>::into_deserializer(self.index))?;
+        Ok((value, self.trace))
+    }
+}
+
+impl<'de> VariantAccess<'de> for Trace<'_> {
+    type Error = Error;
+
+    fn unit_variant(self) -> Result<()> {
+        *self.layout = Layout::Unit;
+        Ok(())
+    }
+
+    fn newtype_variant_seed<S: DeserializeSeed<'de>>(self, seed: S) -> Result<S::Value> {
+        seed.deserialize(self)
+    }
+
+    fn tuple_variant<V: Visitor<'de>>(self, len: usize, visitor: V) -> Result<V::Value> {
+        de::Deserializer::deserialize_tuple(self, len, visitor)
+    }
+
+    fn struct_variant<V: Visitor<'de>>(
+        self,
+        fields: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value> {
+        de::Deserializer::deserialize_struct(self, "", fields, visitor)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use super::*;
+    use crate::encoding::encode;
+
+    #[derive(Serialize, Deserialize)]
+    struct Wrapper(u32);
+
+    #[derive(Serialize, Deserialize)]
+    enum Status {
+        Active,
+        Away(String),
+        Moved { city: String, since: (u16, u8) },
+    }
+
+    #[derive(Serialize, Deserialize)]
+    struct Record {
+        name: String,
+        initial: char,
+        id: Wrapper,
+        balance: i128,
+        ratio: f32,
+        nickname: Option<String>,
+        tags: Vec<String>,
+        scores: HashMap<u8, Option<bool>>,
+        history: Vec<Status>,
+        avatar: Vec<u8>,
+        unit: (),
+    }
+
+    #[test]
+    fn layout_to_json() {
+        let layout = RecordLayout::trace::<Record>().unwrap();
+
+        let record = Record {
+            name: "John".to_owned(),
+            initial: 'é',
+            id: Wrapper(7),
+            balance: -12,
+            ratio: 0.5,
+            nickname: None,
+            tags: vec!["a".to_owned(), "b".to_owned()],
+            scores: HashMap::from([(3, Some(true))]),
+            history: vec![
+                Status::Active,
+                Status::Away("on vacation".to_owned()),
+                Status::Moved {
+                    city: "Berlin".to_owned(),
+                    since: (2020, 4),
+                },
+            ],
+            avatar: vec![1, 2],
+            unit: (),
+        };
+
+        assert_eq!(
+            layout.to_json(&encode(&record).unwrap()).unwrap(),
+            serde_json::to_value(&record).unwrap()
+        );
+    }
+
+    #[test]
+    fn layout_rejects_recursive_types() {
+        #[derive(Deserialize)]
+        #[allow(dead_code)]
+        struct Node(Option<Box<Node>>);
+
+        assert!(matches!(
+            RecordLayout::trace::<Node>(),
+            Err(TinyBaseError::Script(_))
+        ));
+    }
+}
@@ -0,0 +1,278 @@
+use std::fmt::{self, Debug};
+use std::marker::PhantomData;
+use std::sync::OnceLock;
+
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use sled::Tree;
+
+use crate::encoding::{decode, encode};
+use crate::result::{DbResult, TinyBaseError};
+use crate::table::TableType;
+
+/// A field which is stored separately from its record and decoded on first access.
+///
+/// Register the field with [`crate::Table::lazy_field`] so its value is moved to a
+/// secondary tree when the record is written. Records selected from the table then
+/// only load the value once [`Lazy::get`] is called.
+pub struct Lazy<V> {
+    value: OnceLock<V>,
+    /// Tree and record ID the value can be loaded from.
+    source: Option<(Tree, u64)>,
+}
+
+impl<V: Serialize + DeserializeOwned> Lazy<V> {
+    pub fn new(value: V) -> Self {
+        Self {
+            value: OnceLock::from(value),
+            source: None,
+        }
+    }
+
+    /// Get the value, loading it if it wasn't loaded yet.
+    ///
+    /// # Returns
+    ///
+    /// A reference to the value, or [`TinyBaseError::Unloaded`] if it isn't stored anywhere.
+    pub fn get(&self) -> DbResult<&V> {
+        if let Some(value) = self.value.get() {
+            return Ok(value);
+        }
+
+        let value = match &self.source {
+            Some((tree, id)) => match tree.get(encode(id)?)? {
+                Some(bytes) => decode(&bytes)?,
+                None => return Err(TinyBaseError::Unloaded),
+            },
+            None => return Err(TinyBaseError::Unloaded),
+        };
+
+        Ok(self.value.get_or_init(|| value))
+    }
+
+    /// Take the value, loading it if it wasn't loaded yet.
+    pub fn into_inner(self) -> DbResult<V> {
+        self.get()?;
+        Ok(self.value.into_inner().unwrap())
+    }
+
+    /// Whether the value was already loaded.
+    pub fn is_loaded(&self) -> bool {
+        self.value.get().is_some()
+    }
+}
+
+impl<V: Clone> Clone for Lazy<V> {
+    fn clone(&self) -> Self {
+        Self {
+            value: self.value.clone(),
+            source: self.source.clone(),
+        }
+    }
+}
+
+/// Formatted as the value, which is loaded if it wasn't loaded yet.
+impl<V: Serialize + DeserializeOwned + Debug> Debug for Lazy<V> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.get() {
+            Ok(value) => value.fmt(f),
+            Err(_) => f.write_str("<unloaded>"),
+        }
+    }
+}
+
+/// Serialized as the value, which is loaded if it wasn't loaded yet. Only values which
+/// aren't stored anywhere, like the fields of written records, are serialized as none.
+impl<V: Serialize + DeserializeOwned> Serialize for Lazy<V> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        match (self.get(), &self.source) {
+            (Ok(value), _) => Some(value).serialize(serializer),
+            (Err(_), None) => None::<&V>.serialize(serializer),
+            (Err(err), Some(_)) => Err(serde::ser::Error::custom(err)),
+        }
+    }
+}
+
+impl<'de, V: Deserialize<'de>> Deserialize<'de> for Lazy<V> {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let value = OnceLock::new();
+        if let Some(inner) = Option::<V>::deserialize(deserializer)? {
+            let _ = value.set(inner);
+        }
+
+        Ok(Self {
+            value,
+            source: None,
+        })
+    }
+}
+
+type Accessor<T, V> = Box<dyn Fn(&mut T) -> &mut Lazy<V> + Send + Sync>;
+
+/// A lazy field registered on a table.
+pub(crate) struct LazyField<T, V> {
+    accessor: Accessor<T, V>,
+    tree: Tree,
+    _marker: PhantomData<fn() -> T>,
+}
+
+impl<T, V> LazyField<T, V> {
+    pub(crate) fn new(
+        tree: Tree,
+        accessor: impl Fn(&mut T) -> &mut Lazy<V> + Send + Sync + 'static,
+    ) -> Self {
+        Self {
+            accessor: Box::new(accessor),
+            tree,
+            _marker: PhantomData,
+        }
+    }
+}
+
+/// Type erased lazy field, used by the table to move values in and out of records.
+pub(crate) trait AnyLazyField<T>: Send + Sync {
+    /// Tree storing the field values.
+    fn tree(&self) -> &Tree;
+    /// Take the value out of a record before it is written.
+    ///
+    /// # Returns
+    ///
+    /// The encoded value which should be stored for the record, or [`None`] if the
+    /// stored value didn't change.
+    fn detach(&self, data: &mut T, id: u64) -> DbResult<Option<Vec<u8>>>;
+    /// Let the field of a read record load its value from the tree.
+    fn attach(&self, data: &mut T, id: u64);
+}
+
+impl<T: TableType, V: Serialize + DeserializeOwned + Send + Sync> AnyLazyField<T>
+    for LazyField<T, V>
+{
+    fn tree(&self) -> &Tree {
+        &self.tree
+    }
+
+    fn detach(&self, data: &mut T, id: u64) -> DbResult<Option<Vec<u8>>> {
+        let field = (self.accessor)(data);
+
+        // Unloaded values stay where they are, unless they were read from another record.
+        if !field.is_loaded() && !matches!(&field.source, Some((_, source)) if *source != id) {
+            return Ok(None);
+        }
+
+        let value = encode(field.get()?)?;
+        *field = Lazy {
+            value: OnceLock::new(),
+            source: None,
+        };
+
+        Ok(Some(value))
+    }
+
+    fn attach(&self, data: &mut T, id: u64) {
+        let field = (self.accessor)(data);
+        // Records written before the field was registered still contain the value.
+        if !field.is_loaded() {
+            field.source = Some((self.tree.clone(), id));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde::{Deserialize, Serialize};
+
+    use std::sync::Arc;
+
+    use super::*;
+    use crate::record::Record;
+    use crate::subscriber::Event;
+    use crate::table::detach_event;
+    use crate::{Table, TinyBase};
+
+    #[derive(Serialize, Deserialize, Debug, Clone)]
+    struct Post {
+        title: String,
+        body: Lazy<String>,
+    }
+
+    #[test]
+    fn lazy_field() {
+        let db = TinyBase::new(None, true);
+        let table: Table<Post> = db.open_table("test_table").unwrap();
+        table.lazy_field("body", |post| &mut post.body).unwrap();
+
+        let id = table
+            .insert(Post {
+                title: "title".to_string(),
+                body: Lazy::new("body".to_string()),
+            })
+            .unwrap();
+
+        let post = table.select(id).unwrap().unwrap().data;
+        assert!(!post.body.is_loaded());
+        assert_eq!(format!("{:?}", post.body), "\"body\"");
+        assert_eq!(post.body.get().unwrap(), "body");
+
+        // The journal stores the record without the value.
+        let event = Event::Insert(Record {
+            id,
+            data: post.clone(),
+        });
+        let field: Arc<dyn AnyLazyField<Post>> = Arc::new(LazyField::new(
+            db.engine.open_tree("test_table_lazy_body").unwrap(),
+            |post: &mut Post| &mut post.body,
+        ));
+        match detach_event(&event, &[field]).unwrap() {
+            Event::Insert(record) => assert!(!record.data.body.is_loaded()),
+            _ => unreachable!(),
+        }
+
+        // Updates which don't touch the field keep it.
+        table
+            .update(&[id], |post| Post {
+                title: "new title".to_string(),
+                ..post
+            })
+            .unwrap();
+
+        let post = table.select(id).unwrap().unwrap().data;
+        assert_eq!(post.title, "new title");
+        assert_eq!(post.body.into_inner().unwrap(), "body");
+    }
+
+    #[test]
+    fn lazy_field_read_back() {
+        use crate::{ConditionBuilder, QueryBuilder};
+
+        let db = TinyBase::new(None, true);
+        let table: Table<Post> = db.open_table("test_table").unwrap();
+        table.lazy_field("body", |post| &mut post.body).unwrap();
+
+        let id = table
+            .insert(Post {
+                title: "title".to_string(),
+                body: Lazy::new("body".to_string()),
+            })
+            .unwrap();
+
+        // Indexes created on stored records key them by the loaded value.
+        let body = table
+            .create_index("body", |post| match post.body.get() {
+                Ok(body) => body.to_owned(),
+                Err(err) => format!("ERR {}", err),
+            })
+            .unwrap();
+        assert_eq!(body.select(&"body".to_string()).unwrap().len(), 1);
+        assert!(body.verify().unwrap().is_consistent());
+        body.rebuild_in_background().wait().unwrap();
+        assert!(body.verify().unwrap().is_consistent());
+
+        // Serializing an unloaded value loads it, so copies keep the field.
+        let copy = QueryBuilder::new(&table)
+            .with_condition(ConditionBuilder::by(&body, "body".to_string()))
+            .select_into(&db, "copy")
+            .unwrap();
+        let post = copy.select(id).unwrap().unwrap().data;
+        assert_eq!(post.body.get().unwrap(), "body");
+    }
+}
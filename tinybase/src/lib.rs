@@ -1,12 +1,53 @@
-use std::sync::Arc;
+use std::collections::{HashMap, HashSet};
+use std::path::PathBuf;
+use std::sync::{Arc, RwLock, Weak};
+use std::time::Duration;
 
+use serde::de::DeserializeOwned;
+use serde::Serialize;
 use sled::Config;
 
+pub mod id;
+pub use id::IdGenerator;
+
 pub mod index;
-pub use index::Index;
+pub use index::{Index, IndexMetadata, IndexReport, IndexStats};
+
+pub mod cancel;
+pub use cancel::CancellationToken;
+
+pub mod collation;
+pub use collation::Collator;
+
+pub mod column;
+pub use column::Column;
+
+pub mod lazy;
+pub use lazy::Lazy;
 
 pub mod query_builder;
-pub use query_builder::{ConditionBuilder, QueryBuilder};
+pub use query_builder::{Aggregate, ConditionBuilder, Cursor, Direction, PrefixKey, QueryBuilder};
+
+pub mod patch;
+pub use patch::Patch;
+
+pub mod pipeline;
+pub use pipeline::Pipeline;
+
+pub mod plan;
+pub use plan::QueryPlan;
+
+pub mod prepared;
+pub use prepared::{Params, PreparedQuery};
+
+pub mod profile;
+pub use profile::{QueryProfile, QueryStats};
+
+pub mod query_cache;
+pub use query_cache::QueryCache;
+
+pub mod rebuild;
+pub use rebuild::RebuildHandle;
 
 pub mod result;
 pub use result::DbResult;
@@ -15,18 +56,121 @@ pub mod record;
 pub use record::Record;
 
 pub mod table;
-pub use table::Table;
-use table::{TableInner, TableType};
+use table::{AnyTable, TableConfig, TableInner, TableType};
+pub use table::{Table, TableStats, UpsertOutcome};
 
 pub mod constraint;
 pub use constraint::Constraint;
 
+pub mod covering_index;
+pub use covering_index::CoveringIndex;
+
+pub mod graph;
+pub use graph::Graph;
+
+pub mod handle;
+pub use handle::{Reader, Writer};
+
+pub mod hierarchy;
+pub use hierarchy::Hierarchy;
+
+pub mod lock;
+
+pub mod maintenance;
+pub use maintenance::Maintenance;
+
+pub mod migration;
+pub use migration::Migrator;
+
+pub mod ngram_index;
+pub use ngram_index::NgramIndex;
+
+pub mod partition;
+pub use partition::{PartitionCondition, PartitionedTable};
+
+pub mod procedure;
+pub use procedure::OpContext;
+
+pub mod sequence;
+pub use sequence::Sequence;
+
+pub mod subscriber;
+pub use subscriber::{Event, EventKinds, Subscription};
+
+pub mod time_series;
+pub use time_series::TimeSeries;
+
+pub mod text_index;
+pub use text_index::{SearchMode, TextIndex};
+
+pub mod tokenizer;
+pub use tokenizer::Tokenizer;
+pub mod transaction;
+pub use transaction::{MultiTransaction, Transaction, TransactionTable};
+
+pub mod ttl;
+pub use ttl::TtlIndex;
+
+#[cfg(feature = "graphql")]
+pub mod graphql;
+
+#[cfg(feature = "resp")]
+pub mod resp;
+
+#[cfg(feature = "script")]
+pub mod script;
+
+#[cfg(feature = "tui")]
+pub mod tui;
+
 mod encoding;
-mod subscriber;
+mod journal;
+mod key_encoding;
+#[cfg(feature = "script")]
+mod layout;
+mod posting;
+mod process;
+mod sketch;
+
+use procedure::Procedure;
+use process::WriterLock;
+
+type TableRegistry = Arc<RwLock<Vec<Weak<dyn AnyTable>>>>;
+
+/// A database attached to another one.
+struct Attached {
+    engine: sled::Db,
+    tables: TableRegistry,
+    config: TableConfig,
+    /// Writer lock of the other database, held as long as its engine can be written through this one.
+    _lock: Option<Arc<WriterLock>>,
+}
 
 /// A tiny structured database based on sled.
+///
+/// # Multi-process access
+///
+/// Only one process may write to a database at a time. The writer holds an
+/// exclusive lock file inside the database directory for as long as it is open.
+/// Other processes (like a CLI inspecting a running service) can use
+/// [`TinyBase::open_read_only`] which opens a private snapshot of the database,
+/// and [`TinyBase::refresh`] to pick up newer changes. While the writer is running,
+/// readers see the snapshot it last published with [`TinyBase::publish_snapshot`].
 pub struct TinyBase {
     engine: sled::Db,
+    /// Database path of the writer.
+    path: Option<PathBuf>,
+    /// Source database path of a read-only snapshot.
+    snapshot_source: Option<PathBuf>,
+    /// Tables opened on this instance.
+    tables: TableRegistry,
+    /// Databases attached with [`TinyBase::attach`] by their alias.
+    attached: RwLock<HashMap<String, Attached>>,
+    /// Operations defined with [`TinyBase::define_op`] by their name.
+    procedures: RwLock<HashMap<String, Procedure>>,
+    /// Set once the database was split into a [`Writer`] and [`Reader`].
+    single_writer: bool,
+    _lock: Option<Arc<WriterLock>>,
 }
 
 impl TinyBase {
@@ -36,28 +180,899 @@ impl TinyBase {
     ///
     /// * `path` - An optional path to the database file. If `None`, an in-memory database is created.
     /// * `temporary` - If `true`, the database file will be deleted on close.
+    ///
+    /// # Panics
+    ///
+    /// If the database can't be opened, see [`TinyBase::open`].
     pub fn new(path: Option<&str>, temporary: bool) -> Self {
-        Self {
-            engine: if let Some(path) = path {
-                Config::new().path(path).temporary(temporary)
-            } else {
-                Config::new().temporary(temporary)
+        Self::open(path, temporary).unwrap()
+    }
+
+    /// Open an instance of `TinyBase` as the writer of the database.
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - An optional path to the database file. If `None`, an in-memory database is created.
+    /// * `temporary` - If `true`, the database file will be deleted on close.
+    ///
+    /// # Returns
+    ///
+    /// The opened database or [`result::TinyBaseError::Locked`] if another process is writing to it.
+    pub fn open(path: Option<&str>, temporary: bool) -> DbResult<Self> {
+        let (config, lock) = if let Some(path) = path {
+            let lock = WriterLock::acquire(path.as_ref())?;
+            (
+                Config::new().path(path).temporary(temporary),
+                Some(Arc::new(lock)),
+            )
+        } else {
+            (Config::new().temporary(temporary), None)
+        };
+
+        Ok(Self {
+            engine: config.open()?,
+            path: path.map(PathBuf::from),
+            snapshot_source: None,
+            tables: TableRegistry::default(),
+            attached: RwLock::new(HashMap::new()),
+            procedures: RwLock::new(HashMap::new()),
+            single_writer: false,
+            _lock: lock,
+        })
+    }
+
+    /// Open a database which may be in use by another process without writing to it.
+    ///
+    /// The returned instance reads from a private snapshot of the database taken when
+    /// opening. All writes through it fail with [`result::TinyBaseError::ReadOnly`].
+    ///
+    /// If a writer has the database open, the snapshot it last published with
+    /// [`TinyBase::publish_snapshot`] is read instead, or [`result::TinyBaseError::Locked`]
+    /// is returned if it hasn't published one.
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - Path to the database file.
+    pub fn open_read_only(path: &str) -> DbResult<Self> {
+        Ok(Self {
+            engine: process::open_snapshot(path.as_ref())?,
+            path: None,
+            snapshot_source: Some(PathBuf::from(path)),
+            tables: TableRegistry::default(),
+            attached: RwLock::new(HashMap::new()),
+            procedures: RwLock::new(HashMap::new()),
+            single_writer: false,
+            _lock: None,
+        })
+    }
+
+    /// Whether this instance was opened with [`TinyBase::open_read_only`].
+    pub fn is_read_only(&self) -> bool {
+        self.snapshot_source.is_some()
+    }
+
+    /// Update the view of a read-only instance to the latest state of the database, or
+    /// the latest snapshot published by a running writer.
+    /// Changes to opened tables are dispatched to their indexes like regular writes.
+    ///
+    /// This does nothing for the writer.
+    pub fn refresh(&self) -> DbResult<()> {
+        let source = match &self.snapshot_source {
+            Some(source) => process::open_snapshot(source)?,
+            None => return Ok(()),
+        };
+
+        let tables: Vec<Arc<dyn AnyTable>> = self
+            .tables
+            .read()
+            .unwrap()
+            .iter()
+            .filter_map(Weak::upgrade)
+            .collect();
+
+        let mut names = source.tree_names();
+        for name in self.engine.tree_names() {
+            if !names.contains(&name) {
+                names.push(name);
             }
-            .open()
-            .unwrap(),
         }
+
+        // Indexes, columns, versions, journals, cursors and sketches of opened tables
+        // are maintained locally.
+        let is_local = |name: &[u8]| {
+            tables.iter().any(|table| {
+                let table = table.table_name();
+                name.starts_with(format!("{}_idx_", table).as_bytes())
+                    || name.starts_with(format!("{}_col_", table).as_bytes())
+                    || name == format!("{}_versions", table).as_bytes()
+                    || name == journal::journal_name(table).as_bytes()
+            })
+        };
+
+        for name in names {
+            let source_tree = source.open_tree(&name)?;
+            let tree = self.engine.open_tree(&name)?;
+
+            let opened: Vec<_> = tables
+                .iter()
+                .filter(|table| table.table_name().as_bytes() == &*name)
+                .collect();
+
+            if !opened.is_empty() {
+                for table in opened {
+                    table.refresh_from(&source_tree)?;
+                }
+            } else if &*name == journal::CURSORS.as_bytes() || &*name == sketch::SKETCHES.as_bytes()
+            {
+                for key in tree.iter().keys() {
+                    let key = key?;
+                    if !is_local(&key) {
+                        tree.remove(key)?;
+                    }
+                }
+
+                for entry in source_tree.iter() {
+                    let (key, value) = entry?;
+                    if !is_local(&key) {
+                        tree.insert(key, value)?;
+                    }
+                }
+            } else if !is_local(&name) {
+                tree.clear()?;
+                for entry in source_tree.iter() {
+                    let (key, value) = entry?;
+                    tree.insert(key, value)?;
+                }
+            }
+        }
+
+        Ok(())
     }
 
-    /// Open a table for a given type.
+    /// Publish a consistent snapshot of the database for read-only instances of other processes.
+    ///
+    /// The files of a database can't be copied consistently while it is written, so readers
+    /// opened while this instance is running see the latest published snapshot. Writes to
+    /// opened tables wait until the snapshot is taken.
+    ///
+    /// This does nothing for in-memory databases.
+    pub fn publish_snapshot(&self) -> DbResult<()> {
+        let path = match &self.path {
+            Some(path) => path,
+            None if self.is_read_only() => return Err(result::TinyBaseError::ReadOnly),
+            None => return Ok(()),
+        };
+
+        let mut tables: Vec<Arc<dyn AnyTable>> = self
+            .tables
+            .read()
+            .unwrap()
+            .iter()
+            .filter_map(Weak::upgrade)
+            .collect();
+
+        // Same order as multi-table transactions so the two can't deadlock.
+        tables.sort_by(|a, b| a.table_name().cmp(b.table_name()));
+        for table in &tables {
+            table.acquire_exclusive();
+        }
+
+        let result = process::publish_snapshot(&self.engine, path);
+
+        for table in &tables {
+            table.release_exclusive();
+        }
+
+        result
+    }
+
+    /// Split the database into its only [`Writer`] and a [`Reader`] which can be cloned freely.
+    ///
+    /// Tables opened after splitting don't lock on reads. Readers may observe
+    /// multi-record updates of the writer partially applied. [`Table`] handles
+    /// opened before splitting can still write and keep locking on reads.
+    ///
+    /// # Returns
+    ///
+    /// The handles or [`result::TinyBaseError::ReadOnly`] if the database is read-only.
+    pub fn split(mut self) -> DbResult<(Writer, Reader)> {
+        if self.is_read_only() {
+            return Err(result::TinyBaseError::ReadOnly);
+        }
+
+        self.single_writer = true;
+        Ok(handle::split(self))
+    }
+
+    /// Close the database.
+    ///
+    /// Persists the sketches of all indexes, truncates the table journals, flushes everything
+    /// to disk and releases the writer lock. Indexes which were open on close don't need to
+    /// be resynced when they are created again. Tables which are still referenced reject further writes.
+    ///
+    /// # Returns
+    ///
+    /// [`result::TinyBaseError::Close`] with all errors encountered while closing.
+    pub fn close(self) -> DbResult<()> {
+        let mut errors = vec![];
+
+        if !self.is_read_only() {
+            for table in self.tables.read().unwrap().iter().filter_map(Weak::upgrade) {
+                errors.extend(table.close());
+            }
+
+            if let Err(err) = self.flush() {
+                errors.push(err);
+            }
+        }
+
+        for (_, attached) in self.attached.write().unwrap().drain() {
+            errors.extend(attached.close());
+        }
+
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(result::TinyBaseError::Close(errors))
+        }
+    }
+
+    /// Open a durable counter.
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - The name of the sequence, sequences with the same name share their value.
+    ///
+    /// # Returns
+    ///
+    /// The [`Sequence`], which rejects increments if the database is read-only.
+    pub fn sequence(&self, name: &str) -> DbResult<Sequence> {
+        Ok(Sequence::new(
+            name,
+            self.engine.open_tree(sequence::SEQUENCES)?,
+            self.is_read_only(),
+        ))
+    }
+
+    /// Open a time series.
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - The name of the time series.
+    /// * `bucket` - The time span stored per bucket, the unit in which old points are dropped.
+    ///
+    /// # Returns
+    ///
+    /// The [`TimeSeries`], which rejects writes if the database is read-only.
+    pub fn time_series<V: Serialize + DeserializeOwned>(
+        &self,
+        name: &str,
+        bucket: Duration,
+    ) -> DbResult<TimeSeries<V>> {
+        Ok(TimeSeries::new(
+            &self.engine,
+            name,
+            bucket,
+            self.is_read_only(),
+        ))
+    }
+
+    /// Open a table partitioned by a key function.
     ///
     /// # Arguments
     ///
     /// * `name` - The name of the table.
+    /// * `partition_func` - A function which computes the partition key of each record,
+    ///   such as the month of a timestamp.
+    ///
+    /// # Returns
+    ///
+    /// The [`PartitionedTable`], whose partitions are opened like regular tables.
+    pub fn partitioned_table<T: TableType, P: Serialize + DeserializeOwned + Ord + Clone>(
+        &self,
+        name: &str,
+        partition_func: impl Fn(&T) -> P + Send + Sync + 'static,
+    ) -> PartitionedTable<T, P> {
+        PartitionedTable::new(
+            &self.engine,
+            &self.tables,
+            self.table_config(),
+            name,
+            partition_func,
+        )
+    }
+
+    /// Define a named operation, so it can be run consistently by [`TinyBase::run_op`].
+    /// Defining an operation with an existing name replaces it.
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - The name the operation is run by.
+    /// * `tables` - The tables the operation may write through its [`OpContext`].
+    /// * `op` - The operation.
+    pub fn define_op(
+        &self,
+        name: &str,
+        tables: &[&dyn TransactionTable],
+        op: impl Fn(&mut OpContext) -> DbResult<()> + Send + Sync + 'static,
+    ) {
+        self.procedures.write().unwrap().insert(
+            name.to_owned(),
+            Procedure {
+                tables: Arc::new(tables.iter().map(|table| table.boxed()).collect()),
+                op: Arc::new(op),
+            },
+        );
+    }
+
+    /// Run an operation defined with [`TinyBase::define_op`].
+    ///
+    /// The declared tables are locked for writing while the operation runs, and its
+    /// writes to them are applied in a single commit. If it fails, nothing is applied.
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - The name of the operation.
+    ///
+    /// # Returns
+    ///
+    /// The result of the operation, or [`result::TinyBaseError::Procedure`] if no
+    /// operation is defined with the name.
+    pub fn run_op(&self, name: &str) -> DbResult<()> {
+        let procedure = self
+            .procedures
+            .read()
+            .unwrap()
+            .get(name)
+            .cloned()
+            .ok_or_else(|| {
+                result::TinyBaseError::Procedure(format!("operation {} is not defined", name))
+            })?;
+
+        let tables: Vec<&dyn TransactionTable> = procedure
+            .tables
+            .iter()
+            .map(|table| table.as_ref())
+            .collect();
+        self.transaction(&tables, |tx| {
+            (procedure.op)(&mut OpContext::new(self, name, tx))
+        })
+    }
+
+    /// Write to multiple tables in a transaction.
+    ///
+    /// The tables are locked for writing while the closure runs, it writes to them
+    /// through [`MultiTransaction::table`]. The writes to all tables are applied in a
+    /// single commit together with their index and journal entries, so either all
+    /// of them are applied or none is.
+    ///
+    /// # Arguments
+    ///
+    /// * `tables` - The tables written by the transaction.
+    /// * `f` - Closure making the writes, nothing is applied if it fails.
+    ///
+    /// # Returns
+    ///
+    /// The result of the closure.
+    pub fn transaction<R>(
+        &self,
+        tables: &[&dyn TransactionTable],
+        f: impl FnOnce(&mut MultiTransaction<'_>) -> DbResult<R>,
+    ) -> DbResult<R> {
+        let mut tx = MultiTransaction::lock(tables)?;
+        let result = f(&mut tx)?;
+        tx.commit()?;
+
+        Ok(result)
+    }
+
+    /// Names of all defined operations, sorted.
+    pub fn ops(&self) -> Vec<String> {
+        let mut names: Vec<String> = self.procedures.read().unwrap().keys().cloned().collect();
+        names.sort();
+        names
+    }
+
+    /// Create a migrator to run versioned migrations on the database.
+    ///
+    /// # Returns
+    ///
+    /// A [`Migrator`] to register migrations on.
+    pub fn migrator(&self) -> Migrator<'_> {
+        Migrator::new(self)
+    }
+
+    /// Flush all pending writes to disk.
+    pub fn flush(&self) -> DbResult<()> {
+        self.engine.flush()?;
+        Ok(())
+    }
+
+    /// Open a table for a given type.
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - The name of the table, `alias.table` opens a table of an attached database.
     ///
     /// # Returns
     ///
     /// A `Table` instance for the given type.
+    ///
+    /// Opening a table which is already open with the same type returns a handle to the same
+    /// table, sharing its indexes, constraints, subscribers and lock. Opening it with another
+    /// type creates a separate table on the same records, whose indexes don't see writes
+    /// made through the other one.
     pub fn open_table<T: TableType>(&self, name: &str) -> DbResult<Table<T>> {
-        Ok(Table(Arc::new(TableInner::new(&self.engine, name)?)))
+        let (engine, tables, config, name) = self.resolve(name);
+        open_table_in(&engine, &tables, config, name)
+    }
+
+    /// Database a table name refers to, an attached one for `alias.table` names.
+    ///
+    /// # Returns
+    ///
+    /// The engine, opened tables and configuration of the database, with the name
+    /// of the table in it.
+    fn resolve<'a>(&self, name: &'a str) -> (sled::Db, TableRegistry, TableConfig, &'a str) {
+        if let Some((alias, table)) = name.split_once('.') {
+            if let Some(attached) = self.attached.read().unwrap().get(alias) {
+                return (
+                    attached.engine.clone(),
+                    attached.tables.clone(),
+                    attached.config,
+                    table,
+                );
+            }
+        }
+
+        (
+            self.engine.clone(),
+            self.tables.clone(),
+            self.table_config(),
+            name,
+        )
+    }
+
+    /// Whether a tree with the name of a table exists, in an attached database for
+    /// `alias.table` names.
+    pub(crate) fn tree_exists(&self, name: &str) -> bool {
+        let (engine, _, _, name) = self.resolve(name);
+        engine
+            .tree_names()
+            .iter()
+            .any(|tree| &**tree == name.as_bytes())
+    }
+
+    /// Open the script indexes of a table without its record type.
+    ///
+    /// # Arguments
+    ///
+    /// * `table` - The name of the table.
+    ///
+    /// # Returns
+    ///
+    /// The [`script::ScriptIndex`]es created with [`Table::create_script_index`], by name.
+    #[cfg(feature = "script")]
+    pub fn script_indexes(&self, table: &str) -> DbResult<Vec<script::ScriptIndex>> {
+        script::ScriptIndex::open_all(&self.engine, table)
+    }
+
+    /// Names of all tables of the database, sorted.
+    ///
+    /// Trees of indexes, columns and other data kept for tables aren't included,
+    /// but partitions of [`PartitionedTable`]s are since they are separate tables.
+    pub fn list_tables(&self) -> Vec<String> {
+        let names: HashSet<_> = self.engine.tree_names().into_iter().collect();
+
+        let mut tables: Vec<String> = names
+            .iter()
+            .filter_map(|name| std::str::from_utf8(name).ok())
+            .filter(|name| names.contains(journal::journal_name(name).as_bytes()))
+            .map(str::to_owned)
+            .collect();
+        tables.sort();
+        tables
+    }
+
+    /// Remove a table with all its records, indexes, columns and journal.
+    ///
+    /// Open handles of the table reject writes afterwards and must not be used
+    /// anymore, opening the table again creates a new empty table.
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - The name of the table, or `alias.table` for a table of an attached database.
+    ///
+    /// # Returns
+    ///
+    /// Whether the table existed.
+    pub fn drop_table(&self, name: &str) -> DbResult<bool> {
+        let (engine, tables, config, name) = self.resolve(name);
+        if config.read_only {
+            return Err(result::TinyBaseError::ReadOnly);
+        }
+
+        let trees = engine.tree_names();
+        let existed = [name.to_owned(), journal::journal_name(name)]
+            .iter()
+            .all(|tree| trees.iter().any(|other| &**other == tree.as_bytes()));
+
+        let mut tables = tables.write().unwrap();
+        for table in tables.iter().filter_map(Weak::upgrade) {
+            if table.table_name() == name {
+                // Its trees are dropped anyway, errors committing its indexes don't matter.
+                let errors = table.close();
+                if let Some(err) = errors
+                    .into_iter()
+                    .find(|err| matches!(err, result::TinyBaseError::TableInUse(_)))
+                {
+                    return Err(err);
+                }
+            }
+        }
+        tables.retain(|table| {
+            table
+                .upgrade()
+                .is_some_and(|table| table.table_name() != name)
+        });
+
+        table::drop_table_trees(&engine, name)?;
+
+        Ok(existed)
+    }
+
+    /// Attach another database, so its tables can be opened as `alias.table` from this one.
+    ///
+    /// Tables opened through the alias are shared with the other instance, and are
+    /// read-only if it is. The writer lock of the other database stays held until it
+    /// is detached, and its tables are closed when it is detached or this instance
+    /// is closed.
+    ///
+    /// # Arguments
+    ///
+    /// * `alias` - The name the other database is referenced by, which must not contain `.`.
+    /// * `other` - The database to attach.
+    ///
+    /// # Returns
+    ///
+    /// [`result::TinyBaseError::InvalidAlias`] if the alias contains a `.`.
+    pub fn attach(&self, alias: &str, other: &TinyBase) -> DbResult<()> {
+        if alias.contains('.') {
+            return Err(result::TinyBaseError::InvalidAlias(alias.to_owned()));
+        }
+
+        let replaced = self.attached.write().unwrap().insert(
+            alias.to_owned(),
+            Attached {
+                engine: other.engine.clone(),
+                tables: other.tables.clone(),
+                config: other.table_config(),
+                _lock: other._lock.clone(),
+            },
+        );
+
+        match replaced.map(Attached::close) {
+            Some(errors) if !errors.is_empty() => Err(result::TinyBaseError::Close(errors)),
+            _ => Ok(()),
+        }
+    }
+
+    /// Detach a database attached with [`TinyBase::attach`], closing its tables.
+    ///
+    /// # Returns
+    ///
+    /// Whether a database was attached with this alias.
+    pub fn detach(&self, alias: &str) -> DbResult<bool> {
+        let Some(attached) = self.attached.write().unwrap().remove(alias) else {
+            return Ok(false);
+        };
+
+        let errors = attached.close();
+        if errors.is_empty() {
+            Ok(true)
+        } else {
+            Err(result::TinyBaseError::Close(errors))
+        }
+    }
+
+    fn table_config(&self) -> TableConfig {
+        TableConfig {
+            read_only: self.is_read_only(),
+            single_writer: self.single_writer,
+        }
+    }
+}
+
+impl Attached {
+    /// Commit the opened tables of the attached database and flush it, like [`TinyBase::close`].
+    ///
+    /// # Returns
+    ///
+    /// The errors which occurred.
+    fn close(self) -> Vec<result::TinyBaseError> {
+        let mut errors = vec![];
+
+        if !self.config.read_only {
+            for table in self.tables.read().unwrap().iter().filter_map(Weak::upgrade) {
+                errors.extend(table.close());
+            }
+
+            if let Err(err) = self.engine.flush() {
+                errors.push(err.into());
+            }
+        }
+
+        errors
+    }
+}
+
+/// Open a table of a database, reusing it if it is already open with the same type.
+fn open_table_in<T: TableType>(
+    engine: &sled::Db,
+    tables: &TableRegistry,
+    config: TableConfig,
+    name: &str,
+) -> DbResult<Table<T>> {
+    let mut tables = tables.write().unwrap();
+    tables.retain(|table| table.strong_count() > 0);
+
+    let opened = tables
+        .iter()
+        .filter_map(Weak::upgrade)
+        .filter(|table| table.table_name() == name)
+        .find_map(|table| table.into_any().downcast::<TableInner<T>>().ok());
+
+    if let Some(table) = opened {
+        return Ok(Table(table));
+    }
+
+    let table = Arc::new(TableInner::new(engine, name, config)?);
+    tables.push(Arc::downgrade(&table) as Weak<dyn AnyTable>);
+
+    Ok(Table(table))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::result::TinyBaseError;
+
+    fn temp_path(name: &str) -> String {
+        std::env::temp_dir()
+            .join(format!("tinybase-test-{}-{}", name, std::process::id()))
+            .to_string_lossy()
+            .into_owned()
+    }
+
+    #[test]
+    fn single_writer_lock() {
+        let path = temp_path("writer_lock");
+        let _db = TinyBase::open(Some(&path), true).unwrap();
+
+        assert!(matches!(
+            TinyBase::open(Some(&path), true),
+            Err(TinyBaseError::Locked)
+        ));
+    }
+
+    #[test]
+    fn open_table_shares_state() {
+        let db = TinyBase::new(None, true);
+        let first = db.open_table::<String>("test_table").unwrap();
+        let index = first
+            .create_index("name", |value| value.to_owned())
+            .unwrap();
+        first.constraint(Constraint::unique(&index)).unwrap();
+
+        let second = db.open_table::<String>("test_table").unwrap();
+        assert!(Arc::ptr_eq(&first.0, &second.0));
+        assert_eq!(second.list_indexes().unwrap(), ["name"]);
+
+        second.insert("value1".to_string()).unwrap();
+        assert_eq!(index.select(&"value1".to_string()).unwrap().len(), 1);
+        assert!(first.insert("value1".to_string()).is_err());
+    }
+
+    #[test]
+    fn close_commits_indexes() {
+        let path = temp_path("close");
+        {
+            let db = TinyBase::open(Some(&path), false).unwrap();
+            let table: Table<String> = db.open_table("test_table").unwrap();
+            let index = table
+                .create_index("name", |value| value.to_owned())
+                .unwrap();
+            table.insert("value1".to_string()).unwrap();
+
+            db.close().unwrap();
+            assert!(matches!(
+                table.insert("value2".to_string()),
+                Err(TinyBaseError::Closed)
+            ));
+            drop(index);
+        }
+
+        let db = TinyBase::open(Some(&path), true).unwrap();
+        let table: Table<String> = db.open_table("test_table").unwrap();
+
+        // All entries were applied so the journal could be truncated.
+        assert!(table.journal.last().unwrap().is_none());
+        let cursor = table.journal.cursor("test_table_idx_name").unwrap();
+        assert!(cursor.unwrap() >= table.journal.truncated().unwrap());
+
+        let index = table
+            .create_index("name", |value| value.to_owned())
+            .unwrap();
+        assert_eq!(index.select(&"value1".to_string()).unwrap().len(), 1);
+    }
+
+    #[test]
+    fn journal_truncated_on_commit() {
+        let db = TinyBase::new(None, true);
+        let table: Table<String> = db.open_table("test_table").unwrap();
+        let index = table
+            .create_index("name", |value| value.to_owned())
+            .unwrap();
+
+        // Open indexes are written with the table, so nothing is kept.
+        for value in ["value1", "value2", "value3"] {
+            table.insert(value.to_string()).unwrap();
+        }
+        assert!(table.journal.last().unwrap().is_none());
+        assert_eq!(
+            table.journal.cursor("test_table_idx_name").unwrap(),
+            Some(table.journal.head().unwrap())
+        );
+
+        // The index misses this write and is rebuilt when it is created again.
+        drop(index);
+        table.insert("value4".to_string()).unwrap();
+        let cursor = table.journal.cursor("test_table_idx_name").unwrap();
+        assert!(cursor.unwrap() < table.journal.truncated().unwrap());
+
+        let index = table
+            .create_index("name", |value| value.to_owned())
+            .unwrap();
+        assert_eq!(index.select(&"value4".to_string()).unwrap().len(), 1);
+    }
+
+    #[test]
+    fn close_with_held_guard() {
+        let db = TinyBase::new(None, true);
+        let table: Table<String> = db.open_table("test_table").unwrap();
+        let guard = table.lock_shared();
+
+        // Waiting for the guard of the closing thread would never finish.
+        match db.close() {
+            Err(TinyBaseError::Close(errors)) => {
+                assert!(matches!(errors[..], [TinyBaseError::TableInUse(_)]))
+            }
+            _ => panic!("closing succeeded"),
+        }
+        drop(guard);
+    }
+
+    #[test]
+    fn read_only_refresh() {
+        let path = temp_path("read_only");
+        let db = TinyBase::open(Some(&path), true).unwrap();
+        let table: Table<String> = db.open_table("test_table").unwrap();
+        table.insert("value1".to_string()).unwrap();
+
+        // Readers of a running writer need a published snapshot.
+        assert!(matches!(
+            TinyBase::open_read_only(&path),
+            Err(TinyBaseError::Locked)
+        ));
+        db.publish_snapshot().unwrap();
+
+        let reader = TinyBase::open_read_only(&path).unwrap();
+        let reader_table: Table<String> = reader.open_table("test_table").unwrap();
+        let index = reader_table
+            .create_index("name", |value| value.to_owned())
+            .unwrap();
+
+        assert!(reader.is_read_only());
+        assert_eq!(index.select(&"value1".to_string()).unwrap().len(), 1);
+        assert!(matches!(
+            reader_table.insert("value2".to_string()),
+            Err(TinyBaseError::ReadOnly)
+        ));
+
+        // Changes from the writer are only visible after a refresh.
+        let id = table.insert("value2".to_string()).unwrap();
+        db.publish_snapshot().unwrap();
+        assert!(reader_table.select(id).unwrap().is_none());
+
+        reader.refresh().unwrap();
+        assert_eq!(reader_table.select(id).unwrap().unwrap().data, "value2");
+        assert_eq!(index.select(&"value2".to_string()).unwrap().len(), 1);
+    }
+
+    #[test]
+    fn drop_and_list_tables() {
+        let db = TinyBase::new(None, true);
+        let users = db.open_table::<String>("users").unwrap();
+        users.create_index("name", |name| name.to_owned()).unwrap();
+        let _length = users.create_column("length", |name| name.len()).unwrap();
+        users.insert("John".to_string()).unwrap();
+        db.open_table::<u8>("sessions").unwrap();
+
+        // Its name starts like the column trees of `users`.
+        let archive = db.open_table::<String>("users_col_archive").unwrap();
+        archive
+            .create_index("name", |name| name.to_owned())
+            .unwrap();
+        archive.insert("Bob".to_string()).unwrap();
+
+        assert_eq!(db.list_tables(), ["sessions", "users", "users_col_archive"]);
+
+        assert!(db.drop_table("users").unwrap());
+        assert!(!db.drop_table("users").unwrap());
+        assert!(users.insert("Jane".to_string()).is_err());
+        assert_eq!(db.list_tables(), ["sessions", "users_col_archive"]);
+
+        let names = db.engine.tree_names();
+        assert!(names
+            .iter()
+            .filter(|name| name.starts_with(b"users"))
+            .all(|name| name.starts_with(b"users_col_archive")));
+        assert!(names
+            .iter()
+            .any(|name| &**name == b"users_col_archive_idx_name"));
+        assert_eq!(archive.len().unwrap(), 1);
+
+        let users = db.open_table::<String>("users").unwrap();
+        assert!(users.is_empty().unwrap());
+    }
+
+    #[test]
+    fn attach_database() {
+        let db = TinyBase::new(None, true);
+        let other = TinyBase::new(None, true);
+        let other_table: Table<String> = other.open_table("test_table").unwrap();
+        let id = other_table.insert("value1".to_string()).unwrap();
+
+        assert!(matches!(
+            db.attach("other.db", &other),
+            Err(TinyBaseError::InvalidAlias(_))
+        ));
+
+        db.attach("other", &other).unwrap();
+        let table: Table<String> = db.open_table("other.test_table").unwrap();
+        assert_eq!(table.select(id).unwrap().unwrap().data, "value1");
+
+        // The attached table is shared with the other instance.
+        let id = table.insert("value2".to_string()).unwrap();
+        assert_eq!(other_table.select(id).unwrap().unwrap().data, "value2");
+        assert!(db
+            .open_table::<String>("test_table")
+            .unwrap()
+            .select(id)
+            .unwrap()
+            .is_none());
+
+        assert!(db.detach("other").unwrap());
+        assert!(!db.detach("other").unwrap());
+        assert!(table.insert("value3".to_string()).is_err());
+    }
+
+    #[test]
+    fn attach_keeps_writer_lock() {
+        let path = temp_path("attach_lock");
+        let db = TinyBase::new(None, true);
+        {
+            let other = TinyBase::open(Some(&path), true).unwrap();
+            db.attach("other", &other).unwrap();
+        }
+
+        // The attached engine can still be written, so no other writer may open it.
+        assert!(matches!(
+            TinyBase::open(Some(&path), true),
+            Err(TinyBaseError::Locked)
+        ));
+
+        db.close().unwrap();
+        TinyBase::open(Some(&path), true).unwrap();
     }
 }
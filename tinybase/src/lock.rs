@@ -0,0 +1,134 @@
+use std::collections::HashMap;
+use std::sync::{Condvar, Mutex};
+use std::thread::{self, ThreadId};
+
+/// Advisory lock coordinating maintenance operations with regular writes on a table.
+///
+/// Regular writes hold the lock shared. The thread holding the lock exclusively
+/// may keep writing to the table, all other writers wait until it is released.
+#[derive(Default)]
+pub(crate) struct TableLock {
+    state: Mutex<LockState>,
+    released: Condvar,
+}
+
+#[derive(Default)]
+struct LockState {
+    /// Amount of shared acquisitions by thread.
+    shared: HashMap<ThreadId, usize>,
+    /// Thread holding the lock exclusively and how many times it acquired it.
+    exclusive: Option<(ThreadId, usize)>,
+}
+
+impl TableLock {
+    /// Acquire the lock shared, waiting while another thread holds it exclusively.
+    pub(crate) fn shared(&self) -> SharedGuard<'_> {
+        let current = thread::current().id();
+        let mut state = self.state.lock().unwrap();
+
+        loop {
+            match state.exclusive {
+                // Already covered by the exclusive lock of this thread.
+                Some((owner, _)) if owner == current => {
+                    return SharedGuard {
+                        lock: self,
+                        holder: None,
+                    }
+                }
+                Some(_) => state = self.released.wait(state).unwrap(),
+                None => break,
+            }
+        }
+
+        *state.shared.entry(current).or_default() += 1;
+        SharedGuard {
+            lock: self,
+            holder: Some(current),
+        }
+    }
+
+    /// Acquire the lock exclusively, waiting for all other holders to release it.
+    /// Acquiring it exclusively while holding it shared on the same thread deadlocks.
+    pub(crate) fn exclusive(&self) -> ExclusiveGuard<'_> {
+        let current = thread::current().id();
+        let mut state = self.state.lock().unwrap();
+
+        loop {
+            let shared = state.shared.is_empty();
+            match &mut state.exclusive {
+                Some((owner, depth)) if *owner == current => {
+                    *depth += 1;
+                    break;
+                }
+                None if shared => {
+                    state.exclusive = Some((current, 1));
+                    break;
+                }
+                _ => state = self.released.wait(state).unwrap(),
+            }
+        }
+
+        ExclusiveGuard { lock: self }
+    }
+
+    /// Acquire the lock exclusively like [`TableLock::exclusive`], unless the current
+    /// thread holds it shared, which would never be released while waiting.
+    ///
+    /// # Returns
+    ///
+    /// The guard, or [`None`] if the current thread holds the lock shared.
+    pub(crate) fn try_exclusive(&self) -> Option<ExclusiveGuard<'_>> {
+        let current = thread::current().id();
+        if self.state.lock().unwrap().shared.contains_key(&current) {
+            return None;
+        }
+
+        Some(self.exclusive())
+    }
+
+    /// Release one exclusive acquisition, for locks whose guard was forgotten.
+    pub(crate) fn release_exclusive(&self) {
+        let mut state = self.state.lock().unwrap();
+        if let Some((_, depth)) = &mut state.exclusive {
+            *depth -= 1;
+            if *depth == 0 {
+                state.exclusive = None;
+            }
+        }
+
+        self.released.notify_all();
+    }
+}
+
+/// Guard of a shared table lock, released on drop.
+pub struct SharedGuard<'a> {
+    lock: &'a TableLock,
+    /// Thread the acquisition is counted for, unless covered by its exclusive lock.
+    holder: Option<ThreadId>,
+}
+
+impl Drop for SharedGuard<'_> {
+    fn drop(&mut self) {
+        if let Some(holder) = self.holder {
+            let mut state = self.lock.state.lock().unwrap();
+            if let Some(count) = state.shared.get_mut(&holder) {
+                *count -= 1;
+                if *count == 0 {
+                    state.shared.remove(&holder);
+                }
+            }
+            self.lock.released.notify_all();
+        }
+    }
+}
+
+/// Guard of an exclusive table lock, released on drop.
+pub struct ExclusiveGuard<'a> {
+    lock: &'a TableLock,
+}
+
+impl Drop for ExclusiveGuard<'_> {
+    fn drop(&mut self) {
+        self.lock.release_exclusive();
+    }
+}
@@ -0,0 +1,310 @@
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::{Arc, Condvar, Mutex};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+use crate::index::{Index, IndexType};
+use crate::result::{DbResult, TinyBaseError};
+use crate::table::{AnyTable, Table, TableType};
+use crate::ttl::TtlIndex;
+
+type Task = Box<dyn FnMut() -> DbResult<()> + Send>;
+
+struct Job {
+    name: String,
+    interval: Duration,
+    task: Task,
+    next_run: Instant,
+}
+
+/// Metrics of a maintenance job.
+#[derive(Debug, Clone, Default)]
+pub struct JobStats {
+    /// Amount of times the job ran.
+    pub runs: u64,
+    /// Amount of runs which returned an error or panicked.
+    pub failures: u64,
+    /// How long the last run took.
+    pub last_duration: Duration,
+    /// Error of the last run, if it failed.
+    pub last_error: Option<String>,
+}
+
+/// Periodically runs maintenance jobs (reaping expired records, verifying indexes,
+/// flushing, backups) on a background thread.
+#[derive(Default)]
+pub struct Maintenance {
+    jobs: Vec<Job>,
+}
+
+impl Maintenance {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add a job.
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - The name the metrics of the job are reported under.
+    /// * `interval` - Time between runs, the first run happens after one interval.
+    /// * `task` - The work to do on every run.
+    pub fn job(
+        mut self,
+        name: &str,
+        interval: Duration,
+        task: impl FnMut() -> DbResult<()> + Send + 'static,
+    ) -> Self {
+        self.jobs.push(Job {
+            name: name.to_owned(),
+            interval,
+            task: Box::new(task),
+            next_run: Instant::now() + interval,
+        });
+        self
+    }
+
+    /// Add a job deleting the expired records of a TTL index, see [`TtlIndex::expire_now`].
+    /// Its metrics are reported under `expire:` followed by the name of the index tree.
+    ///
+    /// # Arguments
+    ///
+    /// * `ttl` - The TTL index.
+    /// * `interval` - Time between runs.
+    pub fn expire<T: TableType + 'static>(self, ttl: &TtlIndex<T>, interval: Duration) -> Self {
+        let ttl = ttl.clone();
+        self.job(
+            &format!("expire:{}", ttl.index().index_name()),
+            interval,
+            move || ttl.expire_now().map(drop),
+        )
+    }
+
+    /// Add a job verifying an index against its table, see [`Index::verify`].
+    /// Runs fail with [`TinyBaseError::Inconsistent`] if the index doesn't match the table,
+    /// and its metrics are reported under `verify:` followed by the name of the index tree.
+    ///
+    /// # Arguments
+    ///
+    /// * `index` - The index to verify.
+    /// * `interval` - Time between runs.
+    pub fn verify<T, I>(self, index: &Index<T, I>, interval: Duration) -> Self
+    where
+        T: TableType + 'static,
+        I: IndexType + 'static,
+    {
+        let index = index.clone();
+        self.job(
+            &format!("verify:{}", index.index_name()),
+            interval,
+            move || match index.verify()?.is_consistent() {
+                true => Ok(()),
+                false => Err(TinyBaseError::Inconsistent(index.index_name())),
+            },
+        )
+    }
+
+    /// Add a job compacting a table: journal entries which no open index needs anymore
+    /// are removed and the database is flushed, so sled can reclaim the space of
+    /// rewritten segments. Its metrics are reported under `compact:` followed by the
+    /// name of the table.
+    ///
+    /// # Arguments
+    ///
+    /// * `table` - The table to compact.
+    /// * `interval` - Time between runs.
+    pub fn compact<T: TableType + 'static>(self, table: &Table<T>, interval: Duration) -> Self {
+        let table = table.clone();
+        self.job(
+            &format!("compact:{}", AnyTable::table_name(&*table.0)),
+            interval,
+            move || {
+                table.truncate_journal()?;
+                table.engine.flush()?;
+                Ok(())
+            },
+        )
+    }
+
+    /// Start running the jobs on a background thread.
+    ///
+    /// # Returns
+    ///
+    /// A [`MaintenanceHandle`] which stops the thread when dropped.
+    pub fn start(mut self) -> MaintenanceHandle {
+        let stopped = Arc::new((Mutex::new(false), Condvar::new()));
+        let stats = Arc::new(Mutex::new(
+            self.jobs
+                .iter()
+                .map(|job| (job.name.clone(), JobStats::default()))
+                .collect::<Vec<_>>(),
+        ));
+
+        let thread = {
+            let (stopped, stats) = (stopped.clone(), stats.clone());
+            thread::spawn(move || loop {
+                let next_run = self.jobs.iter().map(|job| job.next_run).min();
+
+                let (lock, wake) = &*stopped;
+                let mut stop = lock.lock().unwrap();
+                while !*stop {
+                    let now = Instant::now();
+                    match next_run {
+                        Some(next_run) if next_run <= now => break,
+                        Some(next_run) => stop = wake.wait_timeout(stop, next_run - now).unwrap().0,
+                        None => stop = wake.wait(stop).unwrap(),
+                    }
+                }
+
+                if *stop {
+                    return;
+                }
+                drop(stop);
+
+                self.run_due(&stats);
+            })
+        };
+
+        MaintenanceHandle {
+            stopped,
+            stats,
+            thread: Some(thread),
+        }
+    }
+
+    /// Run all jobs which are due and record their metrics.
+    fn run_due(&mut self, stats: &Mutex<Vec<(String, JobStats)>>) {
+        for (position, job) in self.jobs.iter_mut().enumerate() {
+            let started = Instant::now();
+            if job.next_run > started {
+                continue;
+            }
+
+            // A panicking job is recorded as failed instead of stopping all jobs.
+            let result = match panic::catch_unwind(AssertUnwindSafe(|| (job.task)())) {
+                Ok(result) => result.map_err(|err| err.to_string()),
+                Err(payload) => Err(match payload.downcast_ref::<&str>() {
+                    Some(message) => format!("job panicked: {}", message),
+                    None => match payload.downcast_ref::<String>() {
+                        Some(message) => format!("job panicked: {}", message),
+                        None => "job panicked".to_owned(),
+                    },
+                }),
+            };
+            job.next_run = started + job.interval;
+
+            let mut stats = stats.lock().unwrap();
+            let stats = &mut stats[position].1;
+            stats.runs += 1;
+            stats.last_duration = started.elapsed();
+            stats.last_error = result.err();
+            if stats.last_error.is_some() {
+                stats.failures += 1;
+            }
+        }
+    }
+}
+
+/// Handle to running maintenance jobs, stopping them when dropped.
+pub struct MaintenanceHandle {
+    stopped: Arc<(Mutex<bool>, Condvar)>,
+    stats: Arc<Mutex<Vec<(String, JobStats)>>>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl MaintenanceHandle {
+    /// Metrics of all jobs with their name, in the order the jobs were added.
+    pub fn stats(&self) -> Vec<(String, JobStats)> {
+        self.stats.lock().unwrap().clone()
+    }
+
+    /// Stop running jobs, waiting for a running job to finish.
+    pub fn stop(self) {
+        drop(self);
+    }
+}
+
+impl Drop for MaintenanceHandle {
+    fn drop(&mut self) {
+        let (lock, wake) = &*self.stopped;
+        *lock.lock().unwrap() = true;
+        wake.notify_all();
+
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicU64, Ordering};
+
+    use super::*;
+    use crate::result::TinyBaseError;
+    use crate::TinyBase;
+
+    #[test]
+    fn maintenance_jobs() {
+        let counter = Arc::new(AtomicU64::new(0));
+
+        let handle = Maintenance::new()
+            .job("count", Duration::from_millis(5), {
+                let counter = counter.clone();
+                move || {
+                    counter.fetch_add(1, Ordering::Relaxed);
+                    Ok(())
+                }
+            })
+            .job("fail", Duration::from_millis(5), || {
+                Err(TinyBaseError::Closed)
+            })
+            .job("fail", Duration::from_millis(5), || panic!("broken"))
+            .start();
+
+        while handle.stats()[0].1.runs < 2 || handle.stats()[2].1.runs < 2 {
+            thread::sleep(Duration::from_millis(1));
+        }
+
+        // Jobs with the same name are reported separately, panics don't stop the jobs.
+        let stats = handle.stats();
+        assert!(counter.load(Ordering::Relaxed) >= 2);
+        assert_eq!(stats[0].1.failures, 0);
+        assert!(stats[1].1.failures >= 1);
+        assert!(stats[1].1.last_error.is_some());
+        assert_eq!(stats[2].1.failures, stats[2].1.runs);
+        assert_eq!(
+            stats[2].1.last_error.as_deref(),
+            Some("job panicked: broken")
+        );
+
+        handle.stop();
+        let runs = counter.load(Ordering::Relaxed);
+        thread::sleep(Duration::from_millis(20));
+        assert_eq!(counter.load(Ordering::Relaxed), runs);
+    }
+
+    #[test]
+    fn maintenance_builtin_jobs() {
+        let db = TinyBase::new(None, true);
+        let table: Table<String> = db.open_table("test_table").unwrap();
+        let index = table
+            .create_index("name", |value| value.to_owned())
+            .unwrap();
+        table.insert("value1".to_string()).unwrap();
+
+        let handle = Maintenance::new()
+            .verify(&index, Duration::from_millis(1))
+            .compact(&table, Duration::from_millis(1))
+            .start();
+
+        while handle.stats().iter().any(|(_, stats)| stats.runs == 0) {
+            thread::sleep(Duration::from_millis(1));
+        }
+
+        let stats = handle.stats();
+        assert_eq!(stats[0].0, "verify:test_table_idx_name");
+        assert_eq!(stats[1].0, "compact:test_table");
+        assert!(stats.iter().all(|(_, stats)| stats.failures == 0));
+    }
+}
@@ -0,0 +1,251 @@
+use std::collections::BTreeMap;
+
+use crate::encoding::{decode, encode};
+use crate::result::{DbResult, TinyBaseError};
+use crate::TinyBase;
+
+/// Tree storing the name of every applied migration by its version.
+pub(crate) const MIGRATIONS: &str = "__tinybase_migrations";
+
+type MigrationFn = Box<dyn Fn(&TinyBase) -> DbResult<()>>;
+
+struct Migration {
+    name: String,
+    up: MigrationFn,
+    down: Option<MigrationFn>,
+}
+
+/// Runs versioned migrations in order, created with [`TinyBase::migrator`].
+///
+/// Applied migrations are tracked in the database, so running the same migrations
+/// again on every startup only applies the new ones.
+pub struct Migrator<'a> {
+    db: &'a TinyBase,
+    migrations: BTreeMap<u64, Migration>,
+}
+
+impl<'a> Migrator<'a> {
+    pub(crate) fn new(db: &'a TinyBase) -> Self {
+        Self {
+            db,
+            migrations: BTreeMap::new(),
+        }
+    }
+
+    /// Register a migration which can't be reverted.
+    ///
+    /// # Arguments
+    ///
+    /// * `version` - The version of the migration, migrations run in ascending order.
+    /// * `name` - A name describing the migration.
+    /// * `up` - Function applying the migration.
+    ///
+    /// # Returns
+    ///
+    /// The migrator, or [`TinyBaseError::Migration`] if the version is already registered.
+    pub fn register(
+        self,
+        version: u64,
+        name: &str,
+        up: impl Fn(&TinyBase) -> DbResult<()> + 'static,
+    ) -> DbResult<Self> {
+        self.add(version, name, Box::new(up), None)
+    }
+
+    /// Register a migration which can be reverted with [`Migrator::migrate_down`].
+    ///
+    /// # Arguments
+    ///
+    /// * `version` - The version of the migration, migrations run in ascending order.
+    /// * `name` - A name describing the migration.
+    /// * `up` - Function applying the migration.
+    /// * `down` - Function reverting the migration.
+    ///
+    /// # Returns
+    ///
+    /// The migrator, or [`TinyBaseError::Migration`] if the version is already registered.
+    pub fn register_reversible(
+        self,
+        version: u64,
+        name: &str,
+        up: impl Fn(&TinyBase) -> DbResult<()> + 'static,
+        down: impl Fn(&TinyBase) -> DbResult<()> + 'static,
+    ) -> DbResult<Self> {
+        self.add(version, name, Box::new(up), Some(Box::new(down)))
+    }
+
+    fn add(
+        mut self,
+        version: u64,
+        name: &str,
+        up: MigrationFn,
+        down: Option<MigrationFn>,
+    ) -> DbResult<Self> {
+        if self.migrations.contains_key(&version) {
+            return Err(TinyBaseError::Migration(format!(
+                "migration version {} is registered twice",
+                version
+            )));
+        }
+
+        self.migrations.insert(
+            version,
+            Migration {
+                name: name.to_owned(),
+                up,
+                down,
+            },
+        );
+        Ok(self)
+    }
+
+    /// Versions and names of all applied migrations in ascending order.
+    pub fn applied(&self) -> DbResult<Vec<(u64, String)>> {
+        self.db
+            .engine
+            .open_tree(MIGRATIONS)?
+            .iter()
+            .map(|entry| {
+                let (version, name) = entry?;
+                Ok((decode(&version)?, decode(&name)?))
+            })
+            .collect()
+    }
+
+    /// Apply all registered migrations which weren't applied yet.
+    ///
+    /// Each migration is recorded as applied right after it succeeded, so a failed
+    /// migration is retried by the next call.
+    ///
+    /// # Returns
+    ///
+    /// The versions which were applied.
+    pub fn migrate_up(&self) -> DbResult<Vec<u64>> {
+        let tree = self.writable_tree()?;
+        let mut applied = vec![];
+
+        for (version, migration) in &self.migrations {
+            let key = encode(version)?;
+            if tree.contains_key(&key)? {
+                continue;
+            }
+
+            (migration.up)(self.db)?;
+            tree.insert(key, encode(&migration.name)?)?;
+            self.db.flush()?;
+            applied.push(*version);
+        }
+
+        Ok(applied)
+    }
+
+    /// Revert applied migrations until only migrations up to `target` are applied.
+    ///
+    /// # Arguments
+    ///
+    /// * `target` - The version to migrate down to, `0` reverts everything.
+    ///
+    /// # Returns
+    ///
+    /// The versions which were reverted, or [`TinyBaseError::Migration`] if an applied
+    /// migration isn't registered or can't be reverted.
+    pub fn migrate_down(&self, target: u64) -> DbResult<Vec<u64>> {
+        let tree = self.writable_tree()?;
+        let mut reverted = vec![];
+
+        for version in tree
+            .range(encode(&target.saturating_add(1))?..)
+            .keys()
+            .rev()
+        {
+            let version: u64 = decode(&version?)?;
+
+            let down = match self.migrations.get(&version) {
+                Some(Migration {
+                    down: Some(down), ..
+                }) => down,
+                Some(migration) => {
+                    return Err(TinyBaseError::Migration(format!(
+                        "migration {} ({}) can't be reverted",
+                        version, migration.name
+                    )))
+                }
+                None => {
+                    return Err(TinyBaseError::Migration(format!(
+                        "migration {} isn't registered",
+                        version
+                    )))
+                }
+            };
+
+            down(self.db)?;
+            tree.remove(encode(&version)?)?;
+            self.db.flush()?;
+            reverted.push(version);
+        }
+
+        Ok(reverted)
+    }
+
+    fn writable_tree(&self) -> DbResult<sled::Tree> {
+        if self.db.is_read_only() {
+            return Err(TinyBaseError::ReadOnly);
+        }
+
+        Ok(self.db.engine.open_tree(MIGRATIONS)?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::result::TinyBaseError;
+    use crate::{Table, TinyBase};
+
+    #[test]
+    fn migrate_up_and_down() {
+        let db = TinyBase::new(None, true);
+
+        let migrator = || {
+            db.migrator()
+                .register(1, "create", |db| {
+                    let table: Table<String> = db.open_table("test_table")?;
+                    table.insert("value1".to_string())?;
+                    Ok(())
+                })
+                .unwrap()
+                .register_reversible(
+                    2,
+                    "add-value",
+                    |db| {
+                        let table: Table<String> = db.open_table("test_table")?;
+                        table.insert("value2".to_string())?;
+                        Ok(())
+                    },
+                    |db| {
+                        let table: Table<String> = db.open_table("test_table")?;
+                        let index = table.create_index("name", |value| value.to_owned())?;
+                        index.delete(&"value2".to_string())?;
+                        Ok(())
+                    },
+                )
+                .unwrap()
+        };
+
+        assert_eq!(migrator().migrate_up().unwrap(), vec![1, 2]);
+        // Applied migrations don't run again.
+        assert_eq!(migrator().migrate_up().unwrap(), Vec::<u64>::new());
+        assert_eq!(
+            migrator().applied().unwrap(),
+            vec![(1, "create".to_string()), (2, "add-value".to_string())]
+        );
+
+        assert_eq!(migrator().migrate_down(1).unwrap(), vec![2]);
+        // The first migration can't be reverted.
+        assert!(migrator().migrate_down(0).is_err());
+
+        assert!(matches!(
+            migrator().register(2, "duplicate", |_| Ok(())),
+            Err(TinyBaseError::Migration(_))
+        ));
+    }
+}
@@ -0,0 +1,161 @@
+use std::sync::Arc;
+
+use crate::index::Index;
+use crate::query_builder::ConditionBuilder;
+use crate::record::Record;
+use crate::result::DbResult;
+use crate::table::{Table, TableType};
+
+/// N-gram index on a text of every record, mapping each sequence of `n` characters
+/// of the text to the records containing it.
+///
+/// Created with [`Table::create_ngram_index`]. Substrings with at least `n` characters
+/// are looked up by their n-grams, shorter substrings by reading the n-grams of the index.
+/// Matching is case-sensitive, texts can be lowercased by the text function.
+pub struct NgramIndex<T: TableType + 'static> {
+    index: Index<T, String>,
+    n: usize,
+    text: Arc<dyn Fn(&T) -> String + Send + Sync>,
+}
+
+impl<T: TableType> Clone for NgramIndex<T> {
+    fn clone(&self) -> Self {
+        Self {
+            index: self.index.clone(),
+            n: self.n,
+            text: self.text.clone(),
+        }
+    }
+}
+
+/// The n-grams of a text, or the text itself if it's shorter than `n` characters.
+fn ngrams(text: &str, n: usize) -> Vec<String> {
+    let chars: Vec<char> = text.chars().collect();
+    if chars.len() < n {
+        return vec![text.to_owned()];
+    }
+
+    chars.windows(n).map(|gram| gram.iter().collect()).collect()
+}
+
+impl<T: TableType + 'static> NgramIndex<T> {
+    pub(crate) fn new(
+        table: &Table<T>,
+        name: &str,
+        n: usize,
+        text: impl Fn(&T) -> String + Send + Sync + 'static,
+    ) -> DbResult<Self> {
+        let n = n.max(1);
+        let text: Arc<dyn Fn(&T) -> String + Send + Sync> = Arc::new(text);
+
+        let index = {
+            let text = text.clone();
+            table.create_multi_index(name, move |data| ngrams(&text(data), n))?
+        };
+
+        Ok(Self { index, n, text })
+    }
+
+    /// Select records whose text contains a substring.
+    ///
+    /// # Arguments
+    ///
+    /// * `substring` - The text to search for.
+    ///
+    /// # Returns
+    ///
+    /// All matching [`Record`] instances in ID order.
+    pub fn contains(&self, substring: &str) -> DbResult<Vec<Record<T>>> {
+        let ids = match substring.chars().count() < self.n {
+            true => {
+                let substring = substring.to_owned();
+                self.index
+                    .matching_ids(&move |gram: &str| gram.contains(&substring))?
+            }
+            false => {
+                // Posting lists are sorted, so they are intersected by binary search.
+                let mut grams = ngrams(substring, self.n).into_iter();
+                let mut ids = self.index.select_ids(&grams.next().unwrap())?;
+                for gram in grams {
+                    let matches = self.index.select_ids(&gram)?;
+                    ids.retain(|id| matches.binary_search(id).is_ok());
+                }
+                ids
+            }
+        };
+
+        // Texts can contain all n-grams of the substring without containing it.
+        let mut records = self.index.read_records(ids)?;
+        records.retain(|record| (self.text)(&record.data).contains(substring));
+        Ok(records)
+    }
+
+    /// Condition matching records whose text contains a substring, to combine a
+    /// substring search with other conditions in a [`crate::QueryBuilder`].
+    ///
+    /// # Arguments
+    ///
+    /// * `substring` - The text to search for.
+    pub fn condition(&self, substring: &str) -> ConditionBuilder<T> {
+        let candidates = match substring.chars().count() < self.n {
+            true => {
+                let substring = substring.to_owned();
+                ConditionBuilder::key_matches(&self.index, move |gram| gram.contains(&substring))
+            }
+            false => ConditionBuilder::all(
+                ngrams(substring, self.n)
+                    .into_iter()
+                    .map(|gram| ConditionBuilder::by(&self.index, gram))
+                    .collect(),
+            ),
+        };
+
+        let text = self.text.clone();
+        let substring = substring.to_owned();
+        candidates & ConditionBuilder::filter(move |data| text(data).contains(&substring))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{QueryBuilder, TinyBase};
+
+    #[test]
+    fn ngram_index_contains() {
+        let db = TinyBase::new(None, true);
+        let table: Table<String> = db.open_table("words").unwrap();
+        let grams = table
+            .create_ngram_index("grams", 3, |word| word.clone())
+            .unwrap();
+
+        let substring = table.insert("substring".to_string()).unwrap();
+        let string = table.insert("string".to_string()).unwrap();
+        let ab = table.insert("ab".to_string()).unwrap();
+        let strings = table.insert("tringstring".to_string()).unwrap();
+        // Contains every trigram of "abcd" but not the substring.
+        let lookalike = table.insert("abcxbcd".to_string()).unwrap();
+
+        let ids = |records: Vec<Record<String>>| -> Vec<u64> {
+            records.into_iter().map(|record| record.id).collect()
+        };
+        assert_eq!(ids(grams.contains("ubstr").unwrap()), [substring]);
+        assert_eq!(
+            ids(grams.contains("tring").unwrap()),
+            [substring, string, strings]
+        );
+        assert_eq!(ids(grams.contains("gst").unwrap()), [strings]);
+        assert_eq!(
+            ids(grams.contains("b").unwrap()),
+            [substring, ab, lookalike]
+        );
+        assert!(grams.contains("abcd").unwrap().is_empty());
+
+        table.delete(ab).unwrap();
+        let selected = QueryBuilder::new(&table)
+            .with_condition(grams.condition("b"))
+            .select()
+            .unwrap();
+        assert_eq!(ids(selected), [substring, lookalike]);
+    }
+}
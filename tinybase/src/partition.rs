@@ -0,0 +1,467 @@
+use std::ops::{BitAnd, BitOr, RangeBounds};
+use std::sync::Arc;
+
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use sled::{Db, Tree};
+
+use crate::encoding::{decode, encode};
+use crate::query_builder::{ConditionBuilder, QueryBuilder, QueryCondition};
+use crate::record::Record;
+use crate::result::{DbResult, TinyBaseError};
+use crate::table::{drop_table_trees, Table, TableConfig, TableType};
+use crate::{open_table_in, TableRegistry};
+
+/// Tree storing the tree name of every partition by its table and encoded key.
+pub(crate) const PARTITIONS: &str = "__tinybase_partitions";
+
+/// Builds the condition on the records of a partition with the indexes of its table.
+type RecordsFn<T> = Box<dyn Fn(&Table<T>) -> DbResult<ConditionBuilder<T>>>;
+
+/// A condition of a query over a [`PartitionedTable`].
+///
+/// Conditions on partition keys are evaluated before a partition is read, so
+/// partitions which can't contain matching records are skipped entirely.
+pub enum PartitionCondition<T: TableType + 'static, P> {
+    /// All records of partitions whose key matches a predicate.
+    Key(Box<dyn Fn(&P) -> bool>),
+    /// Records matching a query condition, built for each partition which is read.
+    Records(RecordsFn<T>),
+    And(Box<Self>, Box<Self>),
+    Or(Box<Self>, Box<Self>),
+    Not(Box<Self>),
+}
+
+impl<T: TableType + 'static, P: Ord + 'static> PartitionCondition<T, P> {
+    /// Records of the partitions within a range of keys.
+    pub fn key(range: impl RangeBounds<P> + 'static) -> Self {
+        Self::Key(Box::new(move |key| range.contains(key)))
+    }
+
+    /// Records of the partitions with any of many keys.
+    pub fn key_in(keys: Vec<P>) -> Self {
+        Self::Key(Box::new(move |key| keys.contains(key)))
+    }
+
+    /// Records matching a query condition.
+    ///
+    /// # Arguments
+    ///
+    /// * `condition` - Builds the condition with the table of a partition, such as
+    ///   with an index created on it.
+    pub fn records(
+        condition: impl Fn(&Table<T>) -> DbResult<ConditionBuilder<T>> + 'static,
+    ) -> Self {
+        Self::Records(Box::new(condition))
+    }
+
+    /// Records whose data matches a predicate, found by reading the records.
+    pub fn filter(predicate: impl Fn(&T) -> bool + 'static) -> Self {
+        let predicate = Arc::new(predicate);
+        Self::records(move |_| {
+            let predicate = predicate.clone();
+            Ok(ConditionBuilder::filter(move |data| predicate(data)))
+        })
+    }
+
+    pub fn and(left: Self, right: Self) -> Self {
+        Self::And(Box::new(left), Box::new(right))
+    }
+
+    pub fn or(left: Self, right: Self) -> Self {
+        Self::Or(Box::new(left), Box::new(right))
+    }
+
+    #[allow(clippy::should_implement_trait)]
+    pub fn not(condition: Self) -> Self {
+        Self::Not(Box::new(condition))
+    }
+}
+
+impl<T: TableType + 'static, P: Ord> PartitionCondition<T, P> {
+    /// Whether all or no records of a partition match, if its key alone decides it.
+    fn decided(&self, key: &P) -> Option<bool> {
+        match self {
+            Self::Key(predicate) => Some(predicate(key)),
+            Self::Records(_) => None,
+            Self::And(left, right) => match (left.decided(key), right.decided(key)) {
+                (Some(false), _) | (_, Some(false)) => Some(false),
+                (Some(true), Some(true)) => Some(true),
+                _ => None,
+            },
+            Self::Or(left, right) => match (left.decided(key), right.decided(key)) {
+                (Some(true), _) | (_, Some(true)) => Some(true),
+                (Some(false), Some(false)) => Some(false),
+                _ => None,
+            },
+            Self::Not(condition) => condition.decided(key).map(|decided| !decided),
+        }
+    }
+
+    /// Query condition on the records of a partition, with the conditions on its key
+    /// replaced by their outcome.
+    ///
+    /// # Returns
+    ///
+    /// The condition, or whether all or no records match if that's decided by the key.
+    fn resolve(&self, key: &P, table: &Table<T>) -> DbResult<Result<QueryCondition<T>, bool>> {
+        if let Some(decided) = self.decided(key) {
+            return Ok(Err(decided));
+        }
+
+        Ok(match self {
+            Self::Key(_) => unreachable!("conditions on keys are decided"),
+            Self::Records(condition) => Ok(condition(table)?.build()),
+            Self::And(left, right) => match (left.resolve(key, table)?, right.resolve(key, table)?)
+            {
+                (Ok(left), Ok(right)) => Ok(QueryCondition::And(Box::new(left), Box::new(right))),
+                (Err(_), condition) | (condition, Err(_)) => condition,
+            },
+            Self::Or(left, right) => {
+                match (left.resolve(key, table)?, right.resolve(key, table)?) {
+                    (Ok(left), Ok(right)) => {
+                        Ok(QueryCondition::Or(Box::new(left), Box::new(right)))
+                    }
+                    (Err(_), condition) | (condition, Err(_)) => condition,
+                }
+            }
+            Self::Not(condition) => match condition.resolve(key, table)? {
+                Ok(condition) => Ok(QueryCondition::Not(Box::new(condition))),
+                Err(decided) => Err(!decided),
+            },
+        })
+    }
+}
+
+impl<T: TableType + 'static, P: Ord + 'static> BitAnd for PartitionCondition<T, P> {
+    type Output = Self;
+
+    fn bitand(self, rhs: Self) -> Self {
+        Self::and(self, rhs)
+    }
+}
+
+impl<T: TableType + 'static, P: Ord + 'static> BitOr for PartitionCondition<T, P> {
+    type Output = Self;
+
+    fn bitor(self, rhs: Self) -> Self {
+        Self::or(self, rhs)
+    }
+}
+
+/// A table split into partitions by a key function, created with
+/// [`crate::TinyBase::partitioned_table`].
+///
+/// Every partition is a separate [`Table`], so queries over a range of partition keys
+/// only read the partitions within the range and whole partitions are dropped without
+/// touching other records.
+pub struct PartitionedTable<T: TableType + 'static, P> {
+    engine: Db,
+    tables: TableRegistry,
+    config: TableConfig,
+    name: String,
+    partition_func: Arc<dyn Fn(&T) -> P + Send + Sync>,
+}
+
+impl<T, P> PartitionedTable<T, P>
+where
+    T: TableType + 'static,
+    P: Serialize + DeserializeOwned + Ord + Clone,
+{
+    pub(crate) fn new(
+        engine: &Db,
+        tables: &TableRegistry,
+        config: TableConfig,
+        name: &str,
+        partition_func: impl Fn(&T) -> P + Send + Sync + 'static,
+    ) -> Self {
+        Self {
+            engine: engine.clone(),
+            tables: tables.clone(),
+            config,
+            name: name.to_owned(),
+            partition_func: Arc::new(partition_func),
+        }
+    }
+
+    /// Insert a record into its partition, creating the partition if needed.
+    ///
+    /// # Returns
+    ///
+    /// The partition key and ID of the record.
+    pub fn insert(&self, value: T) -> DbResult<(P, u64)> {
+        let key = (self.partition_func)(&value);
+        let id = self.partition(&key)?.insert(value)?;
+
+        Ok((key, id))
+    }
+
+    /// Open the table of a partition, to select records by ID or create indexes on it.
+    ///
+    /// # Arguments
+    ///
+    /// * `key` - The partition key.
+    pub fn partition(&self, key: &P) -> DbResult<Table<T>> {
+        let name = self.partition_name(key)?;
+
+        if !self.config.read_only {
+            let meta_key = self.meta_key(key)?;
+            let partitions = self.partitions_tree()?;
+            if !partitions.contains_key(&meta_key)? {
+                partitions.insert(meta_key, name.as_bytes())?;
+            }
+        }
+
+        open_table_in(&self.engine, &self.tables, self.config, &name)
+    }
+
+    /// Keys of all created partitions, in order.
+    pub fn partitions(&self) -> DbResult<Vec<P>> {
+        Ok(self
+            .partition_trees()?
+            .into_iter()
+            .map(|(key, _)| key)
+            .collect())
+    }
+
+    /// Select records of the partitions within a range.
+    ///
+    /// Partitions outside of the range are not read.
+    ///
+    /// # Arguments
+    ///
+    /// * `range` - Range of partition keys which may contain matching records.
+    /// * `filter` - Whether a record of a partition within the range is selected.
+    ///
+    /// # Returns
+    ///
+    /// The partition keys and records, ordered by partition and ID.
+    pub fn select(
+        &self,
+        range: impl RangeBounds<P>,
+        filter: impl Fn(&T) -> bool,
+    ) -> DbResult<Vec<(P, Record<T>)>> {
+        let mut results = vec![];
+
+        for (key, name) in self.partition_trees()? {
+            if !range.contains(&key) {
+                continue;
+            }
+
+            let table: Table<T> = open_table_in(&self.engine, &self.tables, self.config, &name)?;
+            for record in Self::scan(&table, &filter)? {
+                results.push((key.clone(), record));
+            }
+        }
+
+        Ok(results)
+    }
+
+    /// Select records matching a condition.
+    ///
+    /// Partitions whose key rules out any match aren't read, and the conditions on
+    /// the records of the other partitions are evaluated by a [`QueryBuilder`].
+    ///
+    /// # Arguments
+    ///
+    /// * `condition` - The condition on partition keys and records.
+    ///
+    /// # Returns
+    ///
+    /// The partition keys and records, ordered by partition and ID.
+    pub fn query(&self, condition: &PartitionCondition<T, P>) -> DbResult<Vec<(P, Record<T>)>> {
+        let mut results = vec![];
+
+        for (key, name) in self.partition_trees()? {
+            if condition.decided(&key) == Some(false) {
+                continue;
+            }
+
+            let table: Table<T> = open_table_in(&self.engine, &self.tables, self.config, &name)?;
+            let records = match condition.resolve(&key, &table)? {
+                Ok(condition) => QueryBuilder::new(&table)
+                    .with_condition(condition)
+                    .select()?,
+                Err(_) => Self::scan(&table, &|_| true)?,
+            };
+
+            results.extend(records.into_iter().map(|record| (key.clone(), record)));
+        }
+
+        Ok(results)
+    }
+
+    /// Remove a partition with all its records, indexes and columns.
+    ///
+    /// Tables of the partition which are still referenced must not be used afterwards.
+    ///
+    /// # Returns
+    ///
+    /// Whether the partition existed.
+    pub fn drop_partition(&self, key: &P) -> DbResult<bool> {
+        if self.config.read_only {
+            return Err(TinyBaseError::ReadOnly);
+        }
+
+        self.partitions_tree()?.remove(self.meta_key(key)?)?;
+        drop_table_trees(&self.engine, &self.partition_name(key)?)
+    }
+
+    pub fn table_name(&self) -> &str {
+        &self.name
+    }
+
+    /// Records of a partition matching a predicate, in ID order.
+    fn scan(table: &Table<T>, filter: &dyn Fn(&T) -> bool) -> DbResult<Vec<Record<T>>> {
+        let root = table.root.read().unwrap();
+        let mut records = vec![];
+
+        for id in root.iter().keys() {
+            if let Some(record) = table.tree_select(&root, decode(&id?)?)? {
+                if filter(&record.data) {
+                    records.push(record);
+                }
+            }
+        }
+
+        Ok(records)
+    }
+
+    fn partitions_tree(&self) -> DbResult<Tree> {
+        Ok(self.engine.open_tree(PARTITIONS)?)
+    }
+
+    /// Key of a partition in [`PARTITIONS`], prefixed by the encoded table name.
+    fn meta_key(&self, key: &P) -> DbResult<Vec<u8>> {
+        let mut meta_key = encode(&self.name)?;
+        meta_key.extend(encode(key)?);
+        Ok(meta_key)
+    }
+
+    /// Name of the tree of a partition.
+    ///
+    /// The reserved prefix keeps partitions apart from user tables, and the hex
+    /// encoded key can't contain the `_` separating it from the table name.
+    fn partition_name(&self, key: &P) -> DbResult<String> {
+        let hex: String = encode(key)?
+            .iter()
+            .map(|byte| format!("{:02x}", byte))
+            .collect();
+
+        Ok(format!("__tinybase_part_{}_{}", self.name, hex))
+    }
+
+    /// Keys and tree names of all partitions, in order.
+    fn partition_trees(&self) -> DbResult<Vec<(P, String)>> {
+        let prefix = encode(&self.name)?;
+        let mut partitions = vec![];
+
+        for entry in self.partitions_tree()?.scan_prefix(&prefix) {
+            let (meta_key, name) = entry?;
+            let key = decode::<P>(&meta_key[prefix.len()..])?;
+            partitions.push((key, String::from_utf8_lossy(&name).into_owned()));
+        }
+
+        partitions.sort_by(|(a, _), (b, _)| a.cmp(b));
+        Ok(partitions)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+
+    use serde::{Deserialize, Serialize};
+
+    use super::PartitionCondition;
+    use crate::query_builder::ConditionBuilder;
+    use crate::TinyBase;
+
+    #[derive(Serialize, Deserialize, Debug, Clone)]
+    struct Event {
+        month: u32,
+        name: String,
+    }
+
+    #[test]
+    fn partition_pruning() {
+        let db = TinyBase::new(None, true);
+        let events = db.partitioned_table("events", |event: &Event| event.month);
+
+        for (month, name) in [(1, "a"), (2, "b"), (2, "c"), (3, "d")] {
+            events
+                .insert(Event {
+                    month,
+                    name: name.to_string(),
+                })
+                .unwrap();
+        }
+
+        assert_eq!(events.partitions().unwrap(), vec![1, 2, 3]);
+
+        let selected = events.select(2.., |event| event.name != "c").unwrap();
+        assert_eq!(
+            selected
+                .iter()
+                .map(|(month, record)| (*month, record.data.name.as_str()))
+                .collect::<Vec<_>>(),
+            vec![(2, "b"), (3, "d")]
+        );
+
+        assert!(events.drop_partition(&2).unwrap());
+        assert_eq!(events.partitions().unwrap(), vec![1, 3]);
+        assert_eq!(events.select(.., |_| true).unwrap().len(), 2);
+    }
+
+    #[test]
+    fn partition_query_condition() {
+        let db = TinyBase::new(None, true);
+        let events = db.partitioned_table("events", |event: &Event| event.month);
+
+        for (month, name) in [(1, "a"), (2, "b"), (2, "c"), (3, "d"), (3, "b")] {
+            events
+                .insert(Event {
+                    month,
+                    name: name.to_string(),
+                })
+                .unwrap();
+        }
+
+        // A user table named like a partition isn't one.
+        db.open_table::<Event>("events_part_04").unwrap();
+        assert_eq!(events.partitions().unwrap(), vec![1, 2, 3]);
+
+        let built = Arc::new(AtomicUsize::new(0));
+        let counter = built.clone();
+        let by_name = PartitionCondition::key(2..)
+            & PartitionCondition::records(move |table| {
+                counter.fetch_add(1, Ordering::SeqCst);
+                let name = table.create_index("name", |event: &Event| event.name.clone())?;
+                Ok(ConditionBuilder::by(&name, "b".to_string()))
+            });
+
+        let names = |selected: Vec<(u32, crate::Record<Event>)>| {
+            selected
+                .into_iter()
+                .map(|(month, record)| (month, record.data.name))
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(
+            names(events.query(&by_name).unwrap()),
+            vec![(2, "b".to_string()), (3, "b".to_string())]
+        );
+        // The first partition is pruned without building its condition.
+        assert_eq!(built.load(Ordering::SeqCst), 2);
+
+        let either = PartitionCondition::key(..2)
+            | PartitionCondition::not(PartitionCondition::filter(|event: &Event| {
+                event.name != "d"
+            }));
+        assert_eq!(
+            names(events.query(&either).unwrap()),
+            vec![(1, "a".to_string()), (3, "d".to_string())]
+        );
+    }
+}
@@ -0,0 +1,131 @@
+use crate::result::DbResult;
+
+/// A partial change to the data of a record, applied with [`crate::table::TableInner::patch`].
+///
+/// Closures modifying the data in place are patches. Deriving `TableEntity` generates
+/// a `<Name>Patch` enum with a variant setting each field, and with the `json`
+/// feature [`JsonMergePatch`] merges a JSON document into the data.
+pub trait Patch<T> {
+    fn apply(&self, data: &mut T) -> DbResult<()>;
+}
+
+impl<T, F: Fn(&mut T)> Patch<T> for F {
+    fn apply(&self, data: &mut T) -> DbResult<()> {
+        self(data);
+        Ok(())
+    }
+}
+
+/// Multiple patches are applied in order.
+impl<T, P: Patch<T>> Patch<T> for Vec<P> {
+    fn apply(&self, data: &mut T) -> DbResult<()> {
+        self.iter().try_for_each(|patch| patch.apply(data))
+    }
+}
+
+/// Patch merging a JSON document into the data, following RFC 7396.
+///
+/// Fields present in the document replace the fields of the data, nested objects
+/// are merged and `null` removes a field, which resets optional fields to [`None`].
+#[cfg(feature = "json")]
+pub struct JsonMergePatch(pub serde_json::Value);
+
+#[cfg(feature = "json")]
+impl<T: serde::Serialize + serde::de::DeserializeOwned> Patch<T> for JsonMergePatch {
+    fn apply(&self, data: &mut T) -> DbResult<()> {
+        let mut value = serde_json::to_value(&*data)?;
+        merge(&mut value, &self.0);
+        *data = serde_json::from_value(value)?;
+        Ok(())
+    }
+}
+
+#[cfg(feature = "json")]
+fn merge(target: &mut serde_json::Value, patch: &serde_json::Value) {
+    use serde_json::Value;
+
+    let Value::Object(patch) = patch else {
+        *target = patch.clone();
+        return;
+    };
+
+    if !target.is_object() {
+        *target = Value::Object(Default::default());
+    }
+
+    let target = target.as_object_mut().unwrap();
+    for (key, value) in patch {
+        match value {
+            Value::Object(_) => merge(target.entry(key).or_insert(Value::Null), value),
+            Value::Null => {
+                target.remove(key);
+            }
+            value => {
+                target.insert(key.clone(), value.clone());
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde::{Deserialize, Serialize};
+
+    use crate::{Table, TinyBase};
+
+    #[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+    struct Person {
+        name: String,
+        age: u8,
+    }
+
+    #[test]
+    fn patch_record() {
+        let db = TinyBase::new(None, true);
+        let table: Table<Person> = db.open_table("people").unwrap();
+        let id = table
+            .insert(Person {
+                name: "John".to_string(),
+                age: 30,
+            })
+            .unwrap();
+
+        let patched = table
+            .patch(id, |person: &mut Person| person.age += 1)
+            .unwrap()
+            .unwrap();
+        assert_eq!(patched.data.age, 31);
+        assert_eq!(table.select(id).unwrap().unwrap().data.name, "John");
+
+        assert!(table
+            .patch(id + 1, |person: &mut Person| person.age = 0)
+            .unwrap()
+            .is_none());
+    }
+
+    #[cfg(feature = "json")]
+    #[test]
+    fn json_merge_patch() {
+        use super::JsonMergePatch;
+
+        let db = TinyBase::new(None, true);
+        let table: Table<Person> = db.open_table("people").unwrap();
+        let id = table
+            .insert(Person {
+                name: "John".to_string(),
+                age: 30,
+            })
+            .unwrap();
+
+        table
+            .patch(id, JsonMergePatch(serde_json::json!({ "name": "Jane" })))
+            .unwrap();
+        assert_eq!(
+            table.select(id).unwrap().unwrap().data,
+            Person {
+                name: "Jane".to_string(),
+                age: 30
+            }
+        );
+    }
+}
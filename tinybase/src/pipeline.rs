@@ -0,0 +1,130 @@
+use crate::record::Record;
+use crate::result::DbResult;
+
+/// Records read by a [`Pipeline`] created from a query.
+pub type RecordIter<T> = Box<dyn Iterator<Item = DbResult<Record<T>>>>;
+
+/// Lazily evaluated stages over query results, created with [`crate::QueryBuilder::pipeline`].
+///
+/// Records are read from the table one at a time as the stages consume them,
+/// so no intermediate vectors are collected. The first error ends the pipeline.
+pub struct Pipeline<I> {
+    iter: I,
+}
+
+impl<V, I: Iterator<Item = DbResult<V>>> Pipeline<I> {
+    pub(crate) fn new(iter: I) -> Self {
+        Self { iter }
+    }
+
+    /// Only keep the values matching a predicate.
+    ///
+    /// # Arguments
+    ///
+    /// * `predicate` - Function returning `true` for values to keep.
+    pub fn filter(
+        self,
+        mut predicate: impl FnMut(&V) -> bool,
+    ) -> Pipeline<impl Iterator<Item = DbResult<V>>> {
+        Pipeline::new(self.iter.filter(move |value| match value {
+            Ok(value) => predicate(value),
+            Err(_) => true,
+        }))
+    }
+
+    /// Transform every value.
+    ///
+    /// # Arguments
+    ///
+    /// * `mapper` - Function transforming a value.
+    pub fn map<U>(
+        self,
+        mut mapper: impl FnMut(V) -> U,
+    ) -> Pipeline<impl Iterator<Item = DbResult<U>>> {
+        Pipeline::new(self.iter.map(move |value| value.map(&mut mapper)))
+    }
+
+    /// Transform every value into any amount of values.
+    ///
+    /// # Arguments
+    ///
+    /// * `mapper` - Function returning the values a value is replaced with.
+    pub fn flat_map<U, It: IntoIterator<Item = U>>(
+        self,
+        mut mapper: impl FnMut(V) -> It,
+    ) -> Pipeline<impl Iterator<Item = DbResult<U>>> {
+        Pipeline::new(self.iter.flat_map(move |value| {
+            let (values, err) = match value {
+                Ok(value) => (Some(mapper(value).into_iter().map(Ok)), None),
+                Err(err) => (None, Some(Err(err))),
+            };
+            values.into_iter().flatten().chain(err)
+        }))
+    }
+
+    /// Only keep the first `n` values, stopping to read records afterwards.
+    pub fn take(self, n: usize) -> Pipeline<impl Iterator<Item = DbResult<V>>> {
+        Pipeline::new(self.iter.take(n))
+    }
+
+    /// Combine all values into a single value.
+    ///
+    /// # Arguments
+    ///
+    /// * `init` - The initial value of the accumulator.
+    /// * `folder` - Function combining the accumulator with the next value.
+    ///
+    /// # Returns
+    ///
+    /// The final accumulator.
+    pub fn fold<A>(mut self, init: A, mut folder: impl FnMut(A, V) -> A) -> DbResult<A> {
+        self.iter
+            .try_fold(init, |acc, value| Ok(folder(acc, value?)))
+    }
+
+    /// Amount of values.
+    pub fn count(self) -> DbResult<usize> {
+        self.fold(0, |count, _| count + 1)
+    }
+
+    /// Collect all values.
+    pub fn collect(self) -> DbResult<Vec<V>> {
+        self.iter.collect()
+    }
+}
+
+impl<V, I: Iterator<Item = DbResult<V>>> IntoIterator for Pipeline<I> {
+    type Item = DbResult<V>;
+    type IntoIter = I;
+
+    fn into_iter(self) -> I {
+        self.iter
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{ConditionBuilder, QueryBuilder, Table, TinyBase};
+
+    #[test]
+    fn pipeline_stages() {
+        let db = TinyBase::new(None, true);
+        let table: Table<String> = db.open_table("test_table").unwrap();
+        let length = table.create_index("length", |value| value.len()).unwrap();
+
+        for value in ["value1", "value2", "other1", "long_value"] {
+            table.insert(value.to_string()).unwrap();
+        }
+
+        let total = QueryBuilder::new(&table)
+            .with_condition(ConditionBuilder::by(&length, 6))
+            .pipeline()
+            .unwrap()
+            .filter(|record| record.data.starts_with("value"))
+            .map(|record| record.data.len())
+            .fold(0, |total, len| total + len)
+            .unwrap();
+
+        assert_eq!(total, 12);
+    }
+}
@@ -0,0 +1,68 @@
+use std::fmt::{self, Display, Formatter};
+
+/// Result of [`crate::QueryBuilder::explain`], how a query would be executed.
+#[derive(Debug, Clone)]
+pub struct QueryPlan {
+    /// Plan of the root condition.
+    pub condition: ConditionPlan,
+    /// How the selected records are ordered.
+    pub order: OrderPlan,
+}
+
+/// Plan of a single condition and its operands.
+#[derive(Debug, Clone)]
+pub struct ConditionPlan {
+    /// Kind of the condition, such as `By`, `And` or `Where`.
+    pub condition: String,
+    /// Name of the index tree the condition looks up.
+    pub index: Option<String>,
+    /// Estimated amount of IDs the condition matches.
+    ///
+    /// Lookups of keys count their posting lists, ranges and predicates are
+    /// estimated from the amount of records in the table.
+    pub estimated: usize,
+    /// Plans of the operands, in the order they are evaluated.
+    pub children: Vec<ConditionPlan>,
+}
+
+/// How the selected records of a query are ordered.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OrderPlan {
+    /// Records are returned in the order the condition produces them.
+    Unordered,
+    /// Records are read in the order of the index of the first sort key if many are
+    /// selected compared to its keys, otherwise they are sorted in memory.
+    IndexWalk { sort_keys: usize },
+    /// Records are sorted in memory.
+    Sort { sort_keys: usize },
+}
+
+impl ConditionPlan {
+    fn fmt_indented(&self, f: &mut Formatter<'_>, depth: usize) -> fmt::Result {
+        write!(f, "{:indent$}{}", "", self.condition, indent = depth * 2)?;
+        if let Some(index) = &self.index {
+            write!(f, " {}", index)?;
+        }
+        writeln!(f, " (estimated {})", self.estimated)?;
+
+        for child in &self.children {
+            child.fmt_indented(f, depth + 1)?;
+        }
+
+        Ok(())
+    }
+}
+
+impl Display for QueryPlan {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        self.condition.fmt_indented(f, 0)?;
+
+        match self.order {
+            OrderPlan::Unordered => Ok(()),
+            OrderPlan::IndexWalk { sort_keys } => {
+                write!(f, "Order by {} keys, walking the first index", sort_keys)
+            }
+            OrderPlan::Sort { sort_keys } => write!(f, "Order by {} keys in memory", sort_keys),
+        }
+    }
+}
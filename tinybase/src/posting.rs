@@ -0,0 +1,68 @@
+use crate::encoding::decode;
+#[cfg(not(feature = "roaring"))]
+use crate::encoding::encode;
+use crate::result::DbResult;
+
+/// Identifies the encoding of posting lists, indexes stored with another encoding are rebuilt.
+#[cfg(not(feature = "roaring"))]
+pub(crate) const POSTING_FORMAT: u8 = 0;
+#[cfg(feature = "roaring")]
+pub(crate) const POSTING_FORMAT: u8 = 1;
+
+/// Encode the IDs of the records with a key.
+///
+/// Posting lists are encoded as a list of IDs, or as a roaring bitmap with the
+/// `roaring` feature which stores runs of sequential IDs compactly. Both start with
+/// the amount of IDs, so counting them only reads the first 8 bytes.
+///
+/// # Arguments
+///
+/// * `ids` - The IDs in ascending order.
+pub(crate) fn encode_posting(ids: &[u64]) -> DbResult<Vec<u8>> {
+    #[cfg(feature = "roaring")]
+    {
+        let bitmap = roaring::RoaringTreemap::from_sorted_iter(ids.iter().copied())
+            .expect("posting lists are sorted");
+        let mut bytes = Vec::with_capacity(8 + bitmap.serialized_size());
+        bytes.extend((ids.len() as u64).to_be_bytes());
+        bitmap.serialize_into(&mut bytes)?;
+        Ok(bytes)
+    }
+
+    #[cfg(not(feature = "roaring"))]
+    encode(&ids)
+}
+
+/// Decode a posting list encoded with [`encode_posting`].
+///
+/// # Returns
+///
+/// The IDs in ascending order.
+pub(crate) fn decode_posting(bytes: &[u8]) -> DbResult<Vec<u64>> {
+    #[cfg(feature = "roaring")]
+    return Ok(roaring::RoaringTreemap::deserialize_from(&bytes[8..])?
+        .iter()
+        .collect());
+
+    #[cfg(not(feature = "roaring"))]
+    decode(bytes)
+}
+
+/// Amount of IDs of a posting list, read from its header without decoding the IDs.
+pub(crate) fn posting_len(bytes: &[u8]) -> DbResult<usize> {
+    Ok(decode::<u64>(bytes)? as usize)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn posting_roundtrip() {
+        for ids in [vec![], vec![7], (1..1000).chain([u64::MAX]).collect()] {
+            let bytes = encode_posting(&ids).unwrap();
+            assert_eq!(decode_posting(&bytes).unwrap(), ids);
+            assert_eq!(posting_len(&bytes).unwrap(), ids.len());
+        }
+    }
+}
@@ -0,0 +1,169 @@
+use std::any::Any;
+use std::cell::RefCell;
+use std::collections::HashMap;
+
+use crate::{
+    index::IndexType,
+    key_encoding::encode_key,
+    query_builder::{KeyFn, QueryBuilder},
+    result::{DbResult, TinyBaseError},
+    table::TableType,
+    Record,
+};
+
+thread_local! {
+    /// Parameters of the prepared query which is running on this thread.
+    static BOUND: RefCell<Option<Params>> = const { RefCell::new(None) };
+}
+
+/// Values of the placeholders of a [`PreparedQuery`], by name.
+#[derive(Default)]
+pub struct Params(HashMap<String, Box<dyn Any>>);
+
+impl Params {
+    /// Creates an empty set of parameters.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Binds a value to a placeholder.
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - Name of the placeholder, see [`crate::ConditionBuilder::by_param`].
+    /// * `value` - The value, which must have the key type of the index of the placeholder.
+    pub fn with<I: IndexType + 'static>(mut self, name: &str, value: I) -> Self {
+        self.0.insert(name.to_owned(), Box::new(value));
+        self
+    }
+}
+
+/// Encodes the value bound to a placeholder when the query runs.
+pub(crate) fn param_key<I: IndexType + 'static>(name: &str) -> KeyFn {
+    let name = name.to_owned();
+
+    Box::new(move || {
+        BOUND.with(|bound| {
+            let bound = bound.borrow();
+            let value = bound
+                .as_ref()
+                .and_then(|params| params.0.get(&name))
+                .ok_or_else(|| {
+                    TinyBaseError::QueryBuilder(format!("parameter {} is not bound", name))
+                })?;
+
+            match value.downcast_ref::<I>() {
+                Some(value) => encode_key(value),
+                None => Err(TinyBaseError::QueryBuilder(format!(
+                    "parameter {} doesn't have the key type of its index",
+                    name
+                ))),
+            }
+        })
+    })
+}
+
+/// A query whose condition is built once and executed any number of times.
+///
+/// Created with [`QueryBuilder::prepare`].
+pub struct PreparedQuery<T: TableType + 'static> {
+    query: QueryBuilder<T>,
+}
+
+impl<T: TableType + 'static> PreparedQuery<T> {
+    pub(crate) fn new(query: QueryBuilder<T>) -> Self {
+        Self { query }
+    }
+
+    /// Executes the query like [`QueryBuilder::select`].
+    ///
+    /// # Returns
+    ///
+    /// All selected [`Record`] instances, or [`TinyBaseError::QueryBuilder`] if the
+    /// condition has placeholders.
+    pub fn run(&self) -> DbResult<Vec<Record<T>>> {
+        self.query.execute()
+    }
+
+    /// Executes the query with values for its placeholders.
+    ///
+    /// # Arguments
+    ///
+    /// * `params` - The values of the placeholders.
+    ///
+    /// # Returns
+    ///
+    /// All selected [`Record`] instances, or [`TinyBaseError::QueryBuilder`] if a
+    /// placeholder isn't bound or its value has another type than the key of its index.
+    pub fn run_with_params(&self, params: Params) -> DbResult<Vec<Record<T>>> {
+        // Predicates may run other prepared queries, whose parameters are restored afterwards.
+        let outer = BOUND.with(|bound| bound.replace(Some(params)));
+        let result = self.query.execute();
+        BOUND.with(|bound| bound.replace(outer));
+
+        result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{ConditionBuilder, Table, TinyBase};
+
+    #[test]
+    fn prepared_query_run_with_params() {
+        let db = TinyBase::new(None, true);
+        let table: Table<(String, u8)> = db.open_table("people").unwrap();
+        let name = table
+            .create_index("name", |(name, _)| name.to_owned())
+            .unwrap();
+        let age = table.create_index("age", |(_, age)| *age).unwrap();
+
+        table.insert(("John".to_string(), 40)).unwrap();
+        table.insert(("John".to_string(), 20)).unwrap();
+        table.insert(("Jane".to_string(), 20)).unwrap();
+
+        let adults = QueryBuilder::new(&table)
+            .with_condition(ConditionBuilder::greater_than(&age, 30))
+            .prepare()
+            .unwrap();
+        assert_eq!(adults.run().unwrap().len(), 1);
+        assert_eq!(adults.run().unwrap().len(), 1);
+
+        let by_name = QueryBuilder::new(&table)
+            .with_condition(ConditionBuilder::and(
+                ConditionBuilder::by_param(&name, "name"),
+                ConditionBuilder::by_param(&age, "age"),
+            ))
+            .prepare()
+            .unwrap();
+
+        let params = |name: &str, age: u8| {
+            Params::new()
+                .with("name", name.to_string())
+                .with("age", age)
+        };
+        assert_eq!(
+            by_name.run_with_params(params("John", 20)).unwrap().len(),
+            1
+        );
+        assert_eq!(
+            by_name.run_with_params(params("Jane", 20)).unwrap().len(),
+            1
+        );
+        assert!(by_name
+            .run_with_params(params("Jane", 40))
+            .unwrap()
+            .is_empty());
+
+        assert!(matches!(by_name.run(), Err(TinyBaseError::QueryBuilder(_))));
+        assert!(matches!(
+            by_name.run_with_params(
+                Params::new()
+                    .with("name", "John".to_string())
+                    .with("age", 20u32)
+            ),
+            Err(TinyBaseError::QueryBuilder(_))
+        ));
+    }
+}
@@ -0,0 +1,105 @@
+use std::sync::Arc;
+
+use crate::result::{DbResult, TinyBaseError};
+use crate::table::TableType;
+use crate::transaction::{MultiTransaction, Transaction, TransactionTable};
+use crate::TinyBase;
+
+pub(crate) type OpFn = Arc<dyn Fn(&mut OpContext) -> DbResult<()> + Send + Sync>;
+
+/// A named operation registered with [`TinyBase::define_op`].
+#[derive(Clone)]
+pub(crate) struct Procedure {
+    pub tables: Arc<Vec<Box<dyn TransactionTable>>>,
+    pub op: OpFn,
+}
+
+/// Access to the declared tables of an operation run by [`TinyBase::run_op`].
+///
+/// All declared tables are locked for writing before the operation starts, and
+/// its writes are applied in a single commit when it returns, like a
+/// [`TinyBase::transaction`]. Nothing is applied if it fails.
+pub struct OpContext<'a, 'b> {
+    db: &'a TinyBase,
+    procedure: &'a str,
+    tx: &'a mut MultiTransaction<'b>,
+}
+
+impl<'a, 'b> OpContext<'a, 'b> {
+    pub(crate) fn new(
+        db: &'a TinyBase,
+        procedure: &'a str,
+        tx: &'a mut MultiTransaction<'b>,
+    ) -> Self {
+        Self { db, procedure, tx }
+    }
+
+    /// Write to a table which was declared by the operation.
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - The name of the table.
+    ///
+    /// # Returns
+    ///
+    /// The [`Transaction`] of the table, or [`TinyBaseError::Procedure`] if the table
+    /// wasn't declared.
+    pub fn table<T: TableType>(&mut self, name: &str) -> DbResult<Transaction<'_, T>> {
+        if !self.tx.contains(name) {
+            return Err(TinyBaseError::Procedure(format!(
+                "table {} is not declared by {}",
+                name, self.procedure
+            )));
+        }
+
+        let table = self.db.open_table::<T>(name)?;
+        self.tx.table(&table)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::result::TinyBaseError;
+    use crate::{Table, TinyBase};
+
+    #[test]
+    fn run_op() {
+        let db = TinyBase::new(None, true);
+        let orders: Table<String> = db.open_table("orders").unwrap();
+        let id = orders.insert("stale".to_string()).unwrap();
+        let status = orders
+            .create_index("status", |value| value.to_owned())
+            .unwrap();
+
+        db.define_op("close_stale_orders", &[&orders], move |ctx| {
+            let mut orders = ctx.table::<String>("orders")?;
+            orders.update(id, |_| "closed".to_string())?;
+            Ok(())
+        });
+        db.define_op("failing", &[&orders], move |ctx| {
+            let mut orders = ctx.table::<String>("orders")?;
+            orders.insert("new".to_string())?;
+            orders.delete(id)?;
+            ctx.table::<String>("customers")?;
+            Ok(())
+        });
+
+        assert_eq!(db.ops(), ["close_stale_orders", "failing"]);
+
+        // All writes of the failing operation are reverted.
+        assert!(matches!(
+            db.run_op("failing"),
+            Err(TinyBaseError::Procedure(_))
+        ));
+        assert_eq!(orders.select(id).unwrap().unwrap().data, "stale");
+        assert!(status.select(&"new".to_string()).unwrap().is_empty());
+
+        db.run_op("close_stale_orders").unwrap();
+        assert_eq!(status.select(&"closed".to_string()).unwrap()[0].id, id);
+
+        assert!(matches!(
+            db.run_op("unknown"),
+            Err(TinyBaseError::Procedure(_))
+        ));
+    }
+}
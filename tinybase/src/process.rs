@@ -0,0 +1,165 @@
+use std::fs::{self, File, TryLockError};
+use std::io::Write;
+use std::path::Path;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use sled::Config;
+
+use crate::result::{DbResult, TinyBaseError};
+
+/// Name of the lock file held by the process which owns the database.
+pub(crate) const LOCK_FILE: &str = "tinybase.lock";
+/// Directory holding the snapshot published by the writer for readers.
+const PUBLISHED_DIR: &str = "published";
+/// Directory the next snapshot is written to before it replaces the published one.
+const STAGING_DIR: &str = "published.staging";
+/// Lock file guarding the published snapshot while it is replaced or copied.
+const PUBLISHED_LOCK: &str = "published.lock";
+
+/// Exclusive lock held by the single writer process of a database.
+/// The lock is released when this is dropped.
+pub(crate) struct WriterLock {
+    _file: File,
+}
+
+impl WriterLock {
+    /// Acquire the writer lock for the database at `path`.
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - Path of the database directory.
+    ///
+    /// # Returns
+    ///
+    /// The held lock or [`TinyBaseError::Locked`] if another writer holds it.
+    pub(crate) fn acquire(path: &Path) -> DbResult<Self> {
+        let mut file = try_lock_writer(path)?.ok_or(TinyBaseError::Locked)?;
+
+        // Record the owner for anyone inspecting the database.
+        file.set_len(0)?;
+        write!(file, "{}", std::process::id())?;
+
+        Ok(Self { _file: file })
+    }
+}
+
+/// Try to take the writer lock of the database at `path` without waiting.
+fn try_lock_writer(path: &Path) -> DbResult<Option<File>> {
+    fs::create_dir_all(path)?;
+
+    let file = File::options()
+        .create(true)
+        .truncate(false)
+        .write(true)
+        .open(path.join(LOCK_FILE))?;
+
+    match file.try_lock() {
+        Ok(()) => Ok(Some(file)),
+        Err(TryLockError::WouldBlock) => Ok(None),
+        Err(TryLockError::Error(err)) => Err(err.into()),
+    }
+}
+
+/// Open the lock file guarding the published snapshot of the database at `path`.
+fn published_lock(path: &Path) -> DbResult<File> {
+    Ok(File::options()
+        .create(true)
+        .truncate(false)
+        .write(true)
+        .open(path.join(PUBLISHED_LOCK))?)
+}
+
+/// Publish a copy of `engine` for readers of the database at `path`.
+///
+/// The copy is exported into a staging directory first and then swapped in
+/// under the published lock, so readers never see a partially written copy.
+/// The caller must keep writers out while the export runs.
+pub(crate) fn publish_snapshot(engine: &sled::Db, path: &Path) -> DbResult<()> {
+    let staging = path.join(STAGING_DIR);
+    if staging.exists() {
+        fs::remove_dir_all(&staging)?;
+    }
+
+    {
+        let copy = Config::new().path(&staging).open()?;
+        copy.import(engine.export());
+        copy.flush()?;
+    }
+
+    let lock = published_lock(path)?;
+    lock.lock()?;
+
+    let published = path.join(PUBLISHED_DIR);
+    if published.exists() {
+        fs::remove_dir_all(&published)?;
+    }
+    fs::rename(&staging, &published)?;
+
+    Ok(())
+}
+
+/// Open a private point-in-time copy of the database at `source`.
+///
+/// The files of a database can only be copied consistently while no writer
+/// has it open. If there is no writer, the database is copied directly while
+/// holding the writer lock; otherwise the snapshot last published by the
+/// writer is copied, or [`TinyBaseError::Locked`] is returned if it hasn't
+/// published one. The copy is deleted when the returned engine is dropped.
+pub(crate) fn open_snapshot(source: &Path) -> DbResult<sled::Db> {
+    static COUNTER: AtomicU64 = AtomicU64::new(0);
+
+    let nanos = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_nanos())
+        .unwrap_or_default();
+
+    let target = std::env::temp_dir().join(format!(
+        "tinybase-snapshot-{}-{}-{}",
+        std::process::id(),
+        nanos,
+        COUNTER.fetch_add(1, Ordering::Relaxed)
+    ));
+
+    match try_lock_writer(source)? {
+        Some(_writer) => copy_dir(source, &target)?,
+        None => {
+            let lock = published_lock(source)?;
+            lock.lock_shared()?;
+
+            let published = source.join(PUBLISHED_DIR);
+            if !published.exists() {
+                return Err(TinyBaseError::Locked);
+            }
+            copy_dir(&published, &target)?;
+        }
+    }
+
+    Ok(Config::new().path(target).temporary(true).open()?)
+}
+
+/// Recursively copy a database directory, skipping the lock files and
+/// published snapshots.
+fn copy_dir(source: &Path, target: &Path) -> DbResult<()> {
+    fs::create_dir_all(target)?;
+
+    for entry in fs::read_dir(source)? {
+        let entry = entry?;
+        let name = entry.file_name();
+        if [LOCK_FILE, PUBLISHED_DIR, STAGING_DIR, PUBLISHED_LOCK]
+            .iter()
+            .any(|skip| name == *skip)
+        {
+            continue;
+        }
+
+        let destination = target.join(name);
+        if entry.file_type()?.is_dir() {
+            copy_dir(&entry.path(), &destination)?;
+        } else {
+            fs::copy(entry.path(), destination)?;
+        }
+    }
+
+    Ok(())
+}
@@ -0,0 +1,149 @@
+use std::cell::{Cell, RefCell};
+use std::time::Duration;
+
+use crate::record::Record;
+
+/// Result of [`crate::QueryBuilder::profile`], the selected records with runtime statistics.
+#[derive(Debug)]
+pub struct QueryProfile<T> {
+    /// The selected records, as returned by [`crate::QueryBuilder::select`].
+    pub records: Vec<Record<T>>,
+    /// Statistics of the root condition.
+    pub condition: ConditionProfile,
+    /// Time spent reading the selected records from the table.
+    pub fetch: Duration,
+    /// Time spent ordering the records.
+    pub sort: Duration,
+    /// Time the whole query took.
+    pub total: Duration,
+}
+
+/// Work done by a query, returned by [`crate::QueryBuilder::select_with_stats`].
+#[derive(Debug, Clone, Default)]
+pub struct QueryStats {
+    /// Names of the index trees which were looked up, in the order of their first lookup.
+    pub indexes: Vec<String>,
+    /// Amount of index keys which were looked up or read by scans of an index.
+    pub keys_touched: usize,
+    /// Amount of IDs read from indexes and from scans of the table.
+    pub ids_scanned: usize,
+    /// Amount of records read and deserialized.
+    pub records_read: usize,
+    /// Time the whole query took.
+    pub duration: Duration,
+}
+
+/// Collects the [`QueryStats`] of a query while it's evaluated.
+#[derive(Default)]
+pub(crate) struct StatsCounter {
+    indexes: RefCell<Vec<String>>,
+    keys_touched: Cell<usize>,
+    ids_scanned: Cell<usize>,
+    records_read: Cell<usize>,
+}
+
+impl StatsCounter {
+    /// Count a lookup of an index which produced some IDs.
+    pub(crate) fn consulted(&self, index: String, ids: usize) {
+        let mut indexes = self.indexes.borrow_mut();
+        if !indexes.contains(&index) {
+            indexes.push(index);
+        }
+        self.scanned(ids);
+    }
+
+    /// Count index keys which were looked up or read by a scan.
+    pub(crate) fn touched(&self, keys: usize) {
+        self.keys_touched.set(self.keys_touched.get() + keys);
+    }
+
+    /// Amount of index keys touched so far.
+    pub(crate) fn keys_touched(&self) -> usize {
+        self.keys_touched.get()
+    }
+
+    /// Count IDs which were read.
+    pub(crate) fn scanned(&self, ids: usize) {
+        self.ids_scanned.set(self.ids_scanned.get() + ids);
+    }
+
+    /// Count a record which was read.
+    pub(crate) fn read(&self) {
+        self.records_read.set(self.records_read.get() + 1);
+    }
+
+    pub(crate) fn finish(&self, duration: Duration) -> QueryStats {
+        QueryStats {
+            indexes: self.indexes.take(),
+            keys_touched: self.keys_touched.take(),
+            ids_scanned: self.ids_scanned.take(),
+            records_read: self.records_read.take(),
+            duration,
+        }
+    }
+}
+
+/// Runtime statistics of a single condition and its operands.
+#[derive(Debug, Clone)]
+pub struct ConditionProfile {
+    /// Kind of the condition, the name of the index tree for lookups.
+    pub condition: String,
+    /// Time spent evaluating the condition, including its operands.
+    pub duration: Duration,
+    /// Amount of index keys looked up or read by scans, including those of the operands.
+    pub keys_touched: usize,
+    /// Amount of IDs the condition matched. Operands of an `And` which are merged
+    /// count the IDs read from their posting lists.
+    pub ids_produced: usize,
+    /// Whether the records matched by the other operand of an `And` were checked
+    /// against the condition instead of evaluating it.
+    pub probed: bool,
+    /// Amount of conditions whose IDs were read from a [`crate::QueryCache`] instead of
+    /// evaluating them, including this one and the operands, see
+    /// [`crate::QueryBuilder::profile_cached`].
+    pub cache_hits: usize,
+    /// Statistics of the operands, in the order they were evaluated.
+    pub children: Vec<ConditionProfile>,
+}
+
+/// Collects the [`ConditionProfile`] of every condition while a query is evaluated.
+pub(crate) struct Profiler {
+    /// Profiles of the operands of the conditions being evaluated, innermost last.
+    operands: RefCell<Vec<Vec<ConditionProfile>>>,
+}
+
+impl Default for Profiler {
+    fn default() -> Self {
+        Self {
+            operands: RefCell::new(vec![vec![]]),
+        }
+    }
+}
+
+impl Profiler {
+    /// Start evaluating a condition, collecting the profiles of its operands.
+    pub(crate) fn enter(&self) {
+        self.operands.borrow_mut().push(vec![]);
+    }
+
+    /// Finish evaluating a condition.
+    ///
+    /// # Returns
+    ///
+    /// The profiles of its operands.
+    pub(crate) fn exit(&self) -> Vec<ConditionProfile> {
+        self.operands.borrow_mut().pop().unwrap_or_default()
+    }
+
+    /// Record the profile of an evaluated condition as an operand of the enclosing one.
+    pub(crate) fn record(&self, profile: ConditionProfile) {
+        if let Some(operands) = self.operands.borrow_mut().last_mut() {
+            operands.push(profile);
+        }
+    }
+
+    /// The profile of the root condition, the last one recorded outside of any other.
+    pub(crate) fn finish(&self) -> Option<ConditionProfile> {
+        self.operands.borrow_mut().first_mut()?.pop()
+    }
+}
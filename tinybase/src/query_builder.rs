@@ -1,22 +1,213 @@
 use std::any::Any;
+use std::cmp::{Ordering, Reverse};
+use std::collections::{BTreeMap, BinaryHeap, HashMap, HashSet};
+use std::ops::{BitAnd, BitOr, Bound, Deref, RangeBounds};
+use std::time::{Duration, Instant};
+
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
 
 use crate::{
-    index::{AnyIndex, Index, IndexType},
-    result::DbResult,
+    cancel::{Abort, CancellationToken},
+    collation::{fold_case, Collator},
+    encoding::{decode, encode},
+    index::{private::KeyLookup, AnyIndex, Index, IndexType},
+    key_encoding::{decode_key, encode_key, escape, ELEMENT},
+    pipeline::{Pipeline, RecordIter},
+    plan::{ConditionPlan, OrderPlan, QueryPlan},
+    prepared::{param_key, PreparedQuery},
+    profile::{ConditionProfile, Profiler, QueryProfile, QueryStats, StatsCounter},
+    query_cache::QueryCache,
+    result::{DbResult, TinyBaseError},
     table::{Table, TableType},
-    Record,
+    Record, TinyBase,
 };
 
+/// Encodes a value of a condition, which is captured with the key type of its index
+/// so it can't be used with an index of another key type.
+pub type KeyFn = Box<dyn Fn() -> DbResult<Vec<u8>>>;
+
+fn key_fn<I: IndexType + 'static>(value: I) -> KeyFn {
+    Box::new(move || encode_key(&value))
+}
+
+/// Encode the values of an `In` condition, sorted and without duplicates.
+pub(crate) fn encode_keys(values: &[KeyFn]) -> DbResult<Vec<Vec<u8>>> {
+    let mut keys = values
+        .iter()
+        .map(|value| value())
+        .collect::<DbResult<Vec<_>>>()?;
+    keys.sort_unstable();
+    keys.dedup();
+    Ok(keys)
+}
+
 /// A single query condition.
 pub enum QueryCondition<T>
 where
     T: TableType + 'static,
 {
-    By(Box<dyn AnyIndex<T>>, Box<dyn Any>),
+    By(Box<dyn AnyIndex<T>>, KeyFn),
+    /// Keys equal to any value of a vector.
+    In(Box<dyn AnyIndex<T>>, Vec<KeyFn>),
+    /// String or byte keys starting with a prefix.
+    Prefix(Box<dyn AnyIndex<T>>, Vec<u8>),
+    /// String keys matching a pattern, found by reading every key of the index.
+    Matches(Box<dyn AnyIndex<T>>, Box<dyn Fn(&str) -> bool>),
+    /// Keys between two values, both inclusive.
+    Between(Box<dyn AnyIndex<T>>, KeyFn, KeyFn),
+    GreaterThan(Box<dyn AnyIndex<T>>, KeyFn),
+    LessThan(Box<dyn AnyIndex<T>>, KeyFn),
+    /// Keys greater than or equal to a value.
+    AtLeast(Box<dyn AnyIndex<T>>, KeyFn),
+    /// Keys less than or equal to a value.
+    AtMost(Box<dyn AnyIndex<T>>, KeyFn),
     And(Box<QueryCondition<T>>, Box<QueryCondition<T>>),
     Or(Box<QueryCondition<T>>, Box<QueryCondition<T>>),
+    /// All records of the table which don't match the condition.
+    Not(Box<QueryCondition<T>>),
+    /// Records whose data matches a predicate, found by reading the records.
+    Where(Box<dyn Fn(&T) -> bool>),
+}
+
+impl<T: TableType + 'static> QueryCondition<T> {
+    /// Name of the condition in a [`ConditionProfile`], the name of the index tree
+    /// for conditions on an index.
+    fn profile_name(&self) -> String {
+        match self {
+            QueryCondition::And(..) => "And".to_owned(),
+            QueryCondition::Or(..) => "Or".to_owned(),
+            QueryCondition::Not(..) => "Not".to_owned(),
+            QueryCondition::Where(..) => "Where".to_owned(),
+            QueryCondition::By(index, _)
+            | QueryCondition::In(index, _)
+            | QueryCondition::Prefix(index, _)
+            | QueryCondition::Matches(index, _)
+            | QueryCondition::Between(index, ..)
+            | QueryCondition::GreaterThan(index, _)
+            | QueryCondition::LessThan(index, _)
+            | QueryCondition::AtLeast(index, _)
+            | QueryCondition::AtMost(index, _) => index.idx_name(),
+        }
+    }
+
+    /// Index and range of encoded keys matched by a condition on a single index.
+    pub(crate) fn key_range(&self) -> DbResult<Option<KeyRange<'_, T>>> {
+        Ok(Some(match self {
+            QueryCondition::By(index, value) => {
+                let key = value()?;
+                (
+                    index.as_ref(),
+                    Bound::Included(key.clone()),
+                    Bound::Included(key),
+                )
+            }
+            QueryCondition::Between(index, low, high) => (
+                index.as_ref(),
+                Bound::Included(low()?),
+                Bound::Included(high()?),
+            ),
+            QueryCondition::GreaterThan(index, value) => {
+                (index.as_ref(), Bound::Excluded(value()?), Bound::Unbounded)
+            }
+            QueryCondition::LessThan(index, value) => {
+                (index.as_ref(), Bound::Unbounded, Bound::Excluded(value()?))
+            }
+            QueryCondition::AtLeast(index, value) => {
+                (index.as_ref(), Bound::Included(value()?), Bound::Unbounded)
+            }
+            QueryCondition::AtMost(index, value) => {
+                (index.as_ref(), Bound::Unbounded, Bound::Included(value()?))
+            }
+            QueryCondition::In(..)
+            | QueryCondition::Prefix(..)
+            | QueryCondition::Matches(..)
+            | QueryCondition::And(..)
+            | QueryCondition::Or(..)
+            | QueryCondition::Not(..)
+            | QueryCondition::Where(..) => return Ok(None),
+        }))
+    }
+
+    /// Whether the data of a record matches the condition, computing its index keys.
+    ///
+    /// Leaves match if any key of the record matches.
+    fn matches(&self, data: &T) -> DbResult<bool> {
+        Ok(match self {
+            QueryCondition::In(index, values) => {
+                let values = encode_keys(values)?;
+                index
+                    .gen_keys(data)?
+                    .iter()
+                    .any(|key| values.binary_search(key).is_ok())
+            }
+            QueryCondition::Prefix(index, prefix) => index
+                .gen_keys(data)?
+                .iter()
+                .any(|key| key.starts_with(prefix)),
+            QueryCondition::Matches(index, pattern) => {
+                let mut matched = false;
+                for key in index.gen_keys(data)? {
+                    matched |= pattern(&decode_key::<String>(&key)?);
+                }
+                matched
+            }
+            QueryCondition::And(left, right) => left.matches(data)? && right.matches(data)?,
+            QueryCondition::Or(left, right) => left.matches(data)? || right.matches(data)?,
+            QueryCondition::Not(condition) => !condition.matches(data)?,
+            QueryCondition::Where(predicate) => predicate(data),
+            leaf => {
+                let (index, start, end) = leaf.key_range()?.unwrap();
+                let range = (start, end);
+                index
+                    .gen_keys(data)?
+                    .iter()
+                    .any(|key| RangeBounds::<Vec<u8>>::contains(&range, key))
+            }
+        })
+    }
+
+    /// Amount of IDs the condition matches, if it's known without scanning.
+    ///
+    /// Only lookups of keys are estimated, by the length of their posting lists.
+    fn estimate(&self) -> DbResult<Option<usize>> {
+        Ok(match self {
+            QueryCondition::By(index, value) => Some(index.count_keys(&[value()?])?),
+            QueryCondition::In(index, values) => Some(index.count_keys(&encode_keys(values)?)?),
+            QueryCondition::And(left, right) => match (left.estimate()?, right.estimate()?) {
+                (Some(left), Some(right)) => Some(left.min(right)),
+                (left, right) => left.or(right),
+            },
+            QueryCondition::Or(left, right) => match (left.estimate()?, right.estimate()?) {
+                (Some(left), Some(right)) => Some(left + right),
+                _ => None,
+            },
+            _ => None,
+        })
+    }
+}
+
+/// Orders the operands of an `And` condition to evaluate the more selective one first.
+///
+/// # Returns
+///
+/// Both operands with their estimates, the one to evaluate first at the front.
+fn plan_and<T: TableType + 'static, C: Deref<Target = QueryCondition<T>>>(
+    left: C,
+    right: C,
+) -> DbResult<[(C, Option<usize>); 2]> {
+    let (left_estimate, right_estimate) = (left.estimate()?, right.estimate()?);
+
+    Ok(match (left_estimate, right_estimate) {
+        (None, Some(_)) => [(right, right_estimate), (left, left_estimate)],
+        (Some(l), Some(r)) if r < l => [(right, right_estimate), (left, left_estimate)],
+        _ => [(left, left_estimate), (right, right_estimate)],
+    })
 }
 
+/// Index of a condition with the lower and upper bound of the encoded keys it matches.
+pub(crate) type KeyRange<'a, T> = (&'a dyn AnyIndex<T>, Bound<Vec<u8>>, Bound<Vec<u8>>);
+
 /// For building and chaining query conditions.
 pub struct ConditionBuilder<T: TableType + 'static>(QueryCondition<T>);
 
@@ -28,7 +219,198 @@ impl<T: TableType + 'static> ConditionBuilder<T> {
     /// * `index` - The index to use for the query.
     /// * `value` - The value to search for in the index.
     pub fn by<I: IndexType + 'static>(index: &Index<T, I>, value: I) -> Self {
-        Self(QueryCondition::By(Box::new(index.clone()), Box::new(value)))
+        Self(QueryCondition::By(Box::new(index.clone()), key_fn(value)))
+    }
+
+    /// Creates a new query condition matching index keys equal to a placeholder,
+    /// whose value is bound when a [`PreparedQuery`] runs.
+    ///
+    /// # Arguments
+    ///
+    /// * `index` - The index to use for the query.
+    /// * `name` - The name of the placeholder, see [`crate::prepared::Params::with`].
+    pub fn by_param<I: IndexType + 'static>(index: &Index<T, I>, name: &str) -> Self {
+        Self(QueryCondition::By(
+            Box::new(index.clone()),
+            param_key::<I>(name),
+        ))
+    }
+
+    /// Creates a new query condition matching string index keys equal to a value, ignoring case.
+    ///
+    /// Indexes created with [`Table::create_index_ci`] are looked up directly, the keys of
+    /// other indexes are compared by reading every record.
+    ///
+    /// # Arguments
+    ///
+    /// * `index` - The index to use for the query.
+    /// * `value` - The value to search for in the index.
+    pub fn by_ci(index: &Index<T, String>, value: &str) -> Self {
+        let value = fold_case(value);
+        if index.case_folded {
+            return Self::by(index, value);
+        }
+
+        let index = index.clone();
+        Self::filter(move |data| {
+            index
+                .keys_of(data)
+                .iter()
+                .any(|key| fold_case(key) == value)
+        })
+    }
+
+    /// Creates a new query condition matching index keys equal to any of many values.
+    ///
+    /// All keys are looked up in a single pass over the index, which is cheaper
+    /// than combining many [`ConditionBuilder::by`] conditions with [`ConditionBuilder::or`].
+    ///
+    /// # Arguments
+    ///
+    /// * `index` - The index to use for the query.
+    /// * `values` - The values to search for in the index.
+    pub fn by_any<I: IndexType + 'static>(index: &Index<T, I>, values: Vec<I>) -> Self {
+        Self(QueryCondition::In(
+            Box::new(index.clone()),
+            values.into_iter().map(key_fn).collect(),
+        ))
+    }
+
+    /// Creates a new query condition matching string or byte index keys starting with a prefix.
+    ///
+    /// # Arguments
+    ///
+    /// * `index` - The index to use for the query.
+    /// * `prefix` - The value the matching keys start with.
+    pub fn starts_with<I: PrefixKey + 'static>(index: &Index<T, I>, prefix: I) -> Self {
+        Self(QueryCondition::Prefix(
+            Box::new(index.clone()),
+            prefix.key_prefix(),
+        ))
+    }
+
+    /// Creates a new query condition matching string index keys against a regular expression.
+    ///
+    /// The keys are read from the index, records are only read for the matching keys.
+    ///
+    /// # Arguments
+    ///
+    /// * `index` - The index to use for the query.
+    /// * `pattern` - The regular expression, such as `^val.*\d$`.
+    ///
+    /// # Returns
+    ///
+    /// The condition, or [`TinyBaseError::Regex`] if the pattern is invalid.
+    #[cfg(feature = "regex")]
+    pub fn matches(index: &Index<T, String>, pattern: &str) -> DbResult<Self> {
+        let regex = regex::Regex::new(pattern)?;
+        Ok(Self::key_matches(index, move |key| regex.is_match(key)))
+    }
+
+    /// Creates a new query condition matching string index keys for which a predicate holds.
+    ///
+    /// The keys are read from the index, records are only read for the matching keys.
+    pub(crate) fn key_matches(
+        index: &Index<T, String>,
+        pattern: impl Fn(&str) -> bool + 'static,
+    ) -> Self {
+        Self(QueryCondition::Matches(
+            Box::new(index.clone()),
+            Box::new(pattern),
+        ))
+    }
+
+    /// Creates a new query condition matching a string field of the records against a
+    /// regular expression, for fields without an index.
+    ///
+    /// Records are read like with [`ConditionBuilder::filter`].
+    ///
+    /// # Arguments
+    ///
+    /// * `field` - Function returning the field of a record.
+    /// * `pattern` - The regular expression, such as `^val.*\d$`.
+    ///
+    /// # Returns
+    ///
+    /// The condition, or [`TinyBaseError::Regex`] if the pattern is invalid.
+    #[cfg(feature = "regex")]
+    pub fn matches_field(field: impl Fn(&T) -> &str + 'static, pattern: &str) -> DbResult<Self> {
+        let regex = regex::Regex::new(pattern)?;
+        Ok(Self::filter(move |data| regex.is_match(field(data))))
+    }
+
+    /// Creates a new query condition matching index keys between two values, both inclusive.
+    ///
+    /// Keys are compared in the order of their encoding, which is the natural order
+    /// of integers, floats, strings and tuples of them.
+    ///
+    /// # Arguments
+    ///
+    /// * `index` - The index to use for the query.
+    /// * `low` - The smallest matching key.
+    /// * `high` - The largest matching key.
+    pub fn between<I: IndexType + 'static>(index: &Index<T, I>, low: I, high: I) -> Self {
+        Self(QueryCondition::Between(
+            Box::new(index.clone()),
+            key_fn(low),
+            key_fn(high),
+        ))
+    }
+
+    /// Creates a new query condition matching index keys greater than a value,
+    /// compared like [`ConditionBuilder::between`].
+    ///
+    /// # Arguments
+    ///
+    /// * `index` - The index to use for the query.
+    /// * `value` - The largest key which doesn't match.
+    pub fn greater_than<I: IndexType + 'static>(index: &Index<T, I>, value: I) -> Self {
+        Self(QueryCondition::GreaterThan(
+            Box::new(index.clone()),
+            key_fn(value),
+        ))
+    }
+
+    /// Creates a new query condition matching index keys less than a value,
+    /// compared like [`ConditionBuilder::between`].
+    ///
+    /// # Arguments
+    ///
+    /// * `index` - The index to use for the query.
+    /// * `value` - The smallest key which doesn't match.
+    pub fn less_than<I: IndexType + 'static>(index: &Index<T, I>, value: I) -> Self {
+        Self(QueryCondition::LessThan(
+            Box::new(index.clone()),
+            key_fn(value),
+        ))
+    }
+
+    /// Creates a new query condition matching index keys greater than or equal to a
+    /// value, compared like [`ConditionBuilder::between`].
+    ///
+    /// # Arguments
+    ///
+    /// * `index` - The index to use for the query.
+    /// * `value` - The smallest key which matches.
+    pub fn at_least<I: IndexType + 'static>(index: &Index<T, I>, value: I) -> Self {
+        Self(QueryCondition::AtLeast(
+            Box::new(index.clone()),
+            key_fn(value),
+        ))
+    }
+
+    /// Creates a new query condition matching index keys less than or equal to a
+    /// value, compared like [`ConditionBuilder::between`].
+    ///
+    /// # Arguments
+    ///
+    /// * `index` - The index to use for the query.
+    /// * `value` - The largest key which matches.
+    pub fn at_most<I: IndexType + 'static>(index: &Index<T, I>, value: I) -> Self {
+        Self(QueryCondition::AtMost(
+            Box::new(index.clone()),
+            key_fn(value),
+        ))
     }
 
     /// Creates a new query condition representing the logical AND of two existing conditions.
@@ -51,6 +433,64 @@ impl<T: TableType + 'static> ConditionBuilder<T> {
         Self(QueryCondition::Or(Box::new(left.0), Box::new(right.0)))
     }
 
+    /// Creates a new query condition matching records which match all of many conditions.
+    ///
+    /// # Arguments
+    ///
+    /// * `conditions` - The conditions to combine, without any all records match.
+    pub fn all(conditions: Vec<Self>) -> Self {
+        Self::fold(conditions, Self::and).unwrap_or_else(|| Self::filter(|_| true))
+    }
+
+    /// Creates a new query condition matching records which match any of many conditions.
+    ///
+    /// # Arguments
+    ///
+    /// * `conditions` - The conditions to combine, without any no record matches.
+    pub fn any(conditions: Vec<Self>) -> Self {
+        Self::fold(conditions, Self::or).unwrap_or_else(|| Self::filter(|_| false))
+    }
+
+    /// Combine conditions into a balanced tree, so its depth only grows with the
+    /// logarithm of their amount.
+    fn fold(mut conditions: Vec<Self>, combine: fn(Self, Self) -> Self) -> Option<Self> {
+        match conditions.len() {
+            0 | 1 => conditions.pop(),
+            len => {
+                let right = conditions.split_off(len / 2);
+                Some(combine(
+                    Self::fold(conditions, combine)?,
+                    Self::fold(right, combine)?,
+                ))
+            }
+        }
+    }
+
+    /// Creates a new query condition matching all records which don't match an existing condition.
+    ///
+    /// This reads the IDs of all records in the table.
+    ///
+    /// # Arguments
+    ///
+    /// * `condition` - The condition to negate.
+    #[allow(clippy::should_implement_trait)]
+    pub fn not(condition: Self) -> Self {
+        Self(QueryCondition::Not(Box::new(condition.0)))
+    }
+
+    /// Creates a new query condition matching records whose data matches a predicate,
+    /// for fields without an index.
+    ///
+    /// On its own this reads every record of the table. As an operand of
+    /// [`ConditionBuilder::and`] only the records matching the other operand are read.
+    ///
+    /// # Arguments
+    ///
+    /// * `predicate` - Whether a record is selected.
+    pub fn filter(predicate: impl Fn(&T) -> bool + 'static) -> Self {
+        Self(QueryCondition::Where(Box::new(predicate)))
+    }
+
     /// Builds the final query condition.
     ///
     /// # Returns
@@ -61,19 +501,320 @@ impl<T: TableType + 'static> ConditionBuilder<T> {
     }
 }
 
-impl<T: TableType + 'static> Into<QueryCondition<T>> for ConditionBuilder<T> {
-    fn into(self) -> QueryCondition<T> {
-        self.build()
+impl<T: TableType + 'static> BitAnd for ConditionBuilder<T> {
+    type Output = Self;
+
+    /// Same as [`ConditionBuilder::and`].
+    fn bitand(self, rhs: Self) -> Self {
+        Self::and(self, rhs)
+    }
+}
+
+impl<T: TableType + 'static> BitOr for ConditionBuilder<T> {
+    type Output = Self;
+
+    /// Same as [`ConditionBuilder::or`].
+    fn bitor(self, rhs: Self) -> Self {
+        Self::or(self, rhs)
+    }
+}
+
+impl<T: TableType + 'static> From<ConditionBuilder<T>> for QueryCondition<T> {
+    fn from(val: ConditionBuilder<T>) -> Self {
+        val.build()
+    }
+}
+
+/// Values which [`condition!`] converts into index keys, so string literals can be
+/// compared with `String` keys.
+pub trait IntoKey<I> {
+    fn into_key(self) -> I;
+}
+
+impl<I> IntoKey<I> for I {
+    fn into_key(self) -> I {
+        self
+    }
+}
+
+impl IntoKey<String> for &str {
+    fn into_key(self) -> String {
+        self.to_owned()
+    }
+}
+
+/// Builds a [`ConditionBuilder`] from an expression over indexes.
+///
+/// Operands compare an index, which has to be a variable, with a value:
+/// `==`, `!=`, `<`, `<=`, `>`, `>=` and `in [values]`. They are combined with
+/// `&&`, `||`, `!` and parentheses, `&&` binds stronger than `||`. Ranges compare
+/// keys like [`ConditionBuilder::between`].
+/// For example `condition!(name == "bob" && (len > 3 || tag in ["x", "y"]))`.
+#[macro_export]
+macro_rules! condition {
+    // Split at `||`, the operands are split at `&&`.
+    (@or [$($done:tt)*] [$($current:tt)*] || $($rest:tt)*) => {
+        $crate::condition!(@or [$($done)* [$($current)*]] [] $($rest)*)
+    };
+    (@or [$($done:tt)*] [$($current:tt)*] $next:tt $($rest:tt)*) => {
+        $crate::condition!(@or [$($done)*] [$($current)* $next] $($rest)*)
+    };
+    (@or [[$($first:tt)*] $($done:tt)*] [$($current:tt)*]) => {
+        $crate::condition!(@fold_or
+            $crate::condition!(@and [] [] $($first)*);
+            $($done)* [$($current)*]
+        )
+    };
+    (@or [] [$($current:tt)*]) => {
+        $crate::condition!(@and [] [] $($current)*)
+    };
+    (@fold_or $condition:expr; [$($next:tt)*] $($rest:tt)*) => {
+        $crate::condition!(@fold_or
+            $crate::ConditionBuilder::or($condition, $crate::condition!(@and [] [] $($next)*));
+            $($rest)*
+        )
+    };
+    (@fold_or $condition:expr;) => {
+        $condition
+    };
+
+    (@and [$($done:tt)*] [$($current:tt)*] && $($rest:tt)*) => {
+        $crate::condition!(@and [$($done)* [$($current)*]] [] $($rest)*)
+    };
+    (@and [$($done:tt)*] [$($current:tt)*] $next:tt $($rest:tt)*) => {
+        $crate::condition!(@and [$($done)*] [$($current)* $next] $($rest)*)
+    };
+    (@and [[$($first:tt)*] $($done:tt)*] [$($current:tt)*]) => {
+        $crate::condition!(@fold_and
+            $crate::condition!(@operand $($first)*);
+            $($done)* [$($current)*]
+        )
+    };
+    (@and [] [$($current:tt)*]) => {
+        $crate::condition!(@operand $($current)*)
+    };
+    (@fold_and $condition:expr; [$($next:tt)*] $($rest:tt)*) => {
+        $crate::condition!(@fold_and
+            $crate::ConditionBuilder::and($condition, $crate::condition!(@operand $($next)*));
+            $($rest)*
+        )
+    };
+    (@fold_and $condition:expr;) => {
+        $condition
+    };
+
+    (@operand ($($inner:tt)*)) => {
+        $crate::condition!($($inner)*)
+    };
+    (@operand ! $($inner:tt)+) => {
+        $crate::ConditionBuilder::not($crate::condition!(@operand $($inner)+))
+    };
+    (@operand $index:ident == $($value:tt)+) => {
+        $crate::ConditionBuilder::by(&$index, $crate::condition!(@key $($value)+))
+    };
+    (@operand $index:ident != $($value:tt)+) => {
+        $crate::ConditionBuilder::not($crate::condition!(@operand $index == $($value)+))
+    };
+    (@operand $index:ident > $($value:tt)+) => {
+        $crate::ConditionBuilder::greater_than(&$index, $crate::condition!(@key $($value)+))
+    };
+    (@operand $index:ident < $($value:tt)+) => {
+        $crate::ConditionBuilder::less_than(&$index, $crate::condition!(@key $($value)+))
+    };
+    (@operand $index:ident >= $($value:tt)+) => {
+        $crate::ConditionBuilder::at_least(&$index, $crate::condition!(@key $($value)+))
+    };
+    (@operand $index:ident <= $($value:tt)+) => {
+        $crate::ConditionBuilder::at_most(&$index, $crate::condition!(@key $($value)+))
+    };
+    (@operand $index:ident in [$($value:expr),* $(,)?]) => {
+        $crate::ConditionBuilder::by_any(
+            &$index,
+            vec![$($crate::query_builder::IntoKey::into_key($value)),*],
+        )
+    };
+    (@key $($value:tt)+) => {
+        $crate::query_builder::IntoKey::into_key($($value)+)
+    };
+
+    ($($condition:tt)+) => {
+        $crate::condition!(@or [] [] $($condition)+)
+    };
+}
+
+/// Builds a [`QueryBuilder`] for a table with a condition written like [`condition!`],
+/// for example `query!(table, name == "bob" && len > 3)`.
+#[macro_export]
+macro_rules! query {
+    ($table:expr, $($condition:tt)+) => {
+        $crate::QueryBuilder::new(&$table).with_condition($crate::condition!($($condition)+))
+    };
+}
+
+/// Direction to order results in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Direction {
+    /// Smallest key first.
+    Asc,
+    /// Largest key first.
+    Desc,
+}
+
+/// Aggregate function computed by [`QueryBuilder::aggregate`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Aggregate {
+    Min,
+    Max,
+    Sum,
+    /// Arithmetic mean.
+    Avg,
+}
+
+/// Index key types which can be aggregated.
+pub trait NumericKey: IndexType {
+    fn as_f64(&self) -> f64;
+
+    /// Sum of keys with the amount of times they occur, accumulated in `i128` or
+    /// `u128` for integer keys so it can't overflow.
+    fn sum(keys: &[(Self, usize)]) -> f64;
+}
+
+macro_rules! numeric_key {
+    ($sum:ty: $($ty:ty),*) => {
+        $(impl NumericKey for $ty {
+            fn as_f64(&self) -> f64 {
+                *self as f64
+            }
+
+            fn sum(keys: &[(Self, usize)]) -> f64 {
+                keys.iter()
+                    .map(|(key, count)| *key as $sum * *count as $sum)
+                    .sum::<$sum>() as f64
+            }
+        })*
+    };
+}
+
+numeric_key!(u128: u8, u16, u32, u64, usize);
+numeric_key!(i128: i8, i16, i32, i64, isize);
+numeric_key!(f64: f32, f64);
+
+/// Index key types which can be matched by a prefix.
+pub trait PrefixKey: IndexType {
+    /// Encoded key without its terminator, which every encoded key starting with
+    /// the value starts with.
+    fn key_prefix(&self) -> Vec<u8>;
+}
+
+impl PrefixKey for String {
+    fn key_prefix(&self) -> Vec<u8> {
+        let mut prefix = vec![];
+        escape(self.as_bytes(), &mut prefix);
+        prefix
+    }
+}
+
+impl PrefixKey for Vec<u8> {
+    fn key_prefix(&self) -> Vec<u8> {
+        self.iter().flat_map(|byte| [ELEMENT, *byte]).collect()
+    }
+}
+
+/// Position after the last record of a page returned by [`QueryBuilder::select_page`].
+///
+/// The cursor can be passed to clients as an opaque token with [`Cursor::to_token`].
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Cursor {
+    id: u64,
+    /// Encoded sort keys of the record, which ordered queries resume after.
+    keys: Vec<Vec<u8>>,
+}
+
+impl Cursor {
+    /// Encode the cursor as a hex string.
+    pub fn to_token(&self) -> DbResult<String> {
+        Ok(encode(self)?
+            .iter()
+            .map(|byte| format!("{:02x}", byte))
+            .collect())
+    }
+
+    /// Decode a cursor from a token created with [`Cursor::to_token`].
+    pub fn from_token(token: &str) -> DbResult<Self> {
+        let invalid = || TinyBaseError::QueryBuilder("invalid cursor token".into());
+
+        let bytes = (0..token.len())
+            .step_by(2)
+            .map(|i| u8::from_str_radix(token.get(i..i + 2)?, 16).ok())
+            .collect::<Option<Vec<u8>>>()
+            .ok_or_else(invalid)?;
+
+        decode(&bytes).map_err(|_| invalid())
     }
 }
 
+/// Amount of posting list entries which are about as costly to decode as reading one record.
+const PROBE_COST: usize = 8;
+
+/// Share of the records of a table estimated to match a range or prefix condition.
+const RANGE_SELECTIVITY: usize = 3;
+
+/// Amount of index keys which are about as costly to walk as sorting one selected record.
+const SORT_COST: usize = 8;
+
+/// Amount of records written per commit by [`QueryBuilder::select_into_with`].
+const SELECT_INTO_BATCH: usize = 1000;
+
+/// Sort key of the data of a record, compared with the [`OrderKey::compare`] it was extracted by.
+type SortKey = Box<dyn Any>;
+
+/// Compares two sort keys extracted by the same [`OrderKey`].
+type KeyComparator = Box<dyn Fn(&SortKey, &SortKey) -> Ordering>;
+
+/// Encodes a sort key for a [`Cursor`].
+type KeyEncoder = Box<dyn Fn(&SortKey) -> DbResult<Vec<u8>>>;
+/// Decodes a sort key stored in a [`Cursor`].
+type KeyDecoder = Box<dyn Fn(&[u8]) -> DbResult<SortKey>>;
+
+/// A sort key of a query.
+struct OrderKey<T> {
+    /// Extracts the key from the data of a record, once per record.
+    key: Box<dyn Fn(&T) -> SortKey>,
+    /// Compares two extracted keys, in the direction of the ordering.
+    compare: KeyComparator,
+    encode: KeyEncoder,
+    decode: KeyDecoder,
+}
+
+/// Lists the IDs of the records in the order of an index starting at the sort key
+/// of a cursor, given the amount of selected records. [`None`] if sorting them in
+/// memory is cheaper or records don't have exactly one key.
+type KeyWalk = Box<dyn Fn(Option<&SortKey>, usize) -> DbResult<Option<Vec<u64>>>>;
+
 /// Builder for building and executing queries.
+///
+/// Selected records are returned in ascending ID order, unless the query is ordered.
+/// Records with equal sort keys are in ascending ID order too, so results are the
+/// same regardless of how the condition was evaluated.
 pub struct QueryBuilder<T>
 where
     T: TableType + 'static,
 {
     table: Table<T>,
     condition: Option<QueryCondition<T>>,
+    /// Sort keys applied to the selected records, in order of priority.
+    order: Vec<OrderKey<T>>,
+    /// Walk of the index of the first sort key, which orders records without sorting them.
+    walk: Option<KeyWalk>,
+    /// When evaluation of the query is aborted.
+    abort: Abort,
+    /// Work done while evaluating the query, see [`QueryBuilder::select_with_stats`].
+    stats: StatsCounter,
+    /// Profiles of the evaluated conditions, see [`QueryBuilder::profile`].
+    profiler: Option<Profiler>,
+    /// IDs of the conditions with a result in a [`QueryCache`] by the address of the
+    /// condition, see [`QueryBuilder::profile_cached`].
+    cached: HashMap<usize, Vec<u64>>,
 }
 
 impl<T> QueryBuilder<T>
@@ -89,6 +830,12 @@ where
         Self {
             table: table.clone(),
             condition: None,
+            order: Vec::new(),
+            walk: None,
+            abort: Abort::default(),
+            stats: StatsCounter::default(),
+            profiler: None,
+            cached: HashMap::new(),
         }
     }
 
@@ -103,144 +850,2305 @@ where
         self
     }
 
-    /// Validates the query builder's state.
-    fn check_valid(&self) -> DbResult<()> {
-        match &self.condition {
-            Some(_) => Ok(()),
-            None => Err(crate::result::TinyBaseError::QueryBuilder(
-                "No search condition provided".into(),
-            )),
-        }
-    }
-
-    /// Executes the query and returns the selected records.
+    /// Aborts the query with [`TinyBaseError::Cancelled`] once a deadline has passed.
     ///
-    /// # Returns
+    /// The deadline is checked before every condition and every record read, a single
+    /// lookup in an index isn't interrupted.
     ///
-    /// All selected [`Record`] instances.
-    pub fn select(self) -> DbResult<Vec<Record<T>>> {
-        self.check_valid()?;
-        Self::select_recursive(self.condition.unwrap())
+    /// # Arguments
+    ///
+    /// * `deadline` - The latest time evaluation may continue.
+    pub fn with_deadline(mut self, deadline: Instant) -> Self {
+        self.abort.deadline = Some(deadline);
+        self
     }
 
-    /// Updates the records in the table based on the query condition and new value.
+    /// Aborts the query with [`TinyBaseError::Cancelled`] if it takes longer than a
+    /// timeout from now, see [`QueryBuilder::with_deadline`].
     ///
     /// # Arguments
     ///
-    /// * `updater` - Closure to generate the new data based on the old data.
+    /// * `timeout` - The time the query may take.
+    pub fn with_timeout(self, timeout: Duration) -> Self {
+        self.with_deadline(Instant::now() + timeout)
+    }
+
+    /// Aborts the query with [`TinyBaseError::Cancelled`] once a token is cancelled,
+    /// checked like [`QueryBuilder::with_deadline`].
     ///
-    /// # Returns
+    /// # Arguments
     ///
-    /// All updated [`Record`] instances.
-    pub fn update(self, updater: fn(T) -> T) -> DbResult<Vec<Record<T>>> {
-        self.check_valid()?;
-        let ids: Vec<u64> = Self::select_recursive(self.condition.unwrap())?
-            .iter()
-            .map(|record| record.id)
-            .collect();
-
-        self.table.update(&ids, updater)
+    /// * `token` - The [`CancellationToken`] cancelling the query.
+    pub fn with_cancellation(mut self, token: &CancellationToken) -> Self {
+        self.abort.token = Some(token.clone());
+        self
     }
 
-    /// Deletes the records from the table based on the query condition.
+    /// Order the selected records by the key of an index.
+    /// This will overwrite the previous ordering (if set).
     ///
-    /// # Returns
+    /// If many records are selected compared to the keys of the index, records are
+    /// read in the order of the index tree instead of being sorted in memory.
     ///
-    /// All deleted [`Record`] instances.
-    pub fn delete(self) -> DbResult<Vec<Record<T>>> {
-        self.check_valid()?;
-        let selected = Self::select_recursive(self.condition.unwrap())?;
+    /// # Arguments
+    ///
+    /// * `index` - The index whose keys the records are ordered by.
+    /// * `direction` - The direction to order in.
+    pub fn order_by<I: IndexType + Ord + 'static>(
+        mut self,
+        index: &Index<T, I>,
+        direction: Direction,
+    ) -> Self {
+        self.order.clear();
 
-        let mut removed = vec![];
+        let walk_index = index.clone();
+        let descending = direction == Direction::Desc;
 
-        for record in &selected {
-            if let Some(record) = self.table.delete(record.id)? {
-                removed.push(record);
+        let mut query = self.then_by(index, direction);
+        query.walk = Some(Box::new(move |from, selected| {
+            if selected.saturating_mul(SORT_COST) < walk_index.estimate_cardinality()? as usize {
+                return Ok(None);
             }
-        }
 
-        Ok(removed)
+            // The sort keys of `then_by` are all keys of a record.
+            let from = from.and_then(|keys| keys.downcast_ref::<Vec<I>>()?.first());
+            walk_index.ordered_ids(descending, from)
+        }));
+        query
     }
 
-    /// Recursively processes the query conditions and returns the selected records.
-    fn select_recursive(condition: QueryCondition<T>) -> DbResult<Vec<Record<T>>> {
-        match condition {
-            QueryCondition::By(index, value) => index.search(value),
-            QueryCondition::And(left, right) => {
-                let left_records = Self::select_recursive(*left)?;
-                let right_records = Self::select_recursive(*right)?;
-
-                let mut intersection: Vec<Record<T>> = left_records.clone();
-                intersection.retain(|record| {
-                    right_records
-                        .iter()
-                        .any(|other_record| record.id == other_record.id)
-                });
-
-                Ok(intersection)
-            }
-            QueryCondition::Or(left, right) => {
-                let mut records: Vec<Record<T>> =
-                    Self::select_recursive(*left)?.into_iter().collect();
-                records.extend(Self::select_recursive(*right)?.into_iter());
-
-                let mut seen = Vec::new();
-                records.retain(|item| {
-                    if seen.contains(&item.id) {
-                        false
-                    } else {
-                        seen.push(item.id);
-                        true
-                    }
-                });
+    /// Order records with equal keys of the previous orderings by the key of another index.
+    ///
+    /// # Arguments
+    ///
+    /// * `index` - The index whose keys the records are ordered by.
+    /// * `direction` - The direction to order in.
+    pub fn then_by<I: IndexType + Ord + 'static>(
+        self,
+        index: &Index<T, I>,
+        direction: Direction,
+    ) -> Self {
+        let index = index.clone();
+        self.push_order(move |data| index.keys_of(data), Ord::cmp, direction)
+    }
 
-                Ok(records)
-            }
+    /// Order the selected records by the string key of an index under a collation.
+    /// This will overwrite the previous ordering (if set).
+    ///
+    /// # Arguments
+    ///
+    /// * `index` - The index whose keys the records are ordered by.
+    /// * `collator` - The [`Collator`] comparing the keys.
+    /// * `direction` - The direction to order in.
+    pub fn order_by_collated(
+        mut self,
+        index: &Index<T, String>,
+        collator: &Collator,
+        direction: Direction,
+    ) -> Self {
+        self.order.clear();
+        self.walk = None;
+        self.then_by_collated(index, collator, direction)
+    }
+
+    /// Order records with equal keys of the previous orderings by the string key of
+    /// another index under a collation.
+    ///
+    /// # Arguments
+    ///
+    /// * `index` - The index whose keys the records are ordered by.
+    /// * `collator` - The [`Collator`] comparing the keys.
+    /// * `direction` - The direction to order in.
+    pub fn then_by_collated(
+        self,
+        index: &Index<T, String>,
+        collator: &Collator,
+        direction: Direction,
+    ) -> Self {
+        let (index, collator) = (index.clone(), collator.clone());
+        self.push_order(
+            move |data| index.keys_of(data),
+            move |a: &Vec<String>, b: &Vec<String>| {
+                a.iter()
+                    .zip(b)
+                    .map(|(a, b)| collator.compare(a, b))
+                    .find(|ordering| ordering.is_ne())
+                    .unwrap_or_else(|| a.len().cmp(&b.len()))
+            },
+            direction,
+        )
+    }
+
+    /// Add a sort key with the lowest priority.
+    ///
+    /// # Arguments
+    ///
+    /// * `key` - Extracts the key from the data of a record.
+    /// * `compare` - Compares two keys in ascending order.
+    /// * `direction` - The direction to order in.
+    fn push_order<K: Serialize + DeserializeOwned + 'static>(
+        mut self,
+        key: impl Fn(&T) -> K + 'static,
+        compare: impl Fn(&K, &K) -> Ordering + 'static,
+        direction: Direction,
+    ) -> Self {
+        self.order.push(OrderKey {
+            key: Box::new(move |data| Box::new(key(data))),
+            compare: Box::new(move |a, b| {
+                // Keys are only compared with keys extracted by the same order key.
+                let (a, b) = (a.downcast_ref().unwrap(), b.downcast_ref().unwrap());
+                match direction {
+                    Direction::Asc => compare(a, b),
+                    Direction::Desc => compare(a, b).reverse(),
+                }
+            }),
+            encode: Box::new(|key| encode(key.downcast_ref::<K>().unwrap())),
+            decode: Box::new(|bytes| Ok(Box::new(decode::<K>(bytes)?))),
+        });
+        self
+    }
+
+    /// Validates the query builder's state.
+    fn check_valid(&self) -> DbResult<()> {
+        match &self.condition {
+            Some(_) => Ok(()),
+            None => Err(crate::result::TinyBaseError::QueryBuilder(
+                "No search condition provided".into(),
+            )),
+        }
+    }
+
+    /// Executes the query and returns the selected records.
+    ///
+    /// # Returns
+    ///
+    /// All selected [`Record`] instances.
+    pub fn select(self) -> DbResult<Vec<Record<T>>> {
+        self.execute()
+    }
+
+    /// Prepares the query to be executed many times, without building it again.
+    ///
+    /// # Returns
+    ///
+    /// The [`PreparedQuery`], or [`TinyBaseError::QueryBuilder`] if no condition is set.
+    pub fn prepare(self) -> DbResult<PreparedQuery<T>> {
+        self.check_valid()?;
+        Ok(PreparedQuery::new(self))
+    }
+
+    /// Executes the query without consuming it, see [`QueryBuilder::select`].
+    pub(crate) fn execute(&self) -> DbResult<Vec<Record<T>>> {
+        self.check_valid()?;
+        let condition = self.condition.as_ref().unwrap();
+
+        let Some(walk) = &self.walk else {
+            let mut records = self.select_records(condition)?;
+            Self::sort(&self.order, &mut records);
+            return Ok(records);
+        };
+
+        let selected = self.sorted_ids(condition)?;
+        let Some(ordered) = walk(None, selected.len())? else {
+            let mut records = self.read_ids(&selected)?;
+            Self::sort(&self.order, &mut records);
+            return Ok(records);
+        };
+
+        let mut records = vec![];
+        for id in ordered {
+            if selected.binary_search(&id).is_ok() {
+                if let Some(record) = self.read(id)? {
+                    records.push(record);
+                }
+            }
+        }
+
+        // Records are already ordered by the first key, only the others are left.
+        if self.order.len() > 1 {
+            Self::sort(&self.order, &mut records);
+        }
+
+        Ok(records)
+    }
+
+    /// Executes the query like [`QueryBuilder::select`], returning the cached result
+    /// of the condition if there is one.
+    ///
+    /// The cached records are ordered by the query, so queries only differing in
+    /// their order share the cached result.
+    ///
+    /// # Arguments
+    ///
+    /// * `cache` - The [`QueryCache`] of the table.
+    ///
+    /// # Returns
+    ///
+    /// All selected [`Record`] instances.
+    pub fn select_cached(self, cache: &QueryCache<T>) -> DbResult<Vec<Record<T>>> {
+        self.check_valid()?;
+
+        let mut records = cache.select(self.condition.unwrap())?;
+        Self::sort(&self.order, &mut records);
+
+        Ok(records)
+    }
+
+    /// Executes the query like [`QueryBuilder::select`] while counting the work it does.
+    ///
+    /// Unlike [`QueryBuilder::profile`] conditions aren't timed one by one, which makes
+    /// it cheap enough to collect for every query.
+    ///
+    /// # Returns
+    ///
+    /// All selected [`Record`] instances with the [`QueryStats`] of the query.
+    pub fn select_with_stats(self) -> DbResult<(Vec<Record<T>>, QueryStats)> {
+        let start = Instant::now();
+        let records = self.execute()?;
+
+        Ok((records, self.stats.finish(start.elapsed())))
+    }
+
+    /// Executes the query and returns one page of the selected records.
+    ///
+    /// Records are in the order of the query, records with equal sort keys and
+    /// unordered queries are in ID order. The cursor holds the sort keys and ID of the
+    /// last record of a page, so records written between calls don't shift the
+    /// following pages. Queries ordered by an index walk it from the key of the cursor.
+    ///
+    /// # Arguments
+    ///
+    /// * `cursor` - Where the previous page ended, [`None`] for the first page.
+    /// * `page_size` - The maximum amount of records on the page.
+    ///
+    /// # Returns
+    ///
+    /// The records of the page and the [`Cursor`] of the next page, if there are more records.
+    pub fn select_page(
+        self,
+        cursor: Option<Cursor>,
+        page_size: usize,
+    ) -> DbResult<(Vec<Record<T>>, Option<Cursor>)> {
+        self.check_valid()?;
+        let condition = self.condition.as_ref().unwrap();
+
+        let after = match &cursor {
+            Some(cursor) if cursor.keys.len() != self.order.len() => {
+                return Err(TinyBaseError::QueryBuilder(
+                    "cursor of a query with another order".into(),
+                ))
+            }
+            Some(cursor) => Some((
+                cursor.id,
+                self.order
+                    .iter()
+                    .zip(&cursor.keys)
+                    .map(|(order, key)| (order.decode)(key))
+                    .collect::<DbResult<Vec<_>>>()?,
+            )),
+            None => None,
+        };
+
+        // Whether a record comes after the last record of the previous page.
+        let past_cursor = |record: &Record<T>| match &after {
+            Some((id, keys)) => {
+                let record_keys = Self::sort_keys(&self.order, &record.data);
+                Self::compare_keys(&self.order, &record_keys, keys)
+                    .then(record.id.cmp(id))
+                    .is_gt()
+            }
+            None => true,
+        };
+
+        let selected = match &self.walk {
+            Some(_) => Some(self.sorted_ids(condition)?),
+            None => None,
+        };
+        let walked = match (&self.walk, &selected) {
+            (Some(walk), Some(selected)) => {
+                walk(after.as_ref().map(|(_, keys)| &keys[0]), selected.len())?
+            }
+            _ => None,
+        };
+
+        let mut remaining: Vec<Record<T>> = if let Some(ordered) = walked {
+            let selected = selected.unwrap();
+            let mut records = vec![];
+
+            for id in ordered {
+                // Without other sort keys the walk is the final order.
+                if self.order.len() == 1 && records.len() > page_size {
+                    break;
+                }
+                if selected.binary_search(&id).is_err() {
+                    continue;
+                }
+                match self.read(id)? {
+                    Some(record) if self.order.len() > 1 || past_cursor(&record) => {
+                        records.push(record)
+                    }
+                    _ => {}
+                }
+            }
+
+            if self.order.len() > 1 {
+                Self::sort(&self.order, &mut records);
+                records.retain(past_cursor);
+            }
+
+            records
+        } else if self.order.is_empty() {
+            let ids = self.sorted_ids(condition)?;
+
+            // Only the IDs after the cursor have to be read.
+            let start = after.as_ref().map_or(0, |(id, _)| *id);
+            let mut records = vec![];
+            for id in ids.into_iter().filter(|id| after.is_none() || *id > start) {
+                if records.len() > page_size {
+                    break;
+                }
+                if let Some(record) = self.read(id)? {
+                    records.push(record);
+                }
+            }
+
+            records
+        } else {
+            let mut records = match &selected {
+                Some(selected) => self.read_ids(selected)?,
+                None => self.select_records(condition)?,
+            };
+            Self::sort(&self.order, &mut records);
+            records.retain(past_cursor);
+            records
+        };
+
+        let more = remaining.len() > page_size;
+        remaining.truncate(page_size);
+
+        let next = match remaining.last() {
+            Some(last) if more => Some(Cursor {
+                id: last.id,
+                keys: self
+                    .order
+                    .iter()
+                    .map(|order| (order.encode)(&(order.key)(&last.data)))
+                    .collect::<DbResult<_>>()?,
+            }),
+            _ => None,
+        };
+
+        Ok((remaining, next))
+    }
+
+    /// Executes the query like [`QueryBuilder::select`] while measuring every condition.
+    ///
+    /// # Returns
+    ///
+    /// A [`QueryProfile`] with the selected records and where the time was spent.
+    pub fn profile(self) -> DbResult<QueryProfile<T>> {
+        self.profile_with(None)
+    }
+
+    /// Executes the query like [`QueryBuilder::profile`], reading the IDs of every
+    /// condition with a cached result from a [`QueryCache`] instead of evaluating it.
+    ///
+    /// Conditions read from the cache are counted in [`ConditionProfile::cache_hits`].
+    /// The cache is only read, results of the profiled query aren't stored in it.
+    ///
+    /// # Arguments
+    ///
+    /// * `cache` - The [`QueryCache`] of the table.
+    ///
+    /// # Returns
+    ///
+    /// A [`QueryProfile`] with the selected records and where the time was spent.
+    pub fn profile_cached(self, cache: &QueryCache<T>) -> DbResult<QueryProfile<T>> {
+        self.profile_with(Some(cache))
+    }
+
+    /// Profiles the query, reading cached conditions from the cache if there is one.
+    fn profile_with(mut self, cache: Option<&QueryCache<T>>) -> DbResult<QueryProfile<T>> {
+        self.check_valid()?;
+        self.profiler = Some(Profiler::default());
+        let start = Instant::now();
+
+        // The conditions are looked up by address, they don't move while `self` is evaluated.
+        if let Some(cache) = cache {
+            self.cached = cache.cached_ids(self.condition.as_ref().unwrap())?;
+        }
+
+        let ids = self.selected_ids()?;
+        let condition = self.profiler.as_ref().unwrap().finish().unwrap();
+
+        let fetch_start = Instant::now();
+        let mut records = self.read_ids(&ids)?;
+        let fetch = fetch_start.elapsed();
+
+        let sort_start = Instant::now();
+        Self::sort(&self.order, &mut records);
+        let sort = sort_start.elapsed();
+
+        Ok(QueryProfile {
+            records,
+            condition,
+            fetch,
+            sort,
+            total: start.elapsed(),
+        })
+    }
+
+    /// Describes how the query would be executed, without executing it.
+    ///
+    /// Only index keys are read to estimate the amount of matching records.
+    ///
+    /// # Returns
+    ///
+    /// The [`QueryPlan`] of the query, which also prints as an indented tree.
+    pub fn explain(&self) -> DbResult<QueryPlan> {
+        self.check_valid()?;
+        let mut records = None;
+
+        let condition =
+            Self::plan_recursive(&self.table, self.condition.as_ref().unwrap(), &mut records)?;
+
+        let sort_keys = self.order.len();
+        let order = match (&self.walk, sort_keys) {
+            (_, 0) => OrderPlan::Unordered,
+            (Some(_), _) => OrderPlan::IndexWalk { sort_keys },
+            (None, _) => OrderPlan::Sort { sort_keys },
+        };
+
+        Ok(QueryPlan { condition, order })
+    }
+
+    /// Sort records by the order keys of the query.
+    ///
+    /// The keys of every record are extracted once, not on every comparison.
+    fn sort(order: &[OrderKey<T>], records: &mut Vec<Record<T>>) {
+        if order.is_empty() {
+            return;
+        }
+
+        let mut keyed: Vec<_> = records
+            .drain(..)
+            .map(|record| (Self::sort_keys(order, &record.data), record))
+            .collect();
+
+        // Stable, so records with equal keys keep their order.
+        keyed.sort_by(|(a, _), (b, _)| Self::compare_keys(order, a, b));
+        records.extend(keyed.into_iter().map(|(_, record)| record));
+    }
+
+    /// Extract the keys of all order keys from the data of a record.
+    fn sort_keys(order: &[OrderKey<T>], data: &T) -> Vec<SortKey> {
+        order.iter().map(|order| (order.key)(data)).collect()
+    }
+
+    /// Compare the keys of two records by the order keys they were extracted by.
+    fn compare_keys(order: &[OrderKey<T>], a: &[SortKey], b: &[SortKey]) -> Ordering {
+        order
+            .iter()
+            .zip(a.iter().zip(b))
+            .map(|(order, (a, b))| (order.compare)(a, b))
+            .find(|ordering| ordering.is_ne())
+            .unwrap_or(Ordering::Equal)
+    }
+
+    /// Executes the query and returns the first selected record.
+    ///
+    /// # Returns
+    ///
+    /// The first [`Record`] in the order of [`QueryBuilder::select`], or [`None`] if
+    /// no record is selected.
+    pub fn first(self) -> DbResult<Option<Record<T>>> {
+        let table = self.table.clone();
+
+        for id in self.select_ids()? {
+            if let Some(record) = table.select(id)? {
+                return Ok(Some(record));
+            }
+        }
+
+        Ok(None)
+    }
+
+    /// Executes the query and returns the only selected record.
+    ///
+    /// # Returns
+    ///
+    /// The selected [`Record`], or [`TinyBaseError::QueryBuilder`] if no record or
+    /// more than one record is selected.
+    pub fn expect_one(self) -> DbResult<Record<T>> {
+        self.check_valid()?;
+        let ids = self.selected_ids()?;
+
+        let record = match ids[..] {
+            [id] => self.read(id)?,
+            _ => None,
+        };
+
+        record.ok_or_else(|| {
+            TinyBaseError::QueryBuilder(format!("expected one record, {} selected", ids.len()))
+        })
+    }
+
+    /// Executes the query and converts the selected records.
+    ///
+    /// # Arguments
+    ///
+    /// * `projection` - Function extracting the needed parts of a record.
+    ///
+    /// # Returns
+    ///
+    /// The projections of all selected records, in the order of [`QueryBuilder::select`].
+    pub fn select_map<U>(self, projection: impl Fn(Record<T>) -> U) -> DbResult<Vec<U>> {
+        Ok(self.select()?.into_iter().map(projection).collect())
+    }
+
+    /// Executes the query and decodes only the leading fields of the selected records.
+    ///
+    /// Records are stored without field names, so `P` has to be a struct with the
+    /// same first fields as the record type, in the same order. The remaining fields
+    /// aren't decoded. Lazy fields aren't attached.
+    ///
+    /// # Returns
+    ///
+    /// The IDs and decoded fields of all selected records, in the order of [`QueryBuilder::select_ids`].
+    pub fn select_as<P: DeserializeOwned>(self) -> DbResult<Vec<(u64, P)>> {
+        let table = self.table.clone();
+
+        let mut projections = vec![];
+        for id in self.select_ids()? {
+            if let Some(projection) = table.select_as(id)? {
+                projections.push((id, projection));
+            }
+        }
+
+        Ok(projections)
+    }
+
+    /// Executes the query and returns only the IDs of the selected records.
+    ///
+    /// Records are only read if the query is ordered by more than the key of an index.
+    ///
+    /// # Returns
+    ///
+    /// The IDs of all selected records, in the order [`QueryBuilder::select`] returns them.
+    pub fn select_ids(self) -> DbResult<Vec<u64>> {
+        self.check_valid()?;
+
+        if self.order.is_empty() {
+            return self.selected_ids();
+        }
+
+        if let (Some(walk), 1) = (&self.walk, self.order.len()) {
+            let selected = self.selected_ids()?;
+
+            return Ok(match walk(None, selected.len())? {
+                Some(ordered) => ordered
+                    .into_iter()
+                    .filter(|id| selected.binary_search(id).is_ok())
+                    .collect(),
+                None => {
+                    let mut records = self.read_ids(&selected)?;
+                    Self::sort(&self.order, &mut records);
+                    records.into_iter().map(|record| record.id).collect()
+                }
+            });
+        }
+
+        Ok(self.select()?.into_iter().map(|record| record.id).collect())
+    }
+
+    /// Groups the selected records by their key in an index.
+    ///
+    /// Groups are computed from the posting lists of the index, every selected record
+    /// is read once. Records of a group are in ascending ID order.
+    ///
+    /// # Arguments
+    ///
+    /// * `index` - The index whose keys the records are grouped by.
+    ///
+    /// # Returns
+    ///
+    /// The selected records of every key with at least one of them.
+    pub fn group_by<I: IndexType + Ord + 'static>(
+        self,
+        index: &Index<T, I>,
+    ) -> DbResult<BTreeMap<I, Vec<Record<T>>>> {
+        self.check_valid()?;
+        let selected = self.selected_ids()?;
+
+        let mut groups = BTreeMap::new();
+        for (key, ids) in index.key_groups(&selected)? {
+            let mut records = Vec::with_capacity(ids.len());
+            for id in ids {
+                if let Some(record) = self.read(id)? {
+                    records.push(record);
+                }
+            }
+
+            groups.insert(key, records);
+        }
+
+        Ok(groups)
+    }
+
+    /// Counts the selected records per key of an index, without reading them.
+    ///
+    /// # Arguments
+    ///
+    /// * `index` - The index whose keys the records are grouped by.
+    ///
+    /// # Returns
+    ///
+    /// The amount of selected records of every key with at least one of them.
+    pub fn group_count<I: IndexType + Ord + 'static>(
+        self,
+        index: &Index<T, I>,
+    ) -> DbResult<BTreeMap<I, usize>> {
+        self.check_valid()?;
+        let selected = self.selected_ids()?;
+
+        Ok(index.key_counts(&selected)?.into_iter().collect())
+    }
+
+    /// Lists the distinct keys of an index among the selected records, without reading them.
+    ///
+    /// Use [`QueryBuilder::group_count`] for the amount of records of every key.
+    ///
+    /// # Arguments
+    ///
+    /// * `index` - The index whose keys are listed.
+    ///
+    /// # Returns
+    ///
+    /// Every key of at least one selected record, in the order of their encoding.
+    pub fn distinct<I: IndexType + 'static>(self, index: &Index<T, I>) -> DbResult<Vec<I>> {
+        self.check_valid()?;
+        let selected = self.selected_ids()?;
+
+        Ok(index
+            .key_counts(&selected)?
+            .into_iter()
+            .map(|(key, _)| key)
+            .collect())
+    }
+
+    /// Aggregates the keys of an index over the selected records.
+    ///
+    /// The keys are read from the index, so no record is read. A record counts with
+    /// the key it has in the index, even if the index isn't part of the condition.
+    /// Min and max stop at the first key with a selected record from either end.
+    ///
+    /// # Arguments
+    ///
+    /// * `index` - The index whose keys are aggregated.
+    /// * `aggregate` - The function to compute.
+    ///
+    /// # Returns
+    ///
+    /// The result of the function, or [`None`] if no record is selected.
+    pub fn aggregate<I: NumericKey + 'static>(
+        self,
+        index: &Index<T, I>,
+        aggregate: Aggregate,
+    ) -> DbResult<Option<f64>> {
+        self.check_valid()?;
+        let selected = self.selected_ids()?;
+
+        // The index is ordered by its keys, only one end has to be walked.
+        let direction = match aggregate {
+            Aggregate::Min => Direction::Asc,
+            Aggregate::Max => Direction::Desc,
+            Aggregate::Sum | Aggregate::Avg => {
+                let counts = index.key_counts(&selected)?;
+                let records: usize = counts.iter().map(|(_, count)| count).sum();
+
+                return Ok(match aggregate {
+                    _ if counts.is_empty() => None,
+                    Aggregate::Sum => Some(I::sum(&counts)),
+                    _ => Some(I::sum(&counts) / records as f64),
+                });
+            }
+        };
+
+        Ok(index
+            .selected_key(&selected, direction)?
+            .map(|key| key.as_f64()))
+    }
+
+    /// Counts the selected records without reading them, only the index posting lists are walked.
+    ///
+    /// # Returns
+    ///
+    /// The amount of selected records.
+    pub fn count(self) -> DbResult<usize> {
+        self.check_valid()?;
+        Ok(self.ids_recursive(self.condition.as_ref().unwrap())?.len())
+    }
+
+    /// Executes the query lazily, reading each selected record when a pipeline stage needs it.
+    ///
+    /// Only the IDs of the selected records are held in memory, unless the query is
+    /// ordered, which requires all records to be selected first.
+    ///
+    /// # Returns
+    ///
+    /// A [`Pipeline`] over the selected [`Record`] instances.
+    pub fn pipeline(self) -> DbResult<Pipeline<RecordIter<T>>> {
+        if !self.order.is_empty() {
+            let records = self.select()?;
+            return Ok(Pipeline::new(Box::new(records.into_iter().map(Ok))));
+        }
+
+        self.check_valid()?;
+        let ids = self.selected_ids()?;
+        let table = self.table;
+
+        Ok(Pipeline::new(Box::new(
+            ids.into_iter()
+                .filter_map(move |id| table.select(id).transpose()),
+        )))
+    }
+
+    /// Executes the query and reads the selected records in chunks.
+    ///
+    /// Only the IDs of the selected records and one chunk are held in memory, ordered
+    /// queries which can't walk an index read all records once to order them first.
+    ///
+    /// # Arguments
+    ///
+    /// * `chunk_size` - The maximum amount of records in a chunk.
+    ///
+    /// # Returns
+    ///
+    /// An iterator over the chunks, in the order of [`QueryBuilder::select`], or
+    /// [`TinyBaseError::QueryBuilder`] if the chunk size is zero.
+    pub fn select_chunks(
+        self,
+        chunk_size: usize,
+    ) -> DbResult<impl Iterator<Item = DbResult<Vec<Record<T>>>>> {
+        if chunk_size == 0 {
+            return Err(TinyBaseError::QueryBuilder(
+                "chunk size must not be zero".into(),
+            ));
+        }
+
+        let table = self.table.clone();
+        let mut ids = self.select_ids()?.into_iter().peekable();
+
+        Ok(std::iter::from_fn(move || {
+            ids.peek()?;
+            Some(
+                ids.by_ref()
+                    .take(chunk_size)
+                    .filter_map(|id| table.select(id).transpose())
+                    .collect(),
+            )
+        }))
+    }
+
+    /// Executes the query and writes the selected records into a new table.
+    ///
+    /// # Arguments
+    ///
+    /// * `db` - The database to create the table in.
+    /// * `name` - The name of the new table.
+    ///
+    /// # Returns
+    ///
+    /// The new [`Table`], see [`QueryBuilder::select_into_with`].
+    pub fn select_into(self, db: &TinyBase, name: &str) -> DbResult<Table<T>> {
+        self.select_into_with(db, name, |data| data)
+    }
+
+    /// Executes the query and writes a projection of the selected records into a new table.
+    ///
+    /// Records keep their IDs and are written in batches, without holding all of
+    /// them in memory. Ordering of the query doesn't apply. If any batch fails, the
+    /// new table is dropped again, so it is only created with all selected records.
+    ///
+    /// # Arguments
+    ///
+    /// * `db` - The database to create the table in.
+    /// * `name` - The name of the new table, or `alias.table` to create it in an attached database.
+    /// * `projection` - Function converting the data of a selected record.
+    ///
+    /// # Returns
+    ///
+    /// The new [`Table`], or [`crate::result::TinyBaseError::QueryBuilder`] if a table
+    /// with the name already exists.
+    pub fn select_into_with<U: TableType>(
+        self,
+        db: &TinyBase,
+        name: &str,
+        projection: impl Fn(T) -> U,
+    ) -> DbResult<Table<U>> {
+        self.check_valid()?;
+
+        if db.tree_exists(name) {
+            return Err(crate::result::TinyBaseError::QueryBuilder(format!(
+                "table {} already exists",
+                name
+            )));
+        }
+
+        let target: Table<U> = db.open_table(name)?;
+
+        if let Err(err) = self.write_into(&target, projection) {
+            drop(target);
+            db.drop_table(name)?;
+            return Err(err);
+        }
+
+        Ok(target)
+    }
+
+    /// Write a projection of the selected records into a table in batches,
+    /// see [`QueryBuilder::select_into_with`].
+    fn write_into<U: TableType>(
+        &self,
+        target: &Table<U>,
+        projection: impl Fn(T) -> U,
+    ) -> DbResult<()> {
+        let ids = self.selected_ids()?;
+
+        for chunk in ids.chunks(SELECT_INTO_BATCH) {
+            let mut records = Vec::with_capacity(chunk.len());
+            for id in chunk {
+                if let Some(record) = self.read(*id)? {
+                    records.push(Record {
+                        id: record.id,
+                        data: projection(record.data),
+                    });
+                }
+            }
+
+            target.insert_records(records)?;
+        }
+
+        Ok(())
+    }
+
+    /// Updates the records in the table based on the query condition and new value.
+    ///
+    /// # Arguments
+    ///
+    /// * `updater` - Closure to generate the new data based on the old data.
+    ///
+    /// # Returns
+    ///
+    /// All updated [`Record`] instances.
+    pub fn update(self, updater: fn(T) -> T) -> DbResult<Vec<Record<T>>> {
+        self.check_valid()?;
+        let ids = self.selected_ids()?;
+
+        self.table.update(&ids, updater)
+    }
+
+    /// Updates the selected records whose current data passes a check.
+    ///
+    /// See [`crate::table::TableInner::update_if`] for how the check is kept atomic.
+    ///
+    /// # Arguments
+    ///
+    /// * `predicate` - Whether a record is updated, given its current data.
+    /// * `updater` - Closure to generate the new data based on the old data.
+    ///
+    /// # Returns
+    ///
+    /// All updated [`Record`] instances.
+    pub fn update_if(
+        self,
+        predicate: impl Fn(&T) -> bool,
+        updater: impl Fn(T) -> T,
+    ) -> DbResult<Vec<Record<T>>> {
+        self.check_valid()?;
+        let ids = self.selected_ids()?;
+
+        self.table.update_if(&ids, predicate, updater)
+    }
+
+    /// Updates the records like [`QueryBuilder::update`], keeping their previous data.
+    ///
+    /// # Arguments
+    ///
+    /// * `updater` - Closure to generate the new data based on the old data.
+    ///
+    /// # Returns
+    ///
+    /// The old and the new version of all updated records.
+    pub fn update_returning_old(
+        self,
+        updater: impl Fn(T) -> T,
+    ) -> DbResult<Vec<(Record<T>, Record<T>)>> {
+        self.check_valid()?;
+        let ids = self.selected_ids()?;
+
+        self.table.update_returning_old(&ids, updater)
+    }
+
+    /// Deletes the records from the table based on the query condition.
+    ///
+    /// All records are deleted in a single commit, a failure deletes none of them.
+    ///
+    /// # Returns
+    ///
+    /// All deleted [`Record`] instances.
+    pub fn delete(self) -> DbResult<Vec<Record<T>>> {
+        self.check_valid()?;
+        let selected = self.selected_ids()?;
+
+        self.table.delete_many(&selected)
+    }
+
+    /// IDs of the records selected by the condition of the query, in ascending order.
+    fn selected_ids(&self) -> DbResult<Vec<u64>> {
+        self.sorted_ids(self.condition.as_ref().unwrap())
+    }
+
+    /// IDs of the records selected by a condition, in ascending order.
+    fn sorted_ids(&self, condition: &QueryCondition<T>) -> DbResult<Vec<u64>> {
+        let mut ids = self.ids_recursive(condition)?;
+        // Branches produce their IDs in any order, e.g. the union of `Or`.
+        ids.sort_unstable();
+        Ok(ids)
+    }
+
+    /// Recursively processes the query conditions and returns the IDs of the selected records.
+    fn ids_recursive(&self, condition: &QueryCondition<T>) -> DbResult<Vec<u64>> {
+        self.abort.check()?;
+
+        if let Some(ids) = self.cache_hit(condition) {
+            return Ok(ids);
+        }
+
+        self.profiled(condition, false, Vec::len, || match condition {
+            QueryCondition::And(left, right) => match (left.as_ref(), right.as_ref()) {
+                (filter @ QueryCondition::Where(predicate), other)
+                | (other, filter @ QueryCondition::Where(predicate)) => {
+                    let ids = self.ids_recursive(other)?;
+                    self.profiled(filter, false, Vec::len, || {
+                        self.filter_ids(ids, predicate.as_ref())
+                    })
+                }
+                (left, right) => self.and_ids(left, right),
+            },
+            QueryCondition::Or(left, right) => {
+                let left = self.ids_recursive(left)?;
+                Ok(union(left, self.ids_recursive(right)?))
+            }
+            QueryCondition::Not(condition) => {
+                let excluded = self.ids_recursive(condition)?;
+                Ok(complement(self.table_ids()?, excluded))
+            }
+            QueryCondition::Where(predicate) => {
+                self.filter_ids(self.table_ids()?, predicate.as_ref())
+            }
+            leaf => Ok(merge_postings(self.leaf_postings(leaf)?.1).collect()),
+        })
+    }
+
+    /// Evaluates a condition, recording its [`ConditionProfile`] if the query is profiled.
+    ///
+    /// # Arguments
+    ///
+    /// * `condition` - The condition being evaluated.
+    /// * `probed` - Whether records are checked against the condition instead.
+    /// * `produced` - Counts the IDs in the result of the evaluation.
+    /// * `evaluate` - Evaluates the condition.
+    fn profiled<R>(
+        &self,
+        condition: &QueryCondition<T>,
+        probed: bool,
+        produced: impl FnOnce(&R) -> usize,
+        evaluate: impl FnOnce() -> DbResult<R>,
+    ) -> DbResult<R> {
+        let Some(profiler) = &self.profiler else {
+            return evaluate();
+        };
+
+        let start = Instant::now();
+        let keys_touched = self.stats.keys_touched();
+
+        profiler.enter();
+        let result = evaluate();
+        let children = profiler.exit();
+        let result = result?;
+
+        profiler.record(ConditionProfile {
+            condition: condition.profile_name(),
+            duration: start.elapsed(),
+            keys_touched: self.stats.keys_touched() - keys_touched,
+            ids_produced: produced(&result),
+            probed,
+            cache_hits: children.iter().map(|child| child.cache_hits).sum(),
+            children,
+        });
+
+        Ok(result)
+    }
+
+    /// IDs of a condition read from the [`QueryCache`] of a profiled query, recording
+    /// the cache hit in its [`ConditionProfile`].
+    ///
+    /// # Returns
+    ///
+    /// The IDs in ascending order, `None` if the condition has no cached result.
+    fn cache_hit(&self, condition: &QueryCondition<T>) -> Option<Vec<u64>> {
+        let ids = self
+            .cached
+            .get(&(condition as *const QueryCondition<T> as usize))?
+            .clone();
+
+        if let Some(profiler) = &self.profiler {
+            profiler.record(ConditionProfile {
+                condition: condition.profile_name(),
+                duration: Duration::ZERO,
+                keys_touched: 0,
+                ids_produced: ids.len(),
+                probed: false,
+                cache_hits: 1,
+                children: vec![],
+            });
+        }
+
+        Some(ids)
+    }
+
+    /// Posting lists of a condition on a single index, counting the keys it visits.
+    ///
+    /// # Returns
+    ///
+    /// The index with the posting list of every matched key, each in ascending ID order.
+    fn leaf_postings<'a>(
+        &self,
+        condition: &'a QueryCondition<T>,
+    ) -> DbResult<(&'a dyn AnyIndex<T>, Vec<Vec<u64>>)> {
+        let (index, lookup) = match condition {
+            QueryCondition::By(index, value) => (index.as_ref(), KeyLookup::Keys(vec![value()?])),
+            QueryCondition::In(index, values) => {
+                (index.as_ref(), KeyLookup::Keys(encode_keys(values)?))
+            }
+            QueryCondition::Prefix(index, prefix) => (index.as_ref(), KeyLookup::Prefix(prefix)),
+            QueryCondition::Matches(index, pattern) => {
+                (index.as_ref(), KeyLookup::Matching(pattern.as_ref()))
+            }
+            range => match range.key_range()? {
+                Some((index, start, end)) => (index, KeyLookup::Range(start, end)),
+                None => unreachable!("only conditions on a single index have posting lists"),
+            },
+        };
+
+        let (postings, visited) = index.postings(lookup)?;
+        self.stats.touched(visited);
+        self.stats
+            .consulted(index.idx_name(), postings.iter().map(Vec::len).sum());
+
+        Ok((index, postings))
+    }
+
+    /// IDs of all records of the table, counted as scanned.
+    fn table_ids(&self) -> DbResult<Vec<u64>> {
+        let ids = self.table.ids()?;
+        self.stats.scanned(ids.len());
+        Ok(ids)
+    }
+
+    /// Reads a record while evaluating the query, aborting it if requested.
+    fn read(&self, id: u64) -> DbResult<Option<Record<T>>> {
+        self.abort.check()?;
+        self.stats.read();
+        self.table.select(id)
+    }
+
+    /// Reads the records of IDs, skipping the ones which were removed.
+    fn read_ids(&self, ids: &[u64]) -> DbResult<Vec<Record<T>>> {
+        let mut records = Vec::with_capacity(ids.len());
+        for id in ids {
+            if let Some(record) = self.read(*id)? {
+                records.push(record);
+            }
+        }
+        Ok(records)
+    }
+
+    /// Intersects the IDs of two conditions, evaluating the more selective one first.
+    ///
+    /// If the first condition matches few records, they are read and probed against
+    /// the other condition instead of evaluating it. Conditions without an estimate,
+    /// like ranges, are evaluated last since they may scan large parts of an index.
+    ///
+    /// Otherwise both conditions are streamed in ID order and merged, so only the IDs
+    /// matching both are collected before any record is read.
+    fn and_ids(&self, left: &QueryCondition<T>, right: &QueryCondition<T>) -> DbResult<Vec<u64>> {
+        let [(first, first_estimate), (second, second_estimate)] = plan_and(left, right)?;
+
+        let probe = first_estimate.is_some_and(|first| {
+            second_estimate.is_none_or(|estimate| first * PROBE_COST < estimate)
+        });
+
+        if probe {
+            let ids = self.sorted_ids(first)?;
+
+            return self.profiled(second, true, Vec::len, || {
+                let mut matching = vec![];
+                for id in ids {
+                    if let Some(record) = self.read(id)? {
+                        if second.matches(&record.data)? {
+                            matching.push(id);
+                        }
+                    }
+                }
+                Ok(matching)
+            });
+        }
+
+        let mut first = self.id_stream(first)?.peekable();
+        if first.peek().is_none() {
+            return Ok(vec![]);
+        }
+
+        Ok(merge_join(first, self.id_stream(second)?, |id| *id).collect())
+    }
+
+    /// IDs matched by a condition in ascending order.
+    ///
+    /// The posting lists of a condition on a single index are each sorted, so they are
+    /// merged while the IDs are consumed instead of being collected and sorted. Other
+    /// conditions are evaluated by [`QueryBuilder::sorted_ids`].
+    fn id_stream(&self, condition: &QueryCondition<T>) -> DbResult<IdStream> {
+        self.abort.check()?;
+
+        if let Some(ids) = self.cache_hit(condition) {
+            return Ok(Box::new(ids.into_iter()));
+        }
+
+        Ok(match condition {
+            QueryCondition::And(..)
+            | QueryCondition::Or(..)
+            | QueryCondition::Not(..)
+            | QueryCondition::Where(..) => Box::new(self.sorted_ids(condition)?.into_iter()),
+            leaf => {
+                let postings = self.profiled(
+                    leaf,
+                    false,
+                    |postings: &Vec<Vec<u64>>| postings.iter().map(Vec::len).sum(),
+                    || Ok(self.leaf_postings(leaf)?.1),
+                )?;
+                Box::new(merge_postings(postings))
+            }
+        })
+    }
+
+    /// Recursively describes the query conditions in the order [`QueryBuilder::ids_recursive`]
+    /// evaluates them.
+    fn plan_recursive(
+        table: &Table<T>,
+        condition: &QueryCondition<T>,
+        records: &mut Option<usize>,
+    ) -> DbResult<ConditionPlan> {
+        let leaf = |condition: &str, index: &dyn AnyIndex<T>, estimated| ConditionPlan {
+            condition: condition.to_owned(),
+            index: Some(index.idx_name()),
+            estimated,
+            children: vec![],
+        };
+        let node = |condition: &str, estimated, children| ConditionPlan {
+            condition: condition.to_owned(),
+            index: None,
+            estimated,
+            children,
+        };
+
+        Ok(match condition {
+            QueryCondition::By(index, value) => {
+                leaf("By", index.as_ref(), index.count_keys(&[value()?])?)
+            }
+            QueryCondition::In(index, values) => leaf(
+                "In",
+                index.as_ref(),
+                index.count_keys(&encode_keys(values)?)?,
+            ),
+            QueryCondition::Prefix(index, _) => leaf(
+                "Prefix",
+                index.as_ref(),
+                table_len(table, records) / RANGE_SELECTIVITY,
+            ),
+            QueryCondition::Matches(index, _) => leaf(
+                "Matches",
+                index.as_ref(),
+                table_len(table, records) / RANGE_SELECTIVITY,
+            ),
+            QueryCondition::Between(index, ..) => leaf(
+                "Between",
+                index.as_ref(),
+                table_len(table, records) / RANGE_SELECTIVITY,
+            ),
+            QueryCondition::GreaterThan(index, _) => leaf(
+                "GreaterThan",
+                index.as_ref(),
+                table_len(table, records) / RANGE_SELECTIVITY,
+            ),
+            QueryCondition::LessThan(index, _) => leaf(
+                "LessThan",
+                index.as_ref(),
+                table_len(table, records) / RANGE_SELECTIVITY,
+            ),
+            QueryCondition::AtLeast(index, _) => leaf(
+                "AtLeast",
+                index.as_ref(),
+                table_len(table, records) / RANGE_SELECTIVITY,
+            ),
+            QueryCondition::AtMost(index, _) => leaf(
+                "AtMost",
+                index.as_ref(),
+                table_len(table, records) / RANGE_SELECTIVITY,
+            ),
+            QueryCondition::And(left, right) => {
+                // Predicates are applied to the IDs of the other operand.
+                let operands = match (left.as_ref(), right.as_ref()) {
+                    (predicate @ QueryCondition::Where(_), other) => [other, predicate],
+                    (_, QueryCondition::Where(_)) => [left.as_ref(), right.as_ref()],
+                    _ => plan_and(left.as_ref(), right.as_ref())?.map(|(operand, _)| operand),
+                };
+
+                let children = operands
+                    .into_iter()
+                    .map(|operand| Self::plan_recursive(table, operand, records))
+                    .collect::<DbResult<Vec<_>>>()?;
+                let estimated = children.iter().map(|child| child.estimated).min();
+
+                node("And", estimated.unwrap_or_default(), children)
+            }
+            QueryCondition::Or(left, right) => {
+                let left = Self::plan_recursive(table, left, records)?;
+                let right = Self::plan_recursive(table, right, records)?;
+                let estimated = (left.estimated + right.estimated).min(table_len(table, records));
+
+                node("Or", estimated, vec![left, right])
+            }
+            QueryCondition::Not(condition) => {
+                let child = Self::plan_recursive(table, condition, records)?;
+                let estimated = table_len(table, records).saturating_sub(child.estimated);
+
+                node("Not", estimated, vec![child])
+            }
+            QueryCondition::Where(_) => node("Where", table_len(table, records), vec![]),
+        })
+    }
+
+    /// Processes the query conditions and reads the selected records in ID order, every record once.
+    ///
+    /// Conditions are evaluated over IDs. Only predicates read the records they test,
+    /// records tested by a predicate at the top of the condition aren't read again.
+    fn select_records(&self, condition: &QueryCondition<T>) -> DbResult<Vec<Record<T>>> {
+        let (mut ids, predicate) = match condition {
+            QueryCondition::And(left, right) => match (left.as_ref(), right.as_ref()) {
+                (QueryCondition::Where(predicate), other)
+                | (other, QueryCondition::Where(predicate)) => {
+                    (self.ids_recursive(other)?, Some(predicate))
+                }
+                (left, right) => (self.and_ids(left, right)?, None),
+            },
+            QueryCondition::Where(predicate) => (self.table_ids()?, Some(predicate)),
+            condition => (self.ids_recursive(condition)?, None),
+        };
+        ids.sort_unstable();
+
+        let mut records = Vec::with_capacity(ids.len());
+        for id in ids {
+            match self.read(id)? {
+                Some(record)
+                    if predicate
+                        .as_ref()
+                        .is_none_or(|predicate| predicate(&record.data)) =>
+                {
+                    records.push(record)
+                }
+                _ => {}
+            }
+        }
+
+        Ok(records)
+    }
+
+    /// IDs of the records whose data matches a predicate, in the given order.
+    fn filter_ids(&self, ids: Vec<u64>, predicate: &dyn Fn(&T) -> bool) -> DbResult<Vec<u64>> {
+        let mut matching = vec![];
+        for id in ids {
+            if let Some(record) = self.read(id)? {
+                if predicate(&record.data) {
+                    matching.push(id);
+                }
+            }
+        }
+
+        Ok(matching)
+    }
+}
+
+/// IDs of all records of the table which aren't excluded, in ID order.
+fn complement(all: Vec<u64>, mut excluded: Vec<u64>) -> Vec<u64> {
+    excluded.sort_unstable();
+
+    all.into_iter()
+        .filter(|id| excluded.binary_search(id).is_err())
+        .collect()
+}
+
+/// Amount of records in the table, counted on first use.
+fn table_len<T: TableType>(table: &Table<T>, records: &mut Option<usize>) -> usize {
+    *records.get_or_insert_with(|| table.root.read().unwrap().len())
+}
+
+/// Combine two lists of IDs without duplicates, the IDs only in the right one are
+/// appended in their order.
+fn union(mut left: Vec<u64>, right: Vec<u64>) -> Vec<u64> {
+    let mut seen: HashSet<u64> = left.iter().copied().collect();
+    left.extend(right.into_iter().filter(|id| seen.insert(*id)));
+    left
+}
+
+/// IDs produced in ascending order as they are consumed, see [`QueryBuilder::id_stream`].
+type IdStream = Box<dyn Iterator<Item = u64>>;
+
+/// Merge posting lists which are each in ascending order into a single ascending
+/// sequence without duplicates, such as the lists of the keys of a range.
+fn merge_postings(postings: Vec<Vec<u64>>) -> impl Iterator<Item = u64> {
+    let mut postings: Vec<_> = postings.into_iter().map(Vec::into_iter).collect();
+    let mut heads: BinaryHeap<_> = postings
+        .iter_mut()
+        .enumerate()
+        .filter_map(|(list, ids)| Some(Reverse((ids.next()?, list))))
+        .collect();
+    let mut last = None;
+
+    std::iter::from_fn(move || loop {
+        let Reverse((id, list)) = heads.pop()?;
+        if let Some(next) = postings[list].next() {
+            heads.push(Reverse((next, list)));
+        }
+
+        // Records with many keys in the range are in many lists.
+        if last != Some(id) {
+            last = Some(id);
+            return Some(id);
+        }
+    })
+}
+
+/// Intersect two sequences ordered by ID in a single pass, without buffering either side.
+///
+/// # Returns
+///
+/// The items of the left sequence whose ID is also in the right one.
+fn merge_join<A>(
+    left: impl IntoIterator<Item = A>,
+    right: impl IntoIterator<Item = A>,
+    id: impl Fn(&A) -> u64,
+) -> impl Iterator<Item = A> {
+    let mut left = left.into_iter().peekable();
+    let mut right = right.into_iter().peekable();
+
+    std::iter::from_fn(move || loop {
+        let left_id = id(left.peek()?);
+        let right_id = id(right.peek()?);
+
+        match left_id.cmp(&right_id) {
+            Ordering::Less => {
+                left.next();
+            }
+            Ordering::Greater => {
+                right.next();
+            }
+            Ordering::Equal => {
+                right.next();
+                return left.next();
+            }
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::TinyBase;
+
+    #[test]
+    fn query_builder_select_and() {
+        let db = TinyBase::new(None, true);
+        let table: Table<String> = db.open_table("test_table").unwrap();
+
+        // Create an index for the table
+        let index = table
+            .create_index("name", |value| value.to_owned())
+            .unwrap();
+
+        let length = table.create_index("length", |value| value.len()).unwrap();
+
+        // Insert string values into the table
+        let value1 = table.insert("value1".to_string()).unwrap();
+        table.insert("value2".to_string()).unwrap();
+
+        let result_1 = QueryBuilder::new(&table)
+            .with_condition(ConditionBuilder::and(
+                ConditionBuilder::by(&index, "value1".to_string()),
+                ConditionBuilder::by(&index, "value2".to_string()),
+            ))
+            .select()
+            .expect("Select failed");
+
+        assert_eq!(result_1.len(), 0);
+
+        let result_2 = QueryBuilder::new(&table)
+            .with_condition(ConditionBuilder::and(
+                ConditionBuilder::by(&index, "value1".to_string()),
+                ConditionBuilder::by(&length, 6),
+            ))
+            .select()
+            .expect("Select failed");
+
+        assert_eq!(result_2.len(), 1);
+        assert_eq!(result_2[0].id, value1);
+    }
+
+    #[test]
+    fn query_builder_select_in() {
+        let db = TinyBase::new(None, true);
+        let table: Table<String> = db.open_table("test_table").unwrap();
+        let name = table
+            .create_index("name", |value| value.to_owned())
+            .unwrap();
+
+        let a = table.insert("a".to_string()).unwrap();
+        table.insert("b".to_string()).unwrap();
+        let c = table.insert("c".to_string()).unwrap();
+
+        let selected = QueryBuilder::new(&table)
+            .with_condition(ConditionBuilder::by_any(
+                &name,
+                vec!["c".to_string(), "a".to_string(), "c".to_string()],
+            ))
+            .select()
+            .unwrap();
+
+        assert_eq!(
+            selected.iter().map(|record| record.id).collect::<Vec<_>>(),
+            [a, c]
+        );
+    }
+
+    #[test]
+    fn query_builder_select_prefix() {
+        let db = TinyBase::new(None, true);
+        let table: Table<String> = db.open_table("test_table").unwrap();
+        let name = table
+            .create_index("name", |value| value.to_owned())
+            .unwrap();
+
+        let mut expected = vec![];
+        for value in ["apple", "apricot", "banana", "ap", "a"] {
+            let id = table.insert(value.to_string()).unwrap();
+            if value.starts_with("ap") {
+                expected.push(id);
+            }
+        }
+
+        let selected = QueryBuilder::new(&table)
+            .with_condition(ConditionBuilder::starts_with(&name, "ap".to_string()))
+            .select_ids()
+            .unwrap();
+        assert_eq!(selected, expected);
+    }
+
+    #[test]
+    fn query_builder_select_where() {
+        let db = TinyBase::new(None, true);
+        let table: Table<(String, u8)> = db.open_table("people").unwrap();
+        let name = table
+            .create_index("name", |(name, _)| name.to_owned())
+            .unwrap();
+
+        let young = table.insert(("John".to_string(), 20)).unwrap();
+        table.insert(("John".to_string(), 40)).unwrap();
+        table.insert(("Jane".to_string(), 25)).unwrap();
+
+        // Only the records named John are read.
+        let selected = QueryBuilder::new(&table)
+            .with_condition(ConditionBuilder::and(
+                ConditionBuilder::filter(|(_, age)| *age < 30),
+                ConditionBuilder::by(&name, "John".to_string()),
+            ))
+            .select_ids()
+            .unwrap();
+        assert_eq!(selected, [young]);
+
+        let count = QueryBuilder::new(&table)
+            .with_condition(ConditionBuilder::filter(|(_, age)| *age < 30))
+            .count()
+            .unwrap();
+        assert_eq!(count, 2);
+    }
+
+    #[test]
+    fn query_builder_select_and_planned() {
+        let db = TinyBase::new(None, true);
+        let table: Table<(String, u8)> = db.open_table("people").unwrap();
+        let name = table
+            .create_index("name", |(name, _)| name.to_owned())
+            .unwrap();
+        let age = table.create_index("age", |(_, age)| *age).unwrap();
+
+        let mut expected = vec![];
+        for i in 0..40u8 {
+            let person = if i % 10 == 0 { "John" } else { "Jane" };
+            let id = table.insert((person.to_string(), i)).unwrap();
+            if person == "John" && i > 15 {
+                expected.push(id);
+            }
+        }
+
+        let by_name = |value: &str| ConditionBuilder::by(&name, value.to_string());
+        let query = QueryBuilder::new(&table).with_condition(ConditionBuilder::and(
+            ConditionBuilder::greater_than(&age, 15),
+            ConditionBuilder::and(
+                by_name("Jane"),
+                ConditionBuilder::or(by_name("John"), by_name("Bob")),
+            ),
+        ));
+
+        // The lookups are evaluated before the range, the rarest one first.
+        let plan = query.explain().unwrap().condition;
+        assert_eq!(plan.children[0].condition, "And");
+        assert_eq!(plan.children[0].children[0].condition, "Or");
+        assert!(query.select_ids().unwrap().is_empty());
+
+        // The records of the lookup are probed against the range.
+        let selected = QueryBuilder::new(&table)
+            .with_condition(ConditionBuilder::and(
+                ConditionBuilder::greater_than(&age, 15),
+                by_name("John"),
+            ))
+            .select_ids()
+            .unwrap();
+        assert_eq!(selected, expected);
+    }
+
+    #[test]
+    fn query_builder_explain() {
+        let db = TinyBase::new(None, true);
+        let table: Table<(String, u8)> = db.open_table("people").unwrap();
+        let name = table
+            .create_index("name", |(name, _)| name.to_owned())
+            .unwrap();
+        let age = table.create_index("age", |(_, age)| *age).unwrap();
+
+        for (person, years) in [("John", 20), ("John", 40), ("Jane", 25)] {
+            table.insert((person.to_string(), years)).unwrap();
+        }
+
+        let plan = QueryBuilder::new(&table)
+            .with_condition(ConditionBuilder::and(
+                ConditionBuilder::filter(|(_, age)| *age > 30),
+                ConditionBuilder::or(
+                    ConditionBuilder::by(&name, "John".to_string()),
+                    ConditionBuilder::by(&age, 25),
+                ),
+            ))
+            .order_by(&age, Direction::Asc)
+            .explain()
+            .unwrap();
+
+        assert_eq!(plan.order, OrderPlan::IndexWalk { sort_keys: 1 });
+        assert_eq!(
+            plan.to_string(),
+            "And (estimated 3)\n  \
+             Or (estimated 3)\n    \
+             By people_idx_name (estimated 2)\n    \
+             By people_idx_age (estimated 1)\n  \
+             Where (estimated 3)\n\
+             Order by 1 keys, walking the first index"
+        );
+    }
+
+    #[test]
+    fn query_builder_merge_join() {
+        let db = TinyBase::new(None, true);
+        let table: Table<u64> = db.open_table("test_table").unwrap();
+        // Descending IDs, so posting lists differ from insertion order.
+        let next = std::sync::atomic::AtomicU64::new(1000);
+        table.id_generator(move || next.fetch_sub(1, std::sync::atomic::Ordering::Relaxed));
+
+        let even = table.create_index("even", |value| value % 2 == 0).unwrap();
+        let third = table.create_index("third", |value| value % 3 == 0).unwrap();
+
+        for value in 0..30 {
+            table.insert(value).unwrap();
+        }
+
+        let ids: Vec<u64> = QueryBuilder::new(&table)
+            .with_condition(ConditionBuilder::and(
+                ConditionBuilder::by(&even, true),
+                ConditionBuilder::by(&third, true),
+            ))
+            .select()
+            .unwrap()
+            .iter()
+            .map(|record| record.id)
+            .collect();
+
+        // Values 24, 18, 12, 6, 0 in ID order.
+        assert_eq!(ids, vec![976, 982, 988, 994, 1000]);
+
+        // Ranges span many posting lists, which are merged in ID order.
+        let digit = table.create_index("digit", |value| value % 10).unwrap();
+        let value = table.create_index("value", |value| *value).unwrap();
+
+        let (records, stats) = QueryBuilder::new(&table)
+            .with_condition(ConditionBuilder::and(
+                ConditionBuilder::between(&digit, 2, 3),
+                ConditionBuilder::greater_than(&value, 10),
+            ))
+            .select_with_stats()
+            .unwrap();
+
+        // Values 23, 22, 13, 12 in ID order.
+        assert_eq!(
+            records.iter().map(|record| record.id).collect::<Vec<_>>(),
+            vec![977, 978, 987, 988]
+        );
+        assert_eq!(stats.records_read, 4);
+        assert_eq!(
+            merge_postings(vec![vec![1, 4, 6], vec![2, 4], vec![], vec![3, 6, 7]])
+                .collect::<Vec<_>>(),
+            vec![1, 2, 3, 4, 6, 7]
+        );
+    }
+
+    #[test]
+    fn query_builder_profile() {
+        let db = TinyBase::new(None, true);
+        let table: Table<String> = db.open_table("test_table").unwrap();
+        let name = table
+            .create_index("name", |value| value.to_owned())
+            .unwrap();
+        let length = table.create_index("length", |value| value.len()).unwrap();
+
+        let value1 = table.insert("value1".to_string()).unwrap();
+        table.insert("value2".to_string()).unwrap();
+
+        let profile = QueryBuilder::new(&table)
+            .with_condition(ConditionBuilder::and(
+                ConditionBuilder::by(&length, 6),
+                ConditionBuilder::by(&name, "value1".to_string()),
+            ))
+            .profile()
+            .unwrap();
+
+        assert_eq!(profile.records.len(), 1);
+        assert_eq!(profile.records[0].id, value1);
+
+        // Operands are profiled in the order they are evaluated, the more selective first.
+        let condition = profile.condition;
+        assert_eq!(condition.condition, "And");
+        assert_eq!(condition.keys_touched, 2);
+        assert_eq!(condition.ids_produced, 1);
+        assert_eq!(condition.children[0].condition, "test_table_idx_name");
+        assert_eq!(condition.children[0].ids_produced, 1);
+        assert_eq!(condition.children[1].condition, "test_table_idx_length");
+        assert_eq!(condition.children[1].ids_produced, 2);
+
+        // Ranges aren't estimated, the records matching the lookup are probed instead.
+        let profile = QueryBuilder::new(&table)
+            .with_condition(ConditionBuilder::and(
+                ConditionBuilder::between(&length, 5, 7),
+                ConditionBuilder::by(&name, "value1".to_string()),
+            ))
+            .profile()
+            .unwrap();
+
+        let condition = profile.condition;
+        assert_eq!(condition.ids_produced, 1);
+        assert!(!condition.children[0].probed);
+        assert_eq!(condition.children[1].condition, "test_table_idx_length");
+        assert!(condition.children[1].probed);
+        assert_eq!(condition.children[1].keys_touched, 0);
+
+        // Ranges count every key they read, `In` every key it looks up.
+        table.insert("value10".to_string()).unwrap();
+        let profile = QueryBuilder::new(&table)
+            .with_condition(ConditionBuilder::or(
+                ConditionBuilder::between(&length, 5, 7),
+                ConditionBuilder::by_any(
+                    &name,
+                    vec!["value1".to_string(), "value2".to_string(), "x".to_string()],
+                ),
+            ))
+            .profile()
+            .unwrap();
+
+        let condition = profile.condition;
+        assert_eq!(condition.children[0].keys_touched, 2);
+        assert_eq!(condition.children[1].keys_touched, 3);
+        assert_eq!(condition.keys_touched, 5);
+    }
+
+    #[test]
+    fn query_builder_profile_cached() {
+        let db = TinyBase::new(None, true);
+        let table: Table<String> = db.open_table("test_table").unwrap();
+        let name = table
+            .create_index("name", |value| value.to_owned())
+            .unwrap();
+        let length = table.create_index("length", |value| value.len()).unwrap();
+
+        table.insert("value1".to_string()).unwrap();
+        table.insert("value2".to_string()).unwrap();
+
+        let cache = QueryCache::new(&table).unwrap();
+        cache
+            .select(ConditionBuilder::by(&name, "value1".to_string()))
+            .unwrap();
+
+        let query = || {
+            QueryBuilder::new(&table).with_condition(ConditionBuilder::or(
+                ConditionBuilder::by(&length, 6),
+                ConditionBuilder::by(&name, "value1".to_string()),
+            ))
+        };
+
+        // Only the operand with a cached result is read from the cache.
+        let condition = query().profile_cached(&cache).unwrap().condition;
+        assert_eq!(condition.cache_hits, 1);
+        assert_eq!(condition.children[0].cache_hits, 0);
+        assert_eq!(condition.children[1].cache_hits, 1);
+        assert_eq!(condition.children[1].keys_touched, 0);
+        assert_eq!(condition.children[1].ids_produced, 1);
+        assert_eq!(condition.ids_produced, 2);
+
+        assert_eq!(query().profile().unwrap().condition.cache_hits, 0);
+    }
+
+    #[test]
+    fn query_builder_select_into() {
+        let db = TinyBase::new(None, true);
+        let table: Table<String> = db.open_table("test_table").unwrap();
+        let length = table.create_index("length", |value| value.len()).unwrap();
+
+        let id = table.insert("value1".to_string()).unwrap();
+        let other = table.insert("value".to_string()).unwrap();
+
+        let lengths = QueryBuilder::new(&table)
+            .with_condition(ConditionBuilder::by(&length, 6))
+            .select_into_with(&db, "lengths", |value| value.len() as u64)
+            .unwrap();
+
+        assert_eq!(lengths.select(id).unwrap().unwrap().data, 6);
+        assert!(lengths.select(other).unwrap().is_none());
+
+        assert!(QueryBuilder::new(&table)
+            .with_condition(ConditionBuilder::by(&length, 6))
+            .select_into(&db, "lengths")
+            .is_err());
+
+        // A failed query doesn't leave a partial table behind.
+        assert!(matches!(
+            QueryBuilder::new(&table)
+                .with_condition(ConditionBuilder::by(&length, 6))
+                .with_deadline(Instant::now())
+                .select_into(&db, "cancelled"),
+            Err(TinyBaseError::Cancelled)
+        ));
+        assert!(!db.list_tables().contains(&"cancelled".to_string()));
+
+        let other = TinyBase::new(None, true);
+        other.open_table::<String>("copy").unwrap();
+        db.attach("other", &other).unwrap();
+
+        assert!(QueryBuilder::new(&table)
+            .with_condition(ConditionBuilder::by(&length, 6))
+            .select_into(&db, "other.copy")
+            .is_err());
+        QueryBuilder::new(&table)
+            .with_condition(ConditionBuilder::by(&length, 6))
+            .select_into(&db, "other.lengths")
+            .unwrap();
+        assert_eq!(other.list_tables(), vec!["copy", "lengths"]);
+    }
+
+    #[test]
+    fn query_builder_select_range() {
+        let db = TinyBase::new(None, true);
+        let table: Table<String> = db.open_table("test_table").unwrap();
+        let length = table.create_index("length", |value| value.len()).unwrap();
+
+        for value in ["a", "abc", "abcd", "abcdefghij", "abcdefghijk"] {
+            table.insert(value.to_string()).unwrap();
+        }
+
+        let select = |condition: ConditionBuilder<String>| -> Vec<String> {
+            QueryBuilder::new(&table)
+                .with_condition(condition)
+                .select()
+                .unwrap()
+                .into_iter()
+                .map(|record| record.data)
+                .collect()
+        };
+
+        assert_eq!(
+            select(ConditionBuilder::between(&length, 3, 10)),
+            ["abc", "abcd", "abcdefghij"]
+        );
+        assert_eq!(
+            select(ConditionBuilder::greater_than(&length, 10)),
+            ["abcdefghijk"]
+        );
+        assert_eq!(
+            select(ConditionBuilder::and(
+                ConditionBuilder::less_than(&length, 4),
+                ConditionBuilder::greater_than(&length, 1),
+            )),
+            ["abc"]
+        );
+        assert!(select(ConditionBuilder::between(&length, 10, 3)).is_empty());
+    }
+
+    #[test]
+    fn query_builder_select_not() {
+        let db = TinyBase::new(None, true);
+        let table: Table<String> = db.open_table("test_table").unwrap();
+        let name = table
+            .create_index("name", |value| value.to_owned())
+            .unwrap();
+        let length = table.create_index("length", |value| value.len()).unwrap();
+
+        for value in ["a", "bb", "cc", "ddd"] {
+            table.insert(value.to_string()).unwrap();
         }
+
+        let records = QueryBuilder::new(&table)
+            .with_condition(ConditionBuilder::and(
+                ConditionBuilder::not(ConditionBuilder::by(&name, "bb".to_string())),
+                ConditionBuilder::not(ConditionBuilder::by(&length, 3)),
+            ))
+            .select()
+            .unwrap();
+
+        assert_eq!(
+            records
+                .into_iter()
+                .map(|record| record.data)
+                .collect::<Vec<_>>(),
+            ["a", "cc"]
+        );
+    }
+
+    #[test]
+    fn query_builder_select_cached() {
+        let db = TinyBase::new(None, true);
+        let table: Table<(String, u8)> = db.open_table("people").unwrap();
+        let name = table
+            .create_index("name", |(name, _)| name.to_owned())
+            .unwrap();
+        let age = table.create_index("age", |(_, age)| *age).unwrap();
+
+        table.insert(("John".to_string(), 40)).unwrap();
+        let young = table.insert(("John".to_string(), 20)).unwrap();
+
+        let cache = QueryCache::new(&table).unwrap();
+        let query = || {
+            QueryBuilder::new(&table)
+                .with_condition(ConditionBuilder::by(&name, "John".to_string()))
+        };
+
+        assert_eq!(query().select_cached(&cache).unwrap().len(), 2);
+        let ordered = query()
+            .order_by(&age, Direction::Asc)
+            .select_cached(&cache)
+            .unwrap();
+        assert_eq!(ordered[0].id, young);
+        assert_eq!(cache.len(), 1);
+
+        // Changes of the records invalidate the cached result.
+        table.delete(young).unwrap();
+        assert_eq!(query().select_cached(&cache).unwrap().len(), 1);
+    }
+
+    #[test]
+    fn query_builder_macro() {
+        let db = TinyBase::new(None, true);
+        let table: Table<(String, usize, String)> = db.open_table("people").unwrap();
+        let name = table
+            .create_index("name", |(name, _, _)| name.to_owned())
+            .unwrap();
+        let len = table.create_index("len", |(_, len, _)| *len).unwrap();
+        let tag = table
+            .create_index("tag", |(_, _, tag)| tag.to_owned())
+            .unwrap();
+
+        let mut ids = vec![];
+        for (person, size, label) in [
+            ("bob", 5, "a"),
+            ("bob", 2, "x"),
+            ("bob", 1, "y"),
+            ("amy", 9, "x"),
+        ] {
+            ids.push(
+                table
+                    .insert((person.to_string(), size, label.to_string()))
+                    .unwrap(),
+            );
+        }
+
+        let selected =
+            |query: QueryBuilder<_>| query.order_by(&len, Direction::Desc).select_ids().unwrap();
+
+        assert_eq!(
+            selected(query!(table, name == "bob" && (len > 3 || tag == "x"))),
+            [ids[0], ids[1]]
+        );
+        assert_eq!(
+            selected(query!(table, tag in ["x", "y"] && !(name == "amy") || len >= 9)),
+            [ids[3], ids[1], ids[2]]
+        );
+        assert_eq!(selected(query!(table, len <= 2 && tag != "x")), [ids[2]]);
+
+        // Inclusive comparisons are a single range of the index.
+        let plan = query!(table, len >= 5).explain().unwrap();
+        assert_eq!(plan.condition.condition, "AtLeast");
+        assert_eq!(selected(query!(table, len >= 5)), [ids[3], ids[0]]);
+    }
+
+    #[test]
+    fn query_builder_select_page() {
+        let db = TinyBase::new(None, true);
+        let table: Table<String> = db.open_table("test_table").unwrap();
+        let length = table.create_index("length", |value| value.len()).unwrap();
+
+        for value in ["aaa", "bb", "c", "dd", "e"] {
+            table.insert(value.to_string()).unwrap();
+        }
+
+        let query = || {
+            QueryBuilder::new(&table)
+                .with_condition(ConditionBuilder::less_than(&length, 3))
+                .order_by(&length, Direction::Desc)
+        };
+
+        let (page, cursor) = query().select_page(None, 3).unwrap();
+        assert_eq!(
+            page.iter().map(|record| &record.data).collect::<Vec<_>>(),
+            ["bb", "dd", "c"]
+        );
+
+        // The token round trips, records inserted before the cursor don't shift the page.
+        let cursor = cursor.unwrap();
+        assert_eq!(cursor.keys, [encode(&vec![1usize]).unwrap()]);
+        let cursor = Cursor::from_token(&cursor.to_token().unwrap()).unwrap();
+        table.insert("ff".to_string()).unwrap();
+
+        let (page, cursor) = query().select_page(Some(cursor), 3).unwrap();
+        assert_eq!(
+            page.iter().map(|record| &record.data).collect::<Vec<_>>(),
+            ["e"]
+        );
+        assert!(cursor.is_none());
+
+        let (page, _) = QueryBuilder::new(&table)
+            .with_condition(ConditionBuilder::less_than(&length, 3))
+            .select_page(None, 2)
+            .unwrap();
+        assert_eq!(
+            page.iter().map(|record| &record.data).collect::<Vec<_>>(),
+            ["bb", "c"]
+        );
+    }
+
+    #[test]
+    fn query_builder_id_order() {
+        let db = TinyBase::new(None, true);
+        let table: Table<(String, u8)> = db.open_table("people").unwrap();
+        let name = table
+            .create_index("name", |(name, _)| name.to_owned())
+            .unwrap();
+        let age = table.create_index("age", |(_, age)| *age).unwrap();
+
+        let john = table.insert(("John".to_string(), 30)).unwrap();
+        let jane = table.insert(("Jane".to_string(), 20)).unwrap();
+        let bob = table.insert(("Bob".to_string(), 30)).unwrap();
+
+        let query = || {
+            QueryBuilder::new(&table).with_condition(ConditionBuilder::or(
+                ConditionBuilder::by(&name, "Bob".to_string()),
+                ConditionBuilder::or(
+                    ConditionBuilder::by(&name, "Jane".to_string()),
+                    ConditionBuilder::by(&name, "John".to_string()),
+                ),
+            ))
+        };
+
+        let ids: Vec<u64> = query().select().unwrap().iter().map(|r| r.id).collect();
+        assert_eq!(ids, vec![john, jane, bob]);
+        assert_eq!(query().select_ids().unwrap(), vec![john, jane, bob]);
+
+        // Records with equal keys are in ID order.
+        let ids = query()
+            .order_by(&age, Direction::Desc)
+            .select_ids()
+            .unwrap();
+        assert_eq!(ids, vec![john, bob, jane]);
+    }
+
+    #[test]
+    fn query_builder_select_chunks() {
+        let db = TinyBase::new(None, true);
+        let table: Table<u8> = db.open_table("numbers").unwrap();
+        let number = table.create_index("number", |number| *number).unwrap();
+
+        for value in 0..10 {
+            table.insert(value).unwrap();
+        }
+
+        let chunks = QueryBuilder::new(&table)
+            .with_condition(ConditionBuilder::less_than(&number, 7))
+            .order_by(&number, Direction::Desc)
+            .select_chunks(3)
+            .unwrap()
+            .map(|chunk| chunk.unwrap().into_iter().map(|r| r.data).collect())
+            .collect::<Vec<Vec<u8>>>();
+        assert_eq!(chunks, vec![vec![6, 5, 4], vec![3, 2, 1], vec![0]]);
+
+        assert!(matches!(
+            QueryBuilder::new(&table)
+                .with_condition(ConditionBuilder::less_than(&number, 7))
+                .select_chunks(0),
+            Err(TinyBaseError::QueryBuilder(_))
+        ));
     }
-}
 
-#[cfg(test)]
-mod tests {
-    use super::*;
-    use crate::TinyBase;
+    #[test]
+    fn query_builder_cancellation() {
+        let db = TinyBase::new(None, true);
+        let table: Table<u8> = db.open_table("numbers").unwrap();
+        for value in 0..10 {
+            table.insert(value).unwrap();
+        }
+
+        let token = CancellationToken::new();
+        let query = || {
+            QueryBuilder::new(&table)
+                .with_condition(ConditionBuilder::filter(|number| number % 2 == 0))
+                .with_cancellation(&token)
+        };
+        assert_eq!(query().select().unwrap().len(), 5);
+
+        token.cancel();
+        assert!(matches!(query().count(), Err(TinyBaseError::Cancelled)));
+
+        let expired = QueryBuilder::new(&table)
+            .with_condition(ConditionBuilder::filter(|_| true))
+            .with_deadline(Instant::now() - Duration::from_secs(1))
+            .select();
+        assert!(matches!(expired, Err(TinyBaseError::Cancelled)));
+    }
 
     #[test]
-    fn query_builder_select_and() {
+    fn query_builder_select_ci() {
         let db = TinyBase::new(None, true);
-        let table: Table<String> = db.open_table("test_table").unwrap();
+        let table: Table<String> = db.open_table("names").unwrap();
+        let folded = table
+            .create_index_ci("folded", |name| name.to_owned())
+            .unwrap();
+        let name = table.create_index("name", |name| name.to_owned()).unwrap();
 
-        // Create an index for the table
-        let index = table
-            .create_index("name", |value| value.to_owned())
+        table.insert("Value1".to_string()).unwrap();
+        table.insert("VALUE1".to_string()).unwrap();
+        table.insert("Value2".to_string()).unwrap();
+
+        for index in [&folded, &name] {
+            let selected = QueryBuilder::new(&table)
+                .with_condition(ConditionBuilder::by_ci(index, "value1"))
+                .select()
+                .unwrap();
+            assert_eq!(selected.len(), 2);
+        }
+
+        // Folded indexes are looked up without reading records.
+        let plan = QueryBuilder::new(&table)
+            .with_condition(ConditionBuilder::by_ci(&folded, "VaLuE2"))
+            .explain()
             .unwrap();
+        assert_eq!(plan.condition.condition, "By");
+        assert_eq!(plan.condition.estimated, 1);
+    }
 
-        let length = table.create_index("length", |value| value.len()).unwrap();
+    #[cfg(feature = "regex")]
+    #[test]
+    fn query_builder_select_matches() {
+        let db = TinyBase::new(None, true);
+        let table: Table<String> = db.open_table("names").unwrap();
+        let name = table.create_index("name", |name| name.to_owned()).unwrap();
 
-        // Insert string values into the table
-        let value1 = table.insert("value1".to_string()).unwrap();
+        table.insert("value1".to_string()).unwrap();
         table.insert("value2".to_string()).unwrap();
+        table.insert("valueX".to_string()).unwrap();
+        table.insert("other3".to_string()).unwrap();
 
-        let result_1 = QueryBuilder::new(&table)
+        for condition in [
+            ConditionBuilder::matches(&name, r"^val.*\d$").unwrap(),
+            ConditionBuilder::matches_field(|name: &String| name.as_str(), r"^val.*\d$").unwrap(),
+        ] {
+            let selected = QueryBuilder::new(&table)
+                .with_condition(condition)
+                .select()
+                .unwrap();
+            assert_eq!(selected.len(), 2);
+        }
+
+        // Probed as the second operand of an `And`.
+        let selected = QueryBuilder::new(&table)
             .with_condition(ConditionBuilder::and(
-                ConditionBuilder::by(&index, "value1".to_string()),
-                ConditionBuilder::by(&index, "value2".to_string()),
+                ConditionBuilder::by(&name, "value2".to_string()),
+                ConditionBuilder::matches(&name, r"2$").unwrap(),
             ))
             .select()
-            .expect("Select failed");
+            .unwrap();
+        assert_eq!(selected.len(), 1);
 
-        assert_eq!(result_1.len(), 0);
+        assert!(matches!(
+            ConditionBuilder::matches(&name, "("),
+            Err(TinyBaseError::Regex(_))
+        ));
+    }
 
-        let result_2 = QueryBuilder::new(&table)
+    #[test]
+    fn query_builder_select_all_any() {
+        let db = TinyBase::new(None, true);
+        let table: Table<(String, u8)> = db.open_table("people").unwrap();
+        let name = table
+            .create_index("name", |(name, _)| name.to_owned())
+            .unwrap();
+        let age = table.create_index("age", |(_, age)| *age).unwrap();
+
+        for (person, years) in [("John", 40), ("Jane", 30), ("Bob", 20), ("Alice", 30)] {
+            table.insert((person.to_string(), years)).unwrap();
+        }
+
+        let count = |condition: ConditionBuilder<(String, u8)>| {
+            QueryBuilder::new(&table)
+                .with_condition(condition)
+                .count()
+                .unwrap()
+        };
+        let by_name = |value: &str| ConditionBuilder::by(&name, value.to_string());
+
+        let names = ["John", "Jane", "Bob", "Alice", "Eve"];
+        assert_eq!(count(ConditionBuilder::any(names.map(by_name).into())), 4);
+        assert_eq!(
+            count(ConditionBuilder::all(vec![
+                ConditionBuilder::by(&age, 30),
+                ConditionBuilder::greater_than(&age, 20),
+                ConditionBuilder::filter(|(name, _): &(String, u8)| name.starts_with('J')),
+            ])),
+            1
+        );
+        assert_eq!(count(ConditionBuilder::all(vec![])), 4);
+        assert_eq!(count(ConditionBuilder::any(vec![])), 0);
+
+        assert_eq!(count(by_name("John") | by_name("Bob")), 2);
+        assert_eq!(
+            count((by_name("Jane") | by_name("Bob")) & ConditionBuilder::by(&age, 30)),
+            1
+        );
+    }
+
+    #[test]
+    fn query_builder_group_by() {
+        let db = TinyBase::new(None, true);
+        let table: Table<(String, u8)> = db.open_table("people").unwrap();
+        let name = table
+            .create_index("name", |(name, _)| name.to_owned())
+            .unwrap();
+        let age = table.create_index("age", |(_, age)| *age).unwrap();
+
+        let john = table.insert(("John".to_string(), 40)).unwrap();
+        let jane = table.insert(("Jane".to_string(), 30)).unwrap();
+        table.insert(("Bob".to_string(), 20)).unwrap();
+        let alice = table.insert(("Alice".to_string(), 30)).unwrap();
+
+        let adults =
+            || QueryBuilder::new(&table).with_condition(ConditionBuilder::greater_than(&age, 25));
+
+        let groups = adults().group_by(&age).unwrap();
+        let ids: Vec<(u8, Vec<u64>)> = groups
+            .into_iter()
+            .map(|(key, records)| (key, records.iter().map(|r| r.id).collect()))
+            .collect();
+        assert_eq!(ids, vec![(30, vec![jane, alice]), (40, vec![john])]);
+
+        let counts = adults().group_count(&name).unwrap();
+        assert_eq!(counts.len(), 3);
+        assert_eq!(counts["Jane"], 1);
+        assert!(!counts.contains_key("Bob"));
+    }
+
+    #[test]
+    fn query_builder_distinct() {
+        let db = TinyBase::new(None, true);
+        let table: Table<(String, u8)> = db.open_table("people").unwrap();
+        let name = table
+            .create_index("name", |(name, _)| name.to_owned())
+            .unwrap();
+        let age = table.create_index("age", |(_, age)| *age).unwrap();
+
+        table.insert(("John".to_string(), 40)).unwrap();
+        table.insert(("Jane".to_string(), 30)).unwrap();
+        table.insert(("Jane".to_string(), 20)).unwrap();
+
+        let names = QueryBuilder::new(&table)
+            .with_condition(ConditionBuilder::less_than(&age, 35))
+            .distinct(&name)
+            .unwrap();
+        assert_eq!(names, vec!["Jane".to_string()]);
+    }
+
+    #[test]
+    fn query_builder_select_with_stats() {
+        let db = TinyBase::new(None, true);
+        let table: Table<(String, u8)> = db.open_table("people").unwrap();
+        let name = table
+            .create_index("name", |(name, _)| name.to_owned())
+            .unwrap();
+
+        table.insert(("John".to_string(), 40)).unwrap();
+        table.insert(("John".to_string(), 20)).unwrap();
+        table.insert(("Jane".to_string(), 30)).unwrap();
+
+        let (records, stats) = QueryBuilder::new(&table)
             .with_condition(ConditionBuilder::and(
-                ConditionBuilder::by(&index, "value1".to_string()),
-                ConditionBuilder::by(&length, 6),
+                ConditionBuilder::by(&name, "John".to_string()),
+                ConditionBuilder::filter(|(_, age): &(String, u8)| *age > 30),
             ))
-            .select()
-            .expect("Select failed");
+            .select_with_stats()
+            .unwrap();
 
-        assert_eq!(result_2.len(), 1);
-        assert_eq!(result_2[0].id, value1);
+        assert_eq!(records.len(), 1);
+        assert_eq!(stats.indexes, vec![name.index_name()]);
+        assert_eq!((stats.keys_touched, stats.ids_scanned), (1, 2));
+        // Records tested by the predicate aren't read again.
+        assert_eq!(stats.records_read, 2);
+
+        let (_, stats) = QueryBuilder::new(&table)
+            .with_condition(ConditionBuilder::filter(|_| true))
+            .select_with_stats()
+            .unwrap();
+        assert!(stats.indexes.is_empty());
+        assert_eq!(stats.keys_touched, 0);
+        assert_eq!((stats.ids_scanned, stats.records_read), (3, 3));
     }
 
     #[test]
@@ -268,6 +3176,73 @@ mod tests {
         assert_eq!(selected_records.len(), 2);
     }
 
+    #[test]
+    fn query_builder_select_reads_once() {
+        use std::sync::atomic::{AtomicUsize, Ordering as AtomicOrdering};
+
+        static DECODED: AtomicUsize = AtomicUsize::new(0);
+
+        #[derive(Serialize, Debug, Clone)]
+        struct Counted(u32);
+
+        impl<'de> Deserialize<'de> for Counted {
+            fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+                DECODED.fetch_add(1, AtomicOrdering::SeqCst);
+                u32::deserialize(deserializer).map(Counted)
+            }
+        }
+
+        let db = TinyBase::new(None, true);
+        let table: Table<Counted> = db.open_table("numbers").unwrap();
+        let even = table.create_index("even", |n| n.0 % 2 == 0).unwrap();
+        let small = table.create_index("small", |n| n.0 < 50).unwrap();
+
+        for n in 0..100 {
+            table.insert(Counted(n)).unwrap();
+        }
+
+        let query = || {
+            QueryBuilder::new(&table).with_condition(ConditionBuilder::or(
+                ConditionBuilder::and(
+                    ConditionBuilder::by(&even, true),
+                    ConditionBuilder::by(&small, true),
+                ),
+                ConditionBuilder::by(&small, true),
+            ))
+        };
+
+        // Apply the pending index updates first.
+        query().select().unwrap();
+        DECODED.store(0, AtomicOrdering::SeqCst);
+
+        assert_eq!(query().select().unwrap().len(), 50);
+        assert_eq!(DECODED.load(AtomicOrdering::SeqCst), 50);
+    }
+
+    #[test]
+    fn query_builder_select_or_overlapping() {
+        let db = TinyBase::new(None, true);
+        let table: Table<u32> = db.open_table("numbers").unwrap();
+        let even = table.create_index("even", |n| n % 2 == 0).unwrap();
+        let small = table.create_index("small", |n| *n < 500).unwrap();
+
+        for n in 0..1000 {
+            table.insert(n).unwrap();
+        }
+
+        let selected = QueryBuilder::new(&table)
+            .with_condition(ConditionBuilder::or(
+                ConditionBuilder::by(&even, true),
+                ConditionBuilder::by(&small, true),
+            ))
+            .select()
+            .unwrap();
+
+        let distinct: HashSet<u64> = selected.iter().map(|record| record.id).collect();
+        assert_eq!(selected.len(), 750);
+        assert_eq!(distinct.len(), 750);
+    }
+
     #[test]
     fn query_builder_select_combined() {
         let db = TinyBase::new(None, true);
@@ -298,6 +3273,282 @@ mod tests {
         assert_eq!(selected_records.len(), 2);
     }
 
+    #[test]
+    fn query_builder_order_by() {
+        let db = TinyBase::new(None, true);
+        let table: Table<String> = db.open_table("test_table").unwrap();
+
+        let name = table
+            .create_index("name", |value| value.to_owned())
+            .unwrap();
+        let length = table.create_index("length", |value| value.len()).unwrap();
+
+        for value in ["b", "a", "ccc", "dd", "aa"] {
+            table.insert(value.to_string()).unwrap();
+        }
+
+        let selected_records = QueryBuilder::new(&table)
+            .with_condition(ConditionBuilder::or(
+                ConditionBuilder::or(
+                    ConditionBuilder::by(&length, 1),
+                    ConditionBuilder::by(&length, 2),
+                ),
+                ConditionBuilder::by(&length, 3),
+            ))
+            .order_by(&length, Direction::Desc)
+            .then_by(&name, Direction::Asc)
+            .select()
+            .expect("Select failed");
+
+        let values: Vec<_> = selected_records.into_iter().map(|r| r.data).collect();
+        assert_eq!(values, ["ccc", "aa", "dd", "a", "b"]);
+    }
+
+    #[test]
+    fn query_builder_then_by_keys_once() {
+        use std::sync::atomic::{AtomicUsize, Ordering};
+        use std::sync::Arc;
+
+        let db = TinyBase::new(None, true);
+        let table: Table<String> = db.open_table("test_table").unwrap();
+
+        let calls = Arc::new(AtomicUsize::new(0));
+        let counted = calls.clone();
+        let name = table
+            .create_index("name", move |value| {
+                counted.fetch_add(1, Ordering::Relaxed);
+                value.to_owned()
+            })
+            .unwrap();
+        let length = table.create_index("length", |value| value.len()).unwrap();
+
+        for value in ["b", "a", "ccc", "dd", "aa", "e", "f", "g"] {
+            table.insert(value.to_string()).unwrap();
+        }
+
+        let before = calls.load(Ordering::Relaxed);
+        let values: Vec<_> = QueryBuilder::new(&table)
+            .with_condition(ConditionBuilder::greater_than(&length, 0))
+            .order_by(&length, Direction::Asc)
+            .then_by(&name, Direction::Asc)
+            .select()
+            .unwrap()
+            .into_iter()
+            .map(|record| record.data)
+            .collect();
+
+        assert_eq!(values, ["a", "b", "e", "f", "g", "aa", "dd", "ccc"]);
+        // The key of every record is computed once, not on every comparison.
+        assert_eq!(calls.load(Ordering::Relaxed) - before, values.len());
+    }
+
+    #[test]
+    fn query_builder_order_by_walk() {
+        let db = TinyBase::new(None, true);
+        let table: Table<String> = db.open_table("test_table").unwrap();
+        let length = table.create_index("length", |value| value.len()).unwrap();
+
+        for value in ["bb", "a", "cccc", "ddd", "e"] {
+            table.insert(value.to_string()).unwrap();
+        }
+
+        assert!(length.ordered_ids(false, None).unwrap().is_some());
+
+        let values: Vec<_> = QueryBuilder::new(&table)
+            .with_condition(ConditionBuilder::greater_than(&length, 1))
+            .order_by(&length, Direction::Desc)
+            .select()
+            .unwrap()
+            .into_iter()
+            .map(|record| record.data)
+            .collect();
+        assert_eq!(values, ["cccc", "ddd", "bb"]);
+
+        for length in 5..50 {
+            table.insert("f".repeat(length)).unwrap();
+        }
+
+        // Few selected records are sorted instead of walking every key.
+        let query = QueryBuilder::new(&table)
+            .with_condition(ConditionBuilder::less_than(&length, 3))
+            .order_by(&length, Direction::Asc);
+        assert!((query.walk.as_ref().unwrap())(None, 3).unwrap().is_none());
+        assert!((query.walk.as_ref().unwrap())(None, 49).unwrap().is_some());
+
+        let values: Vec<_> = query
+            .select()
+            .unwrap()
+            .into_iter()
+            .map(|record| record.data)
+            .collect();
+        assert_eq!(values, ["a", "e", "bb"]);
+    }
+
+    #[test]
+    fn query_builder_count() {
+        let db = TinyBase::new(None, true);
+        let table: Table<String> = db.open_table("test_table").unwrap();
+        let name = table
+            .create_index("name", |value| value.to_owned())
+            .unwrap();
+
+        for value in ["bob", "alice", "bob"] {
+            table.insert(value.to_string()).unwrap();
+        }
+
+        let count = |value: &str| {
+            QueryBuilder::new(&table)
+                .with_condition(ConditionBuilder::by(&name, value.to_string()))
+                .count()
+                .unwrap()
+        };
+
+        assert_eq!(count("bob"), 2);
+        assert_eq!(count("carol"), 0);
+    }
+
+    #[test]
+    fn query_builder_select_ids() {
+        let db = TinyBase::new(None, true);
+        let table: Table<String> = db.open_table("test_table").unwrap();
+        let length = table.create_index("length", |value| value.len()).unwrap();
+
+        let long = table.insert("ccc".to_string()).unwrap();
+        let short = table.insert("a".to_string()).unwrap();
+        table.insert("bb".to_string()).unwrap();
+
+        let ids = QueryBuilder::new(&table)
+            .with_condition(ConditionBuilder::not(ConditionBuilder::by(&length, 2)))
+            .order_by(&length, Direction::Asc)
+            .select_ids()
+            .unwrap();
+        assert_eq!(ids, [short, long]);
+    }
+
+    #[test]
+    fn query_builder_aggregate() {
+        let db = TinyBase::new(None, true);
+        let table: Table<String> = db.open_table("test_table").unwrap();
+        let length = table.create_index("length", |value| value.len()).unwrap();
+        let name = table
+            .create_index("name", |value| value.to_owned())
+            .unwrap();
+
+        for value in ["a", "bb", "bb", "dddd", "eeeeeeee"] {
+            table.insert(value.to_string()).unwrap();
+        }
+
+        let aggregate = |aggregate| {
+            QueryBuilder::new(&table)
+                .with_condition(ConditionBuilder::less_than(&length, 5))
+                .aggregate(&length, aggregate)
+                .unwrap()
+        };
+
+        assert_eq!(aggregate(Aggregate::Min), Some(1.0));
+        assert_eq!(aggregate(Aggregate::Max), Some(4.0));
+        assert_eq!(aggregate(Aggregate::Sum), Some(9.0));
+        assert_eq!(aggregate(Aggregate::Avg), Some(2.25));
+
+        assert_eq!(
+            QueryBuilder::new(&table)
+                .with_condition(ConditionBuilder::by(&name, "c".to_string()))
+                .aggregate(&length, Aggregate::Max)
+                .unwrap(),
+            None
+        );
+
+        // Integer keys are summed exactly, they aren't rounded to `f64` one by one.
+        let numbers: Table<i64> = db.open_table("numbers").unwrap();
+        let number = numbers.create_index("number", |value| *value).unwrap();
+        for value in [(1 << 53) + 1, (1 << 53) + 1, -(1 << 54)] {
+            numbers.insert(value).unwrap();
+        }
+
+        let sum = QueryBuilder::new(&numbers)
+            .with_condition(ConditionBuilder::greater_than(&number, i64::MIN))
+            .aggregate(&number, Aggregate::Sum)
+            .unwrap();
+        assert_eq!(sum, Some(2.0));
+    }
+
+    #[test]
+    fn query_builder_first() {
+        let db = TinyBase::new(None, true);
+        let table: Table<(String, u8)> = db.open_table("people").unwrap();
+        let name = table
+            .create_index("name", |(name, _)| name.to_owned())
+            .unwrap();
+        let age = table.create_index("age", |(_, age)| *age).unwrap();
+
+        table.insert(("John".to_string(), 40)).unwrap();
+        table.insert(("John".to_string(), 20)).unwrap();
+        let jane = table.insert(("Jane".to_string(), 25)).unwrap();
+
+        let by_name = |value: &str| {
+            QueryBuilder::new(&table).with_condition(ConditionBuilder::by(&name, value.to_string()))
+        };
+
+        let first = by_name("John")
+            .order_by(&age, Direction::Asc)
+            .first()
+            .unwrap()
+            .unwrap();
+        assert_eq!(first.data.1, 20);
+        assert!(by_name("Bob").first().unwrap().is_none());
+
+        assert_eq!(by_name("Jane").expect_one().unwrap().id, jane);
+        for name in ["John", "Bob"] {
+            assert!(matches!(
+                by_name(name).expect_one(),
+                Err(TinyBaseError::QueryBuilder(_))
+            ));
+        }
+    }
+
+    #[test]
+    fn query_builder_projection() {
+        #[derive(Serialize, Deserialize, Debug, Clone)]
+        struct Person {
+            name: String,
+            age: u8,
+            biography: String,
+        }
+
+        #[derive(Deserialize, Debug, PartialEq)]
+        struct Name {
+            name: String,
+        }
+
+        let db = TinyBase::new(None, true);
+        let table: Table<Person> = db.open_table("people").unwrap();
+        let age = table.create_index("age", |person| person.age).unwrap();
+
+        let id = table
+            .insert(Person {
+                name: "John".to_string(),
+                age: 30,
+                biography: "A long text".to_string(),
+            })
+            .unwrap();
+
+        let query = || QueryBuilder::new(&table).with_condition(ConditionBuilder::by(&age, 30));
+
+        assert_eq!(
+            query().select_map(|record| record.data.name).unwrap(),
+            ["John"]
+        );
+        assert_eq!(
+            query().select_as::<Name>().unwrap(),
+            [(
+                id,
+                Name {
+                    name: "John".to_string()
+                }
+            )]
+        );
+    }
+
     #[test]
     fn query_builder_update() {
         let db = TinyBase::new(None, true);
@@ -326,6 +3577,52 @@ mod tests {
         assert_eq!(updated_records[0].data, "updated_value");
     }
 
+    #[test]
+    fn query_builder_update_returning_old() {
+        let db = TinyBase::new(None, true);
+        let table: Table<String> = db.open_table("test_table").unwrap();
+        let index = table
+            .create_index("name", |value| value.to_owned())
+            .unwrap();
+
+        let id = table.insert("value1".to_string()).unwrap();
+        table.insert("value2".to_string()).unwrap();
+
+        let updated = QueryBuilder::new(&table)
+            .with_condition(ConditionBuilder::by(&index, "value1".to_string()))
+            .update_returning_old(|value| value.to_uppercase())
+            .unwrap();
+
+        assert_eq!(updated.len(), 1);
+        let (old, new) = &updated[0];
+        assert_eq!((old.id, old.data.as_str()), (id, "value1"));
+        assert_eq!((new.id, new.data.as_str()), (id, "VALUE1"));
+    }
+
+    #[test]
+    fn query_builder_update_if() {
+        let db = TinyBase::new(None, true);
+        let table: Table<(String, u32)> = db.open_table("accounts").unwrap();
+        let name = table
+            .create_index("name", |(name, _)| name.to_owned())
+            .unwrap();
+
+        let rich = table.insert(("John".to_string(), 100)).unwrap();
+        let poor = table.insert(("John".to_string(), 10)).unwrap();
+
+        let updated = QueryBuilder::new(&table)
+            .with_condition(ConditionBuilder::by(&name, "John".to_string()))
+            .update_if(
+                |(_, balance)| *balance >= 50,
+                |(name, balance)| (name, balance - 50),
+            )
+            .unwrap();
+
+        assert_eq!(updated.len(), 1);
+        assert_eq!(table.select(rich).unwrap().unwrap().data.1, 50);
+        assert_eq!(table.select(poor).unwrap().unwrap().data.1, 10);
+    }
+
     #[test]
     fn query_builder_delete() {
         let db = TinyBase::new(None, true);
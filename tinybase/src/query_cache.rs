@@ -0,0 +1,383 @@
+use std::collections::{HashMap, HashSet};
+use std::ops::{Bound, RangeBounds};
+use std::sync::Mutex;
+
+use crate::index::AnyIndex;
+use crate::query_builder::{encode_keys, QueryBuilder, QueryCondition};
+use crate::result::DbResult;
+use crate::subscriber::{Event, Subscription};
+use crate::table::{Table, TableType};
+use crate::Record;
+
+/// Index name and range of encoded keys of a condition on a single index.
+type Leaf = (String, Bound<Vec<u8>>, Bound<Vec<u8>>);
+
+/// Changes of the table which change the result of a condition.
+#[derive(Default)]
+struct Dependencies {
+    /// Leaves of the condition, the result only changes if a record with one of these keys changes.
+    leaves: Vec<Leaf>,
+    /// Whether every change changes the result, such as for the records added to a `Not` condition.
+    table: bool,
+}
+
+struct CachedQuery<T> {
+    dependencies: Dependencies,
+    records: Vec<Record<T>>,
+}
+
+struct CacheState<T: TableType + 'static> {
+    /// Cached results by the encoded condition.
+    queries: HashMap<Vec<u8>, CachedQuery<T>>,
+    /// Indexes used by cached conditions by their name.
+    indexes: HashMap<String, Box<dyn AnyIndex<T>>>,
+}
+
+/// Memoizes the results of query conditions on a table.
+///
+/// Cached results are invalidated by the changes made to the table, only queries
+/// using an index key of a changed record are evaluated again.
+pub struct QueryCache<T: TableType + 'static> {
+    table: Table<T>,
+    subscription: Subscription<T>,
+    state: Mutex<CacheState<T>>,
+}
+
+impl<T: TableType> QueryCache<T> {
+    /// Create an empty cache for the queries of a table.
+    ///
+    /// # Arguments
+    ///
+    /// * `table` - The table to cache queries for.
+    pub fn new(table: &Table<T>) -> DbResult<Self> {
+        Ok(Self {
+            table: table.clone(),
+            subscription: table.subscribe()?,
+            state: Mutex::new(CacheState {
+                queries: HashMap::new(),
+                indexes: HashMap::new(),
+            }),
+        })
+    }
+
+    /// Select the records matching a condition, evaluating it only if there is no cached result.
+    ///
+    /// # Arguments
+    ///
+    /// * `condition` - The condition to select records with.
+    ///
+    /// # Returns
+    ///
+    /// All selected [`Record`] instances.
+    pub fn select<C: Into<QueryCondition<T>>>(&self, condition: C) -> DbResult<Vec<Record<T>>> {
+        let condition = condition.into();
+        let mut state = self.state.lock().unwrap();
+        self.invalidate(&mut state)?;
+
+        let mut key = vec![];
+        let mut dependencies = Dependencies::default();
+        if !Self::describe(&condition, &mut state.indexes, &mut key, &mut dependencies)? {
+            // Predicates can't be compared, such conditions are never cached.
+            return QueryBuilder::new(&self.table)
+                .with_condition(condition)
+                .select();
+        }
+
+        if let Some(cached) = state.queries.get(&key) {
+            return Ok(cached.records.clone());
+        }
+
+        let records = QueryBuilder::new(&self.table)
+            .with_condition(condition)
+            .select()?;
+
+        state.queries.insert(
+            key,
+            CachedQuery {
+                dependencies,
+                records: records.clone(),
+            },
+        );
+
+        Ok(records)
+    }
+
+    /// Amount of cached query results.
+    pub fn len(&self) -> usize {
+        self.state.lock().unwrap().queries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Remove all cached query results.
+    pub fn clear(&self) {
+        self.state.lock().unwrap().queries.clear();
+    }
+
+    /// IDs of the cached results of a condition and of its operands, operands of a
+    /// condition with a cached result aren't looked up.
+    ///
+    /// # Arguments
+    ///
+    /// * `condition` - The condition to look up.
+    ///
+    /// # Returns
+    ///
+    /// The IDs in ascending order by the address of the condition they're the result of.
+    pub(crate) fn cached_ids(
+        &self,
+        condition: &QueryCondition<T>,
+    ) -> DbResult<HashMap<usize, Vec<u64>>> {
+        let mut state = self.state.lock().unwrap();
+        self.invalidate(&mut state)?;
+
+        let mut cached = HashMap::new();
+        let mut conditions = vec![condition];
+        while let Some(condition) = conditions.pop() {
+            let mut key = vec![];
+            if Self::describe(
+                condition,
+                &mut state.indexes,
+                &mut key,
+                &mut Dependencies::default(),
+            )? {
+                if let Some(query) = state.queries.get(&key) {
+                    let mut ids: Vec<u64> = query.records.iter().map(|record| record.id).collect();
+                    ids.sort_unstable();
+                    cached.insert(condition as *const QueryCondition<T> as usize, ids);
+                    continue;
+                }
+            }
+
+            match condition {
+                QueryCondition::And(left, right) | QueryCondition::Or(left, right) => {
+                    conditions.extend([left.as_ref(), right.as_ref()])
+                }
+                QueryCondition::Not(condition) => conditions.push(condition),
+                _ => {}
+            }
+        }
+
+        Ok(cached)
+    }
+
+    /// Encode a condition into a cache key and collect the changes it depends on.
+    ///
+    /// # Returns
+    ///
+    /// Whether the condition can be cached, which it can't if it contains a predicate or pattern.
+    fn describe(
+        condition: &QueryCondition<T>,
+        indexes: &mut HashMap<String, Box<dyn AnyIndex<T>>>,
+        key: &mut Vec<u8>,
+        dependencies: &mut Dependencies,
+    ) -> DbResult<bool> {
+        fn push_part(key: &mut Vec<u8>, part: &[u8]) {
+            key.extend((part.len() as u64).to_be_bytes());
+            key.extend(part);
+        }
+
+        match condition {
+            QueryCondition::And(left, right) | QueryCondition::Or(left, right) => {
+                key.push(match condition {
+                    QueryCondition::And(..) => 1,
+                    _ => 2,
+                });
+                if !Self::describe(left, indexes, key, dependencies)?
+                    || !Self::describe(right, indexes, key, dependencies)?
+                {
+                    return Ok(false);
+                }
+            }
+            QueryCondition::Not(condition) => {
+                key.push(3);
+
+                // Every record added to the table matches unless the negated condition
+                // matches it, even records without a key in its indexes.
+                if !Self::describe(condition, indexes, key, &mut Dependencies::default())? {
+                    return Ok(false);
+                }
+                dependencies.table = true;
+            }
+            QueryCondition::Where(_) | QueryCondition::Matches(..) => return Ok(false),
+            QueryCondition::Prefix(index, prefix) => {
+                let name = index.idx_name();
+
+                key.push(5);
+                push_part(key, name.as_bytes());
+                push_part(key, prefix);
+
+                // Matching keys are below the smallest key after all keys with the prefix.
+                let end = match prefix.iter().rposition(|byte| *byte != 0xFF) {
+                    Some(last) => {
+                        let mut end = prefix[..=last].to_vec();
+                        end[last] += 1;
+                        Bound::Excluded(end)
+                    }
+                    None => Bound::Unbounded,
+                };
+
+                indexes.entry(name.clone()).or_insert_with(|| index.boxed());
+                dependencies
+                    .leaves
+                    .push((name, Bound::Included(prefix.clone()), end));
+            }
+            QueryCondition::In(index, values) => {
+                let name = index.idx_name();
+                let values = encode_keys(values)?;
+
+                key.push(4);
+                push_part(key, name.as_bytes());
+                key.extend((values.len() as u64).to_be_bytes());
+                for value in &values {
+                    push_part(key, value);
+                }
+
+                indexes.entry(name.clone()).or_insert_with(|| index.boxed());
+                dependencies.leaves.extend(values.into_iter().map(|value| {
+                    (
+                        name.clone(),
+                        Bound::Included(value.clone()),
+                        Bound::Included(value),
+                    )
+                }));
+            }
+            leaf => {
+                let (index, start, end) = leaf.key_range()?.unwrap();
+                let name = index.idx_name();
+
+                key.push(0);
+                push_part(key, name.as_bytes());
+                for bound in [&start, &end] {
+                    match bound {
+                        Bound::Included(value) => {
+                            key.push(0);
+                            push_part(key, value);
+                        }
+                        Bound::Excluded(value) => {
+                            key.push(1);
+                            push_part(key, value);
+                        }
+                        Bound::Unbounded => key.push(2),
+                    }
+                }
+
+                indexes.entry(name.clone()).or_insert_with(|| index.boxed());
+                dependencies.leaves.push((name, start, end));
+            }
+        }
+
+        Ok(true)
+    }
+
+    /// Remove the cached results affected by the changes received since the last call.
+    fn invalidate(&self, state: &mut CacheState<T>) -> DbResult<()> {
+        let mut changed = HashSet::new();
+        let mut any_change = false;
+        let mut cleared = false;
+
+        while let Some(event) = self.subscription.try_recv()? {
+            any_change = true;
+            let data = match &event {
+                Event::Insert(record) | Event::Remove(record) => vec![&record.data],
+                Event::Update {
+                    old_data, new_data, ..
+                } => vec![old_data, new_data],
+                Event::Cleared => {
+                    cleared = true;
+                    vec![]
+                }
+            };
+
+            for (name, index) in &state.indexes {
+                for data in &data {
+                    for key in index.gen_keys(data)? {
+                        changed.insert((name.clone(), key));
+                    }
+                }
+            }
+        }
+
+        if cleared {
+            state.queries.clear();
+        } else if any_change {
+            state.queries.retain(|_, cached| {
+                !cached.dependencies.table
+                    && !cached.dependencies.leaves.iter().any(|(name, start, end)| {
+                        changed.iter().any(|(changed_name, key)| {
+                            changed_name == name
+                                && RangeBounds::<Vec<u8>>::contains(
+                                    &(start.as_ref(), end.as_ref()),
+                                    key,
+                                )
+                        })
+                    })
+            });
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::query_builder::ConditionBuilder;
+    use crate::TinyBase;
+
+    #[test]
+    fn query_cache_invalidation() {
+        let db = TinyBase::new(None, true);
+        let table: Table<String> = db.open_table("test_table").unwrap();
+        let name = table
+            .create_index("name", |value| value.to_owned())
+            .unwrap();
+
+        table.insert("value1".to_string()).unwrap();
+        let value2 = table.insert("value2".to_string()).unwrap();
+
+        let cache = QueryCache::new(&table).unwrap();
+        let by_value1 = || ConditionBuilder::by(&name, "value1".to_string());
+        let by_value2 = || ConditionBuilder::by(&name, "value2".to_string());
+
+        assert_eq!(cache.select(by_value1()).unwrap().len(), 1);
+        assert_eq!(cache.select(by_value2()).unwrap().len(), 1);
+        assert_eq!(cache.len(), 2);
+
+        // Only the query using the key of the changed record is invalidated.
+        table.update(&[value2], |_| "value3".to_string()).unwrap();
+        assert_eq!(cache.select(by_value1()).unwrap().len(), 1);
+        assert_eq!(cache.len(), 1);
+
+        assert_eq!(cache.select(by_value2()).unwrap().len(), 0);
+        table.insert("value1".to_string()).unwrap();
+        assert_eq!(cache.select(by_value1()).unwrap().len(), 2);
+
+        table.clear().unwrap();
+        assert_eq!(cache.select(by_value1()).unwrap().len(), 0);
+    }
+
+    #[test]
+    fn query_cache_not() {
+        let db = TinyBase::new(None, true);
+        let table: Table<String> = db.open_table("test_table").unwrap();
+        let long = table
+            .create_sparse_index("long", |value| (value.len() > 3).then(|| value.to_owned()))
+            .unwrap();
+
+        table.insert("value1".to_string()).unwrap();
+        table.insert("a".to_string()).unwrap();
+
+        let cache = QueryCache::new(&table).unwrap();
+        let not_value1 =
+            || ConditionBuilder::not(ConditionBuilder::by(&long, "value1".to_string()));
+
+        assert_eq!(cache.select(not_value1()).unwrap().len(), 1);
+
+        // The new record has no key in the index, but it's selected by the negation.
+        table.insert("b".to_string()).unwrap();
+        assert_eq!(cache.select(not_value1()).unwrap().len(), 2);
+    }
+}
@@ -0,0 +1,110 @@
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::thread::{self, JoinHandle};
+
+use crate::index::{Index, IndexType};
+use crate::result::DbResult;
+use crate::table::TableType;
+
+/// Prefix of the shadow trees which indexes are rebuilt into.
+pub(crate) const REBUILDS: &str = "__tinybase_rebuild_";
+
+/// Amount of records indexed by a background rebuild.
+#[derive(Default)]
+pub(crate) struct RebuildProgress {
+    pub(crate) indexed: AtomicU64,
+    /// Amount of records of the table when the rebuild started.
+    pub(crate) total: AtomicU64,
+}
+
+/// A rebuild of an index running in the background, see [`Index::rebuild_in_background`].
+pub struct RebuildHandle {
+    progress: Arc<RebuildProgress>,
+    thread: JoinHandle<DbResult<()>>,
+}
+
+impl RebuildHandle {
+    /// Fraction of the records of the table which were indexed, between 0 and 1.
+    ///
+    /// Writes made during the rebuild are applied once all records were indexed.
+    pub fn progress(&self) -> f64 {
+        let indexed = self.progress.indexed.load(Ordering::Relaxed);
+        match self.progress.total.load(Ordering::Relaxed) {
+            0 if self.is_finished() => 1.0,
+            0 => 0.0,
+            total => (indexed as f64 / total as f64).min(1.0),
+        }
+    }
+
+    /// Whether the rebuild completed or failed.
+    pub fn is_finished(&self) -> bool {
+        self.thread.is_finished()
+    }
+
+    /// Wait until the rebuild completed.
+    ///
+    /// # Returns
+    ///
+    /// The error which stopped the rebuild, if any. The index is unchanged in that case.
+    pub fn wait(self) -> DbResult<()> {
+        match self.thread.join() {
+            Ok(result) => result,
+            Err(panic) => std::panic::resume_unwind(panic),
+        }
+    }
+}
+
+impl<T: TableType + 'static, I: IndexType + 'static> Index<T, I> {
+    /// Rebuild the index on a background thread, without blocking writes to the table.
+    ///
+    /// The index is built into a separate tree, queries keep using the current contents
+    /// of the index until they are replaced at once when the rebuild is complete.
+    ///
+    /// # Returns
+    ///
+    /// A [`RebuildHandle`] reporting the progress of the rebuild.
+    pub fn rebuild_in_background(&self) -> RebuildHandle {
+        let index = self.clone();
+        let progress = Arc::new(RebuildProgress::default());
+        let thread_progress = progress.clone();
+
+        RebuildHandle {
+            progress,
+            thread: thread::spawn(move || index.rebuild(&thread_progress)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::index::AnyIndexInner;
+    use crate::{Table, TinyBase};
+
+    #[test]
+    fn rebuild_in_background() {
+        let db = TinyBase::new(None, true);
+        let table: Table<u32> = db.open_table("numbers").unwrap();
+        let parity = table.create_index("parity", |value| value % 2).unwrap();
+
+        for value in 0..100 {
+            table.insert(value).unwrap();
+        }
+
+        // Lose the contents of the index, which only a rebuild restores.
+        assert_eq!(parity.select_ids(&0).unwrap().len(), 50);
+        parity.tree().clear().unwrap();
+
+        let rebuild = parity.rebuild_in_background();
+        for value in 100..110 {
+            table.insert(value).unwrap();
+        }
+        while !rebuild.is_finished() {
+            std::thread::yield_now();
+        }
+        assert_eq!(rebuild.progress(), 1.0);
+        rebuild.wait().unwrap();
+
+        assert_eq!(parity.select_ids(&0).unwrap().len(), 55);
+        assert_eq!(parity.select_ids(&1).unwrap().len(), 55);
+    }
+}
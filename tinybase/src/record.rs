@@ -1,5 +1,7 @@
+use serde::{Deserialize, Serialize};
+
 /// A single record in a table.
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Record<T> {
     /// Unique ID of a record.
     pub id: u64,
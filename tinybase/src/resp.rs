@@ -0,0 +1,375 @@
+use std::collections::HashMap;
+use std::fmt::Display;
+use std::io::{self, BufRead, BufReader, BufWriter, Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::str::FromStr;
+use std::sync::Arc;
+use std::thread;
+
+use crate::encoding::{decode, encode};
+use crate::index::IndexType;
+use crate::result::DbResult;
+use crate::table::TableType;
+use crate::{Index, Table};
+
+/// Keys returned by `SCAN` when no `COUNT` is given.
+const DEFAULT_SCAN_COUNT: usize = 10;
+
+/// Longest accepted bulk string, the default `proto-max-bulk-len` of Redis.
+const MAX_BULK_LEN: usize = 512 * 1024 * 1024;
+
+/// Most arguments accepted in a command, like Redis.
+const MAX_ARGS: usize = 1024 * 1024;
+
+/// Longest accepted line, like the inline command limit of Redis.
+const MAX_LINE_LEN: u64 = 64 * 1024;
+
+type IndexQuery = Box<dyn Fn(&str) -> DbResult<Option<Vec<u64>>> + Send + Sync>;
+
+/// Server speaking a subset of the Redis protocol (RESP) on top of a single table.
+///
+/// Keys are record IDs and values are records formatted with [`Display`] and parsed
+/// with [`FromStr`]. Supported commands:
+///
+/// * `PING [message]`
+/// * `GET id`, `SET id value` (only existing records) and `DEL id [id ...]`
+/// * `SCAN cursor [COUNT count]`, iterating records in ID order.
+/// * `INSERT value`, inserting a record and returning its ID.
+/// * `IDX.GET index value`, returning the IDs of all records matching an index.
+pub struct RespServer<T: TableType + 'static> {
+    table: Table<T>,
+    indexes: HashMap<String, IndexQuery>,
+}
+
+impl<T: TableType + FromStr + Display + 'static> RespServer<T> {
+    /// # Arguments
+    ///
+    /// * `table` - The table which is served.
+    pub fn new(table: &Table<T>) -> Self {
+        Self {
+            table: table.clone(),
+            indexes: HashMap::new(),
+        }
+    }
+
+    /// Make an index queryable through `IDX.GET`.
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - The name used in the command.
+    /// * `index` - The index to query, its keys are parsed with [`FromStr`].
+    pub fn index<I: IndexType + FromStr + 'static>(
+        mut self,
+        name: &str,
+        index: &Index<T, I>,
+    ) -> Self {
+        let index = index.clone();
+        self.indexes.insert(
+            name.to_uppercase(),
+            Box::new(move |query| match query.parse() {
+                Ok(query) => Ok(Some(
+                    index
+                        .select(&query)?
+                        .into_iter()
+                        .map(|record| record.id)
+                        .collect(),
+                )),
+                Err(_) => Ok(None),
+            }),
+        );
+        self
+    }
+
+    /// Accept connections until the listener fails, serving each on its own thread.
+    ///
+    /// # Arguments
+    ///
+    /// * `listener` - A bound listener, like `TcpListener::bind("127.0.0.1:6379")`.
+    pub fn serve(self, listener: TcpListener) -> DbResult<()> {
+        let server = Arc::new(self);
+
+        for stream in listener.incoming() {
+            let stream = stream?;
+            let server = server.clone();
+            thread::spawn(move || server.handle(stream));
+        }
+
+        Ok(())
+    }
+
+    /// Serve a single connection until it is closed by the client.
+    fn handle(&self, stream: TcpStream) -> io::Result<()> {
+        let mut reader = BufReader::new(stream.try_clone()?);
+        let mut writer = BufWriter::new(stream);
+
+        while let Some(command) = read_command(&mut reader)? {
+            let reply = match self.execute(&command) {
+                Ok(reply) => reply,
+                Err(err) => Reply::Error(format!("ERR {}", err)),
+            };
+
+            reply.write(&mut writer)?;
+            writer.flush()?;
+        }
+
+        Ok(())
+    }
+
+    fn execute(&self, command: &[String]) -> DbResult<Reply> {
+        let Some((name, args)) = command.split_first() else {
+            return Ok(Reply::error("empty command"));
+        };
+
+        Ok(match (name.to_uppercase().as_str(), args) {
+            ("PING", []) => Reply::Simple("PONG"),
+            ("PING", [message]) => Reply::bulk(message),
+            ("GET", [id]) => match id.parse() {
+                Ok(id) => match self.table.select(id)? {
+                    Some(record) => Reply::bulk(record.data),
+                    None => Reply::Bulk(None),
+                },
+                Err(_) => Reply::Bulk(None),
+            },
+            ("SET", [id, value]) => match (id.parse::<u64>(), value.parse::<T>()) {
+                (Ok(id), Ok(value)) => match self.table.update(&[id], |_| value.clone())? {
+                    updated if updated.is_empty() => Reply::error("no such key"),
+                    _ => Reply::Simple("OK"),
+                },
+                (Err(_), _) => Reply::error("no such key"),
+                (_, Err(_)) => Reply::error("invalid value"),
+            },
+            ("DEL", ids) if !ids.is_empty() => {
+                let mut deleted = 0;
+                for id in ids.iter().filter_map(|id| id.parse().ok()) {
+                    if self.table.delete(id)?.is_some() {
+                        deleted += 1;
+                    }
+                }
+                Reply::Integer(deleted)
+            }
+            ("SCAN", [cursor, options @ ..]) => {
+                let Ok(cursor) = cursor.parse() else {
+                    return Ok(Reply::error("invalid cursor"));
+                };
+                let count = match options {
+                    [] => DEFAULT_SCAN_COUNT,
+                    [option, count] if option.eq_ignore_ascii_case("COUNT") => {
+                        match count.parse() {
+                            Ok(count) if count > 0 => count,
+                            _ => return Ok(Reply::error("value is out of range")),
+                        }
+                    }
+                    _ => return Ok(Reply::error("syntax error")),
+                };
+
+                let (next, ids) = self.scan(cursor, count)?;
+                Reply::Array(vec![
+                    Reply::bulk(next),
+                    Reply::Array(ids.into_iter().map(Reply::bulk).collect()),
+                ])
+            }
+            ("INSERT", [value]) => match value.parse() {
+                Ok(value) => Reply::bulk(self.table.insert(value)?),
+                Err(_) => Reply::error("invalid value"),
+            },
+            ("IDX.GET", [index, query]) => match self.indexes.get(&index.to_uppercase()) {
+                Some(select) => match select(query)? {
+                    Some(ids) => Reply::Array(ids.into_iter().map(Reply::bulk).collect()),
+                    None => Reply::error("invalid index key"),
+                },
+                None => Reply::error("no such index"),
+            },
+            _ => Reply::Error(format!("ERR unknown command or arguments '{}'", name)),
+        })
+    }
+
+    /// IDs of up to `count` records starting at `cursor`, with the cursor to continue
+    /// from, which is zero once all records were returned.
+    fn scan(&self, cursor: u64, count: usize) -> DbResult<(u64, Vec<u64>)> {
+        let root = self.table.root.read().unwrap();
+        let mut ids = Vec::with_capacity(count);
+
+        for key in root.range(encode(&cursor)?..).keys() {
+            let id = decode(&key?)?;
+            if ids.len() == count {
+                return Ok((id, ids));
+            }
+            ids.push(id);
+        }
+
+        Ok((0, ids))
+    }
+}
+
+/// Read a command, either as an array of bulk strings or an inline command.
+///
+/// # Returns
+///
+/// The command arguments, or [`None`] once the connection was closed.
+fn read_command(reader: &mut impl BufRead) -> io::Result<Option<Vec<String>>> {
+    let Some(line) = read_line(reader)? else {
+        return Ok(None);
+    };
+
+    let Some(len) = line.strip_prefix('*') else {
+        return Ok(Some(line.split_whitespace().map(str::to_owned).collect()));
+    };
+
+    // Lengths are checked before anything is allocated for them.
+    let mut command = Vec::new();
+    for _ in 0..parse_len(len, MAX_ARGS)? {
+        let header = read_line(reader)?.ok_or_else(|| protocol_error("unexpected end"))?;
+        let len = header
+            .strip_prefix('$')
+            .ok_or_else(|| protocol_error("expected bulk string"))?;
+
+        // Bulk strings are followed by CRLF.
+        let mut bulk = vec![0; parse_len(len, MAX_BULK_LEN)? + 2];
+        reader.read_exact(&mut bulk)?;
+        bulk.truncate(bulk.len() - 2);
+
+        command.push(String::from_utf8(bulk).map_err(|_| protocol_error("invalid utf-8"))?);
+    }
+
+    Ok(Some(command))
+}
+
+/// Read a line without its CRLF terminator.
+fn read_line(reader: &mut impl BufRead) -> io::Result<Option<String>> {
+    let mut line = String::new();
+    if Read::take(&mut *reader, MAX_LINE_LEN).read_line(&mut line)? == 0 {
+        return Ok(None);
+    }
+
+    if !line.ends_with('\n') && line.len() as u64 == MAX_LINE_LEN {
+        return Err(protocol_error("line too long"));
+    }
+
+    Ok(Some(line.trim_end_matches(['\r', '\n']).to_owned()))
+}
+
+/// Parse a length of the protocol which is at most `max`.
+fn parse_len(len: &str, max: usize) -> io::Result<usize> {
+    match len.parse() {
+        Ok(len) if len <= max => Ok(len),
+        Ok(_) => Err(protocol_error("length too large")),
+        Err(_) => Err(protocol_error("invalid length")),
+    }
+}
+
+fn protocol_error(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+enum Reply {
+    Simple(&'static str),
+    Error(String),
+    Integer(i64),
+    Bulk(Option<String>),
+    Array(Vec<Reply>),
+}
+
+impl Reply {
+    fn bulk(value: impl Display) -> Self {
+        Self::Bulk(Some(value.to_string()))
+    }
+
+    fn error(message: &str) -> Self {
+        Self::Error(format!("ERR {}", message))
+    }
+
+    fn write(&self, writer: &mut impl Write) -> io::Result<()> {
+        match self {
+            Reply::Simple(value) => write!(writer, "+{}\r\n", value),
+            Reply::Error(message) => write!(writer, "-{}\r\n", message),
+            Reply::Integer(value) => write!(writer, ":{}\r\n", value),
+            Reply::Bulk(Some(value)) => write!(writer, "${}\r\n{}\r\n", value.len(), value),
+            Reply::Bulk(None) => write!(writer, "$-1\r\n"),
+            Reply::Array(replies) => {
+                write!(writer, "*{}\r\n", replies.len())?;
+                replies.iter().try_for_each(|reply| reply.write(writer))
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::TinyBase;
+
+    /// Send an inline command and read a reply of `lines` lines.
+    fn command(stream: &mut BufReader<TcpStream>, command: &str, lines: usize) -> String {
+        write!(stream.get_mut(), "{}\r\n", command).unwrap();
+        (0..lines)
+            .map(|_| read_line(stream).unwrap().unwrap() + "\n")
+            .collect()
+    }
+
+    #[test]
+    fn resp_commands() {
+        let db = TinyBase::new(None, true);
+        let table: Table<String> = db.open_table("test_table").unwrap();
+        let index = table
+            .create_index("name", |value| value.to_owned())
+            .unwrap();
+
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        let server = RespServer::new(&table).index("name", &index);
+        thread::spawn(move || server.serve(listener));
+
+        let mut stream = BufReader::new(TcpStream::connect(addr).unwrap());
+        assert_eq!(command(&mut stream, "PING", 1), "+PONG\n");
+
+        let id = table.insert("value1".to_string()).unwrap();
+        assert_eq!(
+            command(&mut stream, &format!("GET {}", id), 2),
+            "$6\nvalue1\n"
+        );
+        assert_eq!(
+            command(&mut stream, &format!("SET {} value2", id), 1),
+            "+OK\n"
+        );
+        assert_eq!(
+            command(&mut stream, "IDX.GET name value2", 3),
+            format!("*1\n${}\n{}\n", id.to_string().len(), id)
+        );
+        assert_eq!(
+            command(&mut stream, "SCAN 0", 6),
+            format!("*2\n$1\n0\n*1\n${}\n{}\n", id.to_string().len(), id)
+        );
+
+        // IDs are replied as bulk strings like by `SCAN` and `IDX.GET`, they don't fit an integer.
+        let reply = command(&mut stream, "INSERT value3", 2);
+        let inserted: u64 = reply.lines().nth(1).unwrap().parse().unwrap();
+        assert_eq!(
+            reply,
+            format!("${}\n{}\n", inserted.to_string().len(), inserted)
+        );
+        assert_eq!(table.select(inserted).unwrap().unwrap().data, "value3");
+
+        // Commands can also be sent as RESP arrays.
+        write!(
+            stream.get_mut(),
+            "*2\r\n$3\r\nDEL\r\n${}\r\n{}\r\n",
+            id.to_string().len(),
+            id
+        )
+        .unwrap();
+        assert_eq!(read_line(&mut stream).unwrap().unwrap(), ":1");
+        assert!(table.select(id).unwrap().is_none());
+    }
+
+    #[test]
+    fn resp_length_limits() {
+        let mut oversized = io::Cursor::new("*1\r\n$536870913\r\n");
+        assert!(read_command(&mut oversized).is_err());
+
+        let mut too_many = io::Cursor::new("*1048577\r\n");
+        assert!(read_command(&mut too_many).is_err());
+
+        let mut long_line = io::Cursor::new("A".repeat(MAX_LINE_LEN as usize + 1));
+        assert!(read_command(&mut long_line).is_err());
+    }
+}
@@ -1,19 +1,132 @@
 use thiserror::Error;
 
+use crate::index::IndexType;
+use crate::key_encoding::decode_key;
+
 #[derive(Error, Debug)]
 pub enum TinyBaseError {
     #[error("sled error")]
     Sled(#[from] sled::Error),
     #[error("serializer error")]
     Serializer(#[from] bincode::Error),
-    #[error("record failed to match unique constraint")]
-    Exists { constraint: String, id: u64 },
-    #[error("a condition check was not met")]
-    Condition,
+    #[error("io error")]
+    Io(#[from] std::io::Error),
+    #[cfg(feature = "json")]
+    #[error("json error")]
+    Json(#[from] serde_json::Error),
+    /// A record has the same key as another record in a unique index.
+    #[error("record {id} violates unique constraint {constraint}, its key is used by record {conflicting}")]
+    Exists {
+        /// Name of the unique index.
+        constraint: String,
+        /// The encoded index key, see [`TinyBaseError::decode_key`].
+        key: Vec<u8>,
+        /// ID of the record which was written.
+        id: u64,
+        /// ID of the record which already has the key.
+        conflicting: u64,
+    },
+    /// A record doesn't satisfy a check constraint.
+    #[error("record {id} doesn't satisfy check constraint{}", constraint.as_ref().map(|name| format!(" {}", name)).unwrap_or_default())]
+    Condition {
+        /// Name of the constraint, if it was created with [`crate::Constraint::named_check`].
+        constraint: Option<String>,
+        /// ID of the record which was written.
+        id: u64,
+    },
+    #[cfg(feature = "regex")]
+    #[error("regex error")]
+    Regex(#[from] regex::Error),
     #[error("query builder error")]
     QueryBuilder(String),
-    #[error("batch operation violates constraints")]
-    BatchOperationConstraints,
+    /// A query was aborted by its deadline or [`crate::cancel::CancellationToken`].
+    #[error("query was cancelled")]
+    Cancelled,
+    /// Multiple records of a batch have the same key in a unique index.
+    #[error("batch operation violates unique constraint {constraint}")]
+    BatchOperationConstraints {
+        /// Name of the unique index.
+        constraint: String,
+        /// The encoded index key, see [`TinyBaseError::decode_key`].
+        key: Vec<u8>,
+    },
+    /// Multiple records have the key of a value written by [`crate::Table::upsert`].
+    #[error("multiple records have the key of the value in index {index}")]
+    AmbiguousKey {
+        /// Name of the index identifying records.
+        index: String,
+        /// The encoded index key, see [`TinyBaseError::decode_key`].
+        key: Vec<u8>,
+    },
+    #[cfg(feature = "icu")]
+    #[error("collation error: {0}")]
+    Collation(String),
+    #[cfg(feature = "script")]
+    #[error("script error: {0}")]
+    Script(String),
+    #[error("record {0} is its own ancestor")]
+    Cycle(u64),
+    #[error("record ID {0} is already used")]
+    DuplicateId(u64),
+    /// A record was changed by another writer since it was read.
+    #[error("record {0} was changed concurrently")]
+    Conflict(u64),
+    #[error("database is locked by another writer")]
+    Locked,
+    /// An index with the name already exists.
+    #[error("index {0} already exists")]
+    IndexExists(String),
+    #[error("database was opened read-only")]
+    ReadOnly,
+    #[error("database was closed")]
+    Closed,
+    /// An index doesn't match the records of its table, see [`crate::Index::verify`].
+    #[error("index {0} is inconsistent")]
+    Inconsistent(String),
+    /// A table couldn't be closed since the closing thread holds a guard of its lock.
+    #[error("table {0} is locked by the closing thread")]
+    TableInUse(String),
+    /// A sequence was incremented past [`u64::MAX`].
+    #[error("sequence {0} overflowed")]
+    Overflow(String),
+    /// An alias passed to [`crate::TinyBase::attach`] contains a `.`.
+    #[error("invalid alias {0}, aliases must not contain `.`")]
+    InvalidAlias(String),
+    #[error("migration error: {0}")]
+    Migration(String),
+    #[error("procedure error: {0}")]
+    Procedure(String),
+    #[error("transaction error: {0}")]
+    Transaction(String),
+    #[error("lazy field value is not stored")]
+    Unloaded,
+    #[error("errors occurred while closing the database")]
+    Close(Vec<TinyBaseError>),
+}
+
+impl From<sled::transaction::TransactionError<TinyBaseError>> for TinyBaseError {
+    fn from(err: sled::transaction::TransactionError<TinyBaseError>) -> Self {
+        match err {
+            sled::transaction::TransactionError::Abort(err) => err,
+            sled::transaction::TransactionError::Storage(err) => Self::Sled(err),
+        }
+    }
+}
+
+impl TinyBaseError {
+    /// Decode the index key of a unique constraint violation.
+    ///
+    /// # Returns
+    ///
+    /// The key, or [`None`] if this isn't a unique constraint violation or the key has another type.
+    pub fn decode_key<I: IndexType>(&self) -> Option<I> {
+        match self {
+            Self::Exists { key, .. }
+            | Self::BatchOperationConstraints { key, .. }
+            | Self::AmbiguousKey { key, .. } => decode_key(key).ok(),
+            _ => None,
+        }
+    }
 }
 
 pub type DbResult<T> = Result<T, TinyBaseError>;
@@ -0,0 +1,332 @@
+use evalexpr::{ContextWithMutableVariables, HashMapContext, Node};
+use serde::{Deserialize, Serialize};
+use sled::Tree;
+
+use crate::encoding::{decode, encode};
+use crate::index::Index;
+use crate::key_encoding::encode_key;
+use crate::layout::RecordLayout;
+use crate::posting::decode_posting;
+use crate::result::{DbResult, TinyBaseError};
+use crate::table::{Table, TableType};
+
+/// Tree storing the source of every script index and the layout of its records by
+/// the name of the index tree.
+pub(crate) const SCRIPTS: &str = "__tinybase_scripts";
+
+/// Key computed by a script index.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum ScriptValue {
+    /// The script returned nothing for the record.
+    Empty,
+    Bool(bool),
+    Int(i64),
+    Float(f64),
+    String(String),
+    Tuple(Vec<ScriptValue>),
+}
+
+impl From<evalexpr::Value> for ScriptValue {
+    fn from(value: evalexpr::Value) -> Self {
+        match value {
+            evalexpr::Value::Empty => ScriptValue::Empty,
+            evalexpr::Value::Boolean(value) => ScriptValue::Bool(value),
+            evalexpr::Value::Int(value) => ScriptValue::Int(value),
+            evalexpr::Value::Float(value) => ScriptValue::Float(value),
+            evalexpr::Value::String(value) => ScriptValue::String(value),
+            evalexpr::Value::Tuple(values) => {
+                ScriptValue::Tuple(values.into_iter().map(Into::into).collect())
+            }
+        }
+    }
+}
+
+impl<T: TableType + 'static> Table<T> {
+    /// Create an index whose key function is an [`evalexpr`] expression.
+    ///
+    /// The fields of the record are variables of the expression, fields of nested
+    /// structs are joined with dots, like `str::to_lowercase(address.city)`. Scripts are
+    /// evaluated on the stored bytes of a record, read with the layout of the record
+    /// type. The source and layout are stored in the database, so
+    /// [`crate::TinyBase::script_indexes`] can open the index without the record type.
+    ///
+    /// A write fails with [`TinyBaseError::Script`] if the script fails for a record.
+    /// The index is rebuilt when the script changes.
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - The name of the index.
+    /// * `script` - The expression computing the key of a record.
+    ///
+    /// # Returns
+    ///
+    /// An [`Index`] instance for the created index, or [`TinyBaseError::Script`] if the
+    /// expression can't be parsed, the record type can't be traced or the script fails
+    /// for a stored record.
+    pub fn create_script_index(&self, name: &str, script: &str) -> DbResult<Index<T, ScriptValue>> {
+        let node = compile(script)?;
+        let layout = RecordLayout::trace::<T>()?;
+
+        let key_layout = layout.clone();
+        let index = self.register_index(
+            name,
+            move |record| Ok(vec![evaluate(&node, &key_layout, &encode(record)?)?]),
+            true,
+            false,
+            script,
+        )?;
+
+        self.engine
+            .open_tree(SCRIPTS)?
+            .insert(index.index_name(), encode(&(script, layout))?)?;
+
+        Ok(index)
+    }
+}
+
+/// A script index opened without the record type of its table, see
+/// [`crate::TinyBase::script_indexes`].
+///
+/// The index is maintained by the writer which created it, the keys of stored
+/// records can be computed from their bytes to recreate it.
+pub struct ScriptIndex {
+    name: String,
+    script: String,
+    node: Node,
+    layout: RecordLayout,
+    records: Tree,
+    keys: Tree,
+}
+
+impl ScriptIndex {
+    /// Open all script indexes created on a table.
+    pub(crate) fn open_all(engine: &sled::Db, table: &str) -> DbResult<Vec<Self>> {
+        let prefix = format!("{}_idx_", table);
+        let mut indexes = vec![];
+
+        for entry in engine.open_tree(SCRIPTS)?.scan_prefix(&prefix) {
+            let (tree_name, stored) = entry?;
+            let (script, layout) = decode::<(String, RecordLayout)>(&stored)?;
+
+            indexes.push(Self {
+                name: String::from_utf8_lossy(&tree_name[prefix.len()..]).into_owned(),
+                node: compile(&script)?,
+                script,
+                layout,
+                records: engine.open_tree(table)?,
+                keys: engine.open_tree(&tree_name)?,
+            });
+        }
+
+        Ok(indexes)
+    }
+
+    /// The name of the index.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// The expression computing the key of a record.
+    pub fn script(&self) -> &str {
+        &self.script
+    }
+
+    /// Compute the key of a stored record.
+    ///
+    /// # Arguments
+    ///
+    /// * `id` - The ID of the record.
+    ///
+    /// # Returns
+    ///
+    /// The key, [`None`] if there is no record with the ID, or [`TinyBaseError::Script`]
+    /// if the script fails for the record.
+    pub fn key_of(&self, id: u64) -> DbResult<Option<ScriptValue>> {
+        match self.records.get(encode(&id)?)? {
+            Some(bytes) => evaluate(&self.node, &self.layout, &bytes).map(Some),
+            None => Ok(None),
+        }
+    }
+
+    /// Compute the keys of all stored records, in ID order.
+    ///
+    /// # Returns
+    ///
+    /// An iterator over the IDs and keys of the records.
+    pub fn keys(&self) -> impl Iterator<Item = DbResult<(u64, ScriptValue)>> + '_ {
+        self.records.iter().map(|entry| {
+            let (id, bytes) = entry?;
+            Ok((decode(&id)?, evaluate(&self.node, &self.layout, &bytes)?))
+        })
+    }
+
+    /// Select the IDs of the records with a key, as stored in the index.
+    ///
+    /// # Arguments
+    ///
+    /// * `key` - The key to look up.
+    ///
+    /// # Returns
+    ///
+    /// The IDs of all records with the key.
+    pub fn select_ids(&self, key: &ScriptValue) -> DbResult<Vec<u64>> {
+        match self.keys.get(encode_key(key)?)? {
+            Some(bytes) => decode_posting(&bytes),
+            None => Ok(vec![]),
+        }
+    }
+}
+
+fn compile(script: &str) -> DbResult<Node> {
+    evalexpr::build_operator_tree(script).map_err(|err| TinyBaseError::Script(err.to_string()))
+}
+
+/// Evaluate a script with the fields of a stored record as variables.
+fn evaluate(node: &Node, layout: &RecordLayout, bytes: &[u8]) -> DbResult<ScriptValue> {
+    let mut context = HashMapContext::new();
+    bind(&mut context, String::new(), layout.to_json(bytes)?);
+
+    node.eval_with_context(&context)
+        .map(Into::into)
+        .map_err(|err| TinyBaseError::Script(err.to_string()))
+}
+
+/// Add a JSON value to the context, flattening objects into dotted names.
+fn bind(context: &mut HashMapContext, name: String, value: serde_json::Value) {
+    if let serde_json::Value::Object(fields) = value {
+        for (field, value) in fields {
+            let name = match name.is_empty() {
+                true => field,
+                false => format!("{}.{}", name, field),
+            };
+            bind(context, name, value);
+        }
+    } else if !name.is_empty() {
+        let _ = context.set_value(name, convert(value));
+    }
+}
+
+fn convert(value: serde_json::Value) -> evalexpr::Value {
+    match value {
+        serde_json::Value::Null => evalexpr::Value::Empty,
+        serde_json::Value::Bool(value) => evalexpr::Value::Boolean(value),
+        serde_json::Value::Number(number) => match number.as_i64() {
+            Some(value) => evalexpr::Value::Int(value),
+            None => evalexpr::Value::Float(number.as_f64().unwrap_or(f64::NAN)),
+        },
+        serde_json::Value::String(value) => evalexpr::Value::String(value),
+        serde_json::Value::Array(values) => {
+            evalexpr::Value::Tuple(values.into_iter().map(convert).collect())
+        }
+        // Objects are flattened by `bind`, nested in arrays they can't be represented.
+        serde_json::Value::Object(_) => evalexpr::Value::Empty,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde::{Deserialize, Serialize};
+
+    use super::*;
+    use crate::TinyBase;
+
+    #[derive(Serialize, Deserialize, Debug, Clone)]
+    struct Address {
+        city: String,
+    }
+
+    #[derive(Serialize, Deserialize, Debug, Clone)]
+    enum Status {
+        Active,
+        Away(String),
+    }
+
+    #[derive(Serialize, Deserialize, Debug, Clone)]
+    struct Person {
+        name: String,
+        address: Address,
+        status: Status,
+    }
+
+    fn person(name: &str, city: &str, status: Status) -> Person {
+        Person {
+            name: name.to_string(),
+            address: Address {
+                city: city.to_string(),
+            },
+            status,
+        }
+    }
+
+    #[test]
+    fn script_index() {
+        let db = TinyBase::new(None, true);
+        let table: Table<Person> = db.open_table("people").unwrap();
+
+        let city = table
+            .create_script_index("city", "str::to_lowercase(address.city)")
+            .unwrap();
+        let john = table
+            .insert(person("John", "Berlin", Status::Active))
+            .unwrap();
+        let jane = table
+            .insert(person(
+                "Jane",
+                "Paris",
+                Status::Away("vacation".to_string()),
+            ))
+            .unwrap();
+
+        assert_eq!(
+            city.select_ids(&ScriptValue::String("berlin".to_string()))
+                .unwrap(),
+            [john]
+        );
+
+        // The index is opened without the record type.
+        drop(city);
+        let indexes = db.script_indexes("people").unwrap();
+        assert_eq!(indexes.len(), 1);
+        assert_eq!(indexes[0].name(), "city");
+        assert_eq!(
+            indexes[0]
+                .select_ids(&ScriptValue::String("berlin".to_string()))
+                .unwrap(),
+            [john]
+        );
+        assert_eq!(
+            indexes[0].key_of(jane).unwrap(),
+            Some(ScriptValue::String("paris".to_string()))
+        );
+        assert_eq!(indexes[0].keys().count(), 2);
+
+        assert!(matches!(
+            table.create_script_index("invalid", "(("),
+            Err(TinyBaseError::Script(_))
+        ));
+    }
+
+    #[test]
+    fn script_index_errors() {
+        let db = TinyBase::new(None, true);
+        let table: Table<Person> = db.open_table("people").unwrap();
+        table
+            .insert(person("John", "Berlin", Status::Active))
+            .unwrap();
+
+        // Scripts failing for a record fail instead of indexing nothing.
+        assert!(matches!(
+            table.create_script_index("nickname", "nickname"),
+            Err(TinyBaseError::Script(_))
+        ));
+
+        let _ratio = table
+            .create_script_index("ratio", "10 / len(name)")
+            .unwrap();
+        assert!(matches!(
+            table.insert(person("", "Berlin", Status::Active)),
+            Err(TinyBaseError::Script(_))
+        ));
+        assert_eq!(table.len().unwrap(), 1);
+    }
+}
@@ -0,0 +1,135 @@
+use sled::Tree;
+
+use crate::encoding::{decode, encode};
+use crate::result::{DbResult, TinyBaseError};
+
+/// Tree storing the current value of every sequence.
+pub(crate) const SEQUENCES: &str = "__tinybase_sequences";
+
+/// A durable counter which is incremented atomically, created with [`crate::TinyBase::sequence`].
+///
+/// Sequences are independent of record IDs, so they can be used for human-visible
+/// numbering without gaps caused by other tables.
+#[derive(Clone)]
+pub struct Sequence {
+    name: String,
+    tree: Tree,
+    read_only: bool,
+}
+
+impl Sequence {
+    pub(crate) fn new(name: &str, tree: Tree, read_only: bool) -> Self {
+        Self {
+            name: name.to_owned(),
+            tree,
+            read_only,
+        }
+    }
+
+    /// Increment the sequence by one.
+    ///
+    /// # Returns
+    ///
+    /// The new value, the first value of a sequence is `1`.
+    pub fn next(&self) -> DbResult<u64> {
+        self.increment_by(1)
+    }
+
+    /// Increment the sequence by `amount`.
+    ///
+    /// # Returns
+    ///
+    /// The new value or [`TinyBaseError::Overflow`] if it would exceed [`u64::MAX`],
+    /// in which case the sequence is left unchanged.
+    pub fn increment_by(&self, amount: u64) -> DbResult<u64> {
+        self.update(|value| {
+            value
+                .checked_add(amount)
+                .ok_or_else(|| TinyBaseError::Overflow(self.name.clone()))
+        })
+    }
+
+    /// Current value of the sequence, `0` if it was never incremented.
+    pub fn current(&self) -> DbResult<u64> {
+        match self.tree.get(&self.name)? {
+            Some(bytes) => Ok(decode(&bytes)?),
+            None => Ok(0),
+        }
+    }
+
+    /// Set the sequence to a value.
+    ///
+    /// # Arguments
+    ///
+    /// * `value` - The new value, the next call to [`Sequence::next`] returns `value + 1`.
+    pub fn set(&self, value: u64) -> DbResult<()> {
+        self.update(|_| Ok(value))?;
+        Ok(())
+    }
+
+    pub fn sequence_name(&self) -> &str {
+        &self.name
+    }
+
+    /// Atomically replace the value and flush it, so a returned value is never
+    /// handed out again after a crash.
+    fn update(&self, updater: impl Fn(u64) -> DbResult<u64>) -> DbResult<u64> {
+        if self.read_only {
+            return Err(TinyBaseError::ReadOnly);
+        }
+
+        let mut error = None;
+        let updated = self.tree.update_and_fetch(&self.name, |old| {
+            let new = old
+                .map(decode)
+                .transpose()
+                .and_then(|old| updater(old.unwrap_or(0)));
+
+            match new {
+                // Encoding a u64 can't fail.
+                Ok(new) => Some(encode(&new).unwrap()),
+                Err(err) => {
+                    error = Some(err);
+                    old.map(<[u8]>::to_vec)
+                }
+            }
+        })?;
+
+        if let Some(err) = error {
+            return Err(err);
+        }
+
+        self.tree.flush()?;
+        decode(&updated.unwrap())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::TinyBase;
+
+    #[test]
+    fn sequence_next() {
+        let db = TinyBase::new(None, true);
+        let sequence = db.sequence("invoice_no").unwrap();
+
+        assert_eq!(sequence.current().unwrap(), 0);
+        assert_eq!(sequence.next().unwrap(), 1);
+        assert_eq!(sequence.next().unwrap(), 2);
+
+        // Sequences with the same name share their value.
+        assert_eq!(db.sequence("invoice_no").unwrap().next().unwrap(), 3);
+        assert_eq!(db.sequence("other").unwrap().next().unwrap(), 1);
+
+        sequence.set(100).unwrap();
+        assert_eq!(sequence.increment_by(10).unwrap(), 110);
+
+        sequence.set(u64::MAX - 1).unwrap();
+        assert!(matches!(
+            sequence.increment_by(2),
+            Err(crate::result::TinyBaseError::Overflow(_))
+        ));
+        assert_eq!(sequence.current().unwrap(), u64::MAX - 1);
+        assert_eq!(sequence.next().unwrap(), u64::MAX);
+    }
+}
@@ -0,0 +1,119 @@
+use serde::{Deserialize, Serialize};
+
+/// Tree storing the sketches of all indexes by the name of the index tree.
+pub(crate) const SKETCHES: &str = "__tinybase_sketches";
+
+/// Bits of the hash selecting the register.
+const PRECISION: u32 = 12;
+const REGISTERS: usize = 1 << PRECISION;
+
+/// HyperLogLog sketch estimating the amount of distinct values added to it.
+///
+/// Values can't be removed, the estimate has a standard error of about 1.6%.
+#[derive(Clone, Serialize, Deserialize)]
+pub(crate) struct HyperLogLog {
+    registers: Vec<u8>,
+}
+
+impl Default for HyperLogLog {
+    fn default() -> Self {
+        Self {
+            registers: vec![0; REGISTERS],
+        }
+    }
+}
+
+impl HyperLogLog {
+    /// Add a value by its encoded bytes.
+    pub fn add(&mut self, bytes: &[u8]) {
+        let hash = hash(bytes);
+        let register = (hash >> (64 - PRECISION)) as usize;
+        // Position of the first set bit in the remaining bits, the sentinel bounds it.
+        let rank = ((hash << PRECISION) | (1 << (PRECISION - 1))).leading_zeros() as u8 + 1;
+
+        self.registers[register] = self.registers[register].max(rank);
+    }
+
+    pub fn estimate(&self) -> u64 {
+        let m = REGISTERS as f64;
+        let sum: f64 = self
+            .registers
+            .iter()
+            .map(|&rank| 2f64.powi(-(rank as i32)))
+            .sum();
+        let estimate = 0.7213 / (1.0 + 1.079 / m) * m * m / sum;
+
+        // Linear counting is more accurate for small cardinalities.
+        let zeros = self.registers.iter().filter(|&&rank| rank == 0).count();
+        if estimate <= 2.5 * m && zeros > 0 {
+            return (m * (m / zeros as f64).ln()).round() as u64;
+        }
+
+        estimate.round() as u64
+    }
+}
+
+/// Bits of the bloom filter per expected value, for a false positive rate of about 1%.
+const BLOOM_BITS_PER_VALUE: usize = 10;
+/// Bits set per value, optimal for the bits per value.
+const BLOOM_HASHES: u64 = 7;
+
+/// Bloom filter answering whether a value was possibly added, without false negatives.
+///
+/// Values can't be removed, the false positive rate is about 1% until more than the
+/// expected amount of values were added.
+pub(crate) struct BloomFilter {
+    bits: Vec<u64>,
+    expected: usize,
+}
+
+impl BloomFilter {
+    pub fn new(expected: usize) -> Self {
+        Self {
+            bits: vec![0; expected.max(1) * BLOOM_BITS_PER_VALUE / 64 + 1],
+            expected,
+        }
+    }
+
+    /// The amount of values the filter was sized for.
+    pub fn expected(&self) -> usize {
+        self.expected
+    }
+
+    /// Add a value by its encoded bytes.
+    pub fn add(&mut self, bytes: &[u8]) {
+        for bit in self.positions(bytes) {
+            self.bits[bit / 64] |= 1 << (bit % 64);
+        }
+    }
+
+    /// Whether a value was possibly added, `false` if it certainly wasn't.
+    pub fn may_contain(&self, bytes: &[u8]) -> bool {
+        self.positions(bytes)
+            .all(|bit| self.bits[bit / 64] & (1 << (bit % 64)) != 0)
+    }
+
+    /// Bits of a value, derived from a single hash by double hashing.
+    fn positions(&self, bytes: &[u8]) -> impl Iterator<Item = usize> {
+        let hash = hash(bytes);
+        let step = hash.rotate_left(32) | 1;
+        let len = self.bits.len() as u64 * 64;
+        (0..BLOOM_HASHES).map(move |i| (hash.wrapping_add(i.wrapping_mul(step)) % len) as usize)
+    }
+}
+
+/// FNV-1a followed by a finalizer mixing all bits, stable across versions since
+/// sketches are persisted.
+fn hash(bytes: &[u8]) -> u64 {
+    let mut hash = 0xcbf29ce484222325u64;
+    for &byte in bytes {
+        hash ^= byte as u64;
+        hash = hash.wrapping_mul(0x100000001b3);
+    }
+
+    hash ^= hash >> 33;
+    hash = hash.wrapping_mul(0xff51afd7ed558ccd);
+    hash ^= hash >> 33;
+    hash = hash.wrapping_mul(0xc4ceb9fe1a85ec53);
+    hash ^ (hash >> 33)
+}
@@ -1,28 +1,148 @@
-use std::sync::mpsc::Receiver;
+use std::ops::BitOr;
+use std::sync::mpsc::{Receiver, RecvTimeoutError, TryRecvError};
+use std::sync::Mutex;
+use std::time::Duration;
 
+use serde::{Deserialize, Serialize};
+
+use crate::result::{DbResult, TinyBaseError};
 use crate::{table::SenderMap, Record};
 
-#[derive(Clone)]
-pub(crate) enum Event<T> {
+/// A change made to a table.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum Event<T> {
+    /// A record was removed.
     Remove(Record<T>),
+    /// A record was inserted.
     Insert(Record<T>),
+    /// The data of a record was replaced.
     Update { id: u64, old_data: T, new_data: T },
+    /// All records were removed by [`crate::table::TableInner::clear`].
+    Cleared,
+}
+
+impl<T> Event<T> {
+    /// The kind of the event.
+    pub fn kind(&self) -> EventKinds {
+        match self {
+            Event::Remove(_) => EventKinds::REMOVE,
+            Event::Insert(_) => EventKinds::INSERT,
+            Event::Update { .. } => EventKinds::UPDATE,
+            Event::Cleared => EventKinds::CLEAR,
+        }
+    }
+}
+
+/// Set of event kinds a subscription is interested in, combined with `|`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct EventKinds(u8);
+
+impl EventKinds {
+    pub const INSERT: Self = Self(1);
+    pub const REMOVE: Self = Self(1 << 1);
+    pub const UPDATE: Self = Self(1 << 2);
+    pub const CLEAR: Self = Self(1 << 3);
+    pub const ALL: Self = Self(Self::INSERT.0 | Self::REMOVE.0 | Self::UPDATE.0 | Self::CLEAR.0);
+
+    /// Check if all kinds of `other` are in the set.
+    pub fn contains(self, other: Self) -> bool {
+        self.0 & other.0 == other.0
+    }
+}
+
+impl BitOr for EventKinds {
+    type Output = Self;
+
+    fn bitor(self, rhs: Self) -> Self {
+        Self(self.0 | rhs.0)
+    }
 }
 
+/// An event together with its sequence number in the table journal.
+pub(crate) type Message<T> = (u64, Event<T>);
+
 pub(crate) struct Subscriber<T> {
     id: u64,
-    pub rx: Receiver<Event<T>>,
-    senders: SenderMap<Event<T>>,
+    pub rx: Mutex<Receiver<Message<T>>>,
+    senders: SenderMap<Message<T>>,
+}
+
+impl<T> Subscriber<T> {
+    pub fn new(id: u64, rx: Receiver<Message<T>>, senders: SenderMap<Message<T>>) -> Self {
+        Self {
+            id,
+            rx: Mutex::new(rx),
+            senders,
+        }
+    }
 }
 
 impl<T> Subscriber<T> {
-    pub fn new(id: u64, rx: Receiver<Event<T>>, senders: SenderMap<Event<T>>) -> Self {
-        Self { id, rx, senders }
+    /// Stop receiving events of the table.
+    pub fn unsubscribe(&self) {
+        self.senders.write().unwrap().remove(&self.id);
     }
 }
 
 impl<T> Drop for Subscriber<T> {
     fn drop(&mut self) {
-        self.senders.write().unwrap().remove(&self.id);
+        self.unsubscribe();
+    }
+}
+
+/// Stream of changes made to a table, created with [`crate::table::TableInner::subscribe`].
+///
+/// Events are buffered until they are received. The subscription ends once the
+/// table is closed, or when it is dropped.
+pub struct Subscription<T>(pub(crate) Subscriber<T>);
+
+impl<T> Subscription<T> {
+    /// Wait for the next event.
+    ///
+    /// # Returns
+    ///
+    /// The next [`Event`], or [`TinyBaseError::Closed`] once the table was closed.
+    pub fn recv(&self) -> DbResult<Event<T>> {
+        match self.0.rx.lock().unwrap().recv() {
+            Ok((_, event)) => Ok(event),
+            Err(_) => Err(TinyBaseError::Closed),
+        }
+    }
+
+    /// Receive the next event if one is pending.
+    ///
+    /// # Returns
+    ///
+    /// The next [`Event`] or [`None`] if there is none, or [`TinyBaseError::Closed`]
+    /// once the table was closed.
+    pub fn try_recv(&self) -> DbResult<Option<Event<T>>> {
+        match self.0.rx.lock().unwrap().try_recv() {
+            Ok((_, event)) => Ok(Some(event)),
+            Err(TryRecvError::Empty) => Ok(None),
+            Err(TryRecvError::Disconnected) => Err(TinyBaseError::Closed),
+        }
+    }
+
+    /// Wait for the next event for at most `timeout`.
+    ///
+    /// # Returns
+    ///
+    /// The next [`Event`] or [`None`] if the timeout elapsed, or [`TinyBaseError::Closed`]
+    /// once the table was closed.
+    pub fn recv_timeout(&self, timeout: Duration) -> DbResult<Option<Event<T>>> {
+        match self.0.rx.lock().unwrap().recv_timeout(timeout) {
+            Ok((_, event)) => Ok(Some(event)),
+            Err(RecvTimeoutError::Timeout) => Ok(None),
+            Err(RecvTimeoutError::Disconnected) => Err(TinyBaseError::Closed),
+        }
+    }
+}
+
+impl<T> Iterator for Subscription<T> {
+    type Item = Event<T>;
+
+    /// Wait for the next event, ending once the table was closed.
+    fn next(&mut self) -> Option<Self::Item> {
+        self.recv().ok()
     }
 }
@@ -1,24 +1,119 @@
-use std::collections::HashMap;
+use std::any::{type_name, Any};
+use std::ascii;
+use std::collections::{HashMap, HashSet};
 use std::fmt::Debug;
+use std::io::Write;
 use std::ops::Deref;
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::mpsc::{self, Sender};
-use std::sync::{Arc, RwLock};
+use std::sync::{Arc, RwLock, RwLockWriteGuard, Weak};
+use std::time::{SystemTime, UNIX_EPOCH};
 
 use serde::de::DeserializeOwned;
 use serde::Serialize;
-use sled::{Db, Tree};
+use sled::transaction::{
+    ConflictableTransactionError, ConflictableTransactionResult, Transactional, TransactionalTree,
+};
+use sled::{Db, IVec, Tree};
 
+use crate::collation::{fold_case, CollationKey, Collator};
+use crate::column::{AnyColumn, Column, ColumnInner, ColumnType};
 use crate::constraint::{Constraint, ConstraintInner};
+use crate::covering_index::CoveringIndex;
 use crate::encoding::{decode, encode};
-use crate::index::{Index, IndexInner, IndexType};
+use crate::id::IdGenerator;
+use crate::index::{
+    AnyIndexInner, Index, IndexInner, IndexMetadata, IndexType, IndexWrite, FORMAT,
+};
+use crate::journal::{journal_name, Journal, CURSORS};
+use crate::lazy::{AnyLazyField, Lazy, LazyField};
+use crate::lock::{ExclusiveGuard, SharedGuard, TableLock};
+use crate::ngram_index::NgramIndex;
+use crate::patch::Patch;
+use crate::posting::decode_posting;
 use crate::record::Record;
-use crate::result::DbResult;
-use crate::subscriber::{Event, Subscriber};
+use crate::result::{DbResult, TinyBaseError};
+use crate::sketch::SKETCHES;
+use crate::subscriber::{Event, EventKinds, Message, Subscriber, Subscription};
+use crate::text_index::TextIndex;
+use crate::tokenizer::Tokenizer;
+use crate::transaction::{PendingWrites, TableWriteGuard, Transaction};
+use crate::ttl::TtlIndex;
 
-pub(crate) type SenderMap<T> = Arc<RwLock<HashMap<u64, Sender<T>>>>;
+/// Receivers of table events together with the kinds of events they receive.
+pub(crate) type SenderMap<T> = Arc<RwLock<HashMap<u64, (Sender<T>, EventKinds)>>>;
 
-pub trait TableType: Serialize + DeserializeOwned + Clone + Debug {}
-impl<T: Serialize + DeserializeOwned + Debug + Clone> TableType for T {}
+/// Outcome of a single value of [`TableInner::upsert_many`].
+#[derive(Debug)]
+pub enum UpsertOutcome {
+    /// No record had the key, the value was inserted with this ID.
+    Inserted(u64),
+    /// The record with this ID had the key and was replaced by the value.
+    Updated(u64),
+    /// The value was not written.
+    Failed(TinyBaseError),
+}
+
+/// Records written by [`TableInner::upsert_many`] which aren't committed yet.
+struct UpsertBatch<T> {
+    /// IDs of the pending records, in the order they were first written.
+    ids: Vec<u64>,
+    /// Pending data of the records.
+    data: Vec<T>,
+    /// Stored data replaced by the records, [`None`] for inserted records.
+    old: Vec<Option<T>>,
+    /// Position of every pending record by its ID.
+    positions: HashMap<u64, usize>,
+    /// ID of the pending record with every encoded key of the key index.
+    keys: HashMap<Vec<u8>, u64>,
+}
+
+/// Error of a value whose key is shared by multiple records.
+fn ambiguous_key<T: TableType, I: IndexType>(
+    index: &Index<T, I>,
+    keys: Vec<Vec<u8>>,
+) -> TinyBaseError {
+    TinyBaseError::AmbiguousKey {
+        index: index.index_name(),
+        key: keys.into_iter().next().unwrap_or_default(),
+    }
+}
+
+/// Size of a table, returned by [`TableInner::stats`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct TableStats {
+    /// Amount of records.
+    pub records: usize,
+    /// Approximate size of the stored records in bytes, without their indexes, columns and lazy fields.
+    pub size: u64,
+    /// Amount of indexes created on the table, including indexes which aren't open.
+    pub indexes: usize,
+}
+
+/// Change of a single key in one of the trees written by a commit.
+enum TreeWrite {
+    Insert(Vec<u8>),
+    Remove,
+    Keep,
+}
+
+/// Key of the stats of a table in its versions tree, which can't be an encoded ID.
+const STATS_KEY: &[u8] = &[];
+
+/// Encoded ID with the values of every tree, encoded sequence and journal entry of an event.
+type EventWrite = (Vec<u8>, Vec<TreeWrite>, Vec<u8>, Vec<u8>);
+
+/// Options of a table which are inherited from the database.
+#[derive(Clone, Copy, Default)]
+pub(crate) struct TableConfig {
+    /// Reject all writes.
+    pub read_only: bool,
+    /// Reads don't take the table lock since there is only a single writer.
+    pub single_writer: bool,
+}
+
+pub trait TableType: Serialize + DeserializeOwned + Clone + Debug + Send + Sync {}
+impl<T: Serialize + DeserializeOwned + Debug + Clone + Send + Sync> TableType for T {}
 
 /// Provides methods for interacting with a typed table.
 pub struct Table<T: TableType + 'static>(pub(crate) Arc<TableInner<T>>);
@@ -34,26 +129,496 @@ impl<T: TableType + 'static> Table<T> {
     /// # Returns
     ///
     /// An [`Index`] instance for the created index.
-    pub fn create_index<I: IndexType>(
+    pub fn create_index<I: IndexType + 'static>(
         &self,
         name: &str,
         key_func: impl Fn(&T) -> I + Send + Sync + 'static,
     ) -> DbResult<Index<T, I>> {
-        let sender_id = self.engine.generate_id()?;
-        let (tx, rx) = mpsc::channel();
+        self.register_index(name, move |data| Ok(vec![key_func(data)]), true, false, "")
+    }
 
-        let subscriber = Subscriber::new(sender_id, rx, self.senders.clone());
-        self.senders.write().unwrap().insert(sender_id, tx);
+    /// Create an index on the table whose key function is identified by a version.
+    ///
+    /// The index is rebuilt when it was stored with another version, so changing the
+    /// key function of an index only requires changing its version.
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - The name of the index.
+    /// * `version` - A fingerprint of the key function, such as `"v2"` or a hash of its source.
+    /// * `key_func` - A function which computes the index key for each record.
+    ///
+    /// # Returns
+    ///
+    /// An [`Index`] instance for the created index.
+    pub fn create_index_versioned<I: IndexType + 'static>(
+        &self,
+        name: &str,
+        version: &str,
+        key_func: impl Fn(&T) -> I + Send + Sync + 'static,
+    ) -> DbResult<Index<T, I>> {
+        self.register_index(
+            name,
+            move |data| Ok(vec![key_func(data)]),
+            true,
+            false,
+            version,
+        )
+    }
+
+    /// Create an index on the table where a record can have many keys, such as one
+    /// for every tag of a collection field.
+    ///
+    /// A record is selected once by a condition even if several of its keys match.
+    /// Ordering by the index sorts records by all of their keys in memory.
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - The name of the index.
+    /// * `keys_func` - A function which computes the index keys for each record.
+    ///
+    /// # Returns
+    ///
+    /// An [`Index`] instance for the created index.
+    pub fn create_multi_index<I: IndexType + 'static>(
+        &self,
+        name: &str,
+        keys_func: impl Fn(&T) -> Vec<I> + Send + Sync + 'static,
+    ) -> DbResult<Index<T, I>> {
+        self.register_index(name, move |data| Ok(keys_func(data)), false, false, "")
+    }
+
+    /// Create an index on the table which only contains records with a key.
+    ///
+    /// Records for which `key_func` returns [`None`] aren't indexed, which keeps the index
+    /// small when only a few records have the field. Conditions on the index never select them.
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - The name of the index.
+    /// * `key_func` - A function which computes the index key for each record, if any.
+    ///
+    /// # Returns
+    ///
+    /// An [`Index`] instance for the created index.
+    pub fn create_sparse_index<I: IndexType + 'static>(
+        &self,
+        name: &str,
+        key_func: impl Fn(&T) -> Option<I> + Send + Sync + 'static,
+    ) -> DbResult<Index<T, I>> {
+        self.register_index(
+            name,
+            move |data| Ok(key_func(data).into_iter().collect()),
+            false,
+            false,
+            "",
+        )
+    }
+
+    /// Create an index on the table which only contains records matching a predicate.
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - The name of the index.
+    /// * `predicate` - A function which decides whether a record is indexed.
+    /// * `key_func` - A function which computes the index key for each indexed record.
+    ///
+    /// # Returns
+    ///
+    /// An [`Index`] instance for the created index.
+    pub fn create_index_where<I: IndexType + 'static>(
+        &self,
+        name: &str,
+        predicate: impl Fn(&T) -> bool + Send + Sync + 'static,
+        key_func: impl Fn(&T) -> I + Send + Sync + 'static,
+    ) -> DbResult<Index<T, I>> {
+        self.create_sparse_index(name, move |data| predicate(data).then(|| key_func(data)))
+    }
+
+    /// Create a full-text index on the table, storing the records containing each token
+    /// of a text.
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - The name of the index.
+    /// * `tokenizer` - Splits the texts and search queries into tokens.
+    /// * `text` - A function which returns the text of each record.
+    ///
+    /// # Returns
+    ///
+    /// A [`TextIndex`] instance for the created index.
+    pub fn create_text_index(
+        &self,
+        name: &str,
+        tokenizer: Tokenizer,
+        text: impl Fn(&T) -> String + Send + Sync + 'static,
+    ) -> DbResult<TextIndex<T>> {
+        TextIndex::new(self, name, tokenizer, text)
+    }
+
+    /// Create a covering index on the table, which stores a projection of every record
+    /// with its key so selecting the projections doesn't read the records.
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - The name of the index.
+    /// * `key_func` - A function which computes the index key for each record.
+    /// * `project` - A function which computes the stored projection of each record.
+    ///
+    /// # Returns
+    ///
+    /// A [`CoveringIndex`] instance for the created index.
+    pub fn create_covering_index<I, P>(
+        &self,
+        name: &str,
+        key_func: impl Fn(&T) -> I + Send + Sync + 'static,
+        project: impl Fn(&T) -> P + Send + Sync + 'static,
+    ) -> DbResult<CoveringIndex<T, I, P>>
+    where
+        I: IndexType + 'static,
+        P: IndexType + Clone + 'static,
+    {
+        CoveringIndex::new(self, name, key_func, project)
+    }
+
+    /// Create an n-gram index on the table, storing the records containing each sequence
+    /// of `n` characters of a text, for substring search.
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - The name of the index.
+    /// * `n` - The amount of characters of every n-gram, such as 3 for trigrams.
+    /// * `text` - A function which returns the text of each record.
+    ///
+    /// # Returns
+    ///
+    /// An [`NgramIndex`] instance for the created index.
+    pub fn create_ngram_index(
+        &self,
+        name: &str,
+        n: usize,
+        text: impl Fn(&T) -> String + Send + Sync + 'static,
+    ) -> DbResult<NgramIndex<T>> {
+        NgramIndex::new(self, name, n, text)
+    }
+
+    /// Create an index on the expiry time of every record, to delete expired records.
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - The name of the index.
+    /// * `expiry` - A function which returns the time each record expires at, in
+    ///   milliseconds since the Unix epoch.
+    ///
+    /// # Returns
+    ///
+    /// A [`TtlIndex`] instance for the created index.
+    pub fn create_ttl_index(
+        &self,
+        name: &str,
+        expiry: impl Fn(&T) -> u64 + Send + Sync + 'static,
+    ) -> DbResult<TtlIndex<T>> {
+        TtlIndex::new(self, name, expiry)
+    }
+
+    /// Create an index on the table whose string keys are compared under a collation,
+    /// so equality and ordering follow the collation instead of the bytes of the strings.
+    ///
+    /// Keys are stored as [`CollationKey`]s computed with the bundled rules, look them up
+    /// with [`Collator::sort_key`]. The index is rebuilt when the strength changes.
+    /// Collators of a locale are rejected, their rules have no sort keys.
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - The name of the index.
+    /// * `collator` - The [`Collator`] computing the keys.
+    /// * `key_func` - A function which computes the string key of each record.
+    ///
+    /// # Returns
+    ///
+    /// An [`Index`] instance for the created index.
+    pub fn create_collated_index(
+        &self,
+        name: &str,
+        collator: &Collator,
+        key_func: impl Fn(&T) -> String + Send + Sync + 'static,
+    ) -> DbResult<Index<T, CollationKey>> {
+        #[cfg(feature = "icu")]
+        if !collator.has_sort_keys() {
+            return Err(TinyBaseError::Collation(
+                "only collators with the bundled rules can key an index".to_owned(),
+            ));
+        }
+
+        let version = format!("collation-{:?}", collator.strength());
+        let collator = collator.clone();
+        self.register_index(
+            name,
+            move |data| Ok(vec![collator.sort_key(&key_func(data))]),
+            true,
+            false,
+            &version,
+        )
+    }
+
+    /// Create an index on the table whose string keys are stored case-folded.
+    ///
+    /// Lookups with [`crate::ConditionBuilder::by_ci`] are then plain key lookups.
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - The name of the index.
+    /// * `key_func` - A function which computes the index key for each record, before folding.
+    ///
+    /// # Returns
+    ///
+    /// An [`Index`] instance for the created index.
+    pub fn create_index_ci(
+        &self,
+        name: &str,
+        key_func: impl Fn(&T) -> String + Send + Sync + 'static,
+    ) -> DbResult<Index<T, String>> {
+        self.register_index(
+            name,
+            move |data| Ok(vec![fold_case(&key_func(data))]),
+            true,
+            true,
+            "",
+        )
+    }
 
+    pub(crate) fn register_index<I: IndexType + 'static>(
+        &self,
+        name: &str,
+        keys_func: impl Fn(&T) -> DbResult<Vec<I>> + Send + Sync + 'static,
+        single_key: bool,
+        case_folded: bool,
+        version: &str,
+    ) -> DbResult<Index<T, I>> {
         let weak_self = Arc::downgrade(&self.0);
 
-        Ok(Index(Arc::new(IndexInner::new(
-            &format!("{}_idx_{}", self.name, name),
+        let mut index = IndexInner::new(
+            &self.index_tree(name),
             &self.engine,
             weak_self,
-            key_func,
-            subscriber,
-        )?)))
+            keys_func,
+            single_key,
+            version,
+        )?;
+        index.case_folded = case_folded;
+        let index = Arc::new(index);
+
+        // No write can happen between catching up and the first write to the index.
+        let root = self.lock_root();
+        index.catch_up(&root)?;
+
+        let stored = self.index_metadata(name)?;
+        let metadata = IndexMetadata {
+            name: name.to_owned(),
+            key_type: type_name::<I>().to_owned(),
+            format: FORMAT,
+            version: version.to_owned(),
+            multi_key: !single_key,
+            case_folded,
+            unique: stored.as_ref().is_some_and(|stored| stored.unique),
+            created_at: match stored {
+                Some(stored) => stored.created_at,
+                None => SystemTime::now()
+                    .duration_since(UNIX_EPOCH)
+                    .unwrap()
+                    .as_millis() as u64,
+            },
+        };
+        self.index_meta.insert(name, encode(&metadata)?)?;
+
+        let mut indexes = self.indexes.write().unwrap();
+        indexes.retain(|index| index.strong_count() > 0);
+        indexes.push(Arc::downgrade(&index) as Weak<dyn AnyIndexInner<T>>);
+
+        Ok(Index(index))
+    }
+
+    /// Rename an index, see [`Index::rename`].
+    pub(crate) fn rename_index<I: IndexType + 'static>(
+        &self,
+        index: &Index<T, I>,
+        new_name: &str,
+    ) -> DbResult<Index<T, I>> {
+        // Writes would be lost between copying the index and registering the copy.
+        let _exclusive = self.lock.exclusive();
+        let _guard = self.check_writable()?;
+
+        let old_tree = index.index_name();
+        let name = old_tree
+            .strip_prefix(&self.index_tree(""))
+            .unwrap()
+            .to_owned();
+        let new_tree = self.index_tree(new_name);
+        if self.index_meta.contains_key(new_name)? {
+            return Err(TinyBaseError::IndexExists(new_name.to_owned()));
+        }
+
+        let mut batch = sled::Batch::default();
+        for entry in index.tree().iter() {
+            let (key, ids) = entry?;
+            batch.insert(key, ids);
+        }
+        self.engine.open_tree(&new_tree)?.apply_batch(batch)?;
+
+        // The copy continues from the cursor and sketch of the index.
+        let sketches = self.engine.open_tree(SKETCHES)?;
+        if let Some(sketch) = sketches.get(&old_tree)? {
+            sketches.insert(&new_tree, sketch)?;
+        }
+        if let Some(cursor) = self.journal.cursors.get(&old_tree)? {
+            self.journal.cursors.insert(&new_tree, cursor)?;
+        }
+        if let Some(mut metadata) = self.index_metadata(&name)? {
+            metadata.name = new_name.to_owned();
+            self.index_meta.insert(new_name, encode(&metadata)?)?;
+        }
+
+        let keys_func = index.keys_func.clone();
+        let renamed = self.register_index(
+            new_name,
+            move |data| keys_func(data),
+            index.single_key,
+            index.case_folded,
+            &index.version,
+        )?;
+
+        for constraint in self.constraints.write().unwrap().iter_mut() {
+            if let ConstraintInner::Unique(unique, _) = &mut constraint.0 {
+                if unique.idx_name() == old_tree {
+                    *unique = Box::new(renamed.clone());
+                }
+            }
+        }
+
+        self.drop_index(&name)?;
+        Ok(renamed)
+    }
+
+    /// Remove an index with all its data, including indexes which weren't created since
+    /// the database was opened. Unique constraints using the index are removed as well.
+    ///
+    /// [`Index`] handles of the dropped index must not be used afterwards.
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - The name of the index.
+    ///
+    /// # Returns
+    ///
+    /// Whether the index existed.
+    pub fn drop_index(&self, name: &str) -> DbResult<bool> {
+        let _guard = self.check_writable()?;
+        let tree = self.index_tree(name);
+
+        self.indexes.write().unwrap().retain(|index| {
+            let Some(index) = index.upgrade() else {
+                return false;
+            };
+
+            index.tree().name() != tree.as_bytes()
+        });
+
+        self.constraints.write().unwrap().retain(|constraint| {
+            !matches!(&constraint.0, ConstraintInner::Unique(index, _) if index.idx_name() == tree)
+        });
+
+        self.journal.cursors.remove(&tree)?;
+        self.engine.open_tree(SKETCHES)?.remove(&tree)?;
+        self.index_meta.remove(name)?;
+        Ok(self.engine.drop_tree(&tree)?)
+    }
+
+    /// Read the stored description of an index, including indexes which weren't
+    /// created since the database was opened.
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - The name of the index.
+    ///
+    /// # Returns
+    ///
+    /// The [`IndexMetadata`] of the index, or [`None`] if it doesn't exist.
+    pub fn index_metadata(&self, name: &str) -> DbResult<Option<IndexMetadata>> {
+        self.index_meta
+            .get(name)?
+            .map(|bytes| decode(&bytes))
+            .transpose()
+    }
+
+    /// Read the stored descriptions of all indexes of the table.
+    ///
+    /// # Returns
+    ///
+    /// The [`IndexMetadata`] of every index, in alphabetical order of their names.
+    pub fn indexes_metadata(&self) -> DbResult<Vec<IndexMetadata>> {
+        self.index_meta
+            .iter()
+            .values()
+            .map(|bytes| decode(&bytes?))
+            .collect()
+    }
+
+    /// List the indexes of the table stored in the database, including indexes which
+    /// weren't created since the database was opened.
+    ///
+    /// # Returns
+    ///
+    /// The names of the indexes, in alphabetical order.
+    pub fn list_indexes(&self) -> DbResult<Vec<String>> {
+        self.index_meta
+            .iter()
+            .keys()
+            .map(|name| Ok(String::from_utf8_lossy(&name?).into_owned()))
+            .collect()
+    }
+
+    /// Create a column on the table, storing one field of every record separately.
+    ///
+    /// Existing records are written to the column if it missed any writes.
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - The name of the column.
+    /// * `value_func` - A function which extracts the column value of each record.
+    ///
+    /// # Returns
+    ///
+    /// A [`Column`] instance for the created column.
+    pub fn create_column<C: ColumnType + 'static>(
+        &self,
+        name: &str,
+        value_func: impl Fn(&T) -> C + Send + Sync + 'static,
+    ) -> DbResult<Column<T, C>> {
+        let name = format!("{}_col_{}", self.name, name);
+        // Block writes until the column is registered.
+        let root = self.lock_root();
+        self.owned_trees.insert(&name, &[])?;
+        let column = Arc::new(ColumnInner::new(self.engine.open_tree(&name)?, value_func));
+
+        // Columns are written along with the table, so they are only behind if the
+        // table was written while the column wasn't open.
+        let head = self.journal.head()?;
+        if self.journal.cursor(&name)? != Some(head) {
+            let tree = column.tree();
+            tree.clear()?;
+            for entry in root.iter() {
+                let (key, value) = entry?;
+                tree.insert(key, column.encode_value(&decode(&value)?)?)?;
+            }
+
+            self.journal.set_cursor(&name, head)?;
+        }
+
+        let mut columns = self.columns.write().unwrap();
+        columns.retain(|column| column.strong_count() > 0);
+        columns.push(Arc::downgrade(&column) as Weak<dyn AnyColumn<T>>);
+
+        Ok(Column(column))
     }
 }
 
@@ -79,8 +644,35 @@ where
     /// This has a global lock to make sure that constraints are honored during inserts.
     pub(crate) root: RwLock<Tree>,
     name: String,
-    senders: SenderMap<Event<T>>,
+    /// Journal of the writes to the table while indexes are rebuilt.
+    pub(crate) journal: Journal,
+    senders: SenderMap<Message<T>>,
     constraints: RwLock<Vec<Constraint<T>>>,
+    /// Descriptions of the indexes of this table by their name.
+    index_meta: Tree,
+    /// Names of the column and lazy field trees of this table, dropped with it.
+    owned_trees: Tree,
+    /// Version of every record, the sequence number of the last write to it, and
+    /// the amount and size of the records under [`STATS_KEY`].
+    versions: Tree,
+    /// Stored sketches of the indexes, which commits mark stale.
+    sketches: Tree,
+    /// Indexes created on this table, written in the same transaction as the table.
+    indexes: RwLock<Vec<Weak<dyn AnyIndexInner<T>>>>,
+    /// Columns created on this table, written in the same transaction as the table.
+    columns: RwLock<Vec<Weak<dyn AnyColumn<T>>>>,
+    /// Fields which are stored in their own tree and loaded on access.
+    lazy_fields: RwLock<Vec<Arc<dyn AnyLazyField<T>>>>,
+    /// Generates IDs of inserted records, sequential database IDs if unset.
+    id_generator: RwLock<Option<Box<dyn IdGenerator>>>,
+    /// Advisory lock held shared by all writes.
+    lock: TableLock,
+    /// Tree handle used for reads without locking in single-writer mode.
+    unlocked_reads: Option<Tree>,
+    /// Rejects all writes when the database was opened as a read-only secondary.
+    read_only: bool,
+    /// Rejects all writes after the database was closed.
+    closed: AtomicBool,
 }
 
 impl<T> TableInner<T>
@@ -96,43 +688,502 @@ where
     ///
     /// * `engine` - The database engine.
     /// * `name` - The name of the table.
-    pub(crate) fn new(engine: &Db, name: &str) -> DbResult<Self> {
-        let root = RwLock::new(engine.open_tree(name)?);
+    /// * `config` - Options inherited from the database.
+    pub(crate) fn new(engine: &Db, name: &str, config: TableConfig) -> DbResult<Self> {
+        let tree = engine.open_tree(name)?;
+
+        let versions = engine.open_tree(format!("{}_versions", name))?;
+        if !versions.contains_key(STATS_KEY)? && !tree.is_empty() {
+            // Tables written before the stats were tracked are counted once.
+            let mut stats = (0u64, 0u64);
+            for entry in tree.iter() {
+                let (key, value) = entry?;
+                stats.0 += 1;
+                stats.1 += (key.len() + value.len()) as u64;
+            }
+
+            versions.insert(STATS_KEY, encode(&stats)?)?;
+        }
+
+        let index_meta = engine.open_tree(format!("{}_indexes", name))?;
+        // Indexes created before their metadata was stored are only known by the name of their tree.
+        let prefix = format!("{}_idx_", name);
+        for tree in engine.tree_names() {
+            let Some(index) = std::str::from_utf8(&tree)
+                .ok()
+                .and_then(|tree| tree.strip_prefix(&prefix))
+            else {
+                continue;
+            };
+
+            if !index_meta.contains_key(index)? {
+                let metadata = IndexMetadata {
+                    name: index.to_owned(),
+                    key_type: String::new(),
+                    format: FORMAT,
+                    version: String::new(),
+                    multi_key: false,
+                    case_folded: false,
+                    unique: false,
+                    created_at: 0,
+                };
+                index_meta.insert(index, encode(&metadata)?)?;
+            }
+        }
 
         Ok(Self {
             engine: engine.clone(),
-            root,
+            unlocked_reads: config.single_writer.then(|| tree.clone()),
+            root: RwLock::new(tree),
             name: name.to_owned(),
+            journal: Journal::open(engine, name)?,
+            index_meta,
+            owned_trees: engine.open_tree(owned_trees_name(name))?,
+            versions,
+            sketches: engine.open_tree(SKETCHES)?,
             senders: Arc::new(RwLock::new(HashMap::new())),
             constraints: RwLock::new(Vec::new()),
+            indexes: RwLock::new(Vec::new()),
+            columns: RwLock::new(Vec::new()),
+            lazy_fields: RwLock::new(Vec::new()),
+            id_generator: RwLock::new(None),
+            lock: TableLock::default(),
+            read_only: config.read_only,
+            closed: AtomicBool::new(false),
         })
     }
 
-    /// Insert a new record into the table.
+    /// Name of the tree of an index.
+    fn index_tree(&self, name: &str) -> String {
+        format!("{}_idx_{}", self.name, name)
+    }
+
+    /// Register a new receiver of the events of the given kinds dispatched by the table.
+    fn subscriber(&self, kinds: EventKinds) -> DbResult<Subscriber<T>> {
+        let sender_id = self.engine.generate_id()?;
+        let (tx, rx) = mpsc::channel();
+
+        let subscriber = Subscriber::new(sender_id, rx, self.senders.clone());
+        self.senders.write().unwrap().insert(sender_id, (tx, kinds));
+
+        Ok(subscriber)
+    }
+
+    /// Subscribe to all changes made to the table from now on.
+    ///
+    /// # Returns
+    ///
+    /// A [`Subscription`] receiving an [`Event`] for every write.
+    pub fn subscribe(&self) -> DbResult<Subscription<T>> {
+        self.subscribe_to(EventKinds::ALL)
+    }
+
+    /// Subscribe to some kinds of changes made to the table from now on.
+    ///
+    /// Other events are never sent to the subscription, so they don't cost anything.
     ///
     /// # Arguments
     ///
-    /// * `value` - The value to insert.
+    /// * `kinds` - The kinds of events to receive, such as `EventKinds::INSERT | EventKinds::REMOVE`.
     ///
     /// # Returns
     ///
-    /// The ID of the new record.
-    pub fn insert(&self, value: T) -> DbResult<u64> {
-        let root = self.root.write().unwrap();
+    /// A [`Subscription`] receiving an [`Event`] for every write of those kinds.
+    pub fn subscribe_to(&self, kinds: EventKinds) -> DbResult<Subscription<T>> {
+        Ok(Subscription(self.subscriber(kinds)?))
+    }
+
+    /// Store a field in its own tree, so it is only decoded when accessed.
+    ///
+    /// Register lazy fields right after opening the table. Records written before
+    /// keep the value inline until they are written again.
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - The name of the field.
+    /// * `accessor` - A function returning the field of a record.
+    pub fn lazy_field<V: Serialize + DeserializeOwned + Send + Sync + 'static>(
+        &self,
+        name: &str,
+        accessor: impl Fn(&mut T) -> &mut Lazy<V> + Send + Sync + 'static,
+    ) -> DbResult<()> {
+        let name = format!("{}_lazy_{}", self.name, name);
+        self.owned_trees.insert(&name, &[])?;
+        let tree = self.engine.open_tree(name)?;
+
+        self.lazy_fields
+            .write()
+            .unwrap()
+            .push(Arc::new(LazyField::new(tree, accessor)));
+
+        Ok(())
+    }
+
+    /// Set how IDs of records inserted from now on are generated.
+    ///
+    /// # Arguments
+    ///
+    /// * `generator` - The [`IdGenerator`], such as [`crate::id::Snowflake`] or a closure.
+    pub fn id_generator(&self, generator: impl IdGenerator + 'static) {
+        *self.id_generator.write().unwrap() = Some(Box::new(generator));
+    }
+
+    /// Generate the ID of a new record.
+    pub(crate) fn generate_id(&self, tree: &Tree) -> DbResult<u64> {
+        match &*self.id_generator.read().unwrap() {
+            Some(generator) => {
+                let id = generator.generate_id()?;
+                if tree.contains_key(encode(&id)?)? {
+                    return Err(TinyBaseError::DuplicateId(id));
+                }
+
+                Ok(id)
+            }
+            None => Ok(self.engine.generate_id()?),
+        }
+    }
+
+    /// Acquire the table lock exclusively.
+    ///
+    /// While the guard is alive, writes from other threads wait for it to be dropped.
+    /// This is meant for maintenance operations (reindex, bulk load) which shouldn't
+    /// interleave with regular writes. The current thread may still write to the table.
+    ///
+    /// # Returns
+    ///
+    /// A guard which releases the lock when dropped.
+    pub fn lock_exclusive(&self) -> ExclusiveGuard<'_> {
+        self.lock.exclusive()
+    }
+
+    /// Acquire the table lock shared.
+    ///
+    /// While the guard is alive, no other thread can acquire the lock exclusively.
+    ///
+    /// # Returns
+    ///
+    /// A guard which releases the lock when dropped.
+    pub fn lock_shared(&self) -> SharedGuard<'_> {
+        self.lock.shared()
+    }
+
+    /// Lock the table tree for writing.
+    ///
+    /// Writers hold the write lock for their whole read-check-write sequence (e.g.
+    /// constraint checks), so it is often taken even when the tree is only read.
+    pub(crate) fn lock_root(&self) -> RwLockWriteGuard<'_, Tree> {
+        self.root.write().unwrap()
+    }
+
+    /// Lock the table for writing by a transaction spanning multiple tables.
+    pub(crate) fn lock_writes(&self) -> DbResult<TableWriteGuard<'_>> {
+        let guard = self.check_writable()?;
+        Ok(TableWriteGuard::new(guard, self.lock_root()))
+    }
+
+    /// Fail if the table doesn't accept writes, otherwise acquire the lock for writing.
+    fn check_writable(&self) -> DbResult<SharedGuard<'_>> {
+        if self.read_only {
+            Err(TinyBaseError::ReadOnly)
+        } else if self.closed.load(Ordering::Acquire) {
+            Err(TinyBaseError::Closed)
+        } else {
+            Ok(self.lock.shared())
+        }
+    }
+
+    /// Apply events to the table, recording them in the journal in the same transaction while
+    /// an index is rebuilt.
+    /// Columns and indexes are written in the same transaction as well.
+    /// The events are dispatched to all subscribers afterwards.
+    /// Any time you pass the tree it should be obtained via a write lock.
+    fn commit(&self, tree: &Tree, events: Vec<Event<T>>) -> DbResult<()> {
+        self.commit_checked(tree, events, &[])
+    }
+
+    /// Apply events to the table like [`TableInner::commit`], if the stored records
+    /// still have the expected encoded data.
+    ///
+    /// # Arguments
+    ///
+    /// * `expected` - IDs of records with their expected data, compared in the transaction.
+    ///
+    /// # Returns
+    ///
+    /// [`TinyBaseError::Conflict`] if a record has other data, nothing is applied then.
+    fn commit_checked(
+        &self,
+        tree: &Tree,
+        events: Vec<Event<T>>,
+        expected: &[(u64, IVec)],
+    ) -> DbResult<()> {
+        let Some(commit) = self.prepare_commit(tree, events, expected)? else {
+            return Ok(());
+        };
+
+        let trees: Vec<&Tree> = commit.trees().iter().collect();
+        trees.as_slice().transaction(|trees| commit.apply(trees))?;
+        commit.finish();
+
+        Ok(())
+    }
+
+    /// Prepare the writes of a commit, so they can be applied in a transaction which
+    /// may span other tables as well.
+    ///
+    /// # Returns
+    ///
+    /// The prepared commit, or [`None`] if there are no events.
+    pub(crate) fn prepare_commit(
+        &self,
+        tree: &Tree,
+        events: Vec<Event<T>>,
+        expected: &[(u64, IVec)],
+    ) -> DbResult<Option<PreparedCommit<'_, T>>> {
+        let columns: Vec<_> = self
+            .columns
+            .read()
+            .unwrap()
+            .iter()
+            .filter_map(Weak::upgrade)
+            .collect();
+
+        let lazy_fields = self.lazy_fields.read().unwrap();
+
+        let indexes: Vec<_> = self
+            .indexes
+            .read()
+            .unwrap()
+            .iter()
+            .filter_map(Weak::upgrade)
+            .collect();
+        let index_writes = indexes
+            .iter()
+            .map(|index| index.prepare(&events))
+            .collect::<DbResult<Vec<_>>>()?;
+
+        // Open indexes are written along with the table, so entries are only needed
+        // by indexes which are rebuilt.
+        let journaled = indexes.iter().any(|index| index.is_rebuilding());
+
+        // Saved sketches miss the keys of the commit until they are saved again.
+        let stale_sketches = Self::stale_sketches(&indexes)?;
+
+        let mut writes = Vec::with_capacity(events.len());
+        let mut sequence = Vec::with_capacity(events.len());
+
+        // Stored size of the records written so far, to track the table stats.
+        let mut sizes: HashMap<u64, Option<usize>> = HashMap::new();
+        let mut stats_delta = (0i64, 0i64);
+
+        for event in &events {
+            let (id, data) = match event {
+                Event::Insert(record) => (record.id, Some(&record.data)),
+                Event::Remove(record) => (record.id, None),
+                Event::Update { id, new_data, .. } => (*id, Some(new_data)),
+                Event::Cleared => unreachable!("tables are cleared without a commit"),
+            };
+
+            let key = encode(&id)?;
+            let old_size = match sizes.get(&id) {
+                Some(size) => *size,
+                None => tree.get(&key)?.map(|value| key.len() + value.len()),
+            };
+
+            let seq = self.engine.generate_id()?;
+            let tree_count = 2 + columns.len() + lazy_fields.len();
+            let mut values = Vec::with_capacity(tree_count);
+            let new_size = match data {
+                Some(data) => {
+                    // Lazy values are moved out of the stored record.
+                    let mut stored = data.clone();
+                    let mut lazy_values = Vec::with_capacity(lazy_fields.len());
+                    for field in lazy_fields.iter() {
+                        lazy_values.push(match field.detach(&mut stored, id)? {
+                            Some(value) => TreeWrite::Insert(value),
+                            None => TreeWrite::Keep,
+                        });
+                    }
+
+                    let stored = encode(&stored)?;
+                    let size = key.len() + stored.len();
+                    values.push(TreeWrite::Insert(stored));
+                    for column in &columns {
+                        values.push(TreeWrite::Insert(column.encode_value(data)?));
+                    }
+                    values.extend(lazy_values);
+                    values.push(TreeWrite::Insert(encode(&seq)?));
+                    Some(size)
+                }
+                None => {
+                    values.resize_with(tree_count, || TreeWrite::Remove);
+                    None
+                }
+            };
+
+            stats_delta.0 += new_size.is_some() as i64 - old_size.is_some() as i64;
+            stats_delta.1 += new_size.unwrap_or(0) as i64 - old_size.unwrap_or(0) as i64;
+            sizes.insert(id, new_size);
+
+            sequence.push(seq);
+            let entry = if journaled {
+                encode(&detach_event(event, &lazy_fields)?)?
+            } else {
+                vec![]
+            };
+            writes.push((key, values, encode(&seq)?, entry));
+        }
+
+        let head = match sequence.last() {
+            Some(seq) => encode(seq)?,
+            None => return Ok(None),
+        };
+
+        let mut trees = vec![tree.clone()];
+        trees.extend(columns.iter().map(|column| column.tree().clone()));
+        trees.extend(lazy_fields.iter().map(|field| field.tree().clone()));
+        trees.push(self.versions.clone());
+        trees.extend(indexes.iter().map(|index| index.tree().clone()));
+        trees.push(self.journal.entries.clone());
+        trees.push(self.journal.cursors.clone());
+        trees.push(self.sketches.clone());
+
+        let expected = expected
+            .iter()
+            .map(|(id, data)| Ok((*id, encode(id)?, data.clone())))
+            .collect::<DbResult<Vec<_>>>()?;
+
+        Ok(Some(PreparedCommit {
+            table: self,
+            trees,
+            columns,
+            indexes,
+            index_writes,
+            writes,
+            expected,
+            stats_delta,
+            journaled,
+            stale_sketches,
+            head,
+            sequence,
+            events,
+        }))
+    }
+
+    /// The stored states marking the saved sketches of indexes stale.
+    ///
+    /// # Returns
+    ///
+    /// The names of the index trees with the encoded stale state, for the indexes
+    /// whose sketch isn't marked yet.
+    fn stale_sketches(indexes: &[Arc<dyn AnyIndexInner<T>>]) -> DbResult<Vec<(IVec, Vec<u8>)>> {
+        indexes
+            .iter()
+            .filter_map(|index| match index.stale_sketch() {
+                Ok(Some(stale)) => Some(Ok((index.tree().name(), stale))),
+                Ok(None) => None,
+                Err(err) => Some(Err(err)),
+            })
+            .collect()
+    }
+
+    /// Remove journal entries which were applied by all open indexes.
+    /// Indexes which aren't open and missed removed entries will be resynced when created.
+    pub(crate) fn truncate_journal(&self) -> DbResult<()> {
+        let mut applied = None;
+        for index in self
+            .indexes
+            .read()
+            .unwrap()
+            .iter()
+            .filter_map(Weak::upgrade)
+        {
+            let cursor = index.applied();
+            applied = Some(applied.map_or(cursor, |applied: u64| applied.min(cursor)));
+        }
+
+        match applied.map_or_else(|| self.journal.last(), |applied| Ok(Some(applied)))? {
+            Some(seq) => self.journal.truncate(seq),
+            None => Ok(()),
+        }
+    }
+
+    /// Insert a new record into the table.
+    ///
+    /// # Arguments
+    ///
+    /// * `value` - The value to insert.
+    ///
+    /// # Returns
+    ///
+    /// The ID of the new record.
+    pub fn insert(&self, value: T) -> DbResult<u64> {
+        let _guard = self.check_writable()?;
+        let root = self.lock_root();
 
         let record = Record {
-            id: self.engine.generate_id()?,
+            id: self.generate_id(&root)?,
             data: value.clone(),
         };
 
-        self.check_constraint(&root, &record, &vec![])?;
-        root.insert(encode(&record.id)?, encode(&value)?)?;
-
-        self.dispatch_event(Event::Insert(record.clone()));
+        self.check_constraint(&root, &record, &[])?;
+        self.commit(&root, vec![Event::Insert(record.clone())])?;
 
         Ok(record.id)
     }
 
+    /// Insert many values in a single commit.
+    ///
+    /// The records, their index entries and journal entries are written in one
+    /// transaction, so either all values are inserted or none is.
+    ///
+    /// # Arguments
+    ///
+    /// * `values` - The values to insert.
+    ///
+    /// # Returns
+    ///
+    /// The IDs of the new records, in the order of the values.
+    pub fn insert_many(&self, values: Vec<T>) -> DbResult<Vec<u64>> {
+        let _guard = self.check_writable()?;
+        let root = self.lock_root();
+
+        let mut ids = HashSet::with_capacity(values.len());
+        let mut records = Vec::with_capacity(values.len());
+        for data in values {
+            let id = self.generate_id(&root)?;
+            if !ids.insert(id) {
+                return Err(TinyBaseError::DuplicateId(id));
+            }
+
+            records.push(Record { id, data });
+        }
+
+        let additional: Vec<T> = records.iter().map(|r| r.data.clone()).collect();
+        for record in &records {
+            self.check_constraint(&root, record, &additional)?;
+        }
+
+        let ids = records.iter().map(|record| record.id).collect();
+        self.commit(&root, records.into_iter().map(Event::Insert).collect())?;
+
+        Ok(ids)
+    }
+
+    /// Insert records with their existing IDs in a single commit.
+    /// Records aren't checked for existing IDs, so this is meant for filling new tables.
+    pub(crate) fn insert_records(&self, records: Vec<Record<T>>) -> DbResult<()> {
+        let _guard = self.check_writable()?;
+        let root = self.lock_root();
+
+        let additional: Vec<T> = records.iter().map(|r| r.data.clone()).collect();
+        for record in &records {
+            self.check_constraint(&root, record, &additional)?;
+        }
+
+        self.commit(&root, records.into_iter().map(Event::Insert).collect())
+    }
+
     /// Check if constraint is met.
     /// Additional items can be specified if there are some items that aren't inserted yet.
     /// Any time you pass the tree it should probably be obtained via a write lock.
@@ -140,33 +1191,72 @@ where
         &self,
         tree: &Tree,
         record: &Record<T>,
-        additional_items: &Vec<T>,
+        additional_items: &[T],
+    ) -> DbResult<()> {
+        self.check_constraint_replacing(tree, record, additional_items, &|_| false)
+    }
+
+    /// Check if constraint is met like [`TableInner::check_constraint`], ignoring
+    /// stored records which are replaced by pending writes. The pending data of
+    /// these records should be passed as additional items.
+    pub(crate) fn check_constraint_replacing(
+        &self,
+        tree: &Tree,
+        record: &Record<T>,
+        additional_items: &[T],
+        replaced: &dyn Fn(u64) -> bool,
     ) -> DbResult<()> {
         for constraint in self.constraints.read().unwrap().iter() {
             match &constraint.0 {
-                ConstraintInner::Unique(index) => {
-                    let matches = index.tree_exists(tree, record)?;
-                    // Check if record being changed is the same record that has the index error.
-                    if matches.len() > 1 || matches.len() == 1 && matches[0] != record.id {
-                        return Err(crate::result::TinyBaseError::Exists {
+                ConstraintInner::Unique(index, filter) => {
+                    // Partial constraints only apply to records matching their filter.
+                    let applies = |data: &T| filter.is_none_or(|filter| filter(data));
+                    if !applies(&record.data) {
+                        continue;
+                    }
+
+                    for (key, conflicting) in index.tree_exists(tree, record)? {
+                        // Check if record being changed is the same record that has the index error.
+                        if conflicting == record.id || replaced(conflicting) {
+                            continue;
+                        }
+
+                        if filter.is_some()
+                            && !self
+                                .tree_select(tree, conflicting)?
+                                .is_some_and(|other| applies(&other.data))
+                        {
+                            continue;
+                        }
+
+                        return Err(TinyBaseError::Exists {
                             constraint: index.idx_name(),
+                            key,
                             id: record.id,
+                            conflicting,
                         });
                     }
 
                     let mut matches = vec![];
-                    for additional in additional_items {
-                        let key = index.gen_key(&additional)?;
-                        if matches.contains(&key) {
-                            return Err(crate::result::TinyBaseError::BatchOperationConstraints);
-                        }
+                    for additional in additional_items.iter().filter(|data| applies(data)) {
+                        for key in index.gen_keys(additional)? {
+                            if matches.contains(&key) {
+                                return Err(TinyBaseError::BatchOperationConstraints {
+                                    constraint: index.idx_name(),
+                                    key,
+                                });
+                            }
 
-                        matches.push(key);
+                            matches.push(key);
+                        }
                     }
                 }
-                ConstraintInner::Check(condition) => {
+                ConstraintInner::Check(name, condition) => {
                     if !condition(&record.data) {
-                        return Err(crate::result::TinyBaseError::Condition);
+                        return Err(TinyBaseError::Condition {
+                            constraint: name.clone(),
+                            id: record.id,
+                        });
                     }
                 }
             };
@@ -185,21 +1275,190 @@ where
     ///
     /// An [`Option`] containing the selected record if it exists, or [`None`] otherwise.
     pub fn select(&self, id: u64) -> DbResult<Option<Record<T>>> {
-        self.tree_select(&self.root.read().unwrap(), id)
+        match &self.unlocked_reads {
+            Some(tree) => self.tree_select(tree, id),
+            None => self.tree_select(&self.root.read().unwrap(), id),
+        }
+    }
+
+    /// Decode the leading fields of a record into another type, without decoding the rest.
+    pub(crate) fn select_as<P: DeserializeOwned>(&self, id: u64) -> DbResult<Option<P>> {
+        let bytes = match &self.unlocked_reads {
+            Some(tree) => tree.get(encode(&id)?)?,
+            None => self.root.read().unwrap().get(encode(&id)?)?,
+        };
+
+        bytes.map(|bytes| decode(&bytes)).transpose()
+    }
+
+    /// IDs of all records, in order.
+    pub(crate) fn ids(&self) -> DbResult<Vec<u64>> {
+        let read = |tree: &Tree| -> DbResult<Vec<u64>> {
+            tree.iter().keys().map(|key| decode(&key?)).collect()
+        };
+
+        match &self.unlocked_reads {
+            Some(tree) => read(tree),
+            None => read(&self.root.read().unwrap()),
+        }
+    }
+
+    /// Amount of records in the table, which is tracked by every commit instead of
+    /// counting the records.
+    pub fn len(&self) -> DbResult<usize> {
+        Ok(self.stats()?.records)
+    }
+
+    /// Whether the table has no records.
+    pub fn is_empty(&self) -> DbResult<bool> {
+        Ok(self.len()? == 0)
+    }
+
+    /// Size of the table, without scanning its records.
+    ///
+    /// # Returns
+    ///
+    /// The [`TableStats`] of the table.
+    pub fn stats(&self) -> DbResult<TableStats> {
+        let (records, size): (u64, u64) = match self.versions.get(STATS_KEY)? {
+            Some(stats) => decode(&stats)?,
+            None => (0, 0),
+        };
+
+        Ok(TableStats {
+            records: records as usize,
+            size,
+            indexes: self.index_meta.len(),
+        })
+    }
+
+    /// Select a record by its ID together with its version, for
+    /// [`TableInner::update_cas`].
+    ///
+    /// # Arguments
+    ///
+    /// * `id` - The ID of the record to select.
+    ///
+    /// # Returns
+    ///
+    /// The record and its version, or [`None`] if there is no record with the ID.
+    pub fn select_versioned(&self, id: u64) -> DbResult<Option<(Record<T>, u64)>> {
+        let root = self.root.read().unwrap();
+        match self.tree_select(&root, id)? {
+            Some(record) => Ok(Some((record, self.version(id)?))),
+            None => Ok(None),
+        }
+    }
+
+    /// Version of a record, 0 for records written before versions were tracked.
+    fn version(&self, id: u64) -> DbResult<u64> {
+        match self.versions.get(encode(&id)?)? {
+            Some(version) => decode(&version),
+            None => Ok(0),
+        }
     }
 
     /// Select that doesn't obtain a read lock.
     pub(crate) fn tree_select(&self, tree: &Tree, id: u64) -> DbResult<Option<Record<T>>> {
         if let Some(serialized) = tree.get(encode(&id)?)? {
-            Ok(Some(Record {
-                id,
-                data: decode(&serialized)?,
-            }))
+            self.decode_record(id, &serialized).map(Some)
         } else {
             Ok(None)
         }
     }
 
+    /// Decode a stored record, attaching its lazy fields.
+    pub(crate) fn decode_record(&self, id: u64, serialized: &[u8]) -> DbResult<Record<T>> {
+        let mut data = decode(serialized)?;
+        for field in self.lazy_fields.read().unwrap().iter() {
+            field.attach(&mut data, id);
+        }
+
+        Ok(Record { id, data })
+    }
+
+    /// Iterate over all records in the order of their IDs.
+    ///
+    /// Records are read lazily without locking the table, so records written while
+    /// iterating may or may not be returned.
+    ///
+    /// # Returns
+    ///
+    /// An iterator over the records.
+    pub fn iter(&self) -> impl Iterator<Item = DbResult<Record<T>>> + '_ {
+        self.iter_from(0)
+    }
+
+    /// Iterate over the records starting at an ID, like [`TableInner::iter`].
+    ///
+    /// # Arguments
+    ///
+    /// * `id` - The first ID to return, e.g. the one after the last ID of a previous scan.
+    ///
+    /// # Returns
+    ///
+    /// An iterator over the records with an ID of at least `id`.
+    pub fn iter_from(&self, id: u64) -> impl Iterator<Item = DbResult<Record<T>>> + '_ {
+        let tree = match &self.unlocked_reads {
+            Some(tree) => tree.clone(),
+            None => self.root.read().unwrap().clone(),
+        };
+
+        // IDs are encoded big-endian, so the order of the keys is the order of the IDs.
+        tree.range(id.to_be_bytes()..).map(move |entry| {
+            let (key, value) = entry?;
+            self.decode_record(decode(&key)?, &value)
+        })
+    }
+
+    /// Write all records and index contents in a deterministic, human-readable format.
+    ///
+    /// Records are written in ID order with their [`Debug`] representation, index keys
+    /// in byte order with their IDs. Combined with [`crate::id::Counter`] IDs the dump
+    /// only depends on the written data, so it can be used for snapshot tests.
+    ///
+    /// # Arguments
+    ///
+    /// * `writer` - Where the dump is written to.
+    pub fn dump_debug(&self, mut writer: impl Write) -> DbResult<()> {
+        let root = self.root.read().unwrap();
+
+        writeln!(writer, "table {} ({} records)", self.name, root.len())?;
+        for id in root.iter().keys() {
+            let record = self.tree_select(&root, decode(&id?)?)?.unwrap();
+            writeln!(writer, "  {}: {:?}", record.id, record.data)?;
+        }
+
+        let mut indexes: Vec<_> = self
+            .indexes
+            .read()
+            .unwrap()
+            .iter()
+            .filter_map(Weak::upgrade)
+            .collect();
+        indexes.sort_by_key(|index| index.tree().name());
+
+        for index in indexes {
+            let tree = index.tree();
+            let name = String::from_utf8_lossy(&tree.name()).into_owned();
+            writeln!(writer, "index {} ({} keys)", name, tree.len())?;
+
+            for entry in tree.iter() {
+                let (key, ids) = entry?;
+                let ids = decode_posting(&ids)?;
+
+                let key: String = key
+                    .iter()
+                    .flat_map(|byte| ascii::escape_default(*byte))
+                    .map(char::from)
+                    .collect();
+                writeln!(writer, "  b\"{}\": {:?}", key, ids)?;
+            }
+        }
+
+        Ok(())
+    }
+
     /// Delete a record by its ID.
     ///
     /// # Arguments
@@ -210,71 +1469,572 @@ where
     ///
     /// An [`Option`] containing the deleted record if it exists, or [`None`] otherwise.
     pub fn delete(&self, id: u64) -> DbResult<Option<Record<T>>> {
-        let serialized_id = encode(&id)?;
-
-        // We don't need to lock table even though we write because deleting will never invalidate unique constraint.
-        if let Some(serialized) = self.root.read().unwrap().remove(serialized_id)? {
-            let record = Record {
-                id,
-                data: decode(&serialized)?,
-            };
-
-            self.dispatch_event(Event::Remove(record.clone()));
+        let _guard = self.check_writable()?;
+        let root = self.lock_root();
 
+        if let Some(record) = self.tree_select(&root, id)? {
+            self.commit(&root, vec![Event::Remove(record.clone())])?;
             Ok(Some(record))
         } else {
             Ok(None)
         }
     }
 
-    /// Update one or more records by their IDs.
+    /// Delete records by their IDs in a single commit, so either all or none of them are deleted.
+    ///
+    /// # Arguments
+    ///
+    /// * `ids` - The IDs of the records to delete.
+    ///
+    /// # Returns
+    ///
+    /// All deleted records, IDs without a record are skipped.
+    pub fn delete_many(&self, ids: &[u64]) -> DbResult<Vec<Record<T>>> {
+        self.delete_if(ids, |_| true)
+    }
+
+    /// Delete the records with the given IDs which pass a check, in a single commit.
+    pub(crate) fn delete_if(
+        &self,
+        ids: &[u64],
+        predicate: impl Fn(&T) -> bool,
+    ) -> DbResult<Vec<Record<T>>> {
+        let _guard = self.check_writable()?;
+        let root = self.lock_root();
+
+        let mut records = vec![];
+        for id in ids {
+            if let Some(record) = self.tree_select(&root, *id)? {
+                if predicate(&record.data) {
+                    records.push(record);
+                }
+            }
+        }
+
+        self.commit(&root, records.iter().cloned().map(Event::Remove).collect())?;
+
+        Ok(records)
+    }
+
+    /// Delete all records of the table in a single transaction.
+    ///
+    /// The indexes, columns and lazy fields of the table are emptied in the same
+    /// transaction and its stats are reset. Subscribers receive a single
+    /// [`Event::Cleared`] instead of an event for each record.
+    ///
+    /// # Returns
+    ///
+    /// The amount of deleted records.
+    pub fn clear(&self) -> DbResult<usize> {
+        let _guard = self.check_writable()?;
+        let root = self.lock_root();
+
+        let columns: Vec<_> = self
+            .columns
+            .read()
+            .unwrap()
+            .iter()
+            .filter_map(Weak::upgrade)
+            .collect();
+        let indexes: Vec<_> = self
+            .indexes
+            .read()
+            .unwrap()
+            .iter()
+            .filter_map(Weak::upgrade)
+            .collect();
+
+        // Columns and lazy fields which aren't open are emptied as well.
+        let mut cleared = vec![root.clone()];
+        for name in self.owned_trees.iter().keys() {
+            cleared.push(self.engine.open_tree(name?)?);
+        }
+        cleared.extend(indexes.iter().map(|index| index.tree().clone()));
+
+        // Transactions can't clear trees, every key is removed instead.
+        let keys = cleared
+            .iter()
+            .map(|tree| tree.iter().keys().collect::<Result<Vec<_>, _>>())
+            .collect::<Result<Vec<_>, _>>()?;
+        let count = keys[0].len();
+        if count == 0 {
+            return Ok(0);
+        }
+
+        let seq = self.engine.generate_id()?;
+        let head = encode(&seq)?;
+        let stats = encode(&(0u64, 0u64))?;
+        // Indexes which are rebuilt replay the clear from the journal.
+        let entry = match indexes.iter().any(|index| index.is_rebuilding()) {
+            true => Some(encode(&Event::<T>::Cleared)?),
+            false => None,
+        };
+        let stale_sketches = Self::stale_sketches(&indexes)?;
+
+        let mut trees = cleared;
+        trees.push(self.versions.clone());
+        trees.push(self.journal.entries.clone());
+        trees.push(self.journal.cursors.clone());
+        trees.push(self.sketches.clone());
+
+        let trees: Vec<&Tree> = trees.iter().collect();
+        trees.as_slice().transaction(
+            |trees| -> ConflictableTransactionResult<(), TinyBaseError> {
+                let [cleared @ .., versions, journal, cursors, sketches] = trees.as_slice() else {
+                    unreachable!("the trees of the table are followed by four others");
+                };
+
+                for (tree, keys) in cleared.iter().zip(&keys) {
+                    for key in keys {
+                        tree.remove(key)?;
+                    }
+                }
+
+                versions.insert(STATS_KEY, stats.as_slice())?;
+                match &entry {
+                    Some(entry) => journal.insert(head.as_slice(), entry.as_slice())?,
+                    None => cursors.insert(self.journal.name(), head.as_slice())?,
+                };
+
+                for column in &columns {
+                    cursors.insert(column.tree().name(), head.as_slice())?;
+                }
+                for index in &indexes {
+                    cursors.insert(index.tree().name(), head.as_slice())?;
+                }
+                for (name, stale) in &stale_sketches {
+                    sketches.insert(name, stale.as_slice())?;
+                }
+
+                Ok(())
+            },
+        )?;
+
+        for index in &indexes {
+            index.cleared(seq);
+        }
+        self.dispatch_event(seq, Event::Cleared);
+
+        Ok(count)
+    }
+
+    /// Update one or more records by their IDs.
+    ///
+    /// # Arguments
+    ///
+    /// * `ids` - The IDs of the records to update.
+    /// * `updater` - Closure to generate the new data based on the old data.
+    ///
+    /// # Returns
+    ///
+    /// All updated records.
+    pub fn update(&self, ids: &[u64], updater: impl Fn(T) -> T) -> DbResult<Vec<Record<T>>> {
+        Ok(self
+            .update_returning_old(ids, updater)?
+            .into_iter()
+            .map(|(_, new)| new)
+            .collect())
+    }
+
+    /// Update one or more records by their IDs, like [`TableInner::update`].
+    ///
+    /// # Returns
+    ///
+    /// The old and the new version of all updated records.
+    pub fn update_returning_old(
+        &self,
+        ids: &[u64],
+        updater: impl Fn(T) -> T,
+    ) -> DbResult<Vec<(Record<T>, Record<T>)>> {
+        self.update_checked(ids, None, updater)
+    }
+
+    /// Update records by their IDs whose current data passes a check.
+    ///
+    /// The check and the write happen under the write lock of the table, and the
+    /// commit compares the stored data of the records with the checked data, so no
+    /// other writer can change a record in between.
+    ///
+    /// # Arguments
+    ///
+    /// * `ids` - The IDs of the records to update.
+    /// * `predicate` - Whether a record is updated, given its current data.
+    /// * `updater` - Closure to generate the new data based on the old data.
+    ///
+    /// # Returns
+    ///
+    /// All updated records, or [`TinyBaseError::Conflict`] if a record was changed
+    /// concurrently, nothing is updated then.
+    pub fn update_if(
+        &self,
+        ids: &[u64],
+        predicate: impl Fn(&T) -> bool,
+        updater: impl Fn(T) -> T,
+    ) -> DbResult<Vec<Record<T>>> {
+        Ok(self
+            .update_checked(ids, Some(&predicate), updater)?
+            .into_iter()
+            .map(|(_, new)| new)
+            .collect())
+    }
+
+    /// Update records by their IDs, only the ones passing a check if there is one.
+    fn update_checked(
+        &self,
+        ids: &[u64],
+        predicate: Option<&dyn Fn(&T) -> bool>,
+        updater: impl Fn(T) -> T,
+    ) -> DbResult<Vec<(Record<T>, Record<T>)>> {
+        let _guard = self.check_writable()?;
+        let root = self.lock_root();
+
+        let mut records = vec![];
+        let mut events = vec![];
+        let mut expected = vec![];
+        for id in ids {
+            if let Some(old) = self.tree_select(&root, *id)? {
+                if let Some(predicate) = predicate {
+                    if !predicate(&old.data) {
+                        continue;
+                    }
+
+                    if let Some(data) = root.get(encode(id)?)? {
+                        expected.push((*id, data));
+                    }
+                }
+
+                let record = Record {
+                    id: old.id,
+                    data: updater(old.data.clone()),
+                };
+
+                events.push(Event::Update {
+                    id: old.id,
+                    old_data: old.data.clone(),
+                    new_data: record.data.clone(),
+                });
+                records.push((old, record));
+            }
+        }
+
+        let additional: Vec<T> = records.iter().map(|(_, r)| r.data.clone()).collect();
+        for (_, record) in &records {
+            self.check_constraint(&root, record, &additional)?;
+        }
+
+        self.commit_checked(&root, events, &expected)?;
+
+        Ok(records)
+    }
+
+    /// Insert a value or replace the record with the same key.
+    ///
+    /// The key is looked up and the value written while holding the write lock of
+    /// the table, so two concurrent upserts of the same key can't both insert.
+    ///
+    /// # Arguments
+    ///
+    /// * `key_index` - The index whose key identifies the record of the value.
+    /// * `value` - The value to write.
+    ///
+    /// # Returns
+    ///
+    /// [`UpsertOutcome::Inserted`] or [`UpsertOutcome::Updated`], failures are returned as errors.
+    pub fn upsert<I: IndexType + 'static>(
+        &self,
+        key_index: &Index<T, I>,
+        value: T,
+    ) -> DbResult<UpsertOutcome> {
+        let _guard = self.check_writable()?;
+        let root = self.lock_root();
+
+        let keys = key_index.generate_keys(&value)?;
+        let old = match key_index.keys_ids(keys.clone())?.as_slice() {
+            [] => None,
+            [id] => self.tree_select(&root, *id)?,
+            _ => return Err(ambiguous_key(key_index, keys)),
+        };
+
+        match old {
+            None => {
+                let record = Record {
+                    id: self.generate_id(&root)?,
+                    data: value,
+                };
+
+                self.check_constraint(&root, &record, &[])?;
+                self.commit(&root, vec![Event::Insert(record.clone())])?;
+
+                Ok(UpsertOutcome::Inserted(record.id))
+            }
+            Some(old) => {
+                let record = self.replace(&root, old, value)?;
+                Ok(UpsertOutcome::Updated(record.id))
+            }
+        }
+    }
+
+    /// Insert values or replace the record with the same key.
+    ///
+    /// All values are written in a single commit. A value which fails, because of a
+    /// constraint or because multiple records have its key, is skipped without
+    /// stopping the batch. Values with the same key replace each other in order.
+    ///
+    /// # Arguments
+    ///
+    /// * `values` - The values to write.
+    /// * `key_index` - The index whose key identifies the record of a value.
+    ///
+    /// # Returns
+    ///
+    /// The [`UpsertOutcome`] of every value, in order.
+    pub fn upsert_many<I: IndexType + 'static>(
+        &self,
+        values: impl IntoIterator<Item = T>,
+        key_index: &Index<T, I>,
+    ) -> DbResult<Vec<UpsertOutcome>> {
+        let _guard = self.check_writable()?;
+        let root = self.lock_root();
+
+        let mut batch = UpsertBatch {
+            ids: vec![],
+            data: vec![],
+            old: vec![],
+            positions: HashMap::new(),
+            keys: HashMap::new(),
+        };
+
+        let outcomes = values
+            .into_iter()
+            .map(|value| {
+                self.stage_upsert(&root, key_index, &mut batch, value)
+                    .unwrap_or_else(UpsertOutcome::Failed)
+            })
+            .collect();
+
+        let events: Vec<_> = batch
+            .ids
+            .into_iter()
+            .zip(batch.data)
+            .zip(batch.old)
+            .map(|((id, data), old)| match old {
+                Some(old_data) => Event::Update {
+                    id,
+                    old_data,
+                    new_data: data,
+                },
+                None => Event::Insert(Record { id, data }),
+            })
+            .collect();
+
+        if !events.is_empty() {
+            self.commit(&root, events)?;
+        }
+
+        Ok(outcomes)
+    }
+
+    /// Add a value of [`TableInner::upsert_many`] to the pending records of the batch,
+    /// replacing the stored or pending record with its key.
+    fn stage_upsert<I: IndexType + 'static>(
+        &self,
+        tree: &Tree,
+        key_index: &Index<T, I>,
+        batch: &mut UpsertBatch<T>,
+        value: T,
+    ) -> DbResult<UpsertOutcome> {
+        let keys = key_index.generate_keys(&value)?;
+
+        // Pending records replace their stored version, including its keys.
+        let mut ids: Vec<u64> = keys
+            .iter()
+            .filter_map(|key| batch.keys.get(key).copied())
+            .collect();
+        ids.extend(
+            key_index
+                .keys_ids(keys.clone())?
+                .into_iter()
+                .filter(|id| !batch.positions.contains_key(id)),
+        );
+        ids.sort_unstable();
+        ids.dedup();
+
+        let (existing, stored) = match ids.as_slice() {
+            [] => (None, None),
+            [id] if batch.positions.contains_key(id) => (Some(*id), None),
+            [id] => match self.tree_select(tree, *id)? {
+                Some(old) => (Some(*id), Some(old.data)),
+                None => (None, None),
+            },
+            _ => return Err(ambiguous_key(key_index, keys)),
+        };
+
+        let record = Record {
+            id: match existing {
+                Some(id) => id,
+                None => self.generate_id(tree)?,
+            },
+            data: value,
+        };
+
+        // The value takes the place of the pending data of the record while it's checked.
+        let position = batch.positions.get(&record.id).copied();
+        let previous = match position {
+            Some(position) => Some(std::mem::replace(
+                &mut batch.data[position],
+                record.data.clone(),
+            )),
+            None => {
+                batch.data.push(record.data.clone());
+                None
+            }
+        };
+
+        let checked = self.check_constraint_replacing(tree, &record, &batch.data, &|id| {
+            batch.positions.contains_key(&id)
+        });
+
+        if let Err(err) = checked {
+            match (position, previous) {
+                (Some(position), Some(previous)) => batch.data[position] = previous,
+                _ => {
+                    batch.data.pop();
+                }
+            }
+            return Err(err);
+        }
+
+        match previous {
+            // Keys of the replaced pending data no longer identify the record.
+            Some(previous) => {
+                for key in key_index.generate_keys(&previous)? {
+                    batch.keys.remove(&key);
+                }
+            }
+            None => {
+                batch.positions.insert(record.id, batch.ids.len());
+                batch.ids.push(record.id);
+                batch.old.push(stored);
+            }
+        }
+
+        for key in keys {
+            batch.keys.insert(key, record.id);
+        }
+
+        Ok(match existing {
+            Some(id) => UpsertOutcome::Updated(id),
+            None => UpsertOutcome::Inserted(record.id),
+        })
+    }
+
+    /// Replace the data of a record if it wasn't written since it was read.
+    ///
+    /// Every write to a record changes its version, so a read-modify-write based on
+    /// [`TableInner::select_versioned`] fails instead of losing concurrent updates.
+    ///
+    /// # Arguments
+    ///
+    /// * `id` - The ID of the record to update.
+    /// * `expected_version` - The version of the record the new value is based on.
+    /// * `value` - The new data of the record.
+    ///
+    /// # Returns
+    ///
+    /// The new version of the record, [`None`] if there is no record with the ID, or
+    /// [`TinyBaseError::Conflict`] if the record has another version.
+    pub fn update_cas(&self, id: u64, expected_version: u64, value: T) -> DbResult<Option<u64>> {
+        let _guard = self.check_writable()?;
+        let root = self.lock_root();
+
+        let Some(old) = self.tree_select(&root, id)? else {
+            return Ok(None);
+        };
+        if self.version(id)? != expected_version {
+            return Err(TinyBaseError::Conflict(id));
+        }
+
+        self.replace(&root, old, value)?;
+
+        self.version(id).map(Some)
+    }
+
+    /// Change part of a record.
+    ///
+    /// # Arguments
+    ///
+    /// * `id` - The ID of the record to change.
+    /// * `patch` - The [`Patch`] to apply to the data of the record.
+    ///
+    /// # Returns
+    ///
+    /// The patched record, or [`None`] if there is no record with the ID.
+    pub fn patch(&self, id: u64, patch: impl Patch<T>) -> DbResult<Option<Record<T>>> {
+        let _guard = self.check_writable()?;
+        let root = self.lock_root();
+
+        let Some(old) = self.tree_select(&root, id)? else {
+            return Ok(None);
+        };
+
+        let mut data = old.data.clone();
+        patch.apply(&mut data)?;
+
+        self.replace(&root, old, data).map(Some)
+    }
+
+    /// Replace the data of a stored record after checking the constraints.
     ///
     /// # Arguments
     ///
-    /// * `ids` - The IDs of the records to update.
-    /// * `updater` - Closure to generate the new data based on the old data.
+    /// * `tree` - The records tree, obtained via the write lock.
+    /// * `old` - The stored record.
+    /// * `data` - The new data of the record.
     ///
     /// # Returns
     ///
-    /// All updated records.
-    pub fn update(&self, ids: &[u64], updater: fn(T) -> T) -> DbResult<Vec<Record<T>>> {
-        let root = self.root.write().unwrap();
-
-        let mut records = vec![];
-        for id in ids {
-            if let Some(old) = self.tree_select(&root, *id)? {
-                records.push(Record {
-                    id: old.id,
-                    data: updater(old.data),
-                });
-            }
-        }
+    /// The written record.
+    fn replace(&self, tree: &Tree, old: Record<T>, data: T) -> DbResult<Record<T>> {
+        let record = Record { id: old.id, data };
+        self.check_constraint(tree, &record, &[])?;
 
-        let additional: Vec<T> = records.iter().map(|r| r.data.clone()).collect();
-        for record in &records {
-            self.check_constraint(&root, record, &additional)?;
-        }
+        self.commit(
+            tree,
+            vec![Event::Update {
+                id: old.id,
+                old_data: old.data,
+                new_data: record.data.clone(),
+            }],
+        )?;
 
-        let mut updated = vec![];
-        for record in records {
-            root.update_and_fetch(encode(&record.id)?, |old_value| {
-                if let Some(old_value) = old_value {
-                    updated.push(record.clone());
+        Ok(record)
+    }
 
-                    self.dispatch_event(Event::Update {
-                        id: record.id.clone(),
-                        old_data: decode(old_value).unwrap(),
-                        new_data: record.data.clone(),
-                    });
+    /// Write to the table in a transaction.
+    ///
+    /// The table is locked for writing while the closure runs. Its writes are
+    /// applied in a single commit together with the index and journal entries, so
+    /// either all of them are applied or none is.
+    ///
+    /// # Arguments
+    ///
+    /// * `f` - Closure making the writes, nothing is applied if it fails.
+    ///
+    /// # Returns
+    ///
+    /// The result of the closure.
+    pub fn transaction<R>(
+        &self,
+        f: impl FnOnce(&mut Transaction<'_, T>) -> DbResult<R>,
+    ) -> DbResult<R> {
+        let _guard = self.check_writable()?;
+        let root = self.lock_root();
 
-                    Some(encode(&record.data).unwrap())
-                } else {
-                    None
-                }
-            })?;
-        }
+        let mut writes = PendingWrites::default();
+        let result = f(&mut Transaction::new(self, &root, &mut writes))?;
+        self.commit(&root, writes.events)?;
 
-        Ok(updated)
+        Ok(result)
     }
 
     /// Add a constraint to the table.
@@ -287,13 +2047,14 @@ where
 
         match &constraint.0 {
             // Check if index has already been added if constraint is unique.
-            ConstraintInner::Unique(index) => {
+            ConstraintInner::Unique(index, None) => {
                 let index_name = index.idx_name();
+                self.set_unique(&index_name)?;
 
                 if constraint_map
                     .iter()
                     .find(|idx| {
-                        if let ConstraintInner::Unique(unique) = &idx.0 {
+                        if let ConstraintInner::Unique(unique, None) = &idx.0 {
                             unique.idx_name() == index_name
                         } else {
                             false
@@ -304,17 +2065,351 @@ where
                     constraint_map.push(constraint);
                 }
             }
-            ConstraintInner::Check(_) => constraint_map.push(constraint),
+            ConstraintInner::Unique(index, Some(_)) => {
+                self.set_unique(&index.idx_name())?;
+                constraint_map.push(constraint);
+            }
+            ConstraintInner::Check(..) => constraint_map.push(constraint),
+        };
+
+        Ok(())
+    }
+
+    /// Record in the metadata of an index that it has a unique constraint.
+    ///
+    /// # Arguments
+    ///
+    /// * `tree` - The name of the index tree.
+    fn set_unique(&self, tree: &str) -> DbResult<()> {
+        let Some(name) = tree.strip_prefix(&self.index_tree("")) else {
+            return Ok(());
         };
 
+        if let Some(bytes) = self.index_meta.get(name)? {
+            let mut metadata: IndexMetadata = decode(&bytes)?;
+            metadata.unique = true;
+            self.index_meta.insert(name, encode(&metadata)?)?;
+        }
+
         Ok(())
     }
 
     /// Dispatch event to all receivers.
-    fn dispatch_event(&self, event: Event<T>) {
-        for sender in self.senders.read().unwrap().values() {
-            sender.send(event.clone()).unwrap();
+    fn dispatch_event(&self, seq: u64, event: Event<T>) {
+        let kind = event.kind();
+        for (sender, kinds) in self.senders.read().unwrap().values() {
+            if kinds.contains(kind) {
+                sender.send((seq, event.clone())).unwrap();
+            }
+        }
+    }
+}
+
+/// Move the lazy values out of the data of an event, so the journal doesn't store them again.
+pub(crate) fn detach_event<T: TableType>(
+    event: &Event<T>,
+    lazy_fields: &[Arc<dyn AnyLazyField<T>>],
+) -> DbResult<Event<T>> {
+    let detach = |data: &T, id: u64| -> DbResult<T> {
+        let mut data = data.clone();
+        for field in lazy_fields {
+            field.detach(&mut data, id)?;
+        }
+        Ok(data)
+    };
+
+    Ok(match event {
+        Event::Insert(record) => Event::Insert(Record {
+            id: record.id,
+            data: detach(&record.data, record.id)?,
+        }),
+        Event::Remove(record) => Event::Remove(Record {
+            id: record.id,
+            data: detach(&record.data, record.id)?,
+        }),
+        Event::Update {
+            id,
+            old_data,
+            new_data,
+        } => Event::Update {
+            id: *id,
+            old_data: detach(old_data, *id)?,
+            new_data: detach(new_data, *id)?,
+        },
+        Event::Cleared => Event::Cleared,
+    })
+}
+
+/// Writes of a commit to a table, prepared by [`TableInner::prepare_commit`].
+pub(crate) struct PreparedCommit<'a, T: TableType + 'static> {
+    table: &'a TableInner<T>,
+    /// The table, its columns, lazy fields, versions and indexes, followed by the journal,
+    /// the cursors and the sketches.
+    trees: Vec<Tree>,
+    columns: Vec<Arc<dyn AnyColumn<T>>>,
+    indexes: Vec<Arc<dyn AnyIndexInner<T>>>,
+    index_writes: Vec<IndexWrite>,
+    writes: Vec<EventWrite>,
+    /// IDs of records with their encoded ID and expected data.
+    expected: Vec<(u64, Vec<u8>, IVec)>,
+    /// Change of the amount of records and their stored size.
+    stats_delta: (i64, i64),
+    /// Whether journal entries are written, otherwise the journal is truncated up to the commit.
+    journaled: bool,
+    /// Names of indexes whose saved sketch is marked stale, with the encoded stale state.
+    stale_sketches: Vec<(IVec, Vec<u8>)>,
+    /// Encoded sequence of the last event.
+    head: Vec<u8>,
+    sequence: Vec<u64>,
+    events: Vec<Event<T>>,
+}
+
+/// Type erased [`PreparedCommit`], so commits to tables of different types can be
+/// applied in one transaction.
+pub(crate) trait AnyCommit {
+    /// The trees written by the commit, in the order expected by [`AnyCommit::apply`].
+    fn trees(&self) -> &[Tree];
+    /// Write the commit within a transaction over its trees.
+    fn apply(
+        &self,
+        trees: &[TransactionalTree],
+    ) -> ConflictableTransactionResult<(), TinyBaseError>;
+    /// Update the indexes and dispatch the events after the transaction succeeded.
+    fn finish(self: Box<Self>);
+}
+
+impl<T: TableType> PreparedCommit<'_, T> {
+    /// Update the indexes and dispatch the events after the transaction succeeded.
+    pub(crate) fn finish(self) {
+        let last = *self.sequence.last().unwrap();
+        for (index, write) in self.indexes.iter().zip(&self.index_writes) {
+            index.committed(last, write);
+        }
+
+        for (seq, event) in self.sequence.into_iter().zip(self.events) {
+            self.table.dispatch_event(seq, event);
+        }
+    }
+}
+
+impl<T: TableType> AnyCommit for PreparedCommit<'_, T> {
+    fn trees(&self) -> &[Tree] {
+        &self.trees
+    }
+
+    fn apply(
+        &self,
+        trees: &[TransactionalTree],
+    ) -> ConflictableTransactionResult<(), TinyBaseError> {
+        let (sketches, trees) = trees.split_last().unwrap();
+        let (cursors, trees) = trees.split_last().unwrap();
+        let (journal, trees) = trees.split_last().unwrap();
+        let (trees, index_trees) = trees.split_at(trees.len() - self.indexes.len());
+
+        for (id, key, data) in &self.expected {
+            if trees[0].get(key)?.as_ref() != Some(data) {
+                return Err(ConflictableTransactionError::Abort(
+                    TinyBaseError::Conflict(*id),
+                ));
+            }
+        }
+
+        for (key, values, seq, entry) in &self.writes {
+            for (tree, value) in trees.iter().zip(values) {
+                match value {
+                    TreeWrite::Insert(value) => {
+                        tree.insert(key.as_slice(), value.as_slice())?;
+                    }
+                    TreeWrite::Remove => {
+                        tree.remove(key.as_slice())?;
+                    }
+                    TreeWrite::Keep => {}
+                }
+            }
+
+            if self.journaled {
+                journal.insert(seq.as_slice(), entry.as_slice())?;
+            }
+        }
+
+        if !self.journaled {
+            cursors.insert(self.table.journal.name(), self.head.as_slice())?;
+        }
+
+        for (name, stale) in &self.stale_sketches {
+            sketches.insert(name, stale.as_slice())?;
+        }
+
+        let versions = trees.last().unwrap();
+        let (count, size): (u64, u64) = match versions.get(STATS_KEY)? {
+            Some(stats) => decode(&stats).map_err(ConflictableTransactionError::Abort)?,
+            None => (0, 0),
+        };
+        let stats = (
+            count.saturating_add_signed(self.stats_delta.0),
+            size.saturating_add_signed(self.stats_delta.1),
+        );
+        versions.insert(
+            STATS_KEY,
+            encode(&stats).map_err(ConflictableTransactionError::Abort)?,
+        )?;
+
+        for column in &self.columns {
+            cursors.insert(column.tree().name(), self.head.as_slice())?;
+        }
+
+        for ((index, write), index_tree) in
+            self.indexes.iter().zip(&self.index_writes).zip(index_trees)
+        {
+            write.apply(index_tree)?;
+            cursors.insert(index.tree().name(), self.head.as_slice())?;
+        }
+
+        Ok(())
+    }
+
+    fn finish(self: Box<Self>) {
+        PreparedCommit::finish(*self)
+    }
+}
+
+/// Name of the tree listing the column and lazy field trees of a table.
+fn owned_trees_name(table: &str) -> String {
+    format!("{}_trees", table)
+}
+
+/// Drop the trees of a table with its indexes, columns, lazy fields and journal,
+/// and remove their cursors and sketches.
+///
+/// Only the trees listed in the metadata of the table are dropped, trees of other
+/// tables whose names start with the name of the table are kept.
+///
+/// # Returns
+///
+/// Whether the table existed.
+pub(crate) fn drop_table_trees(engine: &Db, name: &str) -> DbResult<bool> {
+    let cursors = engine.open_tree(CURSORS)?;
+    let sketches = engine.open_tree(SKETCHES)?;
+    let index_meta = format!("{}_indexes", name);
+    let owned_trees = owned_trees_name(name);
+
+    let mut trees = vec![];
+    for index in engine.open_tree(&index_meta)?.iter().keys() {
+        let index = index?;
+        trees.push(format!("{}_idx_{}", name, String::from_utf8_lossy(&index)).into_bytes());
+    }
+    for tree in engine.open_tree(&owned_trees)?.iter().keys() {
+        trees.push(tree?.to_vec());
+    }
+
+    #[cfg(feature = "script")]
+    let scripts = engine.open_tree(crate::script::SCRIPTS)?;
+    for tree in trees {
+        cursors.remove(&tree)?;
+        sketches.remove(&tree)?;
+        #[cfg(feature = "script")]
+        scripts.remove(&tree)?;
+        engine.drop_tree(&tree)?;
+    }
+
+    let journal = journal_name(name);
+    cursors.remove(&journal)?;
+    engine.drop_tree(&journal)?;
+    engine.drop_tree(index_meta)?;
+    engine.drop_tree(owned_trees)?;
+    engine.drop_tree(format!("{}_versions", name))?;
+
+    Ok(engine.drop_tree(name)?)
+}
+
+/// Type erased table, used by [`crate::TinyBase`] to keep track of opened tables.
+pub(crate) trait AnyTable: Send + Sync {
+    /// Name of the table tree.
+    fn table_name(&self) -> &str;
+    /// Replace the table contents with the contents of `source`.
+    /// Every difference is dispatched as an event so indexes stay consistent.
+    fn refresh_from(&self, source: &Tree) -> DbResult<()>;
+    /// Reject further writes, commit all indexes and stop dispatching events.
+    fn close(&self) -> Vec<TinyBaseError>;
+    /// Cast to [`Any`] so the table can be downcast to its concrete type.
+    fn into_any(self: Arc<Self>) -> Arc<dyn Any + Send + Sync>;
+    /// Acquire the table lock exclusively until [`AnyTable::release_exclusive`] is called.
+    fn acquire_exclusive(&self);
+    fn release_exclusive(&self);
+}
+
+impl<T: TableType> AnyTable for TableInner<T> {
+    fn table_name(&self) -> &str {
+        &self.name
+    }
+
+    fn into_any(self: Arc<Self>) -> Arc<dyn Any + Send + Sync> {
+        self
+    }
+
+    fn acquire_exclusive(&self) {
+        std::mem::forget(self.lock.exclusive());
+    }
+
+    fn release_exclusive(&self) {
+        self.lock.release_exclusive();
+    }
+
+    fn close(&self) -> Vec<TinyBaseError> {
+        // Wait for running writes to finish before closing.
+        let Some(_guard) = self.lock.try_exclusive() else {
+            return vec![TinyBaseError::TableInUse(self.name.clone())];
+        };
+        self.closed.store(true, Ordering::Release);
+
+        let mut errors: Vec<TinyBaseError> = self
+            .indexes
+            .read()
+            .unwrap()
+            .iter()
+            .filter_map(Weak::upgrade)
+            .filter_map(|index| index.close().err())
+            .collect();
+
+        if errors.is_empty() {
+            if let Err(err) = self.truncate_journal() {
+                errors.push(err);
+            }
+        }
+
+        self.senders.write().unwrap().clear();
+
+        errors
+    }
+
+    fn refresh_from(&self, source: &Tree) -> DbResult<()> {
+        let _guard = self.lock.shared();
+        let root = self.lock_root();
+        let mut events = vec![];
+
+        for entry in root.iter() {
+            let (key, old_value) = entry?;
+            let id = decode(&key)?;
+
+            match source.get(&key)? {
+                Some(new_value) if new_value != old_value => events.push(Event::Update {
+                    id,
+                    old_data: self.decode_record(id, &old_value)?.data,
+                    new_data: self.decode_record(id, &new_value)?.data,
+                }),
+                Some(_) => {}
+                None => events.push(Event::Remove(self.decode_record(id, &old_value)?)),
+            }
         }
+
+        for entry in source.iter() {
+            let (key, value) = entry?;
+            if !root.contains_key(&key)? {
+                events.push(Event::Insert(self.decode_record(decode(&key)?, &value)?));
+            }
+        }
+
+        self.commit(&root, events)
     }
 }
 
@@ -336,6 +2431,143 @@ mod tests {
         assert_eq!(record.data, "test_value");
     }
 
+    #[test]
+    fn table_drop_index() {
+        let db = TinyBase::new(None, true);
+        let table: Table<String> = db.open_table("test_table").unwrap();
+        let value = table
+            .create_index("value", |value| value.to_owned())
+            .unwrap();
+        let length = table.create_index("length", |value| value.len()).unwrap();
+        table
+            .constraint(Constraint::unique(&value))
+            .expect("Constraint failed");
+
+        table.insert("a".to_string()).unwrap();
+        assert_eq!(table.list_indexes().unwrap(), ["length", "value"]);
+
+        assert!(table.drop_index("value").unwrap());
+        assert!(!table.drop_index("value").unwrap());
+        assert_eq!(table.list_indexes().unwrap(), ["length"]);
+
+        // The unique constraint was dropped with its index.
+        table.insert("a".to_string()).unwrap();
+        assert_eq!(length.select_ids(&1).unwrap().len(), 2);
+
+        let value = table
+            .create_index("value", |value| value.to_owned())
+            .unwrap();
+        assert_eq!(value.select_ids(&"a".to_string()).unwrap().len(), 2);
+
+        // Indexes stored before their metadata was are listed once their table is opened.
+        db.engine.open_tree("legacy_table_idx_value").unwrap();
+        let legacy: Table<String> = db.open_table("legacy_table").unwrap();
+        assert_eq!(legacy.list_indexes().unwrap(), ["value"]);
+        assert!(legacy.drop_index("value").unwrap());
+        assert!(legacy.list_indexes().unwrap().is_empty());
+    }
+
+    #[test]
+    fn table_index_rename() {
+        let db = TinyBase::new(None, true);
+        let table: Table<String> = db.open_table("test_table").unwrap();
+        let value = table
+            .create_multi_index("value", |value: &String| vec![value.to_owned()])
+            .unwrap();
+        table.constraint(Constraint::unique(&value)).unwrap();
+        let id = table.insert("a".to_string()).unwrap();
+
+        let metadata = table.index_metadata("value").unwrap().unwrap();
+        assert_eq!(metadata.key_type, "alloc::string::String");
+        assert!(metadata.multi_key && metadata.unique);
+
+        let length = table.create_index("length", |value| value.len()).unwrap();
+        assert!(matches!(
+            value.rename("length"),
+            Err(TinyBaseError::IndexExists(_))
+        ));
+
+        let renamed = value.rename("name").unwrap();
+        assert_eq!(table.list_indexes().unwrap(), ["length", "name"]);
+        assert_eq!(renamed.select_ids(&"a".to_string()).unwrap(), [id]);
+
+        let names: Vec<_> = table
+            .indexes_metadata()
+            .unwrap()
+            .into_iter()
+            .map(|metadata| metadata.name)
+            .collect();
+        assert_eq!(names, ["length", "name"]);
+        let renamed_metadata = table.index_metadata("name").unwrap().unwrap();
+        assert_eq!(renamed_metadata.created_at, metadata.created_at);
+
+        // The unique constraint and writes moved to the renamed index.
+        assert!(table.insert("a".to_string()).is_err());
+        let id2 = table.insert("b".to_string()).unwrap();
+        assert_eq!(renamed.select_ids(&"b".to_string()).unwrap(), [id2]);
+        assert_eq!(length.select_ids(&1).unwrap(), [id, id2]);
+    }
+
+    #[test]
+    fn table_insert_many() {
+        let db = TinyBase::new(None, true);
+        let table: Table<String> = db.open_table("test_table").unwrap();
+        let value = table
+            .create_index("value", |value| value.to_owned())
+            .unwrap();
+        table.constraint(Constraint::unique(&value)).unwrap();
+
+        let ids = table
+            .insert_many(vec!["a".to_string(), "b".to_string()])
+            .unwrap();
+        assert_eq!(ids.len(), 2);
+        assert_eq!(value.select_ids(&"b".to_string()).unwrap(), [ids[1]]);
+
+        // A single violation rejects the whole batch.
+        assert!(table
+            .insert_many(vec!["c".to_string(), "a".to_string()])
+            .is_err());
+        assert!(table
+            .insert_many(vec!["d".to_string(), "d".to_string()])
+            .is_err());
+        assert!(value.select_ids(&"c".to_string()).unwrap().is_empty());
+        assert!(value.select_ids(&"d".to_string()).unwrap().is_empty());
+    }
+
+    #[test]
+    fn table_index_versioned() {
+        let path = std::env::temp_dir()
+            .join(format!("tinybase-test-versioned-{}", std::process::id()))
+            .to_string_lossy()
+            .into_owned();
+
+        {
+            let db = TinyBase::new(Some(&path), false);
+            let table: Table<String> = db.open_table("test_table").unwrap();
+            let key = table
+                .create_index_versioned("key", "v1", |value| value.len())
+                .unwrap();
+            table.insert("abc".to_string()).unwrap();
+            assert_eq!(key.select_ids(&3).unwrap().len(), 1);
+        }
+
+        let db = TinyBase::new(Some(&path), true);
+        let table: Table<String> = db.open_table("test_table").unwrap();
+
+        // The same version reuses the stored index, even with another key function.
+        let key = table
+            .create_index_versioned("key", "v1", |value| value.len() * 2)
+            .unwrap();
+        assert_eq!(key.select_ids(&3).unwrap().len(), 1);
+        drop(key);
+
+        let key = table
+            .create_index_versioned("key", "v2", |value| value.len() * 2)
+            .unwrap();
+        assert!(key.select_ids(&3).unwrap().is_empty());
+        assert_eq!(key.select_ids(&6).unwrap().len(), 1);
+    }
+
     #[test]
     fn table_delete() {
         let db = TinyBase::new(None, true);
@@ -354,6 +2586,29 @@ mod tests {
         assert!(table.select(id).unwrap().is_none());
     }
 
+    #[test]
+    fn table_delete_many() {
+        let db = TinyBase::new(None, true);
+        let table: Table<String> = db.open_table("test_table").unwrap();
+        let name = table
+            .create_index("name", |value| value.to_owned())
+            .unwrap();
+
+        let a = table.insert("a".to_string()).unwrap();
+        let b = table.insert("b".to_string()).unwrap();
+        let c = table.insert("c".to_string()).unwrap();
+
+        let deleted = table.delete_many(&[a, c, u64::MAX]).unwrap();
+        assert_eq!(
+            deleted.iter().map(|record| record.id).collect::<Vec<_>>(),
+            [a, c]
+        );
+
+        assert!(table.select(a).unwrap().is_none());
+        assert!(name.select(&"c".to_string()).unwrap().is_empty());
+        assert_eq!(name.select(&"b".to_string()).unwrap()[0].id, b);
+    }
+
     #[test]
     fn table_update() {
         let db = TinyBase::new(None, true);
@@ -375,4 +2630,303 @@ mod tests {
         assert_eq!(updated_records[1].id, id2);
         assert_eq!(updated_records[1].data, "updated_value");
     }
+
+    #[test]
+    fn table_dump_debug() {
+        let db = TinyBase::new(None, true);
+        let table: Table<String> = db.open_table("test_table").unwrap();
+        table.id_generator(crate::id::Counter::new());
+        let _index = table
+            .create_index("length", |value| value.len() as u8)
+            .unwrap();
+
+        for value in ["value1", "value2", "long_value"] {
+            table.insert(value.to_string()).unwrap();
+        }
+
+        let mut dump = vec![];
+        table.dump_debug(&mut dump).unwrap();
+        assert_eq!(
+            String::from_utf8(dump).unwrap(),
+            "table test_table (3 records)\n\
+             \x20 1: \"value1\"\n\
+             \x20 2: \"value2\"\n\
+             \x20 3: \"long_value\"\n\
+             index test_table_idx_length (2 keys)\n\
+             \x20 b\"\\x06\": [1, 2]\n\
+             \x20 b\"\\n\": [3]\n"
+        );
+    }
+
+    #[test]
+    fn table_subscribe() {
+        let db = TinyBase::new(None, true);
+        let table: Table<String> = db.open_table("test_table").unwrap();
+        let subscription = table.subscribe().unwrap();
+
+        let id = table.insert("value1".to_string()).unwrap();
+        table.delete(id).unwrap();
+
+        assert!(matches!(subscription.recv(), Ok(Event::Insert(record)) if record.id == id));
+        assert!(matches!(subscription.recv(), Ok(Event::Remove(record)) if record.id == id));
+        assert!(matches!(subscription.try_recv(), Ok(None)));
+
+        db.close().unwrap();
+        assert!(matches!(subscription.recv(), Err(TinyBaseError::Closed)));
+    }
+
+    #[test]
+    fn table_subscribe_to() {
+        let db = TinyBase::new(None, true);
+        let table: Table<String> = db.open_table("test_table").unwrap();
+        let subscription = table
+            .subscribe_to(EventKinds::REMOVE | EventKinds::UPDATE)
+            .unwrap();
+
+        let id = table.insert("value1".to_string()).unwrap();
+        table.update(&[id], |_| "value2".to_string()).unwrap();
+        table.delete(id).unwrap();
+
+        assert!(
+            matches!(subscription.recv(), Ok(Event::Update { id: updated, .. }) if updated == id)
+        );
+        assert!(matches!(subscription.recv(), Ok(Event::Remove(record)) if record.id == id));
+        assert!(matches!(subscription.try_recv(), Ok(None)));
+    }
+
+    #[test]
+    fn table_lock_exclusive() {
+        let db = TinyBase::new(None, true);
+        let table: Table<String> = db.open_table("test_table").unwrap();
+
+        let guard = table.lock_exclusive();
+        // The holder can keep writing.
+        table.insert("value1".to_string()).unwrap();
+
+        let (tx, rx) = mpsc::channel();
+        let writer = {
+            let table = table.clone();
+            std::thread::spawn(move || {
+                table.insert("value2".to_string()).unwrap();
+                tx.send(()).unwrap();
+            })
+        };
+
+        // Other writers wait for the guard to be released.
+        assert!(rx
+            .recv_timeout(std::time::Duration::from_millis(50))
+            .is_err());
+
+        drop(guard);
+        rx.recv().unwrap();
+        writer.join().unwrap();
+    }
+
+    #[test]
+    fn table_upsert_many() {
+        let db = TinyBase::new(None, true);
+        let table: Table<(String, u8)> = db.open_table("test_table").unwrap();
+        let name = table
+            .create_index("name", |(name, _)| name.to_owned())
+            .unwrap();
+        let age = table.create_index("age", |(_, age)| *age).unwrap();
+        table.constraint(Constraint::unique(&age)).unwrap();
+
+        let john = table.insert(("John".to_string(), 30)).unwrap();
+        table.insert(("Mary".to_string(), 40)).unwrap();
+        table.insert(("Bob".to_string(), 50)).unwrap();
+        table.insert(("Bob".to_string(), 51)).unwrap();
+
+        let events = table.subscribe().unwrap();
+
+        let outcomes = table
+            .upsert_many(
+                [
+                    ("John".to_string(), 31),
+                    ("Jane".to_string(), 25),
+                    ("Bill".to_string(), 25),
+                    ("Ann".to_string(), 40),
+                    ("Bob".to_string(), 52),
+                    ("Jane".to_string(), 26),
+                    ("Ann".to_string(), 30),
+                ],
+                &name,
+            )
+            .unwrap();
+
+        assert!(matches!(outcomes[0], UpsertOutcome::Updated(id) if id == john));
+        let UpsertOutcome::Inserted(jane) = outcomes[1] else {
+            panic!("{:?}", outcomes[1]);
+        };
+        // Pending values of the batch are checked like stored records.
+        assert!(matches!(
+            outcomes[2],
+            UpsertOutcome::Failed(TinyBaseError::BatchOperationConstraints { .. })
+        ));
+        assert!(matches!(
+            outcomes[3],
+            UpsertOutcome::Failed(TinyBaseError::Exists { .. })
+        ));
+        assert!(matches!(
+            &outcomes[4],
+            UpsertOutcome::Failed(err @ TinyBaseError::AmbiguousKey { .. })
+                if err.decode_key::<String>().as_deref() == Some("Bob")
+        ));
+        assert!(matches!(outcomes[5], UpsertOutcome::Updated(id) if id == jane));
+        // John's previous age is free again.
+        assert!(matches!(outcomes[6], UpsertOutcome::Inserted(_)));
+
+        assert_eq!(table.select(john).unwrap().unwrap().data.1, 31);
+        assert_eq!(table.select(jane).unwrap().unwrap().data.1, 26);
+
+        // Values replacing a pending record are merged into a single event.
+        let mut count = 0;
+        while events.try_recv().unwrap().is_some() {
+            count += 1;
+        }
+        assert_eq!(count, 3);
+    }
+
+    #[test]
+    fn table_update_cas() {
+        let db = TinyBase::new(None, true);
+        let table: Table<u32> = db.open_table("test_table").unwrap();
+        let id = table.insert(1).unwrap();
+
+        let (record, version) = table.select_versioned(id).unwrap().unwrap();
+        let (_, other) = table.select_versioned(id).unwrap().unwrap();
+
+        let version = table
+            .update_cas(id, version, record.data + 1)
+            .unwrap()
+            .unwrap();
+        assert!(matches!(
+            table.update_cas(id, other, record.data + 10),
+            Err(TinyBaseError::Conflict(conflict)) if conflict == id
+        ));
+
+        assert_eq!(
+            table.update_cas(id, version, 5).unwrap(),
+            table.select_versioned(id).unwrap().map(|(_, v)| v)
+        );
+        assert_eq!(table.select(id).unwrap().unwrap().data, 5);
+        assert_eq!(table.update_cas(id + 1, 0, 5).unwrap(), None);
+    }
+
+    #[test]
+    fn table_iter() {
+        let db = TinyBase::new(None, true);
+        let table: Table<u32> = db.open_table("test_table").unwrap();
+        let ids: Vec<u64> = (0..10).map(|i| table.insert(i).unwrap()).collect();
+
+        let records: Vec<_> = table.iter().collect::<DbResult<_>>().unwrap();
+        assert_eq!(
+            records.iter().map(|r| r.data).collect::<Vec<_>>(),
+            (0..10).collect::<Vec<_>>()
+        );
+
+        // Resume after the fifth record.
+        let rest: Vec<_> = table
+            .iter_from(ids[4] + 1)
+            .map(|record| record.unwrap().id)
+            .collect();
+        assert_eq!(rest, ids[5..]);
+    }
+
+    #[test]
+    fn table_stats() {
+        let db = TinyBase::new(None, true);
+        let table: Table<String> = db.open_table("test_table").unwrap();
+        table.create_index("name", |name| name.to_owned()).unwrap();
+        assert!(table.is_empty().unwrap());
+
+        let a = table.insert("a".to_string()).unwrap();
+        table
+            .insert_many(vec!["b".to_string(), "c".to_string()])
+            .unwrap();
+        table.update(&[a], |_| "abc".to_string()).unwrap();
+        table.delete(a).unwrap();
+
+        let stats = table.stats().unwrap();
+        assert_eq!(table.len().unwrap(), 2);
+        assert_eq!(stats.indexes, 1);
+        let size: usize = table
+            .root
+            .read()
+            .unwrap()
+            .iter()
+            .map(|entry| entry.map(|(key, value)| key.len() + value.len()).unwrap())
+            .sum();
+        assert_eq!(stats.size, size as u64);
+    }
+
+    #[test]
+    fn table_clear() {
+        let db = TinyBase::new(None, true);
+        let table: Table<String> = db.open_table("test_table").unwrap();
+        let index = table.create_index("name", |name| name.to_owned()).unwrap();
+        let column = table.create_column("length", |name| name.len()).unwrap();
+
+        table
+            .insert_many(vec!["a".to_string(), "b".to_string()])
+            .unwrap();
+        let subscription = table.subscribe().unwrap();
+        assert_eq!(table.clear().unwrap(), 2);
+
+        assert!(table.is_empty().unwrap());
+        assert!(table.iter().next().is_none());
+        assert_eq!(table.stats().unwrap().records, 0);
+        assert_eq!(table.stats().unwrap().size, 0);
+        assert_eq!(index.stats().unwrap().keys, 0);
+        assert!(column.scan().next().is_none());
+
+        // Subscribers receive a single event for the whole table.
+        assert!(matches!(subscription.try_recv(), Ok(Some(Event::Cleared))));
+        assert!(subscription.try_recv().unwrap().is_none());
+
+        table.insert("a".to_string()).unwrap();
+        assert_eq!(index.select_ids(&"a".to_string()).unwrap().len(), 1);
+        assert_eq!(table.stats().unwrap().records, 1);
+        assert_eq!(table.clear().unwrap(), 1);
+        assert_eq!(table.clear().unwrap(), 0);
+    }
+
+    #[test]
+    fn table_upsert() {
+        let db = TinyBase::new(None, true);
+        let table: Table<(String, u8)> = db.open_table("test_table").unwrap();
+        let name = table
+            .create_index("name", |(name, _)| name.to_owned())
+            .unwrap();
+
+        let workers: Vec<_> = (0..4)
+            .map(|age| {
+                let table = table.clone();
+                let name = name.clone();
+                std::thread::spawn(move || {
+                    for _ in 0..25 {
+                        table.upsert(&name, ("John".to_string(), age)).unwrap();
+                    }
+                })
+            })
+            .collect();
+        for worker in workers {
+            worker.join().unwrap();
+        }
+
+        assert_eq!(name.select(&"John".to_string()).unwrap().len(), 1);
+        assert!(matches!(
+            table.upsert(&name, ("John".to_string(), 40)).unwrap(),
+            UpsertOutcome::Updated(_)
+        ));
+        assert!(matches!(
+            table.upsert(&name, ("Jane".to_string(), 25)).unwrap(),
+            UpsertOutcome::Inserted(_)
+        ));
+
+        table.insert(("Jane".to_string(), 26)).unwrap();
+        let err = table.upsert(&name, ("Jane".to_string(), 27)).unwrap_err();
+        assert!(matches!(err, TinyBaseError::AmbiguousKey { .. }));
+        assert_eq!(err.decode_key::<String>().as_deref(), Some("Jane"));
+    }
 }
@@ -0,0 +1,221 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use crate::index::Index;
+use crate::query_builder::ConditionBuilder;
+use crate::record::Record;
+use crate::result::DbResult;
+use crate::table::{Table, TableType};
+use crate::tokenizer::Tokenizer;
+
+/// How the terms of a search query are combined.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SearchMode {
+    /// Documents must contain every term.
+    All,
+    /// Documents must contain at least one term.
+    Any,
+}
+
+/// Full-text index on a text of every record, mapping each token of the text to the
+/// records containing it.
+///
+/// Created with [`Table::create_text_index`]. The index is maintained like any other
+/// index, its tree stores a posting list for every token.
+pub struct TextIndex<T: TableType + 'static> {
+    index: Index<T, String>,
+    tokenizer: Arc<Tokenizer>,
+    text: Arc<dyn Fn(&T) -> String + Send + Sync>,
+}
+
+impl<T: TableType> Clone for TextIndex<T> {
+    fn clone(&self) -> Self {
+        Self {
+            index: self.index.clone(),
+            tokenizer: self.tokenizer.clone(),
+            text: self.text.clone(),
+        }
+    }
+}
+
+impl<T: TableType + 'static> TextIndex<T> {
+    pub(crate) fn new(
+        table: &Table<T>,
+        name: &str,
+        tokenizer: Tokenizer,
+        text: impl Fn(&T) -> String + Send + Sync + 'static,
+    ) -> DbResult<Self> {
+        let tokenizer = Arc::new(tokenizer);
+        let text: Arc<dyn Fn(&T) -> String + Send + Sync> = Arc::new(text);
+
+        let index = {
+            let tokenizer = tokenizer.clone();
+            let text = text.clone();
+            table.create_multi_index(name, move |data| tokenizer.tokenize(&text(data)))?
+        };
+
+        Ok(Self {
+            index,
+            tokenizer,
+            text,
+        })
+    }
+
+    /// The underlying index from tokens to records, for conditions on single tokens.
+    pub fn index(&self) -> &Index<T, String> {
+        &self.index
+    }
+
+    /// Search records whose text contains the terms of a query.
+    ///
+    /// # Arguments
+    ///
+    /// * `query` - The query, split into terms with the tokenizer of the index.
+    /// * `mode` - Whether all or any of the terms must be contained.
+    ///
+    /// # Returns
+    ///
+    /// All matching [`Record`] instances in ID order, none if the query has no terms.
+    pub fn search(&self, query: &str, mode: SearchMode) -> DbResult<Vec<Record<T>>> {
+        let ids = self.search_ids(&self.terms(query), mode)?;
+        self.index.read_records(ids)
+    }
+
+    /// Search records like [`TextIndex::search`], ranking them by how often they contain
+    /// the terms of the query.
+    ///
+    /// # Returns
+    ///
+    /// All matching [`Record`] instances with the total amount of occurrences of the terms
+    /// in their text, the most frequent first and ties in ID order.
+    pub fn search_ranked(
+        &self,
+        query: &str,
+        mode: SearchMode,
+    ) -> DbResult<Vec<(Record<T>, usize)>> {
+        let terms = self.terms(query);
+        let records = self.index.read_records(self.search_ids(&terms, mode)?)?;
+
+        let mut ranked: Vec<_> = records
+            .into_iter()
+            .map(|record| {
+                let mut frequencies: HashMap<String, usize> = HashMap::new();
+                for token in self.tokenizer.tokenize(&(self.text)(&record.data)) {
+                    *frequencies.entry(token).or_default() += 1;
+                }
+
+                let score: usize = terms.iter().filter_map(|term| frequencies.get(term)).sum();
+                (record, score)
+            })
+            .collect();
+
+        ranked.sort_by(|(_, left), (_, right)| right.cmp(left));
+        Ok(ranked)
+    }
+
+    /// Condition matching records whose text contains the terms of a query, to combine
+    /// a search with other conditions in a [`crate::QueryBuilder`].
+    ///
+    /// # Arguments
+    ///
+    /// * `query` - The query, split into terms with the tokenizer of the index.
+    /// * `mode` - Whether all or any of the terms must be contained.
+    pub fn condition(&self, query: &str, mode: SearchMode) -> ConditionBuilder<T> {
+        let terms = self.terms(query);
+
+        match mode {
+            SearchMode::All if !terms.is_empty() => ConditionBuilder::all(
+                terms
+                    .into_iter()
+                    .map(|term| ConditionBuilder::by(&self.index, term))
+                    .collect(),
+            ),
+            _ => ConditionBuilder::by_any(&self.index, terms),
+        }
+    }
+
+    /// Distinct terms of a query.
+    fn terms(&self, query: &str) -> Vec<String> {
+        let mut terms = self.tokenizer.tokenize(query);
+        terms.sort_unstable();
+        terms.dedup();
+        terms
+    }
+
+    fn search_ids(&self, terms: &[String], mode: SearchMode) -> DbResult<Vec<u64>> {
+        match mode {
+            SearchMode::Any => self.index.select_many_ids(terms),
+            SearchMode::All => {
+                let Some((first, rest)) = terms.split_first() else {
+                    return Ok(vec![]);
+                };
+
+                // Posting lists are sorted, so they are intersected by binary search.
+                let mut ids = self.index.select_ids(first)?;
+                for term in rest {
+                    let matches = self.index.select_ids(term)?;
+                    ids.retain(|id| matches.binary_search(id).is_ok());
+                }
+                Ok(ids)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tokenizer::Language;
+    use crate::{QueryBuilder, TinyBase};
+
+    #[test]
+    fn text_index_search() {
+        let db = TinyBase::new(None, true);
+        let table: Table<String> = db.open_table("documents").unwrap();
+        let text = table
+            .create_text_index("text", Tokenizer::language(Language::English), |document| {
+                document.clone()
+            })
+            .unwrap();
+
+        let fox = table.insert("The quick brown fox".to_string()).unwrap();
+        let dog = table.insert("A lazy dog, a lazy day".to_string()).unwrap();
+        let both = table
+            .insert("The fox and the lazy dog".to_string())
+            .unwrap();
+
+        let ids = |records: Vec<Record<String>>| -> Vec<u64> {
+            records.into_iter().map(|record| record.id).collect()
+        };
+        assert_eq!(
+            ids(text.search("FOX dog", SearchMode::All).unwrap()),
+            [both]
+        );
+        assert_eq!(
+            ids(text.search("fox dog", SearchMode::Any).unwrap()),
+            [fox, dog, both]
+        );
+        assert!(text.search("the", SearchMode::Any).unwrap().is_empty());
+
+        let ranked: Vec<_> = text
+            .search_ranked("lazy fox", SearchMode::Any)
+            .unwrap()
+            .into_iter()
+            .map(|(record, score)| (record.id, score))
+            .collect();
+        assert_eq!(ranked, [(dog, 2), (both, 2), (fox, 1)]);
+
+        table
+            .update(&[fox], |_| "A quick brown cat".to_string())
+            .unwrap();
+        let selected = QueryBuilder::new(&table)
+            .with_condition(text.condition("quick", SearchMode::All))
+            .select()
+            .unwrap();
+        assert_eq!(ids(selected), [fox]);
+        assert!(text
+            .search("brown fox", SearchMode::All)
+            .unwrap()
+            .is_empty());
+    }
+}
@@ -0,0 +1,232 @@
+use std::marker::PhantomData;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use sled::{Db, Tree};
+
+use crate::encoding::{decode, encode};
+use crate::result::{DbResult, TinyBaseError};
+
+/// Append-optimized storage of timestamped values, created with [`crate::TinyBase::time_series`].
+///
+/// Points are stored in one tree per time bucket, keyed by their timestamp, so
+/// range queries only read the buckets overlapping the window and old data is
+/// dropped by removing whole buckets. Timestamps are milliseconds since the Unix epoch.
+pub struct TimeSeries<V> {
+    engine: Db,
+    name: String,
+    /// Width of the buckets in milliseconds.
+    bucket: u64,
+    read_only: bool,
+    _value: PhantomData<fn() -> V>,
+}
+
+impl<V: Serialize + DeserializeOwned> TimeSeries<V> {
+    pub(crate) fn new(engine: &Db, name: &str, bucket: Duration, read_only: bool) -> Self {
+        Self {
+            engine: engine.clone(),
+            name: name.to_owned(),
+            bucket: (bucket.as_millis() as u64).max(1),
+            read_only,
+            _value: PhantomData,
+        }
+    }
+
+    /// Append a point.
+    ///
+    /// # Arguments
+    ///
+    /// * `timestamp` - Time of the point, multiple points may have the same time.
+    /// * `value` - The value of the point.
+    pub fn append(&self, timestamp: u64, value: &V) -> DbResult<()> {
+        if self.read_only {
+            return Err(TinyBaseError::ReadOnly);
+        }
+
+        let start = timestamp - timestamp % self.bucket;
+        let tree = self
+            .engine
+            .open_tree(format!("{}{}", self.prefix(), start))?;
+
+        // The unique suffix keeps points with the same timestamp apart.
+        let mut key = timestamp.to_be_bytes().to_vec();
+        key.extend(self.engine.generate_id()?.to_be_bytes());
+        tree.insert(key, encode(value)?)?;
+
+        Ok(())
+    }
+
+    /// Append a point at the current time.
+    pub fn append_now(&self, value: &V) -> DbResult<()> {
+        let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap();
+        self.append(now.as_millis() as u64, value)
+    }
+
+    /// Points within a time window.
+    ///
+    /// # Arguments
+    ///
+    /// * `start` - Start of the window, inclusive.
+    /// * `end` - End of the window, exclusive.
+    ///
+    /// # Returns
+    ///
+    /// The timestamps and values of the points, oldest first.
+    pub fn range(&self, start: u64, end: u64) -> DbResult<Vec<(u64, V)>> {
+        let mut points = vec![];
+        if start >= end {
+            return Ok(points);
+        }
+
+        for (_, tree) in self.buckets(start, end)? {
+            for entry in tree.range(start.to_be_bytes()..end.to_be_bytes()) {
+                let (key, value) = entry?;
+                let timestamp = u64::from_be_bytes(key[..8].try_into().unwrap());
+                points.push((timestamp, decode(&value)?));
+            }
+        }
+
+        Ok(points)
+    }
+
+    /// Combine the points of a time window per interval.
+    ///
+    /// # Arguments
+    ///
+    /// * `start` - Start of the window, inclusive.
+    /// * `end` - End of the window, exclusive.
+    /// * `interval` - Width of the intervals, aligned to multiples of the interval.
+    /// * `reducer` - Function combining the values of an interval, such as an average.
+    ///
+    /// # Returns
+    ///
+    /// The start of every interval containing points with the combined value, oldest first.
+    pub fn downsample<A>(
+        &self,
+        start: u64,
+        end: u64,
+        interval: Duration,
+        reducer: impl Fn(&[V]) -> A,
+    ) -> DbResult<Vec<(u64, A)>> {
+        let interval = (interval.as_millis() as u64).max(1);
+        let mut samples = vec![];
+        let mut current: Option<(u64, Vec<V>)> = None;
+
+        for (timestamp, value) in self.range(start, end)? {
+            let slot = timestamp - timestamp % interval;
+            match &mut current {
+                Some((current_slot, values)) if *current_slot == slot => values.push(value),
+                _ => {
+                    if let Some((slot, values)) = current.replace((slot, vec![value])) {
+                        samples.push((slot, reducer(&values)));
+                    }
+                }
+            }
+        }
+
+        if let Some((slot, values)) = current {
+            samples.push((slot, reducer(&values)));
+        }
+
+        Ok(samples)
+    }
+
+    /// Remove all buckets which only contain points older than a timestamp.
+    ///
+    /// # Arguments
+    ///
+    /// * `timestamp` - Points from this time on are kept.
+    ///
+    /// # Returns
+    ///
+    /// The amount of removed buckets.
+    pub fn drop_before(&self, timestamp: u64) -> DbResult<usize> {
+        if self.read_only {
+            return Err(TinyBaseError::ReadOnly);
+        }
+
+        let mut dropped = 0;
+        for (start, _) in self.buckets(0, timestamp)? {
+            if start + self.bucket <= timestamp {
+                self.engine
+                    .drop_tree(format!("{}{}", self.prefix(), start))?;
+                dropped += 1;
+            }
+        }
+
+        Ok(dropped)
+    }
+
+    pub fn series_name(&self) -> &str {
+        &self.name
+    }
+
+    fn prefix(&self) -> String {
+        format!("{}_ts_", self.name)
+    }
+
+    /// Buckets which may contain points within a time window, oldest first.
+    ///
+    /// A bucket covers the time until the next bucket starts, so buckets written
+    /// with another width are still found.
+    fn buckets(&self, start: u64, end: u64) -> DbResult<Vec<(u64, Tree)>> {
+        let prefix = self.prefix();
+        let mut starts: Vec<u64> = self
+            .engine
+            .tree_names()
+            .iter()
+            .filter_map(|name| name.strip_prefix(prefix.as_bytes()))
+            .filter_map(|start| std::str::from_utf8(start).ok()?.parse().ok())
+            .collect();
+        starts.sort_unstable();
+
+        let mut buckets = vec![];
+        for (i, &bucket) in starts.iter().enumerate() {
+            let next = starts.get(i + 1).copied().unwrap_or(u64::MAX);
+            if bucket < end && next > start {
+                buckets.push((
+                    bucket,
+                    self.engine.open_tree(format!("{}{}", prefix, bucket))?,
+                ));
+            }
+        }
+
+        Ok(buckets)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use crate::TinyBase;
+
+    #[test]
+    fn time_series_range() {
+        let db = TinyBase::new(None, true);
+        let series = db
+            .time_series::<f64>("cpu", Duration::from_secs(10))
+            .unwrap();
+
+        for (timestamp, value) in [(1_000, 1.0), (4_000, 3.0), (12_000, 5.0), (25_000, 7.0)] {
+            series.append(timestamp, &value).unwrap();
+        }
+
+        assert_eq!(
+            series.range(4_000, 25_000).unwrap(),
+            vec![(4_000, 3.0), (12_000, 5.0)]
+        );
+
+        let average = |values: &[f64]| values.iter().sum::<f64>() / values.len() as f64;
+        assert_eq!(
+            series
+                .downsample(0, 30_000, Duration::from_secs(10), average)
+                .unwrap(),
+            vec![(0, 2.0), (10_000, 5.0), (20_000, 7.0)]
+        );
+
+        assert_eq!(series.drop_before(20_000).unwrap(), 2);
+        assert_eq!(series.range(0, 30_000).unwrap(), vec![(25_000, 7.0)]);
+    }
+}
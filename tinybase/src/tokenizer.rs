@@ -0,0 +1,157 @@
+use std::collections::HashSet;
+
+/// Languages with bundled stop words and stemming rules.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Language {
+    English,
+    German,
+    French,
+    Spanish,
+}
+
+impl Language {
+    /// The most common words of the language, which don't help finding documents.
+    fn stop_words(self) -> &'static [&'static str] {
+        match self {
+            Language::English => &[
+                "a", "an", "and", "are", "as", "at", "be", "but", "by", "for", "if", "in", "into",
+                "is", "it", "no", "not", "of", "on", "or", "such", "that", "the", "their", "then",
+                "there", "these", "they", "this", "to", "was", "will", "with",
+            ],
+            Language::German => &[
+                "aber", "als", "am", "an", "auch", "auf", "aus", "bei", "das", "dass", "dem",
+                "den", "der", "des", "die", "ein", "eine", "einen", "einer", "es", "für", "im",
+                "in", "ist", "mit", "nicht", "oder", "sich", "sie", "und", "von", "zu",
+            ],
+            Language::French => &[
+                "au", "aux", "avec", "ce", "ces", "dans", "de", "des", "du", "elle", "en", "et",
+                "il", "je", "la", "le", "les", "leur", "mais", "ne", "nous", "ou", "par", "pas",
+                "pour", "qui", "sa", "se", "son", "sur", "un", "une",
+            ],
+            Language::Spanish => &[
+                "a", "al", "con", "de", "del", "el", "en", "es", "la", "las", "lo", "los", "no",
+                "o", "para", "por", "que", "se", "su", "sus", "un", "una", "y",
+            ],
+        }
+    }
+
+    #[cfg(feature = "stemming")]
+    fn algorithm(self) -> rust_stemmers::Algorithm {
+        match self {
+            Language::English => rust_stemmers::Algorithm::English,
+            Language::German => rust_stemmers::Algorithm::German,
+            Language::French => rust_stemmers::Algorithm::French,
+            Language::Spanish => rust_stemmers::Algorithm::Spanish,
+        }
+    }
+}
+
+/// Splits text into the tokens a full-text index stores.
+///
+/// Text is split at every character which isn't alphanumeric and lowercased. Short
+/// tokens and stop words are dropped, and with the `stemming` feature tokens can be
+/// reduced to their stem so different forms of a word match.
+#[derive(Debug, Clone)]
+pub struct Tokenizer {
+    min_length: usize,
+    stop_words: HashSet<String>,
+    #[cfg(feature = "stemming")]
+    stemming: Option<Language>,
+}
+
+impl Default for Tokenizer {
+    fn default() -> Self {
+        Self {
+            min_length: 1,
+            stop_words: HashSet::new(),
+            #[cfg(feature = "stemming")]
+            stemming: None,
+        }
+    }
+}
+
+impl Tokenizer {
+    /// Create a tokenizer which keeps all tokens.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Create a tokenizer with the rules of a language, dropping its stop words and
+    /// stemming tokens if the `stemming` feature is enabled.
+    pub fn language(language: Language) -> Self {
+        let tokenizer = Self::new().stop_words(language.stop_words().iter().copied());
+
+        #[cfg(feature = "stemming")]
+        let tokenizer = tokenizer.stemming(language);
+
+        tokenizer
+    }
+
+    /// Drop tokens with less characters.
+    pub fn min_length(mut self, min_length: usize) -> Self {
+        self.min_length = min_length;
+        self
+    }
+
+    /// Drop additional words.
+    ///
+    /// # Arguments
+    ///
+    /// * `words` - The words to drop, compared after lowercasing.
+    pub fn stop_words<'a>(mut self, words: impl IntoIterator<Item = &'a str>) -> Self {
+        self.stop_words
+            .extend(words.into_iter().map(str::to_lowercase));
+        self
+    }
+
+    /// Reduce tokens to their stem with the rules of a language.
+    #[cfg(feature = "stemming")]
+    pub fn stemming(mut self, language: Language) -> Self {
+        self.stemming = Some(language);
+        self
+    }
+
+    /// Split text into tokens.
+    ///
+    /// # Returns
+    ///
+    /// The tokens in the order they appear in the text, including duplicates.
+    pub fn tokenize(&self, text: &str) -> Vec<String> {
+        let tokens = text
+            .split(|char: char| !char.is_alphanumeric())
+            .map(str::to_lowercase)
+            .filter(|token| {
+                token.chars().count() >= self.min_length.max(1) && !self.stop_words.contains(token)
+            });
+
+        #[cfg(feature = "stemming")]
+        if let Some(language) = self.stemming {
+            let stemmer = rust_stemmers::Stemmer::create(language.algorithm());
+            return tokens
+                .map(|token| stemmer.stem(&token).into_owned())
+                .collect();
+        }
+
+        tokens.collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tokenize_language() {
+        let tokenizer = Tokenizer::language(Language::English).min_length(3);
+        let tokens = tokenizer.tokenize("The quick fox, and the dog!");
+
+        assert_eq!(tokens, ["quick", "fox", "dog"]);
+    }
+
+    #[cfg(feature = "stemming")]
+    #[test]
+    fn tokenize_stemming() {
+        let tokenizer = Tokenizer::new().stemming(Language::English);
+        assert_eq!(tokenizer.tokenize("Running jumps"), ["run", "jump"]);
+    }
+}
@@ -0,0 +1,436 @@
+use std::any::Any;
+use std::collections::HashMap;
+use std::mem;
+use std::sync::{Arc, RwLockWriteGuard};
+
+use sled::transaction::{ConflictableTransactionError, Transactional};
+use sled::Tree;
+
+use crate::lock::SharedGuard;
+use crate::record::Record;
+use crate::result::{DbResult, TinyBaseError};
+use crate::subscriber::Event;
+use crate::table::{AnyCommit, AnyTable, Table, TableInner, TableType};
+
+/// Writes to a single table buffered by a transaction.
+pub(crate) struct PendingWrites<T> {
+    pub events: Vec<Event<T>>,
+    /// Data of the records written by the transaction, [`None`] if deleted.
+    pending: HashMap<u64, Option<T>>,
+}
+
+impl<T> Default for PendingWrites<T> {
+    fn default() -> Self {
+        Self {
+            events: vec![],
+            pending: HashMap::new(),
+        }
+    }
+}
+
+/// Writes to a table made by [`TableInner::transaction`] or [`MultiTransaction::table`].
+///
+/// Writes are buffered and applied in a single commit when the transaction returns,
+/// reads see the buffered writes. Constraints are checked on every write against
+/// the table as it will be after the commit.
+pub struct Transaction<'a, T: TableType + 'static> {
+    table: &'a TableInner<T>,
+    /// The table tree, obtained via the write lock.
+    tree: &'a Tree,
+    writes: &'a mut PendingWrites<T>,
+}
+
+impl<'a, T: TableType + 'static> Transaction<'a, T> {
+    pub(crate) fn new(
+        table: &'a TableInner<T>,
+        tree: &'a Tree,
+        writes: &'a mut PendingWrites<T>,
+    ) -> Self {
+        Self {
+            table,
+            tree,
+            writes,
+        }
+    }
+
+    /// Select a record by its ID, including the writes of the transaction.
+    ///
+    /// # Arguments
+    ///
+    /// * `id` - The ID of the record to select.
+    ///
+    /// # Returns
+    ///
+    /// An [`Option`] containing the selected record if it exists, or [`None`] otherwise.
+    pub fn select(&self, id: u64) -> DbResult<Option<Record<T>>> {
+        match self.writes.pending.get(&id) {
+            Some(data) => Ok(data.clone().map(|data| Record { id, data })),
+            None => self.table.tree_select(self.tree, id),
+        }
+    }
+
+    /// Insert a new record.
+    ///
+    /// # Arguments
+    ///
+    /// * `value` - The value to insert.
+    ///
+    /// # Returns
+    ///
+    /// The ID of the new record.
+    pub fn insert(&mut self, value: T) -> DbResult<u64> {
+        let id = self.table.generate_id(self.tree)?;
+        if self.writes.pending.contains_key(&id) {
+            return Err(TinyBaseError::DuplicateId(id));
+        }
+
+        let record = Record { id, data: value };
+        self.check_constraint(&record)?;
+        self.write(Event::Insert(record));
+
+        Ok(id)
+    }
+
+    /// Update a record by its ID.
+    ///
+    /// # Arguments
+    ///
+    /// * `id` - The ID of the record to update.
+    /// * `updater` - Closure to generate the new data based on the old data.
+    ///
+    /// # Returns
+    ///
+    /// The updated record, or [`None`] if there is no record with the ID.
+    pub fn update(&mut self, id: u64, updater: impl FnOnce(T) -> T) -> DbResult<Option<Record<T>>> {
+        let Some(old) = self.select(id)? else {
+            return Ok(None);
+        };
+
+        let record = Record {
+            id,
+            data: updater(old.data.clone()),
+        };
+        self.check_constraint(&record)?;
+        self.write(Event::Update {
+            id,
+            old_data: old.data,
+            new_data: record.data.clone(),
+        });
+
+        Ok(Some(record))
+    }
+
+    /// Delete a record by its ID.
+    ///
+    /// # Arguments
+    ///
+    /// * `id` - The ID of the record to delete.
+    ///
+    /// # Returns
+    ///
+    /// An [`Option`] containing the deleted record if it exists, or [`None`] otherwise.
+    pub fn delete(&mut self, id: u64) -> DbResult<Option<Record<T>>> {
+        let record = self.select(id)?;
+        if let Some(record) = &record {
+            self.write(Event::Remove(record.clone()));
+        }
+
+        Ok(record)
+    }
+
+    /// Check the constraints of the table for a record written by the transaction.
+    fn check_constraint(&self, record: &Record<T>) -> DbResult<()> {
+        let mut additional: Vec<T> = self
+            .writes
+            .pending
+            .iter()
+            .filter(|(id, _)| **id != record.id)
+            .filter_map(|(_, data)| data.clone())
+            .collect();
+        additional.push(record.data.clone());
+
+        self.table
+            .check_constraint_replacing(self.tree, record, &additional, &|id| {
+                self.writes.pending.contains_key(&id)
+            })
+    }
+
+    /// Buffer an event and track the resulting data of its record.
+    fn write(&mut self, event: Event<T>) {
+        let pending = &mut self.writes.pending;
+        match &event {
+            Event::Insert(record) => pending.insert(record.id, Some(record.data.clone())),
+            Event::Remove(record) => pending.insert(record.id, None),
+            Event::Update { id, new_data, .. } => pending.insert(*id, Some(new_data.clone())),
+            Event::Cleared => unreachable!("tables are cleared without a transaction"),
+        };
+
+        self.writes.events.push(event);
+    }
+}
+
+/// A table which can be written by [`crate::TinyBase::transaction`].
+pub trait TransactionTable: Send + Sync {
+    /// Name of the table.
+    fn table_name(&self) -> &str;
+
+    /// Lock the table for writing until the guard is dropped.
+    #[doc(hidden)]
+    fn lock_writes(&self) -> DbResult<TableWriteGuard<'_>>;
+
+    /// Clone the table handle behind a new box.
+    #[doc(hidden)]
+    fn boxed(&self) -> Box<dyn TransactionTable>;
+}
+
+impl<T: TableType> TransactionTable for Table<T> {
+    fn table_name(&self) -> &str {
+        AnyTable::table_name(&*self.0)
+    }
+
+    fn lock_writes(&self) -> DbResult<TableWriteGuard<'_>> {
+        self.0.lock_writes()
+    }
+
+    fn boxed(&self) -> Box<dyn TransactionTable> {
+        Box::new(self.clone())
+    }
+}
+
+/// Write lock of a table taken by [`crate::TinyBase::transaction`].
+pub struct TableWriteGuard<'a> {
+    _shared: SharedGuard<'a>,
+    tree: RwLockWriteGuard<'a, Tree>,
+}
+
+impl<'a> TableWriteGuard<'a> {
+    pub(crate) fn new(shared: SharedGuard<'a>, tree: RwLockWriteGuard<'a, Tree>) -> Self {
+        Self {
+            _shared: shared,
+            tree,
+        }
+    }
+}
+
+/// Type erased writes to a table of a [`MultiTransaction`].
+trait AnyTableWrites {
+    fn table_name(&self) -> &str;
+
+    fn as_any_mut(&mut self) -> &mut dyn Any;
+
+    /// Prepare the commit of the buffered writes.
+    fn prepare(&mut self, tree: &Tree) -> DbResult<Option<Box<dyn AnyCommit + '_>>>;
+}
+
+struct TableWrites<T: TableType + 'static> {
+    table: Arc<TableInner<T>>,
+    writes: PendingWrites<T>,
+}
+
+impl<T: TableType> AnyTableWrites for TableWrites<T> {
+    fn table_name(&self) -> &str {
+        AnyTable::table_name(&*self.table)
+    }
+
+    fn as_any_mut(&mut self) -> &mut dyn Any {
+        self
+    }
+
+    fn prepare(&mut self, tree: &Tree) -> DbResult<Option<Box<dyn AnyCommit + '_>>> {
+        let events = mem::take(&mut self.writes.events);
+        Ok(self
+            .table
+            .prepare_commit(tree, events, &[])?
+            .map(|commit| Box::new(commit) as Box<dyn AnyCommit>))
+    }
+}
+
+/// Writes to multiple tables made by [`crate::TinyBase::transaction`].
+///
+/// All tables of the transaction are locked for writing until it returns. The
+/// writes to all tables, their indexes and journals are applied in one commit.
+pub struct MultiTransaction<'a> {
+    /// Tables of the transaction with their write lock, sorted by name.
+    locks: Vec<(&'a str, TableWriteGuard<'a>)>,
+    /// Buffered writes of the tables written so far.
+    writes: Vec<Box<dyn AnyTableWrites>>,
+}
+
+impl<'a> MultiTransaction<'a> {
+    /// Lock the tables for writing, in the order of their names so concurrent
+    /// transactions can't deadlock.
+    pub(crate) fn lock(tables: &[&'a dyn TransactionTable]) -> DbResult<Self> {
+        let mut tables = tables.to_vec();
+        tables.sort_by_key(|table| table.table_name());
+        tables.dedup_by_key(|table| table.table_name());
+
+        let locks = tables
+            .into_iter()
+            .map(|table| Ok((table.table_name(), table.lock_writes()?)))
+            .collect::<DbResult<Vec<_>>>()?;
+
+        Ok(Self {
+            locks,
+            writes: vec![],
+        })
+    }
+
+    /// Whether a table with the name is part of the transaction.
+    pub(crate) fn contains(&self, name: &str) -> bool {
+        self.locks.iter().any(|(locked, _)| *locked == name)
+    }
+
+    /// Write to a table of the transaction.
+    ///
+    /// # Arguments
+    ///
+    /// * `table` - The table, which must be one of the tables of the transaction.
+    ///
+    /// # Returns
+    ///
+    /// The [`Transaction`] of the table, or [`TinyBaseError::Transaction`] if the
+    /// table isn't part of the transaction.
+    pub fn table<T: TableType>(&mut self, table: &Table<T>) -> DbResult<Transaction<'_, T>> {
+        let name = AnyTable::table_name(&*table.0);
+        let Some((_, lock)) = self.locks.iter().find(|(locked, _)| *locked == name) else {
+            return Err(TinyBaseError::Transaction(format!(
+                "table {} is not part of the transaction",
+                name
+            )));
+        };
+
+        let position = match self.writes.iter().position(|w| w.table_name() == name) {
+            Some(position) => position,
+            None => {
+                self.writes.push(Box::new(TableWrites {
+                    table: Arc::clone(&table.0),
+                    writes: PendingWrites::default(),
+                }));
+                self.writes.len() - 1
+            }
+        };
+
+        let TableWrites { table, writes } = self.writes[position]
+            .as_any_mut()
+            .downcast_mut::<TableWrites<T>>()
+            .ok_or_else(|| {
+                TinyBaseError::Transaction(format!("table {} has another record type", name))
+            })?;
+
+        Ok(Transaction::new(table, &lock.tree, writes))
+    }
+
+    /// Apply the writes to all tables in a single sled transaction.
+    pub(crate) fn commit(mut self) -> DbResult<()> {
+        let mut commits = vec![];
+        for writes in &mut self.writes {
+            let (_, lock) = self
+                .locks
+                .iter()
+                .find(|(name, _)| *name == writes.table_name())
+                .unwrap();
+
+            if let Some(commit) = writes.prepare(&lock.tree)? {
+                commits.push(commit);
+            }
+        }
+
+        if commits.is_empty() {
+            return Ok(());
+        }
+
+        let trees: Vec<&Tree> = commits.iter().flat_map(|commit| commit.trees()).collect();
+        trees.as_slice().transaction(|trees| {
+            let mut trees = trees.as_slice();
+            for commit in &commits {
+                let (own, rest) = trees.split_at(commit.trees().len());
+                commit.apply(own)?;
+                trees = rest;
+            }
+
+            Ok::<_, ConflictableTransactionError<TinyBaseError>>(())
+        })?;
+
+        for commit in commits {
+            commit.finish();
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::constraint::Constraint;
+    use crate::result::TinyBaseError;
+    use crate::TinyBase;
+
+    #[test]
+    fn transaction_commit() {
+        let db = TinyBase::new(None, true);
+        let table = db.open_table::<String>("test_table").unwrap();
+        let index = table.create_index("name", |name| name.to_owned()).unwrap();
+        table.constraint(Constraint::unique(&index)).unwrap();
+
+        let a = table.insert("a".to_string()).unwrap();
+        let b = table.insert("b".to_string()).unwrap();
+
+        // Swapping unique keys within a transaction doesn't conflict.
+        let c = table
+            .transaction(|tx| {
+                tx.update(a, |_| "c".to_string())?;
+                tx.update(b, |_| "a".to_string())?;
+                tx.delete(a)?;
+                assert!(tx.select(a)?.is_none());
+                tx.insert("b".to_string())
+            })
+            .unwrap();
+
+        assert!(table.select(a).unwrap().is_none());
+        assert_eq!(index.select(&"a".to_string()).unwrap()[0].id, b);
+        assert_eq!(index.select(&"b".to_string()).unwrap()[0].id, c);
+        assert!(index.select(&"c".to_string()).unwrap().is_empty());
+
+        // Nothing is applied when the transaction fails.
+        assert!(table
+            .transaction(|tx| {
+                tx.delete(c)?;
+                tx.insert("a".to_string())
+            })
+            .is_err());
+        assert!(table.select(c).unwrap().is_some());
+        assert_eq!(index.select(&"a".to_string()).unwrap().len(), 1);
+    }
+
+    #[test]
+    fn transaction_multi_table() {
+        let db = TinyBase::new(None, true);
+        let users = db.open_table::<String>("users").unwrap();
+        let sessions = db.open_table::<(u64, String)>("sessions").unwrap();
+        let token = sessions
+            .create_index("token", |(_, token)| token.to_owned())
+            .unwrap();
+        sessions.constraint(Constraint::unique(&token)).unwrap();
+
+        let create = |name: &str, session: &str| {
+            db.transaction(&[&users, &sessions], |tx| {
+                let user = tx.table(&users)?.insert(name.to_string())?;
+                tx.table(&sessions)?.insert((user, session.to_string()))?;
+                Ok(user)
+            })
+        };
+
+        let john = create("John", "a").unwrap();
+        assert_eq!(token.select(&"a".to_string()).unwrap()[0].data.0, john);
+
+        // The user isn't inserted when its session violates a constraint.
+        assert!(create("Jane", "a").is_err());
+        assert_eq!(users.ids().unwrap(), vec![john]);
+
+        let other = db.open_table::<String>("other").unwrap();
+        assert!(matches!(
+            db.transaction(&[&users], |tx| tx.table(&other)?.insert("x".to_string())),
+            Err(TinyBaseError::Transaction(_))
+        ));
+    }
+}
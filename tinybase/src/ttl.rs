@@ -0,0 +1,140 @@
+use std::ops::Bound;
+use std::sync::Arc;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use crate::index::Index;
+use crate::key_encoding::encode_key;
+use crate::maintenance::{Maintenance, MaintenanceHandle};
+use crate::record::Record;
+use crate::result::{DbResult, TinyBaseError};
+use crate::table::{Table, TableType};
+
+/// Index on the expiry time of every record, deleting records once they expired.
+///
+/// Created with [`Table::create_ttl_index`]. Expired records are deleted like with
+/// [`Table::delete_many`], so other indexes and subscribers see normal remove events.
+pub struct TtlIndex<T: TableType + 'static> {
+    index: Index<T, u64>,
+    expiry: Arc<dyn Fn(&T) -> u64 + Send + Sync>,
+}
+
+impl<T: TableType> Clone for TtlIndex<T> {
+    fn clone(&self) -> Self {
+        Self {
+            index: self.index.clone(),
+            expiry: self.expiry.clone(),
+        }
+    }
+}
+
+impl<T: TableType + 'static> TtlIndex<T> {
+    pub(crate) fn new(
+        table: &Table<T>,
+        name: &str,
+        expiry: impl Fn(&T) -> u64 + Send + Sync + 'static,
+    ) -> DbResult<Self> {
+        let expiry: Arc<dyn Fn(&T) -> u64 + Send + Sync> = Arc::new(expiry);
+
+        let index = {
+            let expiry = expiry.clone();
+            table.create_index(name, move |data| expiry(data))?
+        };
+
+        Ok(Self { index, expiry })
+    }
+
+    /// The underlying index on the expiry times.
+    pub fn index(&self) -> &Index<T, u64> {
+        &self.index
+    }
+
+    /// Delete all records which expired at a time.
+    ///
+    /// # Arguments
+    ///
+    /// * `now` - The current time in milliseconds since the Unix epoch, records whose
+    ///   expiry time isn't after it are deleted.
+    ///
+    /// # Returns
+    ///
+    /// All deleted records, or [`TinyBaseError::Closed`] once all handles of the table were dropped.
+    pub fn expire_at(&self, now: u64) -> DbResult<Vec<Record<T>>> {
+        let table = self.index.table.upgrade().ok_or(TinyBaseError::Closed)?;
+        let ids = self
+            .index
+            .range_ids(Bound::Unbounded, Bound::Included(encode_key(&now)?))?;
+
+        // Records which were extended since the index was read are kept.
+        table.delete_if(&ids, |data| (self.expiry)(data) <= now)
+    }
+
+    /// Delete all records which already expired.
+    ///
+    /// # Returns
+    ///
+    /// All deleted records.
+    pub fn expire_now(&self) -> DbResult<Vec<Record<T>>> {
+        let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap();
+        self.expire_at(now.as_millis() as u64)
+    }
+
+    /// Delete expired records periodically on a background thread.
+    ///
+    /// # Arguments
+    ///
+    /// * `interval` - Time between sweeps.
+    ///
+    /// # Returns
+    ///
+    /// A [`MaintenanceHandle`] which stops the sweeps when dropped, see [`Maintenance::expire`].
+    pub fn sweep_every(&self, interval: Duration) -> MaintenanceHandle {
+        Maintenance::new().expire(self, interval).start()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::TinyBase;
+
+    #[test]
+    fn ttl_index_expire() {
+        let db = TinyBase::new(None, true);
+        let table: Table<(String, u64)> = db.open_table("sessions").unwrap();
+        let ttl = table
+            .create_ttl_index("expiry", |(_, expiry): &(String, u64)| *expiry)
+            .unwrap();
+        let name = table
+            .create_index("name", |(name, _): &(String, u64)| name.clone())
+            .unwrap();
+
+        table.insert(("old".to_string(), 100)).unwrap();
+        table.insert(("due".to_string(), 200)).unwrap();
+        table.insert(("stale".to_string(), 300)).unwrap();
+        let fresh = table.insert(("fresh".to_string(), u64::MAX)).unwrap();
+
+        let expired: Vec<_> = ttl
+            .expire_at(200)
+            .unwrap()
+            .into_iter()
+            .map(|record| record.data.0)
+            .collect();
+        assert_eq!(expired, ["old", "due"]);
+        assert!(name.select_ids(&"old".to_string()).unwrap().is_empty());
+        assert!(ttl.expire_at(200).unwrap().is_empty());
+
+        let sweeper = ttl.sweep_every(Duration::from_millis(1));
+        std::thread::sleep(Duration::from_millis(50));
+        let stats = sweeper.stats();
+        drop(sweeper);
+
+        assert_eq!(stats[0].0, "expire:sessions_idx_expiry");
+        assert!(stats[0].1.runs > 0);
+        assert_eq!(ttl.index().select_ids(&u64::MAX).unwrap(), [fresh]);
+        assert!(name.select_ids(&"stale".to_string()).unwrap().is_empty());
+
+        // Sweeps of a dropped table fail instead of panicking.
+        drop((table, name));
+        assert!(matches!(ttl.expire_now(), Err(TinyBaseError::Closed)));
+    }
+}
@@ -0,0 +1,306 @@
+use std::io::{self, BufRead, IsTerminal, Write};
+
+use crate::result::DbResult;
+use crate::table::TableType;
+use crate::{Table, TinyBase};
+
+/// Records shown per page unless configured otherwise.
+const DEFAULT_PAGE_SIZE: usize = 20;
+
+/// Type erased table which can be browsed.
+trait BrowsedTable {
+    fn name(&self) -> &str;
+    fn len(&self) -> DbResult<usize>;
+    /// Up to `limit` records from ID `from` on, formatted with [`std::fmt::Debug`]
+    /// and containing `filter`, in ID order. Also returns the ID the next page
+    /// starts at, if there is one.
+    fn page(
+        &self,
+        from: u64,
+        limit: usize,
+        filter: Option<&str>,
+    ) -> DbResult<(Vec<String>, Option<u64>)>;
+    fn get(&self, id: u64) -> DbResult<Option<String>>;
+}
+
+struct Browsed<T: TableType + 'static> {
+    name: String,
+    table: Table<T>,
+}
+
+impl<T: TableType> BrowsedTable for Browsed<T> {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn len(&self) -> DbResult<usize> {
+        self.table.len()
+    }
+
+    fn page(
+        &self,
+        from: u64,
+        limit: usize,
+        filter: Option<&str>,
+    ) -> DbResult<(Vec<String>, Option<u64>)> {
+        let mut records = vec![];
+
+        // Records are read through the table, so their lazy fields can be loaded.
+        // Only the records up to the first one of the next page are decoded.
+        for record in self.table.iter_from(from) {
+            let record = record?;
+            let formatted = format!("{}: {:?}", record.id, record.data);
+            if filter.is_none_or(|filter| formatted.contains(filter)) {
+                if records.len() == limit {
+                    return Ok((records, Some(record.id)));
+                }
+                records.push(formatted);
+            }
+        }
+
+        Ok((records, None))
+    }
+
+    fn get(&self, id: u64) -> DbResult<Option<String>> {
+        Ok(self
+            .table
+            .select(id)?
+            .map(|record| format!("{}: {:#?}", record.id, record.data)))
+    }
+}
+
+/// Interactive terminal browser for the tables of a database.
+///
+/// Records are decoded with the types of the registered tables and shown page
+/// by page. Type `help` for the available commands.
+pub struct Browser<'a> {
+    db: &'a TinyBase,
+    tables: Vec<Box<dyn BrowsedTable>>,
+    page_size: usize,
+    /// Index of the opened table.
+    current: Option<usize>,
+    filter: Option<String>,
+    /// First IDs of the pages up to the shown one.
+    pages: Vec<u64>,
+    /// First ID of the page after the shown one.
+    next: Option<u64>,
+}
+
+impl<'a> Browser<'a> {
+    pub fn new(db: &'a TinyBase) -> Self {
+        Self {
+            db,
+            tables: Vec::new(),
+            page_size: DEFAULT_PAGE_SIZE,
+            current: None,
+            filter: None,
+            pages: vec![0],
+            next: None,
+        }
+    }
+
+    /// Make a table browsable.
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - The name of the table.
+    pub fn table<T: TableType + 'static>(mut self, name: &str) -> DbResult<Self> {
+        self.tables.push(Box::new(Browsed {
+            name: name.to_owned(),
+            table: self.db.open_table::<T>(name)?,
+        }));
+        Ok(self)
+    }
+
+    /// Set the amount of records shown per page.
+    pub fn page_size(mut self, page_size: usize) -> Self {
+        self.page_size = page_size.max(1);
+        self
+    }
+
+    /// Browse on the terminal until `quit` is entered.
+    pub fn run_terminal(self) -> DbResult<()> {
+        let clear = io::stdout().is_terminal();
+        self.run(io::stdin().lock(), io::stdout(), clear)
+    }
+
+    /// Browse reading commands from `input` until `quit` is entered or the input ends.
+    ///
+    /// # Arguments
+    ///
+    /// * `input` - Commands, one per line.
+    /// * `output` - Where the screens are written to.
+    /// * `clear` - Clear the screen before each command output.
+    pub fn run(mut self, input: impl BufRead, mut output: impl Write, clear: bool) -> DbResult<()> {
+        writeln!(output, "Type `help` for a list of commands.")?;
+        prompt(&mut output)?;
+
+        for line in input.lines() {
+            let line = line?;
+            let (command, argument) = match line.trim().split_once(' ') {
+                Some((command, argument)) => (command, Some(argument.trim())),
+                None => (line.trim(), None),
+            };
+
+            if command == "quit" || command == "q" {
+                break;
+            }
+
+            if clear {
+                write!(output, "\x1b[2J\x1b[H")?;
+            }
+
+            if let Err(err) = self.execute(command, argument, &mut output) {
+                writeln!(output, "error: {}", err)?;
+            }
+
+            prompt(&mut output)?;
+        }
+
+        Ok(())
+    }
+
+    fn execute(
+        &mut self,
+        command: &str,
+        argument: Option<&str>,
+        output: &mut impl Write,
+    ) -> DbResult<()> {
+        match (command, argument) {
+            ("", _) => {}
+            ("tables", _) => {
+                for table in &self.tables {
+                    writeln!(output, "{} ({} records)", table.name(), table.len()?)?;
+                }
+            }
+            ("open", Some(name)) => match self.tables.iter().position(|table| table.name() == name)
+            {
+                Some(current) => {
+                    self.current = Some(current);
+                    self.filter = None;
+                    self.pages = vec![0];
+                    self.show_page(output)?;
+                }
+                None => writeln!(output, "unknown table `{}`", name)?,
+            },
+            ("next" | "n", _) => {
+                if let Some(next) = self.next {
+                    self.pages.push(next);
+                }
+                self.show_page(output)?;
+            }
+            ("prev" | "p", _) => {
+                if self.pages.len() > 1 {
+                    self.pages.pop();
+                }
+                self.show_page(output)?;
+            }
+            ("filter", filter) => {
+                self.filter = filter.map(str::to_owned);
+                self.pages = vec![0];
+                self.show_page(output)?;
+            }
+            ("get", Some(id)) => match (self.current_table(), id.parse()) {
+                (Some(table), Ok(id)) => match table.get(id)? {
+                    Some(record) => writeln!(output, "{}", record)?,
+                    None => writeln!(output, "no record with ID {}", id)?,
+                },
+                (None, _) => writeln!(output, "no table opened")?,
+                (_, Err(_)) => writeln!(output, "invalid ID `{}`", id)?,
+            },
+            ("indexes", _) => match self.current_table() {
+                Some(table) => {
+                    let prefix = format!("{}_idx_", table.name());
+                    for name in self.db.engine.tree_names() {
+                        if let Some(index) = name.strip_prefix(prefix.as_bytes()) {
+                            let keys = self.db.engine.open_tree(&name)?.len();
+                            writeln!(output, "{} ({} keys)", String::from_utf8_lossy(index), keys)?;
+                        }
+                    }
+                }
+                None => writeln!(output, "no table opened")?,
+            },
+            ("help", _) => writeln!(
+                output,
+                "tables          list tables\n\
+                 open <table>    open a table\n\
+                 next, prev      page through records\n\
+                 filter [text]   only show records containing text, or all records\n\
+                 get <id>        show a single record\n\
+                 indexes         list indexes of the opened table\n\
+                 quit            exit"
+            )?,
+            _ => writeln!(output, "unknown command `{}`, type `help`", command)?,
+        }
+
+        Ok(())
+    }
+
+    fn current_table(&self) -> Option<&dyn BrowsedTable> {
+        self.current.map(|current| self.tables[current].as_ref())
+    }
+
+    fn show_page(&mut self, output: &mut impl Write) -> DbResult<()> {
+        let Some(table) = self.current_table() else {
+            writeln!(output, "no table opened")?;
+            return Ok(());
+        };
+
+        let from = *self.pages.last().unwrap();
+        let (records, next) = table.page(from, self.page_size, self.filter.as_deref())?;
+
+        write!(output, "{} - page {}", table.name(), self.pages.len())?;
+        if next.is_none() {
+            write!(output, " (last)")?;
+        }
+        writeln!(output)?;
+        for record in &records {
+            writeln!(output, "{}", record)?;
+        }
+
+        self.next = next;
+        Ok(())
+    }
+}
+
+fn prompt(output: &mut impl Write) -> io::Result<()> {
+    write!(output, "> ")?;
+    output.flush()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn browse_table() {
+        let db = TinyBase::new(None, true);
+        let table: Table<String> = db.open_table("test_table").unwrap();
+        for value in ["value1", "value2", "value3"] {
+            table.insert(value.to_string()).unwrap();
+        }
+        let _index = table
+            .create_index("name", |value| value.to_owned())
+            .unwrap();
+
+        let mut output = vec![];
+        Browser::new(&db)
+            .table::<String>("test_table")
+            .unwrap()
+            .page_size(2)
+            .run(
+                "open test_table\nnext\nnext\nprev\nfilter value2\nindexes\nquit\n".as_bytes(),
+                &mut output,
+                false,
+            )
+            .unwrap();
+
+        let output = String::from_utf8(output).unwrap();
+        assert_eq!(output.matches("test_table - page").count(), 5);
+        assert_eq!(output.matches("test_table - page 1\n").count(), 2);
+        assert_eq!(output.matches("test_table - page 2 (last)").count(), 2);
+        assert!(output.contains("\"value3\""));
+        assert!(output.contains("test_table - page 1 (last)"));
+        assert!(output.contains("name (3 keys)"));
+    }
+}
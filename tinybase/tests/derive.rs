@@ -0,0 +1,75 @@
+use serde::{Deserialize, Serialize};
+use tinybase::{result::TinyBaseError, TinyBase};
+use tinybase_derive::TableEntity;
+
+#[derive(TableEntity, Serialize, Deserialize, Debug, Clone, PartialEq)]
+struct Item {
+    #[index]
+    pub r#type: String,
+    pub unit_price: u32,
+}
+
+#[derive(TableEntity, Serialize, Deserialize, Debug, Clone)]
+struct Person {
+    #[index(unique)]
+    pub name: String,
+    #[index(name = "last_name_idx")]
+    pub last_name: String,
+}
+
+#[test]
+fn derive_patch_variants() {
+    let db = TinyBase::new(None, true);
+    let items = Item::init(&db, "items").unwrap();
+
+    let id = items
+        .insert(Item {
+            r#type: "bolt".to_owned(),
+            unit_price: 2,
+        })
+        .unwrap();
+
+    items.patch(id, ItemPatch::Type("nut".to_owned())).unwrap();
+    let record = items.patch(id, ItemPatch::UnitPrice(3)).unwrap().unwrap();
+
+    assert_eq!(
+        record.data,
+        Item {
+            r#type: "nut".to_owned(),
+            unit_price: 3,
+        }
+    );
+    assert_eq!(items.find_by_type("nut".to_owned()).unwrap().len(), 1);
+}
+
+#[test]
+fn derive_index_options() {
+    let db = TinyBase::new(None, true);
+    let people = Person::init(&db, "people").unwrap();
+
+    assert_eq!(
+        people.list_indexes().unwrap(),
+        vec!["last_name_idx", "name"]
+    );
+
+    let person = Person {
+        name: "John".to_owned(),
+        last_name: "Smith".to_owned(),
+    };
+    people.insert(person.clone()).unwrap();
+
+    assert!(matches!(
+        people.insert(person),
+        Err(TinyBaseError::Exists { constraint, .. }) if constraint == "people_idx_name"
+    ));
+    assert_eq!(
+        people.find_by_last_name("Smith".to_owned()).unwrap().len(),
+        1
+    );
+}
+
+#[test]
+fn derive_compile_errors() {
+    let cases = trybuild::TestCases::new();
+    cases.compile_fail("tests/ui/*.rs");
+}
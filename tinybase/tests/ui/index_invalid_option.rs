@@ -0,0 +1,10 @@
+use serde::{Deserialize, Serialize};
+use tinybase_derive::TableEntity;
+
+#[derive(TableEntity, Serialize, Deserialize, Debug, Clone)]
+struct Person {
+    #[index(primary)]
+    pub name: String,
+}
+
+fn main() {}
@@ -0,0 +1,10 @@
+use serde::{Deserialize, Serialize};
+use tinybase_derive::TableEntity;
+
+#[derive(TableEntity, Serialize, Deserialize, Debug, Clone)]
+struct Person {
+    pub last_name: String,
+    pub last__name: String,
+}
+
+fn main() {}
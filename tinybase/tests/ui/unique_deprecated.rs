@@ -0,0 +1,13 @@
+#![deny(deprecated)]
+
+use serde::{Deserialize, Serialize};
+use tinybase_derive::TableEntity;
+
+#[derive(TableEntity, Serialize, Deserialize, Debug, Clone)]
+struct Person {
+    #[index]
+    #[unique]
+    pub name: String,
+}
+
+fn main() {}